@@ -0,0 +1,100 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::hex;
+use crate::terminal_colours::background_and_foreground;
+
+// With --format=alacritty, we print the terminal colours as an Alacritty
+// colour scheme, which you can `import` from alacritty.toml:
+//
+//      [colors.primary]
+//      background = "#000000"
+//      foreground = "#ffffff"
+//
+//      [colors.normal]
+//      black = "#000000"
+//      ...
+//
+// The normal colours are slots 0-7, and the bright colours are slots 8-15.
+// With --terminal-colours=256, the rest of the colours go in
+// `[[colors.indexed_colors]]` tables, one for each slot.
+//
+// See https://alacritty.org/config-alacritty.html#colors
+
+const COLOUR_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+pub fn colours_toml(terminal_colours: &[Srgb<u8>], light: bool) -> String {
+    let (background, foreground) = background_and_foreground(terminal_colours, light);
+
+    let mut toml = String::new();
+
+    writeln!(toml, "[colors.primary]").unwrap();
+    writeln!(toml, "background = \"{}\"", hex(background)).unwrap();
+    writeln!(toml, "foreground = \"{}\"", hex(foreground)).unwrap();
+
+    for (table, start) in [("normal", 0), ("bright", 8)] {
+        writeln!(toml).unwrap();
+        writeln!(toml, "[colors.{}]", table).unwrap();
+
+        for (name, c) in COLOUR_NAMES.iter().zip(terminal_colours.iter().skip(start)) {
+            writeln!(toml, "{} = \"{}\"", name, hex(*c)).unwrap();
+        }
+    }
+
+    for (i, c) in terminal_colours.iter().enumerate().skip(16) {
+        writeln!(toml).unwrap();
+        writeln!(toml, "[[colors.indexed_colors]]").unwrap();
+        writeln!(toml, "index = {}", i).unwrap();
+        writeln!(toml, "color = \"{}\"", hex(*c)).unwrap();
+    }
+
+    toml
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::alacritty::colours_toml;
+    use crate::terminal_colours::red_ramp;
+
+    #[test]
+    fn it_writes_valid_toml() {
+        let colours = red_ramp(16);
+        let toml: toml::Table = colours_toml(&colours, false).parse().unwrap();
+
+        assert_eq!(toml["colors"]["primary"]["background"].as_str(), Some("#000000"));
+        assert_eq!(toml["colors"]["primary"]["foreground"].as_str(), Some("#f00000"));
+        assert_eq!(toml["colors"]["normal"]["red"].as_str(), Some("#100000"));
+        assert_eq!(toml["colors"]["bright"]["black"].as_str(), Some("#800000"));
+        assert_eq!(toml["colors"]["bright"]["white"].as_str(), Some("#f00000"));
+    }
+
+    #[test]
+    fn it_writes_the_indexed_colours() {
+        let colours: Vec<Srgb<u8>> = (0..=255).map(|i| Srgb::new(i, 0, 0)).collect();
+        let toml: toml::Table = colours_toml(&colours, false).parse().unwrap();
+
+        let indexed = toml["colors"]["indexed_colors"].as_array().unwrap();
+        assert_eq!(indexed.len(), 240);
+        assert_eq!(indexed[0]["index"].as_integer(), Some(16));
+        assert_eq!(indexed[0]["color"].as_str(), Some("#100000"));
+        assert_eq!(indexed[239]["index"].as_integer(), Some(255));
+
+        let toml: toml::Table = colours_toml(&colours[..16], false).parse().unwrap();
+        assert!(toml["colors"].get("indexed_colors").is_none());
+    }
+
+    #[test]
+    fn it_swaps_the_background_for_a_light_theme() {
+        let colours = red_ramp(16);
+        let toml = colours_toml(&colours, true);
+
+        assert!(
+            toml.starts_with("[colors.primary]\nbackground = \"#f00000\"\nforeground = \"#000000\"\n"),
+            "toml = {}",
+            toml
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+use palette::Srgb;
+
+use crate::errors::Error;
+use crate::terminal_colours::{self, osc4};
+
+// With --apply, we send the terminal colours straight to your terminal, so
+// it changes its palette while it's running, without you editing its config.
+//
+// We write to the terminal rather than stdout, so you can still redirect the
+// usual output somewhere else, e.g. to save the scheme as a file at the same
+// time.  If you need to write somewhere else (e.g. another terminal which
+// you've opened as a file descriptor), use --apply-target.
+
+// Where to send the escape sequences, with --apply-target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    // The controlling terminal, i.e. the terminal you ran the command in.
+    Tty,
+
+    // A file descriptor you've already opened, e.g. `--apply-target=3`.
+    Fd(u32),
+}
+
+pub const DEFAULT_TARGET: &str = "tty";
+
+pub fn parse_target(s: &str) -> Result<Target, String> {
+    if s == "tty" {
+        return Ok(Target::Tty);
+    }
+
+    s.parse::<u32>()
+        .map(Target::Fd)
+        .map_err(|_| String::from("must be `tty` or the number of a file descriptor"))
+}
+
+impl Target {
+    // We open file descriptors through /dev/fd, which is the same as
+    // duplicating them, so we don't close them when we're done.
+    fn path(&self) -> PathBuf {
+        match self {
+            Target::Tty => PathBuf::from("/dev/tty"),
+            Target::Fd(fd) => PathBuf::from(format!("/dev/fd/{}", fd)),
+        }
+    }
+}
+
+// The escape sequences which apply the colours: an OSC 4 sequence for each
+// slot, then OSC 10 and OSC 11 for the default foreground and background,
+// which most terminals use for plain text rather than colours 7 and 0.
+//
+// See https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Operating-System-Commands
+pub fn sequences(colours: &[Srgb<u8>], light: bool) -> String {
+    let (background, foreground) = terminal_colours::background_and_foreground(colours, light);
+
+    let mut output: String = colours.iter().enumerate().map(|(i, c)| osc4(i, *c)).collect();
+
+    output.push_str(&osc_dynamic(10, foreground));
+    output.push_str(&osc_dynamic(11, background));
+
+    output
+}
+
+// An OSC sequence for one of xterm's "dynamic colours", e.g. 10 for the
+// default foreground.  These are written the same as OSC 4, but without
+// a palette index.
+fn osc_dynamic(code: u8, colour: Srgb<u8>) -> String {
+    format!(
+        "\x1b]{};rgb:{:02x}/{:02x}/{:02x}\x1b\\",
+        code, colour.red, colour.green, colour.blue
+    )
+}
+
+// Write the sequences to the target, but only if it's a terminal -- in
+// a file or a pipe, they'd just be noise.
+pub fn apply(colours: &[Srgb<u8>], light: bool, target: Target) -> Result<(), Error> {
+    let path = target.path();
+
+    let no_terminal = || io::Error::other("there's no terminal to apply the colours to; use --osc4 to print the escape sequences instead");
+
+    let mut terminal = match OpenOptions::new().write(true).open(&path) {
+        Ok(file) if file.is_terminal() => file,
+        _ => return Err(Error::Io { path, error: no_terminal() }),
+    };
+
+    terminal
+        .write_all(sequences(colours, light).as_bytes())
+        .and_then(|_| terminal.flush())
+        .map_err(|error| Error::Io { path, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::apply::{apply, parse_target, sequences, Target};
+    use crate::errors::Error;
+
+    // With fewer than 16 colours, the default foreground comes from the
+    // standard ANSI white.
+    #[test]
+    fn it_creates_the_sequences_to_apply_the_colours() {
+        let colours = vec![Srgb::new(0x10, 0x10, 0x10), Srgb::new(0xaa, 0x00, 0x00), Srgb::new(0xf0, 0xf0, 0xe0)];
+
+        assert_eq!(
+            sequences(&colours, false),
+            "\x1b]4;0;rgb:10/10/10\x1b\\\
+             \x1b]4;1;rgb:aa/00/00\x1b\\\
+             \x1b]4;2;rgb:f0/f0/e0\x1b\\\
+             \x1b]10;rgb:ff/ff/ff\x1b\\\
+             \x1b]11;rgb:10/10/10\x1b\\"
+        );
+    }
+
+    #[test]
+    fn it_uses_the_default_colours_from_slots_0_and_15() {
+        let mut colours = vec![Srgb::new(0x80, 0x80, 0x80); 16];
+        colours[0] = Srgb::new(0x1d, 0x1f, 0x21);
+        colours[15] = Srgb::new(0xfd, 0xf6, 0xe3);
+
+        let dark = sequences(&colours, false);
+        assert!(dark.ends_with("\x1b]10;rgb:fd/f6/e3\x1b\\\x1b]11;rgb:1d/1f/21\x1b\\"), "{:?}", dark);
+
+        let light = sequences(&colours, true);
+        assert!(light.ends_with("\x1b]10;rgb:1d/1f/21\x1b\\\x1b]11;rgb:fd/f6/e3\x1b\\"), "{:?}", light);
+
+        assert_eq!(dark.matches("\x1b]4;").count(), 16);
+    }
+
+    #[test]
+    fn it_parses_the_target() {
+        assert_eq!(parse_target("tty"), Ok(Target::Tty));
+        assert_eq!(parse_target("3"), Ok(Target::Fd(3)));
+
+        assert!(parse_target("stdout").is_err());
+        assert!(parse_target("-1").is_err());
+    }
+
+    // Nothing has file descriptor 999 open, so there isn't a terminal there.
+    #[test]
+    fn it_refuses_to_apply_the_colours_without_a_terminal() {
+        let result = apply(&[Srgb::new(0, 0, 0)], false, Target::Fd(999));
+
+        match result {
+            Err(Error::Io { error, .. }) => assert!(error.to_string().starts_with("there's no terminal"), "{}", error),
+            _ => panic!("result = {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use palette::Srgb;
+
+use crate::parse_hex;
+
+// With --background, we draw the image on a solid colour before we look
+// at it, so transparent pixels count as whatever's underneath them -- e.g.
+// a semi-transparent red overlay on a white page looks pink.
+
+pub fn parse(s: &str) -> Result<Srgb<u8>, String> {
+    parse_hex(s).ok_or_else(|| format!("{:?} isn't a hex colour like #ffffff", s))
+}
+
+// Draw a flat buffer of RGBA bytes onto the background colour.  Every
+// pixel in the result is opaque.
+//
+// We blend the sRGB values directly, rather than in linear RGB, because
+// that's what browsers and image editors do, so it matches what you see.
+pub fn composite(bytes: &[u8], background: Srgb<u8>) -> Vec<u8> {
+    let blend = |fg: u8, bg: u8, alpha: u8| {
+        let alpha = alpha as f32 / 255.0;
+        (fg as f32 * alpha + bg as f32 * (1.0 - alpha)).round() as u8
+    };
+
+    bytes
+        .chunks_exact(4)
+        .flat_map(|px| {
+            [
+                blend(px[0], background.red, px[3]),
+                blend(px[1], background.green, px[3]),
+                blend(px[2], background.blue, px[3]),
+                255,
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::background::{composite, parse};
+
+    #[test]
+    fn it_parses_a_background() {
+        assert_eq!(parse("#ffffff"), Ok(Srgb::new(255, 255, 255)));
+        assert_eq!(parse("000000"), Ok(Srgb::new(0, 0, 0)));
+
+        assert!(parse("#fff").is_err());
+        assert!(parse("white").is_err());
+    }
+
+    #[test]
+    fn it_composites_onto_the_background() {
+        let bytes = [255, 0, 0, 255, 255, 0, 0, 128, 255, 0, 0, 0];
+
+        assert_eq!(
+            composite(&bytes, Srgb::new(255, 255, 255)),
+            vec![255, 0, 0, 255, 255, 127, 127, 255, 255, 255, 255, 255]
+        );
+        assert_eq!(
+            composite(&bytes, Srgb::new(0, 0, 0)),
+            vec![255, 0, 0, 255, 128, 0, 0, 255, 0, 0, 0, 255]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::Write;
+
+use palette::{Clamp, FromColor, Lab, Lch, Mix, Srgb};
+
+use crate::distance::to_lab;
+use crate::export::DEFAULT_PALETTE_NAME;
+use crate::json;
+
+// With --format=base16, we print a Base16 scheme, which the Base16 builders
+// can turn into themes for vim, shells, editors and so on:
+//
+//      scheme: "wallpaper"
+//      author: "dominant_colours"
+//      base00: "1d1f21"
+//      ...
+//      base0F: "a3685a"
+//
+// The spec wants the values as lowercase hex strings without a `#`.
+//
+// A Base16 scheme has two halves, and we fill them differently:
+//
+//   - base00 to base07 are a ramp from the background to the brightest
+//     text.  We go from the darkest colour to the lightest in even steps,
+//     mixing them in Lab.  If the image doesn't have much spread (say, a
+//     photo of fog) we mix the dark end with black and the light end with
+//     white, so you can still read the text.
+//
+//   - base08 to base0F are the accents, conventionally red, orange,
+//     yellow, green, cyan, blue, magenta and brown.  We take the most
+//     saturated colours and sort them by hue, starting from red.  If there
+//     aren't eight colours, we go round them again.
+//
+// YAML double-quoted strings are escaped the same way as JSON strings.
+//
+// See https://github.com/chriskempson/base16/blob/main/styling.md
+
+// How dark the dark end of the ramp must be, and how light the light end,
+// as a Lab lightness.
+const DARKEST: f32 = 20.0;
+const LIGHTEST: f32 = 85.0;
+
+// The hue (in Lch) where we start going round the colour wheel, just below
+// red, so pinkish reds come last rather than first.
+const RED_HUE: f32 = 20.0;
+
+pub fn scheme(colours: &[Srgb<u8>], name: &str) -> String {
+    let slots = ramp(colours).into_iter().chain(accents(colours));
+
+    let mut yaml = String::new();
+
+    writeln!(yaml, "scheme: {}", json::string(name)).unwrap();
+    writeln!(yaml, "author: \"{}\"", DEFAULT_PALETTE_NAME).unwrap();
+
+    for (i, c) in slots.enumerate() {
+        writeln!(yaml, "base{:02X}: \"{:02x}{:02x}{:02x}\"", i, c.red, c.green, c.blue).unwrap();
+    }
+
+    yaml
+}
+
+fn ramp(colours: &[Srgb<u8>]) -> Vec<Srgb<u8>> {
+    let labs: Vec<Lab> = colours.iter().map(|c| to_lab(*c)).collect();
+
+    let darkest = labs.iter().copied().min_by(|a, b| a.l.total_cmp(&b.l));
+    let lightest = labs.iter().copied().max_by(|a, b| a.l.total_cmp(&b.l));
+
+    let (black, white) = (Lab::new(0.0, 0.0, 0.0), Lab::new(100.0, 0.0, 0.0));
+
+    // We push the ends towards black and white rather than just changing
+    // their lightness, so they fade out rather than leaving the gamut.
+    let dark = match darkest {
+        Some(dark) if dark.l > DARKEST => dark.mix(&black, 1.0 - DARKEST / dark.l),
+        Some(dark) => dark,
+        None => black,
+    };
+
+    let light = match lightest {
+        Some(light) if light.l < LIGHTEST => light.mix(&white, (LIGHTEST - light.l) / (100.0 - light.l)),
+        Some(light) => light,
+        None => white,
+    };
+
+    (0..8).map(|i| from_lab(dark.mix(&light, i as f32 / 7.0))).collect()
+}
+
+fn accents(colours: &[Srgb<u8>]) -> Vec<Srgb<u8>> {
+    let mut by_chroma: Vec<(Srgb<u8>, Lch)> = colours
+        .iter()
+        .map(|c| (*c, Lch::from_color(to_lab(*c))))
+        .collect();
+
+    by_chroma.sort_by(|(_, a), (_, b)| b.chroma.total_cmp(&a.chroma));
+    by_chroma.truncate(8);
+
+    let from_red = |lch: &Lch| (lch.hue.to_positive_degrees() - RED_HUE).rem_euclid(360.0);
+    by_chroma.sort_by(|(_, a), (_, b)| from_red(a).total_cmp(&from_red(b)));
+
+    match by_chroma.len() {
+        0 => vec![Srgb::new(0, 0, 0); 8],
+        n => (0..8).map(|i| by_chroma[i % n].0).collect(),
+    }
+}
+
+// Mixing in Lab can leave the sRGB gamut, so we clamp back into it.
+fn from_lab(lab: Lab) -> Srgb<u8> {
+    Srgb::from_color(lab).clamp().into_format()
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::base16::{accents, ramp};
+    use crate::distance::to_lab;
+
+    #[test]
+    fn it_ramps_from_the_darkest_to_the_lightest_colour() {
+        let colours = [Srgb::new(128, 128, 128), Srgb::new(255, 255, 255), Srgb::new(0, 0, 0)];
+        let ramp = ramp(&colours);
+
+        assert_eq!(ramp.len(), 8);
+        assert_eq!(ramp[0], Srgb::new(0, 0, 0));
+        assert_eq!(ramp[7], Srgb::new(255, 255, 255));
+
+        for pair in ramp.windows(2) {
+            assert!(to_lab(pair[0]).l < to_lab(pair[1]).l, "ramp = {:?}", ramp);
+        }
+    }
+
+    #[test]
+    fn it_spreads_out_a_ramp_with_no_contrast() {
+        let ramp = ramp(&[Srgb::new(128, 128, 128)]);
+
+        assert!(to_lab(ramp[0]).l < 21.0, "ramp = {:?}", ramp);
+        assert!(to_lab(ramp[7]).l > 84.0, "ramp = {:?}", ramp);
+    }
+
+    #[test]
+    fn it_sorts_the_accents_by_hue_from_red() {
+        let (red, yellow, green, blue) = (Srgb::new(255, 0, 0), Srgb::new(255, 255, 0), Srgb::new(0, 255, 0), Srgb::new(0, 0, 255));
+
+        assert_eq!(
+            accents(&[blue, Srgb::new(128, 128, 128), green, red, yellow]),
+            vec![red, yellow, green, blue, Srgb::new(128, 128, 128), red, yellow, green]
+        );
+    }
+}
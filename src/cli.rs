@@ -1,4 +1,30 @@
 use clap::{Arg, ArgAction, Command};
+use clap_complete::Shell;
+
+use crate::apply;
+use crate::background;
+use crate::clipboard;
+use crate::clustering::{self, ColourCount, SortOrder, Space};
+use crate::compare;
+use crate::css;
+use crate::crop::Crop;
+use crate::download;
+use crate::distance::{self, Distance};
+use crate::exclude::{self, Exclusion};
+use crate::export::{self, ExportFormat};
+use crate::format::OutputFormat;
+use crate::frames::FrameSelection;
+use crate::harmony::Harmony;
+use crate::preview;
+use crate::quantize::Algorithm;
+use crate::roles::Mode;
+use crate::sampling;
+use crate::shell;
+use crate::swatch_image;
+use crate::template::Template;
+use crate::terminal_colours::{self, SlotCount};
+use crate::video;
+use crate::xresources;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -7,25 +33,344 @@ pub fn app() -> Command {
         .version(VERSION)
         .author("Alex Chan <alex@alexwlchan.net>")
         .about("Find the dominant colours in an image")
+        // The `completions` subcommand is a hidden extra; the usual way to
+        // call the tool is still `dominant_colours <PATH> [OPTIONS]`.
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("completions")
+                .about("Print a completion script for your shell")
+                .hide(true)
+                .arg(
+                    Arg::new("SHELL")
+                        .help("which shell to generate completions for")
+                        .required(true)
+                        .value_parser(value_parser!(Shell)),
+                ),
+        )
         .arg(
             Arg::new("PATH")
-                .help("path to the image to inspect")
-                .required(true)
+                .help("path to the image to inspect, a directory of images, an http(s) URL, or - to read an image from stdin")
+                .required_unless_present("FILES-FROM")
                 .index(1),
         )
+        .arg(
+            Arg::new("FILES-FROM")
+                .long("files-from")
+                .value_name("FILE")
+                .help("read a list of images to inspect from this file, one per line, or - to read the list from stdin")
+                .conflicts_with("PATH"),
+        )
+        .arg(
+            Arg::new("recursive")
+                .long("recursive")
+                .help("If PATH is a directory, look at images in its subdirectories as well")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("PATTERN")
+                .long("pattern")
+                .value_name("GLOB")
+                .help("if PATH is a directory, only look at files whose name matches this pattern, e.g. '*.jpg'")
+                .value_parser(|s: &str| glob::Pattern::new(s).map_err(|e| e.to_string())),
+        )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .help("If PATH is a directory, look at hidden files and directories as well")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("MAX-COLOURS")
                 .long("max-colours")
-                .help("how many colours to find")
-                .value_parser(value_parser!(usize))
+                .help("how many colours to find, or `auto` to choose based on the image")
+                .value_parser(ColourCount::parse)
                 .default_value("5"),
         )
+        .arg(
+            Arg::new("MODE")
+                .long("mode")
+                .help("what to print: colours prints the dominant colours, exact counts every exact colour and prints the most common, for pixel art and flat designs, background only looks at the edge of the image and prints its most dominant colour (or --max-colours colours if you pass it), roles picks a colour for each of the roles in Android's Palette API (Vibrant, Dark Vibrant, Light Vibrant, Muted, Dark Muted and Light Muted) from 16 colours, or --max-colours if you pass it, and prints a dash for a role with no colour that fits")
+                .value_parser(value_parser!(Mode))
+                .default_value("colours"),
+        )
+        .arg(
+            Arg::new("HARMONY")
+                .long("harmony")
+                .help("derive a colour scheme from the most dominant colour by rotating its hue, and print the derived colours after the dominant colours")
+                .value_name("HARMONY")
+                .value_parser(value_parser!(Harmony)),
+        )
+        .arg(
+            Arg::new("average")
+                .long("average")
+                .help("Print the average colour of the image, rather than the dominant colours")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["MAX-COLOURS", "terminal-colours", "wal"]),
+        )
+        .arg(
+            Arg::new("MIN-PERCENTAGE")
+                .long("min-percentage")
+                .help("leave out any colours which cover less than this percentage of the image")
+                .value_parser(clustering::parse_percentage)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("MIN-DISTANCE")
+                .long("min-distance")
+                .help("merge any colours closer than this CIEDE2000 delta E into the bigger one, so you may get fewer colours; around 2.3 is just noticeable")
+                .value_name("DELTA-E")
+                .value_parser(distance::parse_delta_e),
+        )
+        .arg(
+            Arg::new("pad-colours")
+                .long("pad-colours")
+                .help("Always print --max-colours colours, even if the image has fewer, by keeping colours that look the same and repeating the last colour")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("MAX-AUTO-COLOURS")
+                .long("max-auto-colours")
+                .help("with --max-colours=auto, the most colours to consider")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..=256))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("RUNS")
+                .long("runs")
+                .help("how many times to run k-means with different seeds, keeping the colours which fit the pixels best")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("BORDER-WIDTH")
+                .long("border-width")
+                .value_name("PERCENT")
+                .help("with --mode=background, how much of the edge of the image to look at, as a percentage of its width and height")
+                .value_parser(sampling::parse_border_width)
+                .default_value(sampling::DEFAULT_BORDER_WIDTH),
+        )
+        .arg(
+            Arg::new("MAX-EXACT-COLOURS")
+                .long("max-exact-colours")
+                .value_name("N")
+                .help("with --mode=exact, give up on any image with more than this many different colours, because it's probably a photo")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .default_value("65536"),
+        )
+        .arg(
+            Arg::new("MAX-ITERATIONS")
+                .long("max-iterations")
+                .help("the most iterations of k-means in each run, if it hasn't converged sooner")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("CONVERGENCE")
+                .long("convergence")
+                .help("stop k-means when the colours move less than this between iterations; smaller values take longer but give more settled colours")
+                .value_name("SCORE")
+                .value_parser(clustering::parse_convergence)
+                .default_value("1.0"),
+        )
+        .arg(
+            Arg::new("SAMPLE-SIZE")
+                .long("sample-size")
+                .help("the most pixels to look at; larger images are randomly sampled")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .default_value("200000"),
+        )
+        .arg(
+            Arg::new("SAMPLE-RATE")
+                .long("sample-rate")
+                .help("randomly look at this fraction of the pixels, e.g. 0.05 for 5% (between 0 and 1)")
+                .value_parser(sampling::parse_rate)
+                .default_value("1.0"),
+        )
+        .arg(
+            Arg::new("CENTER-WEIGHT")
+                .long("center-weight")
+                .visible_alias("centre-weight")
+                .value_name("WEIGHT")
+                .help("look at more of the pixels near the centre of the image, from 0 (every pixel counts the same) to 1 (hardly any pixels in the corners count)")
+                .value_parser(sampling::parse_center_weight)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("EXCLUDE")
+                .long("exclude")
+                .help("leave out pixels close to this colour, e.g. #ff00ff or #ff00ff:5 with a tolerance (delta E, default 10); can be repeated")
+                .value_name("COLOUR")
+                .value_parser(Exclusion::parse)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("EXCLUDE-WHITE")
+                .long("exclude-white")
+                .help("leave out pixels lighter than this lightness (the L of CIELAB, from 0 to 100, default 95), e.g. a white background; percentages are then of the pixels that are left")
+                .value_name("L")
+                .value_parser(exclude::parse_lightness)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(exclude::DEFAULT_WHITE_LIGHTNESS),
+        )
+        .arg(
+            Arg::new("EXCLUDE-BLACK")
+                .long("exclude-black")
+                .help("leave out pixels darker than this lightness (the L of CIELAB, from 0 to 100, default 5), e.g. a black border; percentages are then of the pixels that are left")
+                .value_name("L")
+                .value_parser(exclude::parse_lightness)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(exclude::DEFAULT_BLACK_LIGHTNESS),
+        )
+        .arg(
+            Arg::new("MIN-SATURATION")
+                .long("min-saturation")
+                .help("leave out pixels whose HSL saturation is below this percentage, e.g. 20 to find the accent colours among greys")
+                .value_name("PERCENT")
+                .value_parser(clustering::parse_percentage),
+        )
+        .arg(
+            Arg::new("MIN-SATURATION-FALLBACK")
+                .long("min-saturation-fallback")
+                .help("with --min-saturation, if less than this percentage of the pixels are left, look at every pixel instead")
+                .value_name("PERCENT")
+                .value_parser(clustering::parse_percentage)
+                .default_value(exclude::DEFAULT_SATURATION_FALLBACK),
+        )
+        .arg(
+            Arg::new("ALPHA-THRESHOLD")
+                .long("alpha-threshold")
+                .help("leave out pixels which are more transparent than this, from 0 (fully transparent) to 255 (opaque)")
+                .value_name("ALPHA")
+                .value_parser(value_parser!(u8))
+                .default_value("128"),
+        )
+        .arg(
+            Arg::new("include-transparent")
+                .long("include-transparent")
+                .help("Look at transparent pixels, rather than leaving them out")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("ALPHA-THRESHOLD"),
+        )
+        .arg(
+            Arg::new("BACKGROUND")
+                .long("background")
+                .help("draw the image on this colour before looking at it, so transparent pixels count as the background, e.g. #ffffff")
+                .value_name("COLOUR")
+                .value_parser(background::parse)
+                .conflicts_with("ALPHA-THRESHOLD"),
+        )
+        .arg(
+            Arg::new("MAX-DOWNLOAD-SIZE")
+                .long("max-download-size")
+                .help("if PATH is a URL, the biggest image to download, e.g. 50M")
+                .value_name("SIZE")
+                .value_parser(download::parse_size)
+                .default_value("50M"),
+        )
+        .arg(
+            Arg::new("MAX-DIMENSION")
+                .long("max-dimension")
+                .help("resize the image to fit in a square this big before looking at it; smaller is faster")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<u32>::new().range(1..))
+                .default_value("400"),
+        )
+        .arg(
+            Arg::new("no-resize")
+                .long("no-resize")
+                .help("Look at every pixel of the image, rather than resizing it first; this can be slow for large images")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("MAX-DIMENSION"),
+        )
+        .arg(
+            Arg::new("SVG-SIZE")
+                .long("svg-size")
+                .help("draw SVGs to fit in a square this big before looking at them")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<u32>::new().range(1..))
+                .default_value("512"),
+        )
+        .arg(
+            Arg::new("PAGE")
+                .long("page")
+                .help("for PDFs and multi-page TIFFs, which page to look at, counting from 1 (PDFs use page 1 by default; TIFFs look at every page)")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("PDF-DPI")
+                .long("pdf-dpi")
+                .help("for PDFs, how many pixels per inch to draw the page at")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<u32>::new().range(1..))
+                .default_value("150"),
+        )
+        .arg(
+            Arg::new("CROP")
+                .long("crop")
+                .help("only look at part of the image, as x,y,width,height in pixels or percentages, e.g. 0,0,1920,200 or 0%,0%,100%,20%")
+                .value_name("REGION")
+                .value_parser(Crop::parse)
+        )
+        .arg(
+            Arg::new("SPACE")
+                .long("space")
+                .visible_alias("colorspace")
+                .help("which colour space to find the colours in: lab (CIELAB) and oklab are designed so that distances match how different colours look, and oklab keeps blues and purples apart better; rgb (or srgb) and hsv are simpler")
+                .value_parser(value_parser!(Space))
+                .default_value("lab"),
+        )
+        .arg(
+            Arg::new("ALGORITHM")
+                .long("algorithm")
+                .help("how to find the colours: kmeans finds colours which are a good average of the pixels around them, median-cut and octree are faster and better at keeping small-but-distinct colours, and always find the same colours, so they always look at the same sample of the pixels (--sample-size and --sample-rate still apply) and ignore --seed, --runs and --space")
+                .value_parser(value_parser!(Algorithm))
+                .default_value("kmeans"),
+        )
+        .arg(
+            Arg::new("SORT")
+                .long("sort")
+                .help("which order to print the colours in: size (or dominance) puts the biggest first, luminance the lightest, hue goes around the colour wheel from red with greys last, and none leaves them as k-means found them")
+                .value_parser(value_parser!(SortOrder))
+                .default_value("size"),
+        )
         .arg(
             Arg::new("no-palette")
                 .long("no-palette")
                 .help("Just print the hex values, not colour previews")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("show-percentage")
+                .long("show-percentage")
+                .help("Print what percentage of the image is closest to each colour")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["terminal-colours", "wal", "COMPARE", "osc4", "EXPORT", "json"]),
+        )
+        .arg(
+            Arg::new("names")
+                .long("names")
+                .help("Print the nearest CSS named colour after each colour, e.g. `tomato (ΔE 3.2)`, and add it to the JSON output")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("contrast")
+                .long("contrast")
+                .help("Print whether to use black or white text on each colour, whichever has the higher WCAG contrast ratio, and the ratio, e.g. `→ text #ffffff (4.6:1)`")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("CONTRAST-AGAINST"),
+        )
+        .arg(
+            Arg::new("CONTRAST-AGAINST")
+                .long("contrast-against")
+                .help("print the WCAG contrast ratio between each colour and this colour instead, and flag any below 4.5:1, e.g. #ffffff")
+                .value_name("COLOUR")
+                .value_parser(background::parse),
+        )
         .arg(
             Arg::new("SEED")
                 .long("seed")
@@ -42,13 +387,397 @@ pub fn app() -> Command {
         .arg(
             Arg::new("terminal-colours")
                 .long("terminal-colours")
-                .help("Generate 16 colours for the terminal")
-                .action(ArgAction::SetTrue),
+                .help("generate 16 colours for the terminal, or with --terminal-colours=256, all 256 of xterm's colours, with the colour cube and grey ramp built from the 16")
+                .value_name("COUNT")
+                .value_parser(value_parser!(SlotCount))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("16"),
+        )
+        .arg(
+            Arg::new("BRIGHTNESS")
+                .long("brightness")
+                .value_name("PERCENT")
+                .help("for terminal-colours, how far to push colours 1-7 and 9-15 towards full brightness, from 0 (leave them alone) to 100; this is the value in HSV, so at 100 each colour is as bright as it can be with the same hue and saturation (like --max-brightness), rather than white as it would be in LCh")
+                .value_parser(clap::builder::RangedU64ValueParser::<u8>::new().range(0..=100))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("SATURATION")
+                .long("saturation")
+                .value_name("PERCENT")
+                .help("for terminal-colours, how far to push colours 1-7 and 9-15 towards full saturation (in HSV, like --brightness), from 0 (leave them alone) to 100")
+                .value_parser(clap::builder::RangedU64ValueParser::<u8>::new().range(0..=100))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("MIN-BRIGHTNESS")
+                .long("min-brightness")
+                .value_name("LIGHTNESS")
+                .help("for terminal-colours, raise colours 1-6 and 9-14 which are darker than this to this lightness (L* in Lab, from 0 to 100), keeping their hue; lighter colours are left alone")
+                .value_parser(clap::builder::RangedU64ValueParser::<u8>::new().range(0..=100))
+                .default_value("0"),
         )
         .arg(
             Arg::new("max-brightness")
                 .long("max-brightness")
-                .help("For terminal-colours, ignore darker colours and only generate the bright ones")
+                .help("Deprecated: the same as --brightness=100")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("BRIGHTNESS"),
+        )
+        .arg(
+            Arg::new("MIN-CONTRAST")
+                .long("min-contrast")
+                .value_name("RATIO")
+                .help("for terminal-colours, the smallest contrast ratio between the background (colour 0) and the foreground (colours 7 and 15), from 1 to 21; colours 1-6 get at least 3, or this if it's lower, and 1 turns it off")
+                .value_parser(terminal_colours::parse_min_contrast)
+                .default_value(terminal_colours::DEFAULT_MIN_CONTRAST),
+        )
+        .arg(
+            Arg::new("keep-neutrals")
+                .long("keep-neutrals")
+                .help("For terminal-colours, keep the black, white and greys (colours 0, 7, 8 and 15) from the palette, and only pick the other colours from the image")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("TERMINAL-PALETTE")
+                .long("terminal-palette")
+                .help("for terminal-colours, a file with 16 hex colours (one per line) to match against, instead of the standard ANSI colours, or a .toml file with a colour for each named slot, e.g. bright_red = \"#ff5555\"")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("show-index")
+                .long("show-index")
+                .help("For terminal-colours, print the colour number (0-15, or 0-255 with --terminal-colours=256) before each colour")
+                .action(ArgAction::SetTrue)
+                .requires("terminal-colours")
+                .conflicts_with_all(["json", "EXPORT", "wal"]),
+        )
+        .arg(
+            Arg::new("osc4")
+                .long("osc4")
+                .help("For terminal-colours, print OSC 4 escape sequences which set the terminal's palette")
+                .action(ArgAction::SetTrue)
+                .requires("terminal-colours")
+                .conflicts_with_all(["show-index", "json", "EXPORT", "wal"]),
+        )
+        .arg(
+            Arg::new("apply")
+                .long("apply")
+                .help("For terminal-colours, change the colours of the terminal you're running in, by sending it OSC 4, 10 and 11 escape sequences")
+                .action(ArgAction::SetTrue)
+                .requires("terminal-colours")
+                .conflicts_with("wal"),
+        )
+        .arg(
+            Arg::new("APPLY-TARGET")
+                .long("apply-target")
+                .value_name("TARGET")
+                .help("with --apply, where to send the escape sequences: tty (the terminal you're running in) or the number of a file descriptor")
+                .value_parser(apply::parse_target)
+                .default_value(apply::DEFAULT_TARGET),
+        )
+        .arg(
+            Arg::new("preview")
+                .long("preview")
+                .help("For terminal-colours, print a preview of the colours after them, with some text in each colour on the background")
+                .action(ArgAction::SetTrue)
+                .requires("terminal-colours")
+                .conflicts_with_all(["osc4", "json", "EXPORT", "wal"]),
+        )
+        .arg(
+            Arg::new("COLOR")
+                .long("color")
+                .visible_alias("colour")
+                .value_name("WHEN")
+                .help("with --preview, when to print the preview: auto (only if stdout is a terminal), always, or never")
+                .value_parser(value_parser!(preview::When))
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("DISTANCE")
+                .long("distance")
+                .visible_alias("mapping-metric")
+                .help("with --terminal-colours, how to measure the distance between two colours when picking the closest colour for each slot: euclidean (or rgb) is a weighted distance in RGB, lab is the plain distance in Lab (CIE76), and ciede2000 is the distance in Lab which best matches how different colours look")
+                .value_parser(value_parser!(Distance))
+                .default_value("ciede2000"),
+        )
+        .arg(
+            Arg::new("wal")
+                .long("wal")
+                .help("Print the terminal colours as JSON in the same format as pywal's colors.json")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("EXPORT")
+                .long("export")
+                .help("write the colours as a palette file in this format")
+                .value_parser(value_parser!(ExportFormat))
+                .conflicts_with_all(["wal", "COMPARE"]),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .long("output")
+                .visible_alias("output-file")
+                .help("write the output to this file, rather than printing it")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("no-clobber")
+                .long("no-clobber")
+                .help("With --output, fail rather than replace a file that already exists")
+                .action(ArgAction::SetTrue)
+                .requires("OUTPUT"),
+        )
+        .arg(
+            Arg::new("PALETTE-NAME")
+                .long("palette-name")
+                .help("with --export=gpl, the name of the palette (default: the name of the image)")
+                .value_name("NAME"),
+        )
+        .arg(
+            Arg::new("SWATCH-NAME")
+                .long("swatch-name")
+                .help("with --export, how to name each colour, where {index} counts from 1 and {hex} is the hex value, e.g. cover-{index}")
+                .value_name("TEMPLATE")
+                .value_parser(export::parse_swatch_name)
+                .default_value(export::DEFAULT_SWATCH_NAME),
+        )
+        .arg(
+            Arg::new("SAVE-SWATCH")
+                .long("save-swatch")
+                .help("also save the colours as a PNG, drawn as a strip of blocks in the order they're printed")
+                .value_name("FILE")
+                .conflicts_with_all(["wal", "COMPARE"]),
+        )
+        .arg(
+            Arg::new("SWATCH-BLOCK-SIZE")
+                .long("swatch-block-size")
+                .help("with --save-swatch, the width and height of each block, in pixels")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<u32>::new().range(1..=4096))
+                .default_value(swatch_image::DEFAULT_BLOCK_SIZE),
+        )
+        .arg(
+            Arg::new("COPY")
+                .long("copy")
+                .help("copy the Nth colour (default: the first) to the clipboard, or `all` for every colour")
+                .value_name("N")
+                .value_parser(clipboard::Selection::parse)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("1")
+                .conflicts_with_all(["wal", "osc4", "COMPARE"]),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print the colours as JSON, as a map from each path to its colours")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["wal", "EXPORT", "COMPARE"]),
+        )
+        .arg(
+            Arg::new("FORMAT")
+                .long("format")
+                .help("how to print the colours; text prints hex strings, rgb and hsl print CSS rgb() and hsl() values, json prints a JSON document with each colour's hex and RGB values, the path and the seed, csv and tsv print a table with a row for each colour, xresources, alacritty, kitty, windows-terminal, itermcolors and wezterm print the terminal colours as X resources or a colour scheme for that terminal, tmux prints tmux options which use the most dominant colour as the status line's background and the most saturated other colour as the accent for the current window, messages and the active pane's border, base16 prints a Base16 scheme with a ramp from the darkest to the lightest colour and the most saturated colours as accents, sh prints shell variables you can eval, bash adds an array of all the colours, html prints a self-contained page with a swatch for each colour, css prints CSS custom properties, scss prints SCSS variables, css-gradient prints a CSS linear-gradient() with a band for each colour as wide as its share of the image, and gpl and ase print palette files like --export")
+                .value_parser(value_parser!(OutputFormat))
+                .default_value("text")
+                .requires_if("xresources", "terminal-colours")
+                .requires_if("alacritty", "terminal-colours")
+                .requires_if("kitty", "terminal-colours")
+                .requires_if("windows-terminal", "terminal-colours")
+                .requires_if("itermcolors", "terminal-colours")
+                .requires_if("wezterm", "terminal-colours")
+                .conflicts_with_all(["json", "wal", "EXPORT", "COMPARE", "osc4", "show-index"]),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .help("With --format=csv or tsv, print a row of column names first (the default)")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-header"),
+        )
+        .arg(
+            Arg::new("no-header")
+                .long("no-header")
+                .help("With --format=csv or tsv, don't print the row of column names")
+                .action(ArgAction::SetTrue)
+                .overrides_with("header"),
+        )
+        .arg(
+            Arg::new("TEMPLATE")
+                .long("template")
+                .help("print a line for each colour in this format, with the placeholders {hex}, {HEX}, {r}, {g}, {b}, {h}, {s}, {l}, {index} (counting from 1), {percent} and {path}, e.g. \"{hex} {r},{g},{b} {percent}\"; use {{ and }} for literal braces")
+                .value_parser(Template::parse)
+                .conflicts_with_all(["json", "wal", "EXPORT", "COMPARE", "osc4", "show-index", "FORMAT"]),
+        )
+        .arg(
+            Arg::new("light-theme")
+                .long("light-theme")
+                .help("With --format=xresources, alacritty, kitty, windows-terminal, itermcolors or wezterm, or --apply, use the light colour (15) for the background and the dark colour (0) for the text")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("CURSOR-COLOUR")
+                .long("cursor-colour")
+                .help("with --format=kitty, windows-terminal, itermcolors or wezterm, the colour of the cursor (default: the text colour), e.g. #ffffff")
+                .value_name("COLOUR")
+                .value_parser(background::parse),
+        )
+        .arg(
+            Arg::new("SELECTION-COLOUR")
+                .long("selection-colour")
+                .help("with --format=kitty, windows-terminal, itermcolors or wezterm, the background colour of selected text (default: a dim version of colour 4), e.g. #44475a")
+                .value_name("COLOUR")
+                .value_parser(background::parse),
+        )
+        .arg(
+            Arg::new("SCHEME-NAME")
+                .long("scheme-name")
+                .help("with --format=windows-terminal, wezterm or base16, the name of the colour scheme (default: the name of the image)")
+                .value_name("NAME"),
+        )
+        .arg(
+            Arg::new("XRESOURCES-PREFIX")
+                .long("prefix")
+                .help("with --format=xresources, the program to set the colours for, e.g. URxvt (default: every program)")
+                .value_name("PROGRAM")
+                .value_parser(xresources::parse_prefix)
+                .default_value(xresources::DEFAULT_PREFIX),
+        )
+        .arg(
+            Arg::new("BACKGROUND-INDEX")
+                .long("background-index")
+                .help("with --format=tmux, use the Nth colour (counting from 1) as the background instead of the most dominant colour")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("ACCENT-INDEX")
+                .long("accent-index")
+                .help("with --format=tmux, use the Nth colour (counting from 1) as the accent instead of the most saturated colour")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("CSS-PREFIX")
+                .long("css-prefix")
+                .help("with --format=css or scss, the start of each property or variable name, e.g. brand for --brand-1, --brand-2, ...")
+                .value_name("PREFIX")
+                .value_parser(css::parse_prefix)
+                .default_value(css::DEFAULT_PREFIX),
+        )
+        .arg(
+            Arg::new("VAR-PREFIX")
+                .long("var-prefix")
+                .help("with --format=sh or bash, the start of each variable name, e.g. WALLPAPER for WALLPAPER_1, WALLPAPER_2, ...")
+                .value_name("PREFIX")
+                .value_parser(shell::parse_prefix)
+                .default_value(shell::DEFAULT_PREFIX),
+        )
+        .arg(
+            Arg::new("CSS-SELECTOR")
+                .long("css-selector")
+                .help("with --format=css, the selector for the rule which holds the properties")
+                .value_name("SELECTOR")
+                .value_parser(css::parse_selector)
+                .default_value(css::DEFAULT_SELECTOR),
+        )
+        .arg(
+            Arg::new("GRADIENT-ANGLE")
+                .long("gradient-angle")
+                .help("with --format=css-gradient, the direction of the gradient in degrees; 90 goes from left to right, 180 from top to bottom")
+                .value_name("DEGREES")
+                .value_parser(css::parse_angle)
+                .allow_hyphen_values(true)
+                .default_value(css::DEFAULT_GRADIENT_ANGLE),
+        )
+        .arg(
+            Arg::new("gradient-even")
+                .long("gradient-even")
+                .help("With --format=css-gradient, give every colour the same width, rather than its share of the image")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("scss-map")
+                .long("scss-map")
+                .help("With --format=scss, also print a Sass map of all the colours, e.g. $dominant-colours: (1: #ff0000, 2: #00ff00)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("COMPARE")
+                .long("compare")
+                .help("compare the colours with those of another image, and print how similar they are")
+                .value_name("OTHER_PATH")
+                .conflicts_with_all(["terminal-colours", "wal"]),
+        )
+        .arg(
+            Arg::new("THRESHOLD")
+                .long("threshold")
+                .help("with --compare, exit with status 4 if the similarity is below this (between 0 and 1)")
+                .value_parser(compare::parse_threshold)
+                .requires("COMPARE"),
+        )
+        .arg(
+            Arg::new("REQUIRE-DISTINCT")
+                .long("require-distinct")
+                .help("exit with status 4 if there are fewer than this many perceptually distinct colours")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .conflicts_with("COMPARE"),
+        )
+        .arg(
+            Arg::new("dedupe-frames")
+                .long("dedupe-frames")
+                .help("For animated GIFs, skip frames which are the same as the previous frame, so a long pause on one frame doesn't take over the palette (the default)")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-dedupe-frames"),
+        )
+        .arg(
+            Arg::new("no-dedupe-frames")
+                .long("no-dedupe-frames")
+                .help("For animated GIFs, look at every frame, even if it's a duplicate")
+                .action(ArgAction::SetTrue)
+                .overrides_with("dedupe-frames"),
+        )
+        .arg(
+            Arg::new("video")
+                .long("video")
+                .help("Treat PATH as a video, whatever it looks like")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("FRAMES")
+                .long("frames")
+                .help("for animations, which frames to look at: auto, all, first, every:<N> or index:<N> (counting from 0)")
+                .value_name("WHICH")
+                .value_parser(FrameSelection::parse)
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("MAX-FRAMES")
+                .long("max-frames")
+                .help("for animations and videos, the most frames to look at (videos look at 25 by default)")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("SAMPLE-EVERY")
+                .long("sample-every")
+                .help("for videos, how far apart to take frames, e.g. 2s or 500ms (by default, frames are spread evenly through the video)")
+                .value_name("INTERVAL")
+                .value_parser(video::parse_interval),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .help("Print diagnostic information to stderr")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .help("Don't print anything to stdout; useful with --require-distinct")
                 .action(ArgAction::SetTrue),
         )
 }
@@ -0,0 +1,175 @@
+use palette::Srgb;
+
+use crate::hex;
+
+// Which colours to copy with --copy: the Nth colour (counting from 1, so
+// `--copy` on its own copies the first, most dominant colour), or all of
+// them, one per line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    Nth(usize),
+    All,
+}
+
+impl Selection {
+    pub fn parse(s: &str) -> Result<Selection, String> {
+        if s == "all" {
+            return Ok(Selection::All);
+        }
+
+        match s.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(Selection::Nth(n)),
+            Ok(_) => Err(String::from("must be at least 1 (the first colour)")),
+            Err(_) => Err(String::from("must be a number or `all`")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CopyError {
+    // The user asked for a colour we didn't find.
+    OutOfRange { n: usize, colour_count: usize },
+
+    // There's no clipboard we can use, e.g. on a headless server.
+    Unavailable(String),
+}
+
+// This is a trait so the tests can swap in a fake clipboard, rather than
+// overwriting whatever's on the clipboard of the machine running them.
+pub trait Clipboard {
+    fn set_text(&mut self, text: String) -> Result<(), String>;
+}
+
+pub struct SystemClipboard;
+
+// On Linux, the clipboard doesn't hold the text itself: the app which copied
+// it has to hand it over to whichever app pastes it, so it disappears when
+// we exit.  Instead, we start a copy of ourselves in the background which
+// holds on to it until something else is copied, the same as `xclip` and
+// `wl-copy` do.  We pass it this argument so it knows that's its job.
+//
+// See https://github.com/1Password/arboard/blob/master/examples/daemonize.rs
+pub const DAEMON_ARG: &str = "__dominant_colours_clipboard_daemon";
+
+impl Clipboard for SystemClipboard {
+    #[cfg(target_os = "linux")]
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        // Check there's a clipboard before we start the background process,
+        // because it has nowhere to tell us if there isn't.
+        arboard::Clipboard::new().map_err(|e| e.to_string())?;
+
+        std::process::Command::new(std::env::current_exe().map_err(|e| e.to_string())?)
+            .args([DAEMON_ARG, &text])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .current_dir("/")
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(text))
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Run as the background process which holds the clipboard, until something
+// else is copied.
+#[cfg(target_os = "linux")]
+pub fn serve(text: String) {
+    use arboard::SetExtLinux;
+
+    let _ = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set().wait().text(text));
+}
+
+pub fn copy(clipboard: &mut impl Clipboard, colours: &[Srgb<u8>], selection: Selection) -> Result<(), CopyError> {
+    let text = match selection {
+        Selection::All => colours.iter().map(|c| hex(*c)).collect::<Vec<_>>().join("\n"),
+        Selection::Nth(n) => match colours.get(n - 1) {
+            Some(c) => hex(*c),
+            None => {
+                return Err(CopyError::OutOfRange {
+                    n,
+                    colour_count: colours.len(),
+                })
+            }
+        },
+    };
+
+    clipboard.set_text(text).map_err(CopyError::Unavailable)
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::clipboard::{copy, Clipboard, CopyError, Selection};
+
+    #[derive(Default)]
+    struct FakeClipboard {
+        text: Option<String>,
+    }
+
+    impl Clipboard for FakeClipboard {
+        fn set_text(&mut self, text: String) -> Result<(), String> {
+            self.text = Some(text);
+            Ok(())
+        }
+    }
+
+    struct HeadlessClipboard;
+
+    impl Clipboard for HeadlessClipboard {
+        fn set_text(&mut self, _text: String) -> Result<(), String> {
+            Err(String::from("no display"))
+        }
+    }
+
+    fn colours() -> Vec<Srgb<u8>> {
+        vec![Srgb::new(255, 0, 0), Srgb::new(18, 52, 86)]
+    }
+
+    #[test]
+    fn it_parses_the_selection() {
+        assert_eq!(Selection::parse("1"), Ok(Selection::Nth(1)));
+        assert_eq!(Selection::parse("all"), Ok(Selection::All));
+        assert!(Selection::parse("0").is_err());
+        assert!(Selection::parse("first").is_err());
+    }
+
+    #[test]
+    fn it_copies_one_colour() {
+        let mut clipboard = FakeClipboard::default();
+        copy(&mut clipboard, &colours(), Selection::Nth(2)).unwrap();
+
+        assert_eq!(clipboard.text, Some(String::from("#123456")));
+    }
+
+    #[test]
+    fn it_copies_all_the_colours() {
+        let mut clipboard = FakeClipboard::default();
+        copy(&mut clipboard, &colours(), Selection::All).unwrap();
+
+        assert_eq!(clipboard.text, Some(String::from("#ff0000\n#123456")));
+    }
+
+    #[test]
+    fn it_fails_if_the_colour_is_out_of_range() {
+        let mut clipboard = FakeClipboard::default();
+        let result = copy(&mut clipboard, &colours(), Selection::Nth(3));
+
+        assert_eq!(result, Err(CopyError::OutOfRange { n: 3, colour_count: 2 }));
+        assert_eq!(clipboard.text, None);
+    }
+
+    #[test]
+    fn it_reports_a_missing_clipboard() {
+        let result = copy(&mut HeadlessClipboard, &colours(), Selection::Nth(1));
+
+        assert_eq!(result, Err(CopyError::Unavailable(String::from("no display"))));
+    }
+}
@@ -0,0 +1,777 @@
+use clap::ValueEnum;
+use kmeans_colors::{get_kmeans, get_kmeans_hamerly, Calculate, Kmeans};
+use palette::{FromColor, Hsv, IntoColor, Lab, Lch, LinSrgb, Oklab, Pixel, Srgb, Srgba};
+use rand::Rng;
+
+use crate::distance::{Distance, GREY_CHROMA};
+
+// The colour spaces we can run the k-means clustering in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Space {
+    // CIELAB is designed so that distances roughly match how different
+    // two colours look, which is what we usually want.
+    Lab,
+
+    // Oklab is a newer take on CIELAB, which keeps hues more even -- in
+    // CIELAB, blues drift towards purple as they get lighter, so blue and
+    // violet clusters can split oddly.
+    // See https://bottosson.github.io/posts/oklab/
+    Oklab,
+
+    // Clustering in sRGB gives "flatter" results, which can be better for
+    // pixel art and flat designs.
+    #[value(alias = "srgb")]
+    Rgb,
+
+    // Clustering in HSV groups colours by hue.
+    Hsv,
+}
+
+// The order to print the colours in.  Every order except `none` breaks
+// ties by hex value, so the output is the same every time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    // Biggest cluster first.
+    #[value(alias = "dominance")]
+    Size,
+
+    // Lightest first, by the L of CIELAB.
+    Luminance,
+
+    // Around the colour wheel from red, by the hue of CIELCh, with greys
+    // (which don't really have a hue) at the end, lightest first.
+    Hue,
+
+    // Whatever order the k-means library gave us.
+    None,
+}
+
+// Centroids closer than this (as a CIEDE2000 delta E) are the same colour
+// as far as anybody can tell, e.g. two clusters which split a solid colour
+// and came out a bit of float noise apart.
+pub const DUPLICATE_DISTANCE: f32 = 1.0;
+
+// How many colours to look for: either a fixed number, or `auto` to
+// pick a number based on the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColourCount {
+    Fixed(usize),
+    Auto,
+}
+
+impl ColourCount {
+    pub fn parse(s: &str) -> Result<ColourCount, String> {
+        if s == "auto" {
+            return Ok(ColourCount::Auto);
+        }
+
+        // k-means can't find zero colours, so we'd never have anything
+        // to print.
+        match s.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(ColourCount::Fixed(n)),
+            Ok(_) => Err(String::from("must be at least 1, or `auto`")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+// The result of a k-means run, with the centroids converted back to sRGB.
+//
+// The `score` is the library's convergence score (how far the centroids
+// moved on the last iteration); the `error` is the within-cluster sum of
+// squared distances, which tells us how well the centroids fit the pixels.
+// `counts` is how many pixels were assigned to each centroid.
+pub struct Clustering {
+    pub score: f32,
+    pub error: f32,
+    pub centroids: Vec<Srgb<u8>>,
+    pub counts: Vec<usize>,
+}
+
+impl Clustering {
+    // Drop any centroids which cover less than `min_percentage` of the
+    // pixels.  We always keep the biggest centroid, even if it's below
+    // the threshold, so there's something to print.
+    pub fn drop_small_clusters(&mut self, min_percentage: f32) {
+        let total: usize = self.counts.iter().sum();
+
+        let largest = self
+            .counts
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, count)| **count)
+            .map(|(i, _)| i);
+
+        let keep: Vec<bool> = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| Some(i) == largest || *count as f32 * 100.0 >= min_percentage * total as f32)
+            .collect();
+
+        let mut keep_iter = keep.iter();
+        self.centroids.retain(|_| *keep_iter.next().unwrap());
+
+        let mut keep_iter = keep.iter();
+        self.counts.retain(|_| *keep_iter.next().unwrap());
+    }
+
+    // Drop any centroids which have no pixels.  k-means can leave these
+    // behind if the image has fewer colours than we asked for, and we don't
+    // want to print a colour that isn't in the image.
+    pub fn drop_empty_clusters(&mut self) {
+        let keep: Vec<bool> = self.counts.iter().map(|count| *count > 0).collect();
+
+        let mut keep_iter = keep.iter();
+        self.centroids.retain(|_| *keep_iter.next().unwrap());
+
+        self.counts.retain(|count| *count > 0);
+    }
+
+    // Merge any centroids which are closer than `min_distance` (as a
+    // CIEDE2000 delta E), so we don't print colours that look the same.
+    // We merge the closest pair first, and the bigger cluster keeps its
+    // colour and gains the smaller cluster's pixels.  This may leave fewer
+    // colours than we asked for.
+    pub fn merge_close_colours(&mut self, min_distance: f32) {
+        loop {
+            let closest = (0..self.centroids.len())
+                .flat_map(|i| (i + 1..self.centroids.len()).map(move |j| (i, j)))
+                .map(|(i, j)| (i, j, Distance::Ciede2000.between(self.centroids[i], self.centroids[j])))
+                .filter(|(_, _, d)| *d < min_distance)
+                .fold(None, |best: Option<(usize, usize, f32)>, this| match best {
+                    Some(best) if best.2 <= this.2 => Some(best),
+                    _ => Some(this),
+                });
+
+            let (i, j) = match closest {
+                Some((i, j, _)) => (i, j),
+                None => return,
+            };
+
+            // On a tie, the earlier cluster keeps its colour.
+            let (keep, merge) = if self.counts[j] > self.counts[i] { (j, i) } else { (i, j) };
+
+            self.counts[keep] += self.counts[merge];
+            self.counts.remove(merge);
+            self.centroids.remove(merge);
+        }
+    }
+
+    pub fn sort(&mut self, order: SortOrder) {
+        if order == SortOrder::None {
+            return;
+        }
+
+        let mut clusters: Vec<(Srgb<u8>, usize)> = self.centroids.iter().copied().zip(self.counts.iter().copied()).collect();
+
+        let lch = |c: &Srgb<u8>| Lch::from_color(c.into_format::<f32>());
+        let hex = |c: &Srgb<u8>| (c.red, c.green, c.blue);
+
+        match order {
+            SortOrder::Size => clusters.sort_by_key(|(c, count)| (std::cmp::Reverse(*count), hex(c))),
+            SortOrder::Luminance => clusters.sort_by(|(a, _), (b, _)| lch(b).l.total_cmp(&lch(a).l).then(hex(a).cmp(&hex(b)))),
+            SortOrder::Hue => clusters.sort_by(|(a, _), (b, _)| {
+                let (a_lch, b_lch) = (lch(a), lch(b));
+                let (a_is_grey, b_is_grey) = (a_lch.chroma < GREY_CHROMA, b_lch.chroma < GREY_CHROMA);
+
+                let by_hue = if a_is_grey && b_is_grey {
+                    b_lch.l.total_cmp(&a_lch.l)
+                } else {
+                    let (a_hue, b_hue) = (a_lch.hue.to_positive_degrees(), b_lch.hue.to_positive_degrees());
+                    a_is_grey.cmp(&b_is_grey).then(a_hue.total_cmp(&b_hue))
+                };
+
+                by_hue.then(hex(a).cmp(&hex(b)))
+            }),
+            SortOrder::None => unreachable!(),
+        }
+
+        self.centroids = clusters.iter().map(|(c, _)| *c).collect();
+        self.counts = clusters.iter().map(|(_, count)| *count).collect();
+    }
+}
+
+pub fn parse_percentage(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(percentage) if (0.0..=100.0).contains(&percentage) => Ok(percentage),
+        Ok(_) => Err(String::from("must be between 0 and 100")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// k-means stops when the centroids move less than this between iterations,
+// so it has to be more than 0 or it might never stop early.
+pub fn parse_convergence(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(convergence) if convergence > 0.0 && convergence.is_finite() => Ok(convergence),
+        Ok(_) => Err(String::from("must be a number greater than 0")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// The pixels of an image, converted into the colour space we're going
+// to cluster them in.  We only want to do the conversion once, even if
+// we run k-means several times.
+pub enum Pixels {
+    Lab(Vec<Lab>),
+    Oklab(Vec<OklabPoint>),
+    Rgb(Vec<Srgb>),
+    Hsv(Vec<HsvPoint>),
+}
+
+impl Pixels {
+    pub fn new(img_bytes: &[u8], space: Space) -> Pixels {
+        let srgba = Srgba::from_raw_slice(img_bytes)
+            .iter()
+            .map(|x| x.into_format::<_, f32>());
+
+        match space {
+            Space::Lab => Pixels::Lab(srgba.map(|x| x.into_color()).collect()),
+            Space::Oklab => Pixels::Oklab(srgba.map(|x| OklabPoint::from(Oklab::from_color(x.color))).collect()),
+            Space::Rgb => Pixels::Rgb(srgba.map(|x| x.color).collect()),
+            Space::Hsv => Pixels::Hsv(srgba.map(|x| HsvPoint::from(Hsv::from_color(x.color))).collect()),
+        }
+    }
+
+    // Note: the library's own `verbose` flag prints to stdout, so we
+    // always pass `false` and leave any reporting to the caller.
+    pub fn kmeans(&self, colour_count: usize, max_iterations: usize, converge: f32, seed: u64) -> Clustering {
+        match self {
+            Pixels::Lab(lab) => {
+                let result = get_kmeans_hamerly(colour_count, max_iterations, converge, false, lab, seed);
+                let result = with_exact_means(lab, result, |c| [c.l, c.a, c.b], |[l, a, b]| Lab::new(l, a, b));
+                clustering(lab, result, |c| Srgb::from_color(c).into_format())
+            }
+            Pixels::Rgb(rgb) => {
+                let result = get_kmeans_hamerly(colour_count, max_iterations, converge, false, rgb, seed);
+                let result = with_exact_means(rgb, result, |c| [c.red, c.green, c.blue], |[r, g, b]| Srgb::new(r, g, b));
+                clustering(rgb, result, |c| c.into_format())
+            }
+
+            // The library doesn't know about our Oklab and HSV points, so we
+            // can't use the faster Hamerly algorithm for them.
+            Pixels::Oklab(oklab) => {
+                let result = get_kmeans(colour_count, max_iterations, converge, false, oklab, seed);
+                let result = with_exact_means(oklab, result, Point::coords, Point::from_coords);
+                clustering(oklab, result, |c| Srgb::from_color(Oklab::from(c)).into_format())
+            }
+            Pixels::Hsv(hsv) => {
+                let result = get_kmeans(colour_count, max_iterations, converge, false, hsv, seed);
+                let result = with_exact_means(hsv, result, Point::coords, Point::from_coords);
+                clustering(hsv, result, |c| Srgb::from_color(Hsv::from(c)).into_format())
+            }
+        }
+    }
+
+    // Pick a number of colours for this image, by running k-means for every
+    // k up to `max_colours` and looking for the "elbow" -- the point after
+    // which adding more colours stops making the clusters much tighter.
+    //
+    // Returns the chosen number, and the error for each k we tried.
+    pub fn choose_colour_count(&self, max_colours: usize, max_iterations: usize, converge: f32, seed: u64) -> (usize, Vec<f32>) {
+        let errors: Vec<f32> = (1..=max_colours)
+            .map(|k| self.kmeans(k, max_iterations, converge, seed).error)
+            .collect();
+
+        (choose_elbow(&errors, self.len()), errors)
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Pixels::Lab(lab) => lab.len(),
+            Pixels::Oklab(oklab) => oklab.len(),
+            Pixels::Rgb(rgb) => rgb.len(),
+            Pixels::Hsv(hsv) => hsv.len(),
+        }
+    }
+}
+
+// The library adds up the pixels in each cluster as f32s, and with a few
+// hundred thousand pixels the rounding errors add up -- e.g. a solid red
+// image comes back as #fe0000.  We recompute each centroid as the mean of
+// its pixels using f64s, so the result only depends on which pixels are
+// in the cluster, and a cluster of identical pixels is exactly that colour.
+fn with_exact_means<C: Calculate + Copy>(
+    buf: &[C],
+    mut result: Kmeans<C>,
+    to_array: impl Fn(&C) -> [f32; 3],
+    from_array: impl Fn([f32; 3]) -> C,
+) -> Kmeans<C> {
+    let mut sums = vec![([0f64; 3], 0usize); result.centroids.len()];
+
+    for (point, i) in buf.iter().zip(&result.indices) {
+        let (sum, count) = &mut sums[*i as usize];
+        for (total, value) in sum.iter_mut().zip(to_array(point)) {
+            *total += value as f64;
+        }
+        *count += 1;
+    }
+
+    // If a cluster is empty, we keep the library's centroid.
+    for (centroid, (sum, count)) in result.centroids.iter_mut().zip(sums) {
+        if count > 0 {
+            *centroid = from_array(sum.map(|total| (total / count as f64) as f32));
+        }
+    }
+
+    result
+}
+
+fn clustering<C: Calculate + Copy>(buf: &[C], result: Kmeans<C>, to_srgb: impl Fn(C) -> Srgb<u8>) -> Clustering {
+    // Note: `difference` gives us the squared distance, which is what we want.
+    let error = buf
+        .iter()
+        .zip(&result.indices)
+        .map(|(point, i)| C::difference(point, &result.centroids[*i as usize]))
+        .sum();
+
+    let mut counts = vec![0; result.centroids.len()];
+    for i in &result.indices {
+        counts[*i as usize] += 1;
+    }
+
+    Clustering {
+        score: result.score,
+        error,
+        centroids: result.centroids.into_iter().map(to_srgb).collect(),
+        counts,
+    }
+}
+
+// The mean colour of the pixels.
+//
+// We average in linear RGB, because sRGB values are gamma-encoded -- if
+// you average them directly, blends come out too dark (e.g. the average
+// of red and blue would be #800080 rather than #bc00bc).
+pub fn average(img_bytes: &[u8]) -> Srgb<u8> {
+    let pixels = Srgba::from_raw_slice(img_bytes);
+
+    let sum = pixels
+        .iter()
+        .map(|x| x.color.into_format::<f32>().into_linear())
+        .fold(LinSrgb::new(0.0, 0.0, 0.0), |sum, c| sum + c);
+
+    let count = pixels.len().max(1) as f32;
+
+    Srgb::from_linear(sum / count).into_format()
+}
+
+// Given the within-cluster error for k = 1, 2, 3, ..., find the elbow.
+//
+// If some k already fits the pixels almost exactly, e.g. an image with
+// exactly four colours, we use the smallest such k -- there's nothing more
+// to find, and the line below can't see it.  Otherwise we draw a straight
+// line from the first point to the last point, and pick the k which is
+// furthest below that line.
+//
+// See https://en.wikipedia.org/wiki/Elbow_method_(clustering)
+pub fn choose_elbow(errors: &[f32], pixel_count: usize) -> usize {
+    let first = errors[0];
+    let last = errors[errors.len() - 1];
+
+    // This is an average (squared) distance of less than 1 per pixel, which
+    // is a barely noticeable difference.
+    if let Some(i) = errors.iter().position(|error| *error <= pixel_count as f32) {
+        return i + 1;
+    }
+
+    if errors.len() == 1 {
+        return 1;
+    }
+
+    let steps = (errors.len() - 1) as f32;
+
+    errors
+        .iter()
+        .enumerate()
+        .map(|(i, error)| {
+            let on_the_line = first + (last - first) * (i as f32 / steps);
+            (i + 1, (on_the_line - error) / (first - last).max(f32::EPSILON))
+        })
+        .fold((1, 0.0), |best, this| if this.1 > best.1 { this } else { best })
+        .0
+}
+
+// Hue is an angle, so we can't cluster HSV values directly -- a hue of 359°
+// is very close to a hue of 1°, but a naive average would put them at 180°.
+//
+// Instead, we place each colour in the HSV cone: the hue is the angle around
+// the axis, the value is the height, and the saturation (scaled by the value,
+// so all the blacks meet at a point) is the distance from the axis.  Then we
+// can use plain Euclidean distances and averages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HsvPoint {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+impl From<Hsv> for HsvPoint {
+    fn from(hsv: Hsv) -> HsvPoint {
+        let radius = hsv.saturation * hsv.value;
+        let angle = hsv.hue.to_radians();
+
+        HsvPoint {
+            x: radius * angle.cos(),
+            y: radius * angle.sin(),
+            z: hsv.value,
+        }
+    }
+}
+
+impl From<HsvPoint> for Hsv {
+    fn from(p: HsvPoint) -> Hsv {
+        let radius = (p.x * p.x + p.y * p.y).sqrt();
+        let saturation = if p.z > 0.0 { (radius / p.z).min(1.0) } else { 0.0 };
+
+        Hsv::new(p.y.atan2(p.x).to_degrees(), saturation, p.z)
+    }
+}
+
+// Our own points are plain points in 3D space, so we can cluster them with
+// Euclidean distances and averages, and they share the k-means code.
+trait Point: Copy {
+    fn coords(&self) -> [f32; 3];
+    fn from_coords(coords: [f32; 3]) -> Self;
+}
+
+impl Point for HsvPoint {
+    fn coords(&self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    fn from_coords([x, y, z]: [f32; 3]) -> HsvPoint {
+        HsvPoint { x, y, z }
+    }
+}
+
+// Oklab comes from palette and Calculate from the k-means library, so we
+// need a type of our own to connect them.
+//
+// Oklab's lightness goes from 0 to 1, where CIELAB's goes to 100, so we
+// scale it up by 100.  Then the distances are about the same size as in
+// CIELAB, and --convergence and --max-colours=auto work the same way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OklabPoint {
+    l: f32,
+    a: f32,
+    b: f32,
+}
+
+const OKLAB_SCALE: f32 = 100.0;
+
+impl From<Oklab> for OklabPoint {
+    fn from(oklab: Oklab) -> OklabPoint {
+        OklabPoint { l: oklab.l * OKLAB_SCALE, a: oklab.a * OKLAB_SCALE, b: oklab.b * OKLAB_SCALE }
+    }
+}
+
+impl From<OklabPoint> for Oklab {
+    fn from(p: OklabPoint) -> Oklab {
+        Oklab::new(p.l / OKLAB_SCALE, p.a / OKLAB_SCALE, p.b / OKLAB_SCALE)
+    }
+}
+
+impl Point for OklabPoint {
+    fn coords(&self) -> [f32; 3] {
+        [self.l, self.a, self.b]
+    }
+
+    fn from_coords([l, a, b]: [f32; 3]) -> OklabPoint {
+        OklabPoint { l, a, b }
+    }
+}
+
+impl Calculate for HsvPoint {
+    fn get_closest_centroid(buffer: &[Self], centroids: &[Self], indices: &mut Vec<u8>) {
+        closest_centroids(buffer, centroids, indices)
+    }
+
+    fn recalculate_centroids(rng: &mut impl Rng, buf: &[Self], centroids: &mut [Self], indices: &[u8]) {
+        mean_centroids(rng, buf, centroids, indices)
+    }
+
+    fn check_loop(centroids: &[Self], old_centroids: &[Self]) -> f32 {
+        movement(centroids, old_centroids)
+    }
+
+    fn create_random(rng: &mut impl Rng) -> Self {
+        HsvPoint::from(Hsv::new(
+            rng.gen_range(0.0..360.0),
+            rng.gen_range(0.0..=1.0),
+            rng.gen_range(0.0..=1.0),
+        ))
+    }
+
+    fn difference(c1: &Self, c2: &Self) -> f32 {
+        squared_distance(c1, c2)
+    }
+}
+
+impl Calculate for OklabPoint {
+    fn get_closest_centroid(buffer: &[Self], centroids: &[Self], indices: &mut Vec<u8>) {
+        closest_centroids(buffer, centroids, indices)
+    }
+
+    fn recalculate_centroids(rng: &mut impl Rng, buf: &[Self], centroids: &mut [Self], indices: &[u8]) {
+        mean_centroids(rng, buf, centroids, indices)
+    }
+
+    fn check_loop(centroids: &[Self], old_centroids: &[Self]) -> f32 {
+        movement(centroids, old_centroids)
+    }
+
+    // Most points in Oklab aren't real colours, so we pick a random sRGB
+    // colour rather than a random point.
+    fn create_random(rng: &mut impl Rng) -> Self {
+        let rgb = Srgb::new(rng.gen_range(0.0..=1.0), rng.gen_range(0.0..=1.0), rng.gen_range(0.0..=1.0));
+
+        OklabPoint::from(Oklab::from_color(rgb))
+    }
+
+    fn difference(c1: &Self, c2: &Self) -> f32 {
+        squared_distance(c1, c2)
+    }
+}
+
+fn closest_centroids<P: Point>(buffer: &[P], centroids: &[P], indices: &mut Vec<u8>) {
+    for point in buffer {
+        let (index, _) = centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, squared_distance(point, c)))
+            .fold((0, f32::MAX), |best, this| if this.1 < best.1 { this } else { best });
+
+        indices.push(index as u8);
+    }
+}
+
+fn mean_centroids<P: Point + Calculate>(rng: &mut impl Rng, buf: &[P], centroids: &mut [P], indices: &[u8]) {
+    for (idx, centroid) in centroids.iter_mut().enumerate() {
+        let mut sum = [0.0; 3];
+        let mut counter: u64 = 0;
+
+        for (point, _) in buf.iter().zip(indices).filter(|(_, i)| **i as usize == idx) {
+            for (total, value) in sum.iter_mut().zip(point.coords()) {
+                *total += value;
+            }
+            counter += 1;
+        }
+
+        *centroid = if counter == 0 {
+            P::create_random(rng)
+        } else {
+            P::from_coords(sum.map(|total| total / counter as f32))
+        };
+    }
+}
+
+fn movement<P: Point>(centroids: &[P], old_centroids: &[P]) -> f32 {
+    let mut moved = [0.0; 3];
+
+    for (c, old) in centroids.iter().zip(old_centroids) {
+        for ((total, new), old) in moved.iter_mut().zip(c.coords()).zip(old.coords()) {
+            *total += new - old;
+        }
+    }
+
+    moved.iter().map(|m| m * m).sum()
+}
+
+fn squared_distance<P: Point>(c1: &P, c2: &P) -> f32 {
+    c1.coords().iter().zip(c2.coords()).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::{FromColor, Hsv, Oklab, Srgb};
+
+    use crate::clustering::{average, choose_elbow, parse_convergence, Clustering, ColourCount, HsvPoint, OklabPoint, Pixels, SortOrder, Space};
+
+    #[test]
+    fn it_parses_the_colour_count() {
+        assert_eq!(ColourCount::parse("auto"), Ok(ColourCount::Auto));
+        assert_eq!(ColourCount::parse("5"), Ok(ColourCount::Fixed(5)));
+        assert!(ColourCount::parse("NaN").is_err());
+        assert!(ColourCount::parse("0").is_err());
+    }
+
+    #[test]
+    fn it_parses_the_convergence() {
+        assert_eq!(parse_convergence("1.0"), Ok(1.0));
+        assert_eq!(parse_convergence("0.001"), Ok(0.001));
+
+        assert!(parse_convergence("0").is_err());
+        assert!(parse_convergence("-1").is_err());
+        assert!(parse_convergence("NaN").is_err());
+        assert!(parse_convergence("inf").is_err());
+        assert!(parse_convergence("fast").is_err());
+    }
+
+    #[test]
+    fn it_chooses_one_colour_for_a_flat_image() {
+        assert_eq!(choose_elbow(&[10.0, 5.0, 1.0], 100), 1);
+    }
+
+    #[test]
+    fn it_chooses_the_elbow() {
+        assert_eq!(choose_elbow(&[1000.0, 900.0, 200.0, 150.0, 120.0, 100.0], 10), 3);
+    }
+
+    // The error drops to nothing at k = 4, which isn't the point furthest
+    // below the line, but it's the right answer.
+    #[test]
+    fn it_stops_when_the_colours_fit_exactly() {
+        assert_eq!(choose_elbow(&[531866300.0, 135655470.0, 42492490.0, 0.0, 0.0, 0.0, 0.0, 0.0], 1000), 4);
+    }
+
+    fn clustering(counts: Vec<usize>) -> Clustering {
+        Clustering {
+            score: 0.0,
+            error: 0.0,
+            centroids: (0..counts.len()).map(|i| Srgb::new(i as u8, 0, 0)).collect(),
+            counts,
+        }
+    }
+
+    #[test]
+    fn it_drops_small_clusters() {
+        let mut c = clustering(vec![46, 5, 30, 9, 10]);
+        c.drop_small_clusters(10.0);
+
+        assert_eq!(c.counts, vec![46, 30, 10]);
+        assert_eq!(c.centroids, vec![Srgb::new(0, 0, 0), Srgb::new(2, 0, 0), Srgb::new(4, 0, 0)]);
+    }
+
+    #[test]
+    fn it_keeps_the_biggest_cluster_if_theyre_all_small() {
+        let mut c = clustering(vec![30, 40, 30]);
+        c.drop_small_clusters(50.0);
+
+        assert_eq!(c.counts, vec![40]);
+        assert_eq!(c.centroids, vec![Srgb::new(1, 0, 0)]);
+    }
+
+    #[test]
+    fn it_drops_empty_clusters() {
+        let mut c = clustering(vec![0, 5, 0, 9]);
+        c.drop_empty_clusters();
+
+        assert_eq!(c.counts, vec![5, 9]);
+        assert_eq!(c.centroids, vec![Srgb::new(1, 0, 0), Srgb::new(3, 0, 0)]);
+    }
+
+    #[test]
+    fn it_merges_close_colours_into_the_bigger_cluster() {
+        let mut c = Clustering {
+            score: 0.0,
+            error: 0.0,
+            centroids: vec![Srgb::new(248, 4, 4), Srgb::new(0, 0, 255), Srgb::new(255, 0, 0), Srgb::new(250, 2, 2)],
+            counts: vec![10, 40, 30, 5],
+        };
+        c.merge_close_colours(5.0);
+
+        assert_eq!(c.centroids, vec![Srgb::new(0, 0, 255), Srgb::new(255, 0, 0)]);
+        assert_eq!(c.counts, vec![40, 45]);
+    }
+
+    #[test]
+    fn it_sorts_by_size_then_hex() {
+        let mut c = Clustering {
+            score: 0.0,
+            error: 0.0,
+            centroids: vec![Srgb::new(9, 0, 0), Srgb::new(3, 0, 0), Srgb::new(1, 0, 0)],
+            counts: vec![10, 20, 20],
+        };
+        c.sort(SortOrder::Size);
+
+        assert_eq!(c.centroids, vec![Srgb::new(1, 0, 0), Srgb::new(3, 0, 0), Srgb::new(9, 0, 0)]);
+        assert_eq!(c.counts, vec![20, 20, 10]);
+
+        c.sort(SortOrder::None);
+        assert_eq!(c.counts, vec![20, 20, 10]);
+    }
+
+    #[test]
+    fn it_sorts_by_luminance_then_hex() {
+        let mut c = clustering(vec![1, 1, 1, 1]);
+        c.centroids = vec![Srgb::new(0, 0, 0), Srgb::new(255, 255, 255), Srgb::new(0, 0, 255), Srgb::new(128, 128, 128)];
+        c.sort(SortOrder::Luminance);
+
+        assert_eq!(
+            c.centroids,
+            vec![Srgb::new(255, 255, 255), Srgb::new(128, 128, 128), Srgb::new(0, 0, 255), Srgb::new(0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn it_sorts_by_hue_with_greys_last() {
+        let mut c = clustering(vec![1, 1, 1, 1, 1]);
+        c.centroids = vec![
+            Srgb::new(128, 128, 128),
+            Srgb::new(0, 0, 255),
+            Srgb::new(255, 255, 255),
+            Srgb::new(0, 255, 0),
+            Srgb::new(255, 0, 0),
+        ];
+        c.sort(SortOrder::Hue);
+
+        assert_eq!(
+            c.centroids,
+            vec![
+                Srgb::new(255, 0, 0),
+                Srgb::new(0, 255, 0),
+                Srgb::new(0, 0, 255),
+                Srgb::new(255, 255, 255),
+                Srgb::new(128, 128, 128),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_finds_the_exact_colour_of_a_solid_image() {
+        for colour in [[255, 0, 0, 255], [0, 0, 255, 255], [18, 52, 86, 255]] {
+            let bytes = colour.repeat(160_000);
+
+            for space in [Space::Lab, Space::Oklab, Space::Rgb, Space::Hsv] {
+                let clustering = Pixels::new(&bytes, space).kmeans(1, 20, 1.0, 0);
+
+                assert_eq!(
+                    clustering.centroids,
+                    vec![Srgb::new(colour[0], colour[1], colour[2])],
+                    "space = {:?}",
+                    space
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_averages_in_linear_rgb() {
+        let bytes = [255, 0, 0, 255, 0, 0, 255, 255];
+
+        assert_eq!(average(&bytes), Srgb::new(188, 0, 188));
+        assert_eq!(average(&bytes[..4]), Srgb::new(255, 0, 0));
+    }
+
+    #[test]
+    fn it_round_trips_oklab_points() {
+        for rgb in [Srgb::new(255u8, 0, 0), Srgb::new(18, 52, 86), Srgb::new(128, 128, 128), Srgb::new(0, 0, 0)] {
+            let oklab = Oklab::from_color(rgb.into_format::<f32>());
+            let round_tripped: Srgb<u8> = Srgb::from_color(Oklab::from(OklabPoint::from(oklab))).into_format();
+
+            assert_eq!(round_tripped, rgb);
+        }
+    }
+
+    #[test]
+    fn it_round_trips_hsv_points() {
+        for rgb in [Srgb::new(255u8, 0, 0), Srgb::new(18, 52, 86), Srgb::new(128, 128, 128), Srgb::new(0, 0, 0)] {
+            let hsv = Hsv::from_color(rgb.into_format::<f32>());
+            let round_tripped: Srgb<u8> = Srgb::from_color(Hsv::from(HsvPoint::from(hsv))).into_format();
+
+            assert_eq!(round_tripped, rgb);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::Cursor;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult, RgbImage};
+use jpeg_decoder::{Decoder, PixelFormat};
+
+// Most JPEGs are RGB (stored as YCbCr), but JPEGs made for print are often
+// CMYK, and there are two ways to store those:
+//
+//   - Adobe apps (which write most of them) store the inks inverted, and
+//     add an "Adobe" APP14 marker -- either as plain CMYK, or as YCCK,
+//     where the CMY channels are compressed like YCbCr.
+//   - Other apps store the inks as-is, with no marker.
+//
+// The `image` crate converts CMYK to RGB, but it assumes every CMYK JPEG
+// was written by Adobe, so the others come out with inverted colours.  We
+// decode CMYK JPEGs ourselves, and check for the marker.
+//
+// We convert to RGB with the naive formula, ignoring any ICC profile, so
+// the colours won't match a colour-managed app exactly, but they'll be close.
+//
+// See https://exiftool.org/TagNames/JPEG.html#Adobe
+
+pub fn decode(bytes: &[u8]) -> ImageResult<DynamicImage> {
+    let mut decoder = Decoder::new(Cursor::new(bytes));
+
+    // If we can't read the header, let `image` try, so broken JPEGs get
+    // the same error as before.
+    let info = match decoder.read_info().ok().and_then(|_| decoder.info()) {
+        Some(info) if info.pixel_format == PixelFormat::CMYK32 => info,
+        _ => return image::load_from_memory_with_format(bytes, ImageFormat::Jpeg),
+    };
+
+    let pixels = decoder.decode().map_err(|e| format_error(e.to_string()))?;
+
+    // jpeg-decoder undoes Adobe's inversion, so if there's no marker, we
+    // have to invert the inks again to get back what was stored.
+    let is_adobe = has_adobe_marker(bytes);
+    let ink = |value: u8| if is_adobe { value } else { 255 - value };
+
+    let rgb: Vec<u8> = pixels
+        .chunks_exact(4)
+        .flat_map(|px| {
+            let k = ink(px[3]);
+            let channel = |c: u8| ((255 - ink(c)) as u32 * (255 - k) as u32 + 127) / 255;
+            [channel(px[0]) as u8, channel(px[1]) as u8, channel(px[2]) as u8]
+        })
+        .collect();
+
+    RgbImage::from_raw(info.width as u32, info.height as u32, rgb)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| format_error(String::from("decoded image is the wrong size")))
+}
+
+// Look for an APP14 segment that starts "Adobe", in the markers before
+// the image data.
+fn has_adobe_marker(bytes: &[u8]) -> bool {
+    // Skip the SOI marker at the start of the file.
+    let mut i = 2;
+
+    while i + 4 <= bytes.len() && bytes[i] == 0xFF {
+        let marker = bytes[i + 1];
+
+        // The image data starts after the SOS marker.
+        if marker == 0xDA {
+            return false;
+        }
+
+        let length = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+
+        if marker == 0xEE && bytes[i + 4..].starts_with(b"Adobe") {
+            return true;
+        }
+
+        i += 2 + length;
+    }
+
+    false
+}
+
+fn format_error(message: String) -> ImageError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Exact(ImageFormat::Jpeg),
+        format!("Couldn't decode this CMYK JPEG: {}", message),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmyk_jpeg::has_adobe_marker;
+
+    #[test]
+    fn it_finds_the_adobe_marker() {
+        let adobe = [
+            0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E, b'A', b'd', b'o', b'b', b'e', 0, 100, 0, 0, 0, 0, 0, 0xFF, 0xDA,
+        ];
+        assert!(has_adobe_marker(&adobe));
+
+        let no_marker = [0xFF, 0xD8, 0xFF, 0xDB, 0x00, 0x03, 0x00, 0xFF, 0xDA, 0x00];
+        assert!(!has_adobe_marker(&no_marker));
+
+        // An APP14 marker after the image data doesn't count.
+        let too_late = [0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x07, b'A', b'd', b'o', b'b', b'e'];
+        assert!(!has_adobe_marker(&too_late));
+    }
+}
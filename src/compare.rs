@@ -0,0 +1,200 @@
+use palette::Srgb;
+
+use crate::distance::lab_distance;
+
+// Past this distance in Lab, two colours are completely different (for
+// comparison, pure red and pure blue are about 176 apart).
+const MAX_DISTANCE: f32 = 100.0;
+
+pub struct Pair {
+    pub colour: Srgb<u8>,
+    pub other_colour: Srgb<u8>,
+    pub distance: f32,
+}
+
+pub struct Comparison {
+    pub pairs: Vec<Pair>,
+    pub similarity: f32,
+}
+
+// Compare two palettes, by matching up their colours so the total
+// distance between the pairs is as small as possible.
+//
+// The similarity is 1 if every colour has an identical partner, and 0 if
+// every pair is at least MAX_DISTANCE apart.  If one palette has more
+// colours than the other, the leftover colours count as unmatched.
+pub fn compare(colours: &[Srgb<u8>], other_colours: &[Srgb<u8>]) -> Comparison {
+    let costs: Vec<Vec<f32>> = colours
+        .iter()
+        .map(|c| other_colours.iter().map(|o| lab_distance(*c, *o)).collect())
+        .collect();
+
+    let pairs: Vec<Pair> = assign(&costs)
+        .into_iter()
+        .map(|(i, j)| Pair {
+            colour: colours[i],
+            other_colour: other_colours[j],
+            distance: costs[i][j],
+        })
+        .collect();
+
+    let total_colours = colours.len().max(other_colours.len());
+
+    let similarity = if total_colours == 0 {
+        1.0
+    } else {
+        pairs
+            .iter()
+            .map(|p| 1.0 - (p.distance / MAX_DISTANCE).min(1.0))
+            .sum::<f32>()
+            / total_colours as f32
+    };
+
+    Comparison { pairs, similarity }
+}
+
+pub fn parse_threshold(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(threshold) if (0.0..=1.0).contains(&threshold) => Ok(threshold),
+        Ok(_) => Err(String::from("must be between 0 and 1")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Find the pairing of rows and columns with the smallest total cost, using
+// the Hungarian algorithm.  Every row or every column is matched, whichever
+// there are fewer of.  Returns (row, column) pairs, sorted by row.
+//
+// See https://en.wikipedia.org/wiki/Hungarian_algorithm
+fn assign(costs: &[Vec<f32>]) -> Vec<(usize, usize)> {
+    let rows = costs.len();
+    let columns = costs.first().map_or(0, |row| row.len());
+
+    if rows == 0 || columns == 0 {
+        return vec![];
+    }
+
+    // The algorithm needs at least as many columns as rows, so flip the
+    // matrix if we need to.
+    if rows > columns {
+        let transposed: Vec<Vec<f32>> = (0..columns)
+            .map(|j| (0..rows).map(|i| costs[i][j]).collect())
+            .collect();
+
+        let mut pairs: Vec<(usize, usize)> = assign(&transposed).into_iter().map(|(j, i)| (i, j)).collect();
+        pairs.sort_unstable();
+        return pairs;
+    }
+
+    // This follows the usual formulation with row and column potentials,
+    // where index 0 is a dummy row/column.  `matched[j]` is the row matched
+    // to column j.
+    let mut row_potential = vec![0.0f32; rows + 1];
+    let mut column_potential = vec![0.0f32; columns + 1];
+    let mut matched = vec![0usize; columns + 1];
+    let mut way = vec![0usize; columns + 1];
+
+    for row in 1..=rows {
+        matched[0] = row;
+        let mut j0 = 0;
+        let mut min_slack = vec![f32::INFINITY; columns + 1];
+        let mut used = vec![false; columns + 1];
+
+        loop {
+            used[j0] = true;
+            let i0 = matched[j0];
+            let mut delta = f32::INFINITY;
+            let mut j1 = 0;
+
+            for j in 1..=columns {
+                if !used[j] {
+                    let slack = costs[i0 - 1][j - 1] - row_potential[i0] - column_potential[j];
+
+                    if slack < min_slack[j] {
+                        min_slack[j] = slack;
+                        way[j] = j0;
+                    }
+
+                    if min_slack[j] < delta {
+                        delta = min_slack[j];
+                        j1 = j;
+                    }
+                }
+            }
+
+            for j in 0..=columns {
+                if used[j] {
+                    row_potential[matched[j]] += delta;
+                    column_potential[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+
+            if matched[j0] == 0 {
+                break;
+            }
+        }
+
+        // Walk back along the augmenting path, flipping the matches.
+        while j0 != 0 {
+            let j1 = way[j0];
+            matched[j0] = matched[j1];
+            j0 = j1;
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = (1..=columns)
+        .filter(|j| matched[*j] != 0)
+        .map(|j| (matched[j] - 1, j - 1))
+        .collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::compare::{assign, compare};
+
+    #[test]
+    fn it_finds_the_cheapest_assignment() {
+        // Greedily pairing row 0 with its cheapest column (0) would force
+        // row 1 into column 1, for a total of 1 + 10 = 11; the best is 2 + 3.
+        let costs = vec![vec![1.0, 2.0], vec![3.0, 10.0]];
+
+        assert_eq!(assign(&costs), vec![(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn it_assigns_non_square_matrices() {
+        let costs = vec![vec![5.0, 1.0, 9.0]];
+        assert_eq!(assign(&costs), vec![(0, 1)]);
+
+        let costs = vec![vec![5.0], vec![1.0], vec![9.0]];
+        assert_eq!(assign(&costs), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn it_finds_identical_palettes_completely_similar() {
+        let palette = [Srgb::new(255, 0, 0), Srgb::new(0, 128, 0), Srgb::new(12, 34, 56)];
+        let mut shuffled = palette;
+        shuffled.reverse();
+
+        let comparison = compare(&palette, &shuffled);
+
+        assert_eq!(comparison.similarity, 1.0);
+        assert!(comparison.pairs.iter().all(|p| p.colour == p.other_colour));
+    }
+
+    #[test]
+    fn it_reduces_the_similarity_for_unmatched_colours() {
+        let comparison = compare(&[Srgb::new(255, 0, 0)], &[Srgb::new(255, 0, 0), Srgb::new(0, 0, 255)]);
+
+        assert_eq!(comparison.pairs.len(), 1);
+        assert_eq!(comparison.similarity, 0.5);
+    }
+}
@@ -0,0 +1,99 @@
+use palette::Srgb;
+
+// With --contrast, we print which text colour to use on each colour --
+// black or white, whichever has the higher contrast ratio -- and the ratio,
+// e.g. `#1f6feb → text #ffffff (4.6:1)`.  With --contrast-against, we
+// compare every colour to a colour you pick instead, and say if the ratio
+// is too low for normal text.
+//
+// The ratio and relative luminance are as defined by WCAG.  A ratio goes
+// from 1:1 (the same colour) to 21:1 (black and white).
+//
+// See https://www.w3.org/TR/WCAG21/#dfn-contrast-ratio
+// See https://www.w3.org/TR/WCAG21/#dfn-relative-luminance
+
+// The lowest ratio WCAG allows for normal text (level AA).
+pub const MIN_RATIO: f32 = 4.5;
+
+// How bright a colour looks, from 0 for black to 1 for white.  sRGB values
+// are gamma-encoded, so we have to convert them to linear light first.
+pub fn relative_luminance(c: Srgb<u8>) -> f32 {
+    let linear = c.into_format::<f32>().into_linear();
+
+    0.2126 * linear.red + 0.7152 * linear.green + 0.0722 * linear.blue
+}
+
+// The contrast ratio between two colours, which is the same whichever way
+// round they are.
+pub fn ratio(c1: Srgb<u8>, c2: Srgb<u8>) -> f32 {
+    let (l1, l2) = (relative_luminance(c1), relative_luminance(c2));
+
+    (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+}
+
+// Black or white, whichever has the higher contrast ratio with `c`.
+pub fn text_colour(c: Srgb<u8>) -> Srgb<u8> {
+    let (black, white) = (Srgb::new(0, 0, 0), Srgb::new(255, 255, 255));
+
+    if ratio(c, black) > ratio(c, white) {
+        black
+    } else {
+        white
+    }
+}
+
+// The ratios against white and black, for the JSON output.
+pub fn against_white_and_black(c: Srgb<u8>) -> (f32, f32) {
+    (ratio(c, Srgb::new(255, 255, 255)), ratio(c, Srgb::new(0, 0, 0)))
+}
+
+// Write a ratio like `4.6:1`.  We round down, so a ratio which is just
+// below the minimum is never printed as if it were the minimum.
+pub fn format_ratio(ratio: f32) -> String {
+    format!("{:.1}:1", (ratio * 10.0).floor() / 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::contrast::{format_ratio, ratio, relative_luminance, text_colour};
+
+    #[test]
+    fn it_calculates_the_relative_luminance() {
+        assert_eq!(relative_luminance(Srgb::new(0, 0, 0)), 0.0);
+        assert!((relative_luminance(Srgb::new(255, 255, 255)) - 1.0).abs() < 0.0001);
+
+        // Mid-grey in sRGB is much darker than halfway in linear light;
+        // if we forgot to linearise, this would be about 0.5.
+        assert!((relative_luminance(Srgb::new(128, 128, 128)) - 0.2158).abs() < 0.0001);
+
+        assert!((relative_luminance(Srgb::new(255, 0, 0)) - 0.2126).abs() < 0.0001);
+        assert!((relative_luminance(Srgb::new(0, 255, 0)) - 0.7152).abs() < 0.0001);
+        assert!((relative_luminance(Srgb::new(0, 0, 255)) - 0.0722).abs() < 0.0001);
+    }
+
+    #[test]
+    fn it_calculates_the_contrast_ratio() {
+        let (black, white) = (Srgb::new(0, 0, 0), Srgb::new(255, 255, 255));
+
+        assert!((ratio(black, white) - 21.0).abs() < 0.001);
+        assert!((ratio(white, black) - 21.0).abs() < 0.001);
+        assert_eq!(ratio(Srgb::new(18, 52, 86), Srgb::new(18, 52, 86)), 1.0);
+
+        // This is the grey WebAIM gives as the lightest with 4.5:1 on white.
+        assert_eq!(format_ratio(ratio(Srgb::new(118, 118, 118), white)), "4.5:1");
+        assert_eq!(format_ratio(ratio(Srgb::new(119, 119, 119), white)), "4.4:1");
+    }
+
+    #[test]
+    fn it_picks_the_text_colour_with_more_contrast() {
+        let (black, white) = (Srgb::new(0, 0, 0), Srgb::new(255, 255, 255));
+
+        assert_eq!(text_colour(white), black);
+        assert_eq!(text_colour(Srgb::new(255, 255, 0)), black);
+        assert_eq!(text_colour(Srgb::new(255, 0, 0)), black);
+        assert_eq!(text_colour(Srgb::new(0, 0, 255)), white);
+        assert_eq!(text_colour(black), white);
+    }
+}
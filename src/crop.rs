@@ -0,0 +1,131 @@
+use std::fmt;
+
+// The part of the image to look at with --crop, as `x,y,width,height`.
+//
+// Each value can be in pixels (`0,0,1920,200`) or a percentage of the
+// image's width or height (`0%,0%,100%,20%`), which is handy if your
+// images come in different sizes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crop {
+    x: Length,
+    y: Length,
+    width: Length,
+    height: Length,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Length {
+    Pixels(u32),
+    Percent(f64),
+}
+
+// A crop region in pixels, after we've fitted it to a particular image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Length {
+    fn parse(s: &str) -> Option<Length> {
+        match s.strip_suffix('%') {
+            Some(p) => p.parse::<f64>().ok().filter(|p| *p >= 0.0).map(Length::Percent),
+            None => s.parse::<u32>().ok().map(Length::Pixels),
+        }
+    }
+
+    fn to_pixels(self, size: u32) -> u32 {
+        match self {
+            Length::Pixels(n) => n,
+            Length::Percent(p) => (p / 100.0 * size as f64).round() as u32,
+        }
+    }
+}
+
+impl Crop {
+    pub fn parse(s: &str) -> Result<Crop, String> {
+        let lengths: Vec<Option<Length>> = s.split(',').map(|part| Length::parse(part.trim())).collect();
+
+        match lengths.as_slice() {
+            [Some(x), Some(y), Some(width), Some(height)] => Ok(Crop {
+                x: *x,
+                y: *y,
+                width: *width,
+                height: *height,
+            }),
+            _ => Err(String::from(
+                "should be x,y,width,height in pixels or percentages, e.g. 0,0,1920,200 or 0%,0%,100%,20%",
+            )),
+        }
+    }
+
+    // Work out which pixels to look at in an image of the given size.
+    //
+    // If the crop goes outside the image, we only look at the part which
+    // is inside it; the bool tells you if that happened.
+    pub fn region(&self, image_width: u32, image_height: u32) -> (Region, bool) {
+        let x = self.x.to_pixels(image_width);
+        let y = self.y.to_pixels(image_height);
+        let width = self.width.to_pixels(image_width);
+        let height = self.height.to_pixels(image_height);
+
+        let right = x.saturating_add(width);
+        let bottom = y.saturating_add(height);
+        let clamped = right > image_width || bottom > image_height;
+
+        let x = x.min(image_width);
+        let y = y.min(image_height);
+
+        let region = Region {
+            x,
+            y,
+            width: right.min(image_width) - x,
+            height: bottom.min(image_height) - y,
+        };
+
+        (region, clamped)
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crop::{Crop, Region};
+
+    #[test]
+    fn it_parses_a_crop() {
+        assert!(Crop::parse("0,0,1920,200").is_ok());
+        assert!(Crop::parse("0%,0%,100%,20%").is_ok());
+        assert!(Crop::parse("0, 10%, 50, 12.5%").is_ok());
+
+        assert!(Crop::parse("0,0,1920").is_err());
+        assert!(Crop::parse("0,0,1920,200,1").is_err());
+        assert!(Crop::parse("-1,0,10,10").is_err());
+        assert!(Crop::parse("0,0,10,ten").is_err());
+    }
+
+    #[test]
+    fn it_fits_the_crop_to_the_image() {
+        let crop = Crop::parse("0,0,10,20").unwrap();
+        assert_eq!(crop.region(20, 20), (Region { x: 0, y: 0, width: 10, height: 20 }, false));
+
+        let crop = Crop::parse("0%,50%,50%,50%").unwrap();
+        assert_eq!(crop.region(200, 100), (Region { x: 0, y: 50, width: 100, height: 50 }, false));
+    }
+
+    #[test]
+    fn it_clamps_a_crop_outside_the_image() {
+        let crop = Crop::parse("15,0,100,20").unwrap();
+        assert_eq!(crop.region(20, 20), (Region { x: 15, y: 0, width: 5, height: 20 }, true));
+
+        let crop = Crop::parse("30,30,10,10").unwrap();
+        assert_eq!(crop.region(20, 20), (Region { x: 20, y: 20, width: 0, height: 0 }, true));
+    }
+}
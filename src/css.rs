@@ -0,0 +1,247 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::hex;
+
+// With --format=css, we print the colours as CSS custom properties, so you
+// can drop them straight into a stylesheet, e.g.
+//
+//      :root {
+//          --dominant-colour-1: #d0c6b2;
+//          --dominant-colour-2: #3f3336;
+//      }
+//
+// With --format=scss, we print them as SCSS variables instead, and with
+// --scss-map, a map of all the colours that you can loop over with `@each`:
+//
+//      $dominant-colour-1: #d0c6b2;
+//      $dominant-colour-2: #3f3336;
+//
+//      $dominant-colours: (1: #d0c6b2, 2: #3f3336);
+//
+// The colours are numbered from 1 in the order we print them, so with the
+// default --sort=size, `-1` is always the most dominant colour.
+//
+// See https://developer.mozilla.org/en-US/docs/Web/CSS/--*
+// and https://sass-lang.com/documentation/variables/
+//
+// With --format=css-gradient, we print a `background` declaration with a
+// gradient that goes through the colours in order, and gives each colour
+// a band as wide as its share of the image, e.g.
+//
+//      background: linear-gradient(90deg, #d0c6b2 0% 62.5%, #3f3336 62.5% 100%);
+//
+// With --gradient-even, or if we don't know the shares (e.g. with
+// --terminal-colours), every colour gets the same width.
+//
+// See https://developer.mozilla.org/en-US/docs/Web/CSS/gradient/linear-gradient
+
+pub const DEFAULT_PREFIX: &str = "dominant-colour";
+pub const DEFAULT_SELECTOR: &str = ":root";
+pub const DEFAULT_GRADIENT_ANGLE: &str = "90";
+
+pub fn stylesheet(colours: &[Srgb<u8>], selector: &str, prefix: &str) -> String {
+    let mut css = String::new();
+
+    writeln!(css, "{} {{", selector).unwrap();
+
+    for (i, c) in colours.iter().enumerate() {
+        writeln!(css, "    --{}-{}: {};", prefix, i + 1, hex(*c)).unwrap();
+    }
+
+    writeln!(css, "}}").unwrap();
+
+    css
+}
+
+pub fn scss_variables(colours: &[Srgb<u8>], prefix: &str, include_map: bool) -> String {
+    let mut scss = String::new();
+
+    for (i, c) in colours.iter().enumerate() {
+        writeln!(scss, "${}-{}: {};", prefix, i + 1, hex(*c)).unwrap();
+    }
+
+    if include_map {
+        let entries: Vec<String> = colours.iter().enumerate().map(|(i, c)| format!("{}: {}", i + 1, hex(*c))).collect();
+
+        writeln!(scss).unwrap();
+        writeln!(scss, "${}s: ({});", prefix, entries.join(", ")).unwrap();
+    }
+
+    scss
+}
+
+pub fn linear_gradient(colours: &[Srgb<u8>], proportions: Option<&[f32]>, angle: f32) -> String {
+    let widths: Vec<f32> = match proportions {
+        Some(proportions) if proportions.iter().sum::<f32>() > 0.0 => {
+            let total: f32 = proportions.iter().sum();
+            proportions.iter().map(|p| p / total).collect()
+        }
+        _ => vec![1.0 / colours.len() as f32; colours.len()],
+    };
+
+    // We add up the widths before we round, so the rounding errors don't
+    // add up, and the last band always ends at exactly 100%.
+    let mut stops = Vec::new();
+    let mut start = 0.0;
+
+    for (i, (c, width)) in colours.iter().zip(widths).enumerate() {
+        let end = if i == colours.len() - 1 { 1.0 } else { start + width };
+
+        stops.push(format!("{} {}% {}%", hex(*c), percentage(start), percentage(end)));
+        start = end;
+    }
+
+    format!("background: linear-gradient({}deg, {});\n", angle, stops.join(", "))
+}
+
+// Write a fraction as a percentage to one decimal place, leaving off
+// a trailing `.0`, e.g. `37.5` or `50`.
+fn percentage(fraction: f32) -> String {
+    format!("{}", (fraction * 1000.0).round() / 10.0)
+}
+
+// Parse the angle of the gradient with --gradient-angle, in degrees.  We
+// allow a `deg` suffix, because that's how you'd write it in CSS.
+pub fn parse_angle(s: &str) -> Result<f32, String> {
+    let number = s.trim().strip_suffix("deg").unwrap_or(s.trim());
+
+    match number.parse::<f32>() {
+        Ok(angle) if angle.is_finite() => Ok(angle),
+        _ => Err(String::from("should be an angle in degrees, e.g. 90 or 45deg")),
+    }
+}
+
+// Parse the prefix for the property or variable names with --css-prefix.
+// We allow the leading `--` of a CSS property, but don't need it.
+//
+// The prefix has to start a valid SCSS identifier, so it can't start with
+// a digit (or a hyphen) -- CSS is more forgiving, but it's simpler to use
+// the same rules for both.
+pub fn parse_prefix(s: &str) -> Result<String, String> {
+    let prefix = s.strip_prefix("--").unwrap_or(s);
+
+    let is_valid = prefix.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !is_valid {
+        return Err(String::from(
+            "should start with a letter or underscore, and only use letters, numbers, hyphens and underscores, e.g. brand",
+        ));
+    }
+
+    Ok(prefix.to_owned())
+}
+
+// Parse the selector for the rule with --css-selector.  We don't try to
+// check it's a valid selector, but we don't allow anything which would
+// break out of the rule.
+pub fn parse_selector(s: &str) -> Result<String, String> {
+    let selector = s.trim();
+
+    if selector.is_empty() || selector.contains(['{', '}', ';']) {
+        return Err(String::from("should be a CSS selector, e.g. :root or .theme-dark"));
+    }
+
+    Ok(selector.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::css::{linear_gradient, parse_angle, parse_prefix, parse_selector, scss_variables, stylesheet};
+
+    #[test]
+    fn it_prints_custom_properties() {
+        let css = stylesheet(&[Srgb::new(255, 0, 0), Srgb::new(0, 255, 0)], ":root", "dominant-colour");
+
+        assert_eq!(
+            css,
+            ":root {\n    --dominant-colour-1: #ff0000;\n    --dominant-colour-2: #00ff00;\n}\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_a_prefix() {
+        assert_eq!(parse_prefix("brand"), Ok(String::from("brand")));
+        assert_eq!(parse_prefix("--brand"), Ok(String::from("brand")));
+        assert_eq!(parse_prefix("brand_accent-2"), Ok(String::from("brand_accent-2")));
+
+        assert!(parse_prefix("").is_err());
+        assert!(parse_prefix("--").is_err());
+        assert!(parse_prefix("my brand").is_err());
+        assert!(parse_prefix("brand:").is_err());
+        assert!(parse_prefix("1brand").is_err());
+        assert!(parse_prefix("-brand").is_err());
+    }
+
+    #[test]
+    fn it_prints_scss_variables() {
+        let colours = [Srgb::new(255, 0, 0), Srgb::new(0, 255, 0)];
+
+        assert_eq!(
+            scss_variables(&colours, "dominant-colour", false),
+            "$dominant-colour-1: #ff0000;\n$dominant-colour-2: #00ff00;\n"
+        );
+
+        assert_eq!(
+            scss_variables(&colours, "brand", true),
+            "$brand-1: #ff0000;\n$brand-2: #00ff00;\n\n$brands: (1: #ff0000, 2: #00ff00);\n"
+        );
+    }
+
+    #[test]
+    fn it_gives_each_colour_a_band_as_wide_as_its_share() {
+        let (red, blue) = (Srgb::new(255, 0, 0), Srgb::new(0, 0, 255));
+
+        assert_eq!(
+            linear_gradient(&[red, blue], Some(&[0.5, 0.5]), 90.0),
+            "background: linear-gradient(90deg, #ff0000 0% 50%, #0000ff 50% 100%);\n"
+        );
+
+        // The stops are rounded to one decimal place, but the last band
+        // still ends at 100%.
+        assert_eq!(
+            linear_gradient(&[red, blue, red], Some(&[0.3333, 0.3333, 0.3333]), 22.5),
+            "background: linear-gradient(22.5deg, #ff0000 0% 33.3%, #0000ff 33.3% 66.7%, #ff0000 66.7% 100%);\n"
+        );
+
+        // If some pixels were left out, the shares don't add up to 1.
+        assert_eq!(
+            linear_gradient(&[red, blue], Some(&[0.2, 0.6]), 90.0),
+            "background: linear-gradient(90deg, #ff0000 0% 25%, #0000ff 25% 100%);\n"
+        );
+    }
+
+    #[test]
+    fn it_spaces_the_colours_evenly() {
+        let colours = [Srgb::new(255, 0, 0), Srgb::new(0, 255, 0), Srgb::new(0, 0, 255), Srgb::new(0, 0, 0)];
+
+        assert_eq!(
+            linear_gradient(&colours, None, 180.0),
+            "background: linear-gradient(180deg, #ff0000 0% 25%, #00ff00 25% 50%, #0000ff 50% 75%, #000000 75% 100%);\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_an_angle() {
+        assert_eq!(parse_angle("90"), Ok(90.0));
+        assert_eq!(parse_angle("45deg"), Ok(45.0));
+        assert_eq!(parse_angle("-22.5"), Ok(-22.5));
+
+        assert!(parse_angle("").is_err());
+        assert!(parse_angle("90rad").is_err());
+        assert!(parse_angle("NaN").is_err());
+    }
+
+    #[test]
+    fn it_parses_a_selector() {
+        assert_eq!(parse_selector(":root"), Ok(String::from(":root")));
+        assert_eq!(parse_selector(" .theme-dark "), Ok(String::from(".theme-dark")));
+
+        assert!(parse_selector("").is_err());
+        assert!(parse_selector("body { color: red }").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// Information about how a run went, which we print to stderr with --verbose.
+//
+// We collect everything in one place rather than printing as we go, so
+// stdout is never affected and so the tests can inspect what was recorded.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub pixel_count: usize,
+    pub sampled_pixel_count: usize,
+    pub frame_count: usize,
+    pub frames_skipped: usize,
+    pub stages: Vec<(&'static str, Duration)>,
+    pub runs: Vec<Run>,
+    pub converged: Option<bool>,
+
+    // If we picked the number of colours automatically, the error for
+    // each number we tried, and the number we chose.
+    pub auto_colour_errors: Vec<f32>,
+    pub chosen_colour_count: Option<usize>,
+}
+
+// One run of k-means.  The `score` is how far the centroids moved on the
+// last iteration, and the `error` is the within-cluster sum of squares,
+// which is how we pick the best run.
+#[derive(Debug)]
+pub struct Run {
+    pub seed: u64,
+    pub score: f32,
+    pub error: f32,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    // Run `f`, and record how long it took as the named stage.
+    pub fn time<T>(&mut self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.stages.push((stage, start.elapsed()));
+        result
+    }
+
+    // The k-means run with the lowest error, and its index, if there were
+    // any.  Ties go to the earliest run, the same as when we pick the colours.
+    pub fn best_run(&self) -> Option<(usize, &Run)> {
+        self.runs
+            .iter()
+            .enumerate()
+            .fold(None, |best: Option<(usize, &Run)>, (i, run)| match best {
+                Some((_, best_run)) if best_run.error <= run.error => best,
+                _ => Some((i, run)),
+            })
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "pixels: {}", self.pixel_count)?;
+        writeln!(f, "pixels sampled: {}", self.sampled_pixel_count)?;
+        writeln!(f, "frames sampled: {}", self.frame_count)?;
+        writeln!(f, "duplicate frames skipped: {}", self.frames_skipped)?;
+
+        for (stage, elapsed) in &self.stages {
+            writeln!(f, "{}: {:.3}s", stage, elapsed.as_secs_f64())?;
+        }
+
+        for (i, error) in self.auto_colour_errors.iter().enumerate() {
+            writeln!(f, "error with {} colour(s): {}", i + 1, error)?;
+        }
+
+        if let Some(colour_count) = self.chosen_colour_count {
+            writeln!(f, "chose {} colour(s)", colour_count)?;
+        }
+
+        for (i, run) in self.runs.iter().enumerate() {
+            writeln!(f, "run {} (seed {}): score {}, error {}", i + 1, run.seed, run.score, run.error)?;
+        }
+
+        if let Some((i, run)) = self.best_run() {
+            writeln!(f, "best run: {} (seed {}), score {}, error {}", i + 1, run.seed, run.score, run.error)?;
+        }
+
+        match self.converged {
+            Some(true) => writeln!(f, "k-means converged"),
+            Some(false) => writeln!(f, "k-means stopped at the iteration limit without converging"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::{Diagnostics, Run};
+
+    #[test]
+    fn it_records_each_stage() {
+        let mut diagnostics = Diagnostics::new();
+
+        let answer = diagnostics.time("thinking", || 42);
+        diagnostics.time("resting", || ());
+
+        assert_eq!(answer, 42);
+        assert_eq!(
+            diagnostics.stages.iter().map(|(stage, _)| *stage).collect::<Vec<_>>(),
+            vec!["thinking", "resting"]
+        );
+    }
+
+    #[test]
+    fn it_reports_the_best_run() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.runs.push(Run { seed: 0, score: 3.0, error: 200.0 });
+        diagnostics.runs.push(Run { seed: 1, score: 1.5, error: 100.0 });
+        diagnostics.runs.push(Run { seed: 2, score: 0.5, error: 100.0 });
+        diagnostics.converged = Some(false);
+
+        assert_eq!(diagnostics.best_run().map(|(i, _)| i), Some(1));
+
+        let report = diagnostics.to_string();
+        assert!(report.contains("run 2 (seed 1): score 1.5, error 100\n"), "report = {:?}", report);
+        assert!(report.contains("best run: 2 (seed 1), score 1.5, error 100\n"), "report = {:?}", report);
+        assert!(report.contains("without converging"), "report = {:?}", report);
+    }
+}
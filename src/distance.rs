@@ -0,0 +1,213 @@
+use clap::ValueEnum;
+use palette::convert::FromColorUnclamped;
+use palette::{Clamp, ColorDifference, IntoColor, Lab, Lch, Srgb};
+
+// The metrics we can use when deciding how close two colours are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Distance {
+    // A weighted Euclidean distance in RGB space.
+    // See https://stackoverflow.com/a/9085524/6802309
+    #[value(alias = "rgb")]
+    Euclidean,
+
+    // The plain Euclidean distance in Lab, aka CIE76 delta E.  This is
+    // better than RGB, but overstates differences in saturated colours.
+    // See https://en.wikipedia.org/wiki/Color_difference#CIE76
+    Lab,
+
+    // The CIEDE2000 delta E, computed in Lab.  This tracks human perception
+    // much more closely than anything computed in RGB.
+    // See https://en.wikipedia.org/wiki/Color_difference#CIEDE2000
+    Ciede2000,
+}
+
+impl Distance {
+    pub fn between(&self, c1: Srgb<u8>, c2: Srgb<u8>) -> f32 {
+        match self {
+            Distance::Euclidean => weighted_rgb_distance(c1, c2),
+            Distance::Lab => lab_distance(c1, c2),
+            Distance::Ciede2000 => to_lab(c1).get_color_difference(&to_lab(c2)),
+        }
+    }
+
+    // Returns the index of the candidate which is closest to `target`, or
+    // None if there are no candidates.  Ties go to the earliest candidate.
+    pub fn nearest(&self, target: Srgb<u8>, candidates: &[Srgb<u8>]) -> Option<usize> {
+        candidates
+            .iter()
+            .map(|c| self.between(target, *c))
+            .enumerate()
+            .fold(None, |best: Option<(usize, f32)>, (i, d)| match best {
+                Some((_, best_d)) if best_d <= d => best,
+                _ => Some((i, d)),
+            })
+            .map(|(i, _)| i)
+    }
+}
+
+pub fn to_lab(c: Srgb<u8>) -> Lab {
+    c.into_format::<f32>().into_color()
+}
+
+// Rotating the hue or raising the lightness of a vivid colour can take it
+// outside sRGB, e.g. there's no cyan as colourful as pure red.  If it does,
+// we reduce the chroma until it fits, rather than clipping each channel --
+// clipping the channels would change the hue and the lightness, which are
+// the things we want to keep.
+pub fn to_srgb(lch: Lch) -> Srgb<u8> {
+    // `from_color` would quietly clip the channels for us, so we convert
+    // without clamping and check for ourselves.
+    let at_chroma = |chroma: f32| Srgb::from_color_unclamped(Lch::new(lch.l, chroma, lch.hue));
+
+    if !at_chroma(lch.chroma).is_within_bounds() {
+        // A grey is always in gamut, so we can binary search for the
+        // largest chroma which is.
+        let (mut low, mut high) = (0.0, lch.chroma);
+
+        for _ in 0..20 {
+            let mid = (low + high) / 2.0;
+
+            if at_chroma(mid).is_within_bounds() {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        return at_chroma(low).clamp().into_format();
+    }
+
+    at_chroma(lch.chroma).into_format()
+}
+
+// A colour with less chroma (in LCh) than this is a grey, or close enough
+// that its hue is mostly noise from rounding -- so rotating it wouldn't
+// give you a different colour, and it doesn't count as colourful.
+pub const GREY_CHROMA: f32 = 5.0;
+
+// The plain Euclidean distance in Lab, aka CIE76 delta E.
+pub fn lab_distance(c1: Srgb<u8>, c2: Srgb<u8>) -> f32 {
+    let (lab1, lab2) = (to_lab(c1), to_lab(c2));
+
+    ((lab1.l - lab2.l).powi(2) + (lab1.a - lab2.a).powi(2) + (lab1.b - lab2.b).powi(2)).sqrt()
+}
+
+// Colours closer than this (as a CIE76 delta E) look like the same colour,
+// or near enough that you wouldn't pick both for a palette.  A delta E of
+// around 2.3 is just noticeable; we want colours to be clearly different.
+pub const DISTINCT_THRESHOLD: f32 = 10.0;
+
+// How many of the colours are perceptually distinct?  We go through the
+// colours in order, and count each one which isn't close to one we've
+// already counted.
+pub fn count_distinct(colours: &[Srgb<u8>]) -> usize {
+    let mut distinct: Vec<Srgb<u8>> = Vec::new();
+
+    for c in colours {
+        if distinct.iter().all(|d| lab_distance(*c, *d) >= DISTINCT_THRESHOLD) {
+            distinct.push(*c);
+        }
+    }
+
+    distinct.len()
+}
+
+// Parse a delta E, e.g. for --min-distance.  It has to be positive, or
+// it would never apply.
+pub fn parse_delta_e(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(delta_e) if delta_e > 0.0 && delta_e.is_finite() => Ok(delta_e),
+        Ok(_) => Err(String::from("must be a number greater than 0, e.g. 5")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// This function calculates the distance between two colours in the RGB colour space.
+// It uses a formula that takes into account the human perception of colour differences.
+fn weighted_rgb_distance(c1: Srgb<u8>, c2: Srgb<u8>) -> f32 {
+    let r = c1.red as f32 - c2.red as f32;
+    let g = c1.green as f32 - c2.green as f32;
+    let b = c1.blue as f32 - c2.blue as f32;
+
+    // Apparently more pleasing to the human eyes
+    // https://stackoverflow.com/a/9085524/6802309
+    let r_mean = (c1.red as f32 + c2.red as f32) / 2.0;
+    (((512.0 + r_mean) * r * r) / 256.0 + 4.0 * g * g + ((767.0 - r_mean) * b * b) / 256.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::{FromColor, Lch, Srgb};
+
+    use crate::distance::{count_distinct, parse_delta_e, to_lab, to_srgb, Distance};
+
+    #[test]
+    fn it_counts_distinct_colours() {
+        assert_eq!(count_distinct(&[]), 0);
+        assert_eq!(count_distinct(&[Srgb::new(255, 0, 0), Srgb::new(250, 2, 2), Srgb::new(0, 0, 255)]), 2);
+        assert_eq!(count_distinct(&[Srgb::new(0, 0, 0), Srgb::new(255, 255, 255)]), 2);
+    }
+
+    // Red's complement at the same chroma is outside sRGB, so we get a less
+    // colourful cyan with the same lightness, not a clipped one.
+    #[test]
+    fn it_reduces_the_chroma_to_fit_in_srgb() {
+        let red = Lch::from_color(Srgb::new(1.0, 0.0, 0.0));
+        let cyan = to_srgb(Lch::new(red.l, red.chroma, red.hue + 180.0));
+
+        assert!((to_lab(cyan).l - red.l).abs() < 1.0, "cyan = {:?}", cyan);
+        assert!(Lch::from_color(cyan.into_format::<f32>()).chroma < red.chroma);
+    }
+
+    #[test]
+    fn it_parses_a_delta_e() {
+        assert_eq!(parse_delta_e("5"), Ok(5.0));
+        assert_eq!(parse_delta_e("2.3"), Ok(2.3));
+
+        assert!(parse_delta_e("0").is_err());
+        assert!(parse_delta_e("-5").is_err());
+        assert!(parse_delta_e("NaN").is_err());
+        assert!(parse_delta_e("far").is_err());
+    }
+
+    #[test]
+    fn it_finds_no_distance_between_identical_colours() {
+        let c = Srgb::new(12, 34, 56);
+
+        assert_eq!(Distance::Euclidean.between(c, c), 0.0);
+        assert_eq!(Distance::Lab.between(c, c), 0.0);
+        assert_eq!(Distance::Ciede2000.between(c, c), 0.0);
+    }
+
+    #[test]
+    fn it_finds_no_nearest_colour_without_candidates() {
+        assert_eq!(Distance::Ciede2000.nearest(Srgb::new(0, 0, 0), &[]), None);
+    }
+
+    // In Lab, the candidates here are roughly:
+    //
+    //      dark teal   #003030  L=17.1  a=-14.9  b=-4.4
+    //      periwinkle  #6060c0  L=45.2  a=26.2   b=-50.6
+    //
+    // and the target (ANSI blue, #0000aa) is L=19.6 a=58.4 b=-79.6.
+    //
+    // The teal is closer in RGB because it's similarly dark, but it's a
+    // completely different hue -- a person would pick the periwinkle.
+    #[test]
+    fn it_matches_a_dark_teal_differently_with_ciede2000_and_euclidean() {
+        let target = Srgb::new(0, 0, 170);
+        let candidates = [Srgb::new(0, 48, 48), Srgb::new(96, 96, 192)];
+
+        assert_eq!(Distance::Euclidean.nearest(target, &candidates), Some(0));
+        assert_eq!(Distance::Ciede2000.nearest(target, &candidates), Some(1));
+    }
+
+    #[test]
+    fn it_matches_a_pale_blue_differently_with_ciede2000_and_euclidean() {
+        let target = Srgb::new(0, 0, 170);
+        let candidates = [Srgb::new(0, 48, 48), Srgb::new(80, 80, 248)];
+
+        assert_eq!(Distance::Euclidean.nearest(target, &candidates), Some(0));
+        assert_eq!(Distance::Ciede2000.nearest(target, &candidates), Some(1));
+    }
+}
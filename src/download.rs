@@ -0,0 +1,101 @@
+use std::io::{self, Read};
+use std::time::Duration;
+
+// If PATH is a URL, we download the image and decode it from memory.
+//
+// We don't look at the URL to work out what sort of image it is -- URLs
+// often have query strings (`cover.gif?width=300`) or no extension at all --
+// so we always sniff the format from the contents.
+
+// How long we wait for the server before giving up.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// Download the body of a URL, as long as it's no bigger than `max_size`
+// bytes.  Each way this can fail gets its own message, so you can tell if
+// it's a problem with the network, the server, or the file.
+pub fn download(url: &str, max_size: u64) -> io::Result<Vec<u8>> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            return Err(io::Error::other(format!(
+                "the server responded with HTTP {} {}",
+                code,
+                response.status_text()
+            )));
+        }
+        Err(ureq::Error::Transport(e)) => {
+            return Err(io::Error::other(format!("couldn't download the image: {}", e)));
+        }
+    };
+
+    let too_big = || {
+        io::Error::other(format!(
+            "the image is bigger than {} bytes; use --max-download-size to allow bigger downloads",
+            max_size
+        ))
+    };
+
+    // If the server tells us how big the file is, we can stop before we
+    // start -- but it might not, so we also check as we read.
+    let content_length = response.header("Content-Length").and_then(|s| s.parse::<u64>().ok());
+
+    if content_length.is_some_and(|length| length > max_size) {
+        return Err(too_big());
+    }
+
+    // We read one byte more than we allow, so we can tell if there's too
+    // much -- unless you allow as much as fits in a u64 anyway.
+    let mut bytes = Vec::new();
+    response.into_reader().take(max_size.saturating_add(1)).read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 > max_size {
+        return Err(too_big());
+    }
+
+    Ok(bytes)
+}
+
+// Parse a size like `50M`, with an optional K, M or G suffix (in powers
+// of 1000, like download sizes usually are).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'K')) | Some((i, 'k')) => (&s[..i], 1_000),
+        Some((i, 'M')) | Some((i, 'm')) => (&s[..i], 1_000_000),
+        Some((i, 'G')) | Some((i, 'g')) => (&s[..i], 1_000_000_000),
+        _ => (s, 1),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| String::from("must be a number of bytes, optionally with a K, M or G suffix, e.g. 50M"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::download::{is_url, parse_size};
+
+    #[test]
+    fn it_recognises_urls() {
+        assert!(is_url("https://example.com/cover.jpg"));
+        assert!(is_url("http://example.com/cover.gif?width=300"));
+        assert!(!is_url("./src/tests/red.png"));
+        assert!(!is_url("https.png"));
+    }
+
+    #[test]
+    fn it_parses_sizes() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("50M"), Ok(50_000_000));
+        assert_eq!(parse_size("2k"), Ok(2_000));
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("ten").is_err());
+    }
+}
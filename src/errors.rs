@@ -0,0 +1,139 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use image::error::{ImageFormatHint, UnsupportedErrorKind};
+use image::ImageError;
+
+use crate::get_bytes;
+
+// Everything that can go wrong, with enough context to tell the user which
+// file was the problem.
+//
+// Each kind of error has its own exit code, so scripts can tell them apart:
+//
+//   1 = we couldn't read or write a file, or a file we need is invalid
+//   2 = the command-line arguments don't make sense (as reported by clap)
+//   3 = we read the file, but couldn't decode it as an image
+//
+// This isn't an error, but we also use a separate exit code when one of the
+// checks you can run (--threshold or --require-distinct) doesn't pass.
+pub const CHECK_FAILED: i32 = 4;
+
+#[derive(Debug)]
+pub enum Error {
+    Io { path: PathBuf, error: io::Error },
+    Decode { path: PathBuf, error: ImageError },
+    Palette { path: PathBuf, line: usize, message: String },
+    Usage(clap::Error),
+}
+
+impl Error {
+    pub fn from_image_error(path: &Path, error: ImageError) -> Error {
+        let path = path.to_path_buf();
+
+        match error {
+            ImageError::IoError(error) => Error::Io { path, error },
+            error => Error::Decode { path, error },
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io { .. } | Error::Palette { .. } => 1,
+            Error::Usage(_) => 2,
+            Error::Decode { .. } => 3,
+        }
+    }
+
+    // Print the error and exit.  Usage errors are printed by clap, so
+    // they look the same as clap's own errors.
+    pub fn exit(&self) -> ! {
+        match self {
+            Error::Usage(e) => e.exit(),
+            _ => {
+                eprintln!("{}", self);
+                std::process::exit(self.exit_code());
+            }
+        }
+    }
+
+    // A suggestion for what to do next, if we have one.
+    fn hint(&self) -> Option<&'static str> {
+        let unsupported = match self {
+            Error::Decode { error: ImageError::Unsupported(e), .. } => e,
+            _ => return None,
+        };
+
+        // We only get an unknown format if we couldn't recognise the contents
+        // of the file, and the extension (if any) didn't help either.
+        match unsupported.kind() {
+            UnsupportedErrorKind::Format(ImageFormatHint::Unknown) => Some(
+                "dominant_colours didn't recognise the contents of the file; it can read JPEG, PNG, GIF, TIFF, BMP, ICO, WebP and SVG images",
+            ),
+            UnsupportedErrorKind::Format(_) => Some("dominant_colours can read JPEG, PNG, GIF, TIFF, BMP, ICO, WebP and SVG images"),
+            _ => None,
+        }
+    }
+}
+
+// If we were reading from stdin, the path is `-`, which isn't very clear
+// in an error message.
+fn display_path(path: &Path) -> String {
+    if path == Path::new(get_bytes::STDIN) {
+        String::from("<stdin>")
+    } else {
+        path.display().to_string()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, error } => write!(f, "{}: {}", display_path(path), error)?,
+            Error::Decode { path, error } => write!(f, "{}: {}", display_path(path), error)?,
+            Error::Palette { path, line, message } => write!(f, "{}, line {}: {}", display_path(path), line, message)?,
+            Error::Usage(e) => write!(f, "{}", e)?,
+        }
+
+        if let Some(hint) = self.hint() {
+            write!(f, "\nhint: {}", hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::Path;
+
+    use image::error::{ImageFormatHint, UnsupportedError};
+    use image::{ImageError, ImageFormat};
+
+    use crate::errors::Error;
+
+    #[test]
+    fn it_sorts_image_errors_into_io_and_decode() {
+        let io_error = ImageError::IoError(io::Error::new(io::ErrorKind::NotFound, "not found"));
+        let e = Error::from_image_error(Path::new("a.png"), io_error);
+        assert!(matches!(e, Error::Io { .. }), "e = {:?}", e);
+        assert_eq!(e.exit_code(), 1);
+        assert_eq!(e.to_string(), "a.png: not found");
+
+        let decode_error = ImageError::Unsupported(ImageFormatHint::Exact(ImageFormat::Pnm).into());
+        let e = Error::from_image_error(Path::new("b.ppm"), decode_error);
+        assert!(matches!(e, Error::Decode { .. }), "e = {:?}", e);
+        assert_eq!(e.exit_code(), 3);
+    }
+
+    #[test]
+    fn it_adds_a_hint_for_unsupported_formats() {
+        let error = ImageError::Unsupported(UnsupportedError::from(ImageFormatHint::Unknown));
+        let e = Error::from_image_error(Path::new("README.md"), error);
+
+        assert!(e.to_string().starts_with("README.md: The image format could not be determined\nhint: "), "e = {}", e);
+        assert!(e.to_string().contains("didn't recognise the contents of the file"), "e = {}", e);
+    }
+}
@@ -0,0 +1,215 @@
+use palette::Srgb;
+
+use crate::distance::{lab_distance, to_lab};
+use crate::format::hsl_components;
+use crate::parse_hex;
+
+// If you don't give a tolerance, we exclude anything within this CIE76
+// delta E of the colour, which catches slight variations (e.g. from JPEG
+// compression) without eating into nearby colours.
+const DEFAULT_TOLERANCE: f32 = 10.0;
+
+// A colour to leave out with --exclude, e.g. a watermark that's in every
+// screenshot.  We drop any pixels within `tolerance` of it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exclusion {
+    pub colour: Srgb<u8>,
+    pub tolerance: f32,
+}
+
+impl Exclusion {
+    // Parse an exclusion like `#ff00ff` or `#ff00ff:5`.
+    pub fn parse(s: &str) -> Result<Exclusion, String> {
+        let (hex, tolerance) = match s.split_once(':') {
+            Some((hex, tolerance)) => (hex, Some(tolerance)),
+            None => (s, None),
+        };
+
+        let colour = parse_hex(hex).ok_or_else(|| format!("{:?} isn't a hex colour like #ff00ff", hex))?;
+
+        let tolerance = match tolerance {
+            None => DEFAULT_TOLERANCE,
+            Some(t) => match t.parse::<f32>() {
+                Ok(t) if t >= 0.0 => t,
+                _ => return Err(format!("{:?} isn't a valid tolerance; it should be a number like 10", t)),
+            },
+        };
+
+        Ok(Exclusion { colour, tolerance })
+    }
+
+    fn matches(&self, c: Srgb<u8>) -> bool {
+        lab_distance(self.colour, c) <= self.tolerance
+    }
+}
+
+// Drop any pixels which match one of the exclusions from a flat buffer of
+// RGBA bytes.
+//
+// Returns None if that drops every pixel, so the caller can decide what
+// to do instead.
+pub fn exclude(bytes: &[u8], exclusions: &[Exclusion]) -> Option<Vec<u8>> {
+    let kept: Vec<u8> = bytes
+        .chunks_exact(4)
+        .filter(|px| {
+            let c = Srgb::new(px[0], px[1], px[2]);
+            !exclusions.iter().any(|e| e.matches(c))
+        })
+        .flatten()
+        .copied()
+        .collect();
+
+    if kept.is_empty() && !bytes.is_empty() {
+        None
+    } else {
+        Some(kept)
+    }
+}
+
+// With --exclude-white and --exclude-black, we drop pixels which are
+// lighter or darker than these lightnesses (the L of CIELAB, from 0 to 100).
+pub const DEFAULT_WHITE_LIGHTNESS: &str = "95";
+pub const DEFAULT_BLACK_LIGHTNESS: &str = "5";
+
+pub fn parse_lightness(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(lightness) if (0.0..=100.0).contains(&lightness) => Ok(lightness),
+        Ok(_) => Err(String::from("must be a lightness between 0 and 100")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Drop any pixels which are lighter than `max_lightness` or darker than
+// `min_lightness` from a flat buffer of RGBA bytes, e.g. the white
+// background of a product photo.
+pub fn drop_by_lightness(bytes: &[u8], max_lightness: Option<f32>, min_lightness: Option<f32>) -> Vec<u8> {
+    bytes
+        .chunks_exact(4)
+        .filter(|px| {
+            let l = to_lab(Srgb::new(px[0], px[1], px[2])).l;
+            max_lightness.is_none_or(|max| l <= max) && min_lightness.is_none_or(|min| l >= min)
+        })
+        .flatten()
+        .copied()
+        .collect()
+}
+
+// With --min-saturation, if fewer than this percentage of the pixels are
+// saturated enough, we'd rather look at every pixel than a few strays.
+pub const DEFAULT_SATURATION_FALLBACK: &str = "1";
+
+// Drop any pixels whose HSL saturation (from 0 to 100) is below
+// `min_saturation` from a flat buffer of RGBA bytes, e.g. the greys of
+// a screenshot, so the accent colours come through.
+pub fn drop_unsaturated(bytes: &[u8], min_saturation: f32) -> Vec<u8> {
+    bytes
+        .chunks_exact(4)
+        .filter(|px| {
+            let (_, saturation, _) = hsl_components(Srgb::new(px[0], px[1], px[2]));
+            saturation >= min_saturation
+        })
+        .flatten()
+        .copied()
+        .collect()
+}
+
+// Drop any pixels which are more transparent than `alpha_threshold` from
+// a flat buffer of RGBA bytes.
+//
+// Transparent pixels usually have an RGB value of black, but you can't
+// see them, so they shouldn't count as black -- e.g. a logo on a
+// transparent background would otherwise come out mostly black.
+pub fn drop_transparent(bytes: &[u8], alpha_threshold: u8) -> Vec<u8> {
+    bytes
+        .chunks_exact(4)
+        .filter(|px| px[3] >= alpha_threshold)
+        .flatten()
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::exclude::{drop_by_lightness, drop_transparent, drop_unsaturated, exclude, parse_lightness, Exclusion};
+
+    #[test]
+    fn it_parses_an_exclusion() {
+        assert_eq!(
+            Exclusion::parse("#ff00ff"),
+            Ok(Exclusion { colour: Srgb::new(255, 0, 255), tolerance: 10.0 })
+        );
+        assert_eq!(
+            Exclusion::parse("#808080:2.5"),
+            Ok(Exclusion { colour: Srgb::new(128, 128, 128), tolerance: 2.5 })
+        );
+
+        assert!(Exclusion::parse("#ff00").is_err());
+        assert!(Exclusion::parse("magenta").is_err());
+        assert!(Exclusion::parse("#ff00ff:").is_err());
+        assert!(Exclusion::parse("#ff00ff:-1").is_err());
+    }
+
+    #[test]
+    fn it_drops_pixels_close_to_an_excluded_colour() {
+        let bytes = [255, 0, 0, 255, 250, 5, 5, 255, 0, 0, 255, 255];
+        let red = Exclusion::parse("#ff0000").unwrap();
+
+        assert_eq!(exclude(&bytes, &[red]), Some(vec![0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn it_applies_every_exclusion() {
+        let bytes = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255];
+        let red = Exclusion::parse("#ff0000").unwrap();
+        let green = Exclusion::parse("#00ff00").unwrap();
+
+        assert_eq!(exclude(&bytes, &[red, green]), Some(vec![0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn it_returns_none_if_every_pixel_is_excluded() {
+        let bytes = [255, 0, 0, 255];
+        let red = Exclusion::parse("#ff0000").unwrap();
+
+        assert_eq!(exclude(&bytes, &[red]), None);
+        assert_eq!(exclude(&[], &[red]), Some(vec![]));
+    }
+
+    #[test]
+    fn it_drops_transparent_pixels() {
+        let bytes = [255, 0, 0, 255, 0, 0, 0, 0, 0, 0, 255, 127, 0, 255, 0, 128];
+
+        assert_eq!(drop_transparent(&bytes, 128), vec![255, 0, 0, 255, 0, 255, 0, 128]);
+        assert_eq!(drop_transparent(&bytes, 0), bytes.to_vec());
+    }
+
+    #[test]
+    fn it_drops_pixels_which_are_too_light_or_dark() {
+        let bytes = [255, 255, 255, 255, 250, 250, 250, 255, 200, 0, 0, 255, 5, 5, 5, 255];
+
+        assert_eq!(drop_by_lightness(&bytes, Some(95.0), None), vec![200, 0, 0, 255, 5, 5, 5, 255]);
+        assert_eq!(drop_by_lightness(&bytes, None, Some(5.0)), bytes[..12].to_vec());
+        assert_eq!(drop_by_lightness(&bytes, Some(95.0), Some(5.0)), vec![200, 0, 0, 255]);
+        assert_eq!(drop_by_lightness(&bytes, Some(10.0), Some(90.0)), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn it_parses_a_lightness() {
+        assert_eq!(parse_lightness("95"), Ok(95.0));
+        assert_eq!(parse_lightness("0"), Ok(0.0));
+
+        assert!(parse_lightness("101").is_err());
+        assert!(parse_lightness("-1").is_err());
+        assert!(parse_lightness("white").is_err());
+    }
+
+    #[test]
+    fn it_drops_unsaturated_pixels() {
+        let bytes = [128, 128, 128, 255, 150, 146, 140, 255, 30, 80, 220, 255];
+
+        assert_eq!(drop_unsaturated(&bytes, 20.0), vec![30, 80, 220, 255]);
+        assert_eq!(drop_unsaturated(&bytes, 0.0), bytes.to_vec());
+    }
+}
@@ -0,0 +1,250 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use palette::Srgb;
+
+use crate::hex;
+
+// The palette file formats we can export to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    // GIMP palettes, which are plain text.
+    Gpl,
+
+    // Adobe Swatch Exchange, a binary format used by Photoshop,
+    // Illustrator, and friends.
+    Ase,
+}
+
+impl ExportFormat {
+    // Can we safely print this format to a terminal?
+    pub fn is_text(&self) -> bool {
+        match self {
+            ExportFormat::Gpl => true,
+            ExportFormat::Ase => false,
+        }
+    }
+
+    // The name is shown in the app's list of palettes.  ASE files don't
+    // have a name for the whole palette, so they don't use it.
+    //
+    // Each colour is named with `swatch_name` -- see `name_swatch`.
+    pub fn encode(&self, colours: &[Srgb<u8>], name: &str, swatch_name: &str) -> Vec<u8> {
+        match self {
+            ExportFormat::Gpl => encode_gpl(colours, name, swatch_name).into_bytes(),
+            ExportFormat::Ase => encode_ase(colours, swatch_name),
+        }
+    }
+}
+
+// The name we use for a palette if there isn't a better one, e.g. if the
+// image came from stdin.
+pub const DEFAULT_PALETTE_NAME: &str = "dominant_colours";
+
+// By default, each colour is named with its hex string.
+pub const DEFAULT_SWATCH_NAME: &str = "{hex}";
+
+// The placeholders you can use in --swatch-name.
+const SWATCH_PLACEHOLDERS: &[&str] = &["{index}", "{hex}"];
+
+// Name a colour with a template like `cover-{index}`, where `{index}`
+// counts from 1 and `{hex}` is the hex string.
+fn name_swatch(template: &str, index: usize, c: Srgb<u8>) -> String {
+    template.replace("{index}", &(index + 1).to_string()).replace("{hex}", &hex(c))
+}
+
+// Parse a template for --swatch-name, checking it only uses placeholders
+// we know about, so a typo doesn't end up in every name.
+pub fn parse_swatch_name(s: &str) -> Result<String, String> {
+    let mut rest = s;
+
+    while let Some(start) = rest.find('{') {
+        match SWATCH_PLACEHOLDERS.iter().find(|p| rest[start..].starts_with(**p)) {
+            Some(placeholder) => rest = &rest[start + placeholder.len()..],
+            None => return Err(String::from("can only use the placeholders {index} and {hex}, e.g. cover-{index}")),
+        }
+    }
+
+    Ok(s.to_owned())
+}
+
+// A GIMP palette is a header, then one line per colour with the red, green
+// and blue values and a name.  GIMP,
+// Inkscape and Krita can all read this format.
+//
+// The header has the palette's name and how many columns to show it in --
+// we put all the colours in one row.
+//
+// See https://developer.gimp.org/core/standards/gpl/
+fn encode_gpl(colours: &[Srgb<u8>], name: &str, swatch_name: &str) -> String {
+    let mut gpl = String::new();
+
+    // The name is the rest of the line, so it can't contain a newline.
+    let name: String = name.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+
+    writeln!(gpl, "GIMP Palette").unwrap();
+    writeln!(gpl, "Name: {}", name).unwrap();
+    writeln!(gpl, "Columns: {}", colours.len()).unwrap();
+    writeln!(gpl, "#").unwrap();
+
+    for (i, c) in colours.iter().enumerate() {
+        let swatch = name_swatch(swatch_name, i, *c).replace(|c: char| c.is_control(), " ");
+        writeln!(gpl, "{:3} {:3} {:3}\t{}", c.red, c.green, c.blue, swatch).unwrap();
+    }
+
+    gpl
+}
+
+// An ASE file is a signature, a version, and a list of blocks.  Each of our
+// colours is one block, with a UTF-16 name and the RGB values as floats.
+// Everything is big-endian.
+//
+// We write global swatches, so changing one in Illustrator changes it
+// everywhere it's used.
+//
+// See http://www.selapa.net/swatches/colors/fileformats.php#adobe_ase
+const ASE_SIGNATURE: &[u8] = b"ASEF";
+const ASE_COLOUR_BLOCK: u16 = 0x0001;
+const ASE_GLOBAL_COLOUR: u16 = 0;
+
+fn encode_ase(colours: &[Srgb<u8>], swatch_name: &str) -> Vec<u8> {
+    let mut ase = Vec::new();
+
+    ase.extend_from_slice(ASE_SIGNATURE);
+    ase.extend_from_slice(&1u16.to_be_bytes());
+    ase.extend_from_slice(&0u16.to_be_bytes());
+    ase.extend_from_slice(&(colours.len() as u32).to_be_bytes());
+
+    for (i, c) in colours.iter().enumerate() {
+        // The name is null-terminated, and its length is counted in
+        // UTF-16 code units, including the null.
+        let mut name: Vec<u16> = name_swatch(swatch_name, i, *c).encode_utf16().collect();
+        name.push(0);
+
+        let mut block = Vec::new();
+        block.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        for unit in name {
+            block.extend_from_slice(&unit.to_be_bytes());
+        }
+
+        block.extend_from_slice(b"RGB ");
+        let rgb = c.into_format::<f32>();
+        for value in [rgb.red, rgb.green, rgb.blue] {
+            block.extend_from_slice(&value.to_be_bytes());
+        }
+        block.extend_from_slice(&ASE_GLOBAL_COLOUR.to_be_bytes());
+
+        ase.extend_from_slice(&ASE_COLOUR_BLOCK.to_be_bytes());
+        ase.extend_from_slice(&(block.len() as u32).to_be_bytes());
+        ase.extend_from_slice(&block);
+    }
+
+    ase
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::export::{
+        encode_ase, encode_gpl, parse_swatch_name, ASE_COLOUR_BLOCK, ASE_GLOBAL_COLOUR, ASE_SIGNATURE, DEFAULT_SWATCH_NAME,
+    };
+
+    #[test]
+    fn it_encodes_gpl() {
+        let gpl = encode_gpl(&[Srgb::new(255, 0, 0), Srgb::new(18, 52, 86)], "corgis", DEFAULT_SWATCH_NAME);
+
+        assert_eq!(
+            gpl,
+            "GIMP Palette\nName: corgis\nColumns: 2\n#\n255   0   0\t#ff0000\n 18  52  86\t#123456\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_gpl_name_on_one_line() {
+        let gpl = encode_gpl(&[Srgb::new(255, 0, 0)], "two\nlines", DEFAULT_SWATCH_NAME);
+
+        assert!(gpl.starts_with("GIMP Palette\nName: two lines\nColumns: 1\n#\n"), "gpl = {:?}", gpl);
+    }
+
+    // Read an ASE file back into (name, [r, g, b]) pairs.
+    fn decode_ase(bytes: &[u8]) -> Vec<(String, [f32; 3])> {
+        let u16_at = |i: usize| u16::from_be_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at = |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let f32_at = |i: usize| f32::from_bits(u32_at(i));
+
+        assert_eq!(&bytes[0..4], ASE_SIGNATURE);
+        assert_eq!((u16_at(4), u16_at(6)), (1, 0));
+
+        let block_count = u32_at(8) as usize;
+        let mut colours = Vec::new();
+        let mut i = 12;
+
+        for _ in 0..block_count {
+            assert_eq!(u16_at(i), ASE_COLOUR_BLOCK);
+            let block_length = u32_at(i + 2) as usize;
+            let block_start = i + 6;
+
+            let name_length = u16_at(block_start) as usize;
+            let name: Vec<u16> = (0..name_length).map(|n| u16_at(block_start + 2 + n * 2)).collect();
+            assert_eq!(name.last(), Some(&0));
+
+            let j = block_start + 2 + name_length * 2;
+            assert_eq!(&bytes[j..j + 4], b"RGB ");
+            let rgb = [f32_at(j + 4), f32_at(j + 8), f32_at(j + 12)];
+            assert_eq!(u16_at(j + 16), ASE_GLOBAL_COLOUR);
+            assert_eq!(j + 18, block_start + block_length);
+
+            colours.push((String::from_utf16(&name[..name_length - 1]).unwrap(), rgb));
+            i = block_start + block_length;
+        }
+
+        assert_eq!(i, bytes.len());
+        colours
+    }
+
+    #[test]
+    fn it_round_trips_ase() {
+        let colours = [Srgb::new(255, 0, 0), Srgb::new(18, 52, 86), Srgb::new(0, 0, 0)];
+
+        let decoded = decode_ase(&encode_ase(&colours, DEFAULT_SWATCH_NAME));
+
+        assert_eq!(decoded.len(), colours.len());
+
+        for ((name, rgb), c) in decoded.iter().zip(colours) {
+            assert_eq!(*name, crate::hex(c));
+
+            let round_tripped: Srgb<u8> = Srgb::new(rgb[0], rgb[1], rgb[2]).into_format();
+            assert_eq!(round_tripped, c);
+        }
+    }
+
+    #[test]
+    fn it_encodes_an_empty_ase() {
+        assert_eq!(decode_ase(&encode_ase(&[], DEFAULT_SWATCH_NAME)), vec![]);
+    }
+
+    #[test]
+    fn it_names_swatches_with_a_template() {
+        let colours = [Srgb::new(255, 0, 0), Srgb::new(18, 52, 86)];
+
+        let names: Vec<String> = decode_ase(&encode_ase(&colours, "cover-{index} ({hex})"))
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["cover-1 (#ff0000)", "cover-2 (#123456)"]);
+
+        let gpl = encode_gpl(&colours, "corgis", "cover-{index}");
+        assert!(gpl.ends_with("255   0   0\tcover-1\n 18  52  86\tcover-2\n"), "gpl = {:?}", gpl);
+    }
+
+    #[test]
+    fn it_parses_a_swatch_name() {
+        assert_eq!(parse_swatch_name("cover-{index}"), Ok(String::from("cover-{index}")));
+        assert_eq!(parse_swatch_name("{hex}"), Ok(String::from("{hex}")));
+        assert_eq!(parse_swatch_name("brand"), Ok(String::from("brand")));
+
+        assert!(parse_swatch_name("cover-{idx}").is_err());
+        assert!(parse_swatch_name("{").is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use clap::ValueEnum;
+use palette::{FromColor, Hsl, Srgb};
+
+use crate::hex;
+
+// How to print the colours, with --format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    // One colour per line as a hex string, with a swatch unless you pass
+    // --no-palette.
+    Text,
+
+    // Like `text`, but as CSS rgb() or hsl() values.
+    Rgb,
+    Hsl,
+
+    // A JSON document with each colour and some details of how we found
+    // them, so other programs don't have to parse the text output.
+    Json,
+
+    // CSS custom properties, one for each colour -- see `css::stylesheet`.
+    Css,
+
+    // SCSS variables, one for each colour -- see `css::scss_variables`.
+    Scss,
+
+    // A CSS `background` with a band for each colour -- see
+    // `css::linear_gradient`.
+    CssGradient,
+
+    // A GIMP palette, the same as --export=gpl.
+    Gpl,
+
+    // An Adobe Swatch Exchange file, the same as --export=ase.
+    Ase,
+
+    // A table with a row for each colour -- see `table::table`.
+    Csv,
+    Tsv,
+
+    // With --terminal-colours, X resources for the terminal's palette --
+    // see `xresources::resources`.
+    Xresources,
+
+    // With --terminal-colours, an Alacritty colour scheme -- see
+    // `alacritty::colours_toml`.
+    Alacritty,
+
+    // With --terminal-colours, a kitty theme -- see `kitty::theme`.
+    Kitty,
+
+    // With --terminal-colours, a Windows Terminal colour scheme -- see
+    // `windows_terminal::scheme`.
+    WindowsTerminal,
+
+    // With --terminal-colours, an iTerm2 colour preset -- see
+    // `itermcolors::preset`.
+    Itermcolors,
+
+    // With --terminal-colours, a WezTerm colour scheme -- see
+    // `wezterm::scheme`.
+    Wezterm,
+
+    // `set -g` options to theme tmux's status line and borders -- see
+    // `tmux::config`.
+    Tmux,
+
+    // A Base16 scheme -- see `base16::scheme`.
+    Base16,
+
+    // Shell variables, one for each colour and its components, and with
+    // `Bash`, an array of all the colours -- see `shell::variables`.
+    Sh,
+    Bash,
+
+    // A page with a swatch for each colour -- see `html::page`.
+    Html,
+}
+
+impl OutputFormat {
+    // How to write a single colour in the text output.  The other formats
+    // always use hex strings.
+    pub fn colour(&self, c: Srgb<u8>) -> String {
+        match self {
+            OutputFormat::Rgb => format!("rgb({}, {}, {})", c.red, c.green, c.blue),
+            OutputFormat::Hsl => hsl(c),
+            _ => hex(c),
+        }
+    }
+
+    // Formats which describe one image's palette, so they don't make sense
+    // for a directory or a list of files.
+    pub fn single_image_only(&self) -> bool {
+        !matches!(
+            self,
+            OutputFormat::Text | OutputFormat::Rgb | OutputFormat::Hsl | OutputFormat::Json | OutputFormat::Csv | OutputFormat::Tsv
+        )
+    }
+
+    // For the table formats, the character between the columns.
+    pub fn table_delimiter(&self) -> Option<u8> {
+        match self {
+            OutputFormat::Csv => Some(b','),
+            OutputFormat::Tsv => Some(b'\t'),
+            _ => None,
+        }
+    }
+}
+
+// Write a colour as hsl(), with the hue in degrees and the saturation
+// and lightness as percentages.
+fn hsl(c: Srgb<u8>) -> String {
+    let (hue, saturation, lightness) = hsl_components(c);
+
+    format!("hsl({:.1}, {:.0}%, {:.0}%)", hue, saturation, lightness)
+}
+
+// The hue in degrees (to one decimal place), and the saturation and
+// lightness as percentages.
+pub fn hsl_components(c: Srgb<u8>) -> (f32, f32, f32) {
+    let hsl = Hsl::from_color(c.into_format::<f32>());
+
+    // We round the hue before we wrap it, so a hue just below 360 becomes
+    // 0.0 rather than 360.0.
+    let hue = ((hsl.hue.to_positive_degrees() * 10.0).round() / 10.0) % 360.0;
+
+    (hue, hsl.saturation * 100.0, hsl.lightness * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::format::OutputFormat;
+
+    #[test]
+    fn it_only_allows_some_formats_for_a_single_image() {
+        assert!(!OutputFormat::Text.single_image_only());
+        assert!(!OutputFormat::Json.single_image_only());
+        assert!(!OutputFormat::Csv.single_image_only());
+
+        assert!(OutputFormat::Css.single_image_only());
+        assert!(OutputFormat::Ase.single_image_only());
+        assert!(OutputFormat::Html.single_image_only());
+    }
+
+    #[test]
+    fn it_writes_rgb() {
+        assert_eq!(OutputFormat::Rgb.colour(Srgb::new(255, 0, 0)), "rgb(255, 0, 0)");
+        assert_eq!(OutputFormat::Rgb.colour(Srgb::new(18, 52, 86)), "rgb(18, 52, 86)");
+    }
+
+    #[test]
+    fn it_writes_hsl() {
+        assert_eq!(OutputFormat::Hsl.colour(Srgb::new(255, 0, 0)), "hsl(0.0, 100%, 50%)");
+        assert_eq!(OutputFormat::Hsl.colour(Srgb::new(18, 52, 86)), "hsl(210.0, 65%, 20%)");
+        assert_eq!(OutputFormat::Hsl.colour(Srgb::new(0, 128, 0)), "hsl(120.0, 100%, 25%)");
+        assert_eq!(OutputFormat::Hsl.colour(Srgb::new(128, 128, 128)), "hsl(0.0, 0%, 50%)");
+        assert_eq!(OutputFormat::Hsl.colour(Srgb::new(255, 0, 1)), "hsl(359.8, 100%, 50%)");
+    }
+
+    #[test]
+    fn it_writes_hex_for_everything_else() {
+        assert_eq!(OutputFormat::Text.colour(Srgb::new(255, 0, 0)), "#ff0000");
+        assert_eq!(OutputFormat::Css.colour(Srgb::new(255, 0, 0)), "#ff0000");
+    }
+}
@@ -0,0 +1,203 @@
+use image::RgbaImage;
+
+// Which frames of an animation to look at, with --frames.
+//
+// Looking at more frames gives a more accurate palette, but a GIF can have
+// hundreds of frames, and fewer frames are much faster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameSelection {
+    // Our usual sampling: every frame of a short animation, and about 25
+    // evenly spaced frames of a longer one.
+    Auto,
+
+    All,
+    First,
+
+    // Every nth frame, starting with the first.
+    Every(usize),
+
+    // A single frame, counting from zero.  If the animation is shorter than
+    // that, we look at the last frame.
+    Index(usize),
+}
+
+impl FrameSelection {
+    pub fn parse(s: &str) -> Result<FrameSelection, String> {
+        let number = |n: &str| n.parse::<usize>().ok();
+
+        let selection = match s.split_once(':') {
+            None if s == "auto" => Some(FrameSelection::Auto),
+            None if s == "all" => Some(FrameSelection::All),
+            None if s == "first" => Some(FrameSelection::First),
+            Some(("every", n)) => number(n).filter(|n| *n > 0).map(FrameSelection::Every),
+            Some(("index", n)) => number(n).map(FrameSelection::Index),
+            _ => None,
+        };
+
+        selection.ok_or_else(|| String::from("should be auto, all, first, every:<N> or index:<N>, e.g. every:10"))
+    }
+
+    // Do we want to look at the frame at this index?
+    //
+    // Frame indexes count from zero, in the original animation.
+    fn includes(&self, i: usize, frame_count: usize) -> bool {
+        match self {
+            FrameSelection::Auto | FrameSelection::All => true,
+            FrameSelection::First => i == 0,
+            FrameSelection::Every(n) => i.is_multiple_of(*n),
+            FrameSelection::Index(n) => i == (*n).min(frame_count.saturating_sub(1)),
+        }
+    }
+}
+
+// Pick the frames we asked for with --frames.
+//
+// This happens before we skip repeated frames, so `index:<N>` and
+// `every:<N>` count frames the way you'd see them in an image viewer.
+pub fn select<T>(frames: Vec<T>, selection: FrameSelection) -> Vec<T> {
+    let frame_count = frames.len();
+
+    frames
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| selection.includes(*i, frame_count))
+        .map(|(_, frame)| frame)
+        .collect()
+}
+
+// If we've picked more than `max_frames` frames, only keep an evenly
+// spaced subset of them, always including the first.
+pub fn limit<T>(frames: Vec<T>, max_frames: Option<usize>) -> Vec<T> {
+    let step = match max_frames {
+        Some(max_frames) if frames.len() > max_frames => frames.len().div_ceil(max_frames),
+        _ => return frames,
+    };
+
+    frames.into_iter().step_by(step).collect()
+}
+
+// One frame of an animation, as it appears on screen, and how long it's
+// shown for (in milliseconds).
+pub struct Frame {
+    pub image: RgbaImage,
+    pub delay: u32,
+}
+
+// A lot of GIFs have frames with a delay of 0, which means "as fast as
+// you can".  Browsers slow those frames down, and don't show any frame
+// for less than 20ms, so we count anything shorter as 20ms.  That way a
+// frame with no delay still counts for something, but not for much.
+const MIN_DELAY: u32 = 20;
+
+impl Frame {
+    pub fn new(image: RgbaImage, delay: u32) -> Frame {
+        Frame { image, delay: delay.max(MIN_DELAY) }
+    }
+}
+
+// Join the pixels of every frame together, so each frame gets a share of
+// the pixels in proportion to how long it's on screen.
+//
+// Each item is the RGBA bytes of a frame and its delay.  We keep the same
+// total number of pixels, so a frame which is shown for longer has some of
+// its pixels repeated, and a frame which flashes by only has some of its
+// pixels kept.  If every frame has the same delay, this just joins them.
+pub fn weighted_pixels(frames: Vec<(Vec<u8>, u32)>) -> Vec<u8> {
+    let total_pixels: u64 = frames.iter().map(|(bytes, _)| (bytes.len() / 4) as u64).sum();
+    let total_delay: u64 = frames.iter().map(|(_, delay)| *delay as u64).sum();
+
+    if total_delay == 0 {
+        return frames.into_iter().flat_map(|(bytes, _)| bytes).collect();
+    }
+
+    frames
+        .into_iter()
+        .flat_map(|(bytes, delay)| {
+            let pixel_count = bytes.len() / 4;
+            let share = (total_pixels * delay as u64 / total_delay) as usize;
+
+            // Spread the pixels we keep evenly across the frame.
+            (0..share)
+                .map(|i| i * pixel_count / share)
+                .flat_map(|p| bytes[p * 4..p * 4 + 4].to_vec())
+                .collect::<Vec<u8>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frames::{limit, select, weighted_pixels, Frame, FrameSelection};
+
+    #[test]
+    fn it_parses_a_frame_selection() {
+        assert_eq!(FrameSelection::parse("auto"), Ok(FrameSelection::Auto));
+        assert_eq!(FrameSelection::parse("all"), Ok(FrameSelection::All));
+        assert_eq!(FrameSelection::parse("first"), Ok(FrameSelection::First));
+        assert_eq!(FrameSelection::parse("every:10"), Ok(FrameSelection::Every(10)));
+        assert_eq!(FrameSelection::parse("index:0"), Ok(FrameSelection::Index(0)));
+
+        assert!(FrameSelection::parse("every:0").is_err());
+        assert!(FrameSelection::parse("every").is_err());
+        assert!(FrameSelection::parse("index:-1").is_err());
+        assert!(FrameSelection::parse("last").is_err());
+    }
+
+    #[test]
+    fn it_selects_frames() {
+        let frames: Vec<usize> = (0..10).collect();
+
+        assert_eq!(select(frames.clone(), FrameSelection::All), frames);
+        assert_eq!(select(frames.clone(), FrameSelection::First), vec![0]);
+        assert_eq!(select(frames.clone(), FrameSelection::Every(4)), vec![0, 4, 8]);
+        assert_eq!(select(frames.clone(), FrameSelection::Index(3)), vec![3]);
+    }
+
+    #[test]
+    fn it_clamps_the_index_to_the_last_frame() {
+        let frames: Vec<usize> = (0..10).collect();
+
+        assert_eq!(select(frames, FrameSelection::Index(100)), vec![9]);
+    }
+
+    #[test]
+    fn it_limits_the_number_of_frames() {
+        let frames: Vec<usize> = (0..10).collect();
+
+        assert_eq!(limit(frames.clone(), None), frames);
+        assert_eq!(limit(frames.clone(), Some(20)), frames);
+        assert_eq!(limit(frames.clone(), Some(5)), vec![0, 2, 4, 6, 8]);
+        assert_eq!(limit(frames.clone(), Some(3)), vec![0, 4, 8]);
+        assert_eq!(limit(frames, Some(1)), vec![0]);
+    }
+
+    #[test]
+    fn it_treats_a_zero_delay_as_a_short_delay() {
+        assert_eq!(Frame::new(image::RgbaImage::new(1, 1), 0).delay, 20);
+        assert_eq!(Frame::new(image::RgbaImage::new(1, 1), 10).delay, 20);
+        assert_eq!(Frame::new(image::RgbaImage::new(1, 1), 500).delay, 500);
+    }
+
+    #[test]
+    fn it_joins_frames_with_the_same_delay() {
+        let red = vec![255, 0, 0, 255, 255, 0, 0, 255];
+        let blue = vec![0, 0, 255, 255, 0, 0, 255, 255];
+
+        assert_eq!(
+            weighted_pixels(vec![(red.clone(), 100), (blue.clone(), 100)]),
+            [red, blue].concat()
+        );
+    }
+
+    #[test]
+    fn it_weights_frames_by_their_delay() {
+        let red = vec![255, 0, 0, 255, 255, 0, 0, 255];
+        let blue = vec![0, 0, 255, 255, 0, 0, 255, 255];
+
+        let pixels = weighted_pixels(vec![(red, 300), (blue, 100)]);
+        let blue_pixels = pixels.chunks_exact(4).filter(|px| px[2] == 255).count();
+
+        assert_eq!(pixels.len(), 16);
+        assert_eq!(blue_pixels, 1);
+    }
+}
@@ -1,23 +1,349 @@
 use std::fs::File;
+use std::io::{self, BufReader, Cursor, IsTerminal, Read};
+use std::path::Path;
 
-use image::codecs::gif::GifDecoder;
+use gif::DisposalMethod;
+use image::error::{DecodingError, ImageFormatHint};
 use image::imageops::FilterType;
-use image::{AnimationDecoder, DynamicImage, Frame};
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult, Pixel, Rgba, RgbaImage};
 
-pub fn get_bytes_for_image(path: &str) -> Vec<u8> {
-    let img = match image::open(&path) {
-        Ok(im) => im,
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(1);
+use crate::crop::{Crop, Region};
+use crate::download;
+use crate::cmyk_jpeg;
+use crate::frames::{self, Frame, FrameSelection};
+use crate::heif;
+use crate::ico;
+use crate::pdf;
+use crate::sampling;
+use crate::svg;
+use crate::tiff_pages;
+use crate::video;
+use crate::webp;
+
+// The pixels we've decoded from an image, as a flat buffer of RGBA bytes,
+// and how many frames they were taken from.
+//
+// The bytes are always tightly-packed 8-bit RGBA, four bytes per pixel,
+// whatever was in the original file -- see `rgba_bytes`.
+pub struct Decoded {
+    pub bytes: Vec<u8>,
+    pub frame_count: usize,
+    pub frames_skipped: usize,
+
+    // If --crop went outside the image, the part of it we looked at.
+    pub clamped_crop: Option<Region>,
+}
+
+// The formats we have decoders for.
+const SUPPORTED_FORMATS: &[ImageFormat] = &[
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+    ImageFormat::Gif,
+    ImageFormat::Tiff,
+    ImageFormat::Bmp,
+    ImageFormat::Ico,
+    ImageFormat::WebP,
+];
+
+// HEIF files are an ISO media container, which starts with an `ftyp` box
+// saying what sort of file it is.  AVIF files use the same container, and
+// we read them the same way.
+const HEIF_BRANDS: &[&[u8]] = &[
+    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1", b"avif", b"avis",
+];
+
+// Video containers: MP4 (and QuickTime) files start with an `ftyp` box
+// like HEIF, and Matroska/WebM files start with an EBML header.
+const VIDEO_BRANDS: &[&[u8]] = &[b"isom", b"iso2", b"mp41", b"mp42", b"avc1", b"M4V ", b"dash", b"qt  "];
+const EBML_SIGNATURE: &[u8] = b"\x1a\x45\xdf\xa3";
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "webm"];
+
+// SVGs are text, so they don't have a magic number, but the root element
+// is `<svg>`, maybe after an XML declaration, comments or a doctype.  Lots
+// of other files are XML (e.g. plists), so it's the root element that tells
+// us it's an SVG, not the declaration.
+const SVG_ROOTS: &[&[u8]] = &[b"<svg", b"<!DOCTYPE svg"];
+
+// How much of a file we read to work out what it is.  Most formats only need
+// the first few bytes, but an SVG can have a long comment before the root.
+const SNIFF_LENGTH: u64 = 4096;
+
+const PDF_SIGNATURE: &[u8] = b"%PDF-";
+
+// The kind of file we're looking at.  Most formats are handled by the
+// `image` crate, but it can't read HEIF, AVIF, SVG, PDF or videos.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Image(ImageFormat),
+    Heif,
+    Svg,
+    Pdf,
+    Video,
+}
+
+// Options which affect how we read the pixels from a file.
+#[derive(Clone)]
+pub struct DecodeOptions {
+    // For animated GIFs and videos, whether to skip repeated frames.
+    pub dedupe_frames: bool,
+
+    // Treat the file as a video, whatever it looks like.
+    pub video: bool,
+
+    // Which frames of an animation to look at.
+    pub frames: FrameSelection,
+
+    // The most frames to look at, in an animation or a video.
+    pub max_frames: Option<usize>,
+
+    // For videos, how many seconds apart to take frames, or None to spread
+    // them evenly through the video.
+    pub sample_every: Option<f64>,
+
+    // If the path is a URL, the most bytes we'll download.
+    pub max_download_size: u64,
+
+    // Only look at part of the image.
+    pub crop: Option<Crop>,
+
+    // Resize the image to fit in a square this big before we look at the
+    // pixels, or None to look at every pixel.
+    pub max_dimension: Option<u32>,
+
+    // For animations, keep the parts of the canvas that no frame draws on,
+    // e.g. so --background can fill them in, or for --include-transparent.
+    pub keep_undrawn: bool,
+
+    // How big to draw SVGs, which don't have a size in pixels.
+    pub svg_size: u32,
+
+    // Which page to look at (counting from 1).  PDFs use the first page
+    // if this is None, and multi-page TIFFs use every page.
+    pub page: Option<usize>,
+
+    // How many pixels per inch to draw a PDF page at.
+    pub pdf_dpi: u32,
+
+    // Only look at a band this wide around the edge of the image, as
+    // a percentage of its width and height, for --mode=background.
+    pub border_width: Option<f64>,
+
+    // How much more to look at the pixels near the centre of the image,
+    // from 0 (not at all) to 1, for --center-weight.
+    pub center_weight: f64,
+
+    // The seed for anything random we do while reading the pixels.
+    pub seed: u64,
+}
+
+// If you pass `-` as the path, we read the image from stdin.
+pub const STDIN: &str = "-";
+
+// Work out what sort of image this is from the first few bytes of the file
+// (the "magic number"), or None if we don't recognise it.
+fn sniff(path: &Path) -> io::Result<Option<Format>> {
+    let mut header = Vec::new();
+    File::open(path)?.take(SNIFF_LENGTH).read_to_end(&mut header)?;
+
+    Ok(sniff_bytes(&header))
+}
+
+fn sniff_bytes(header: &[u8]) -> Option<Format> {
+    if header.len() >= 12 && &header[4..8] == b"ftyp" {
+        if HEIF_BRANDS.contains(&&header[8..12]) {
+            return Some(Format::Heif);
+        }
+
+        if VIDEO_BRANDS.contains(&&header[8..12]) {
+            return Some(Format::Video);
+        }
+    }
+
+    if header.starts_with(EBML_SIGNATURE) {
+        return Some(Format::Video);
+    }
+
+    if header.starts_with(PDF_SIGNATURE) {
+        return Some(Format::Pdf);
+    }
+
+    if is_svg(header.strip_prefix(b"\xef\xbb\xbf").unwrap_or(header)) {
+        return Some(Format::Svg);
+    }
+
+    image::guess_format(header).ok().map(Format::Image)
+}
+
+// Skip past the XML declaration, comments and any other processing
+// instructions, and check whether the next thing is the `<svg>` element.
+// If we run out of header before we find it, we say no, and leave it to
+// the extension.
+fn is_svg(text: &[u8]) -> bool {
+    let mut text = text;
+
+    loop {
+        text = &text[text.iter().take_while(|b| b.is_ascii_whitespace()).count()..];
+
+        let end = if text.starts_with(b"<?") {
+            b"?>".as_slice()
+        } else if text.starts_with(b"<!--") {
+            b"-->".as_slice()
+        } else {
+            return SVG_ROOTS.iter().any(|root| text.starts_with(root));
+        };
+
+        match text.windows(end.len()).position(|w| w == end) {
+            Some(i) => text = &text[i + end.len()..],
+            None => return false,
+        }
+    }
+}
+
+// Work out what sort of image this is.  We look at the contents of the file
+// first, because files downloaded from the web often have the wrong extension
+// (or none at all).  If that doesn't tell us, we fall back to the extension,
+// which means we get a more useful error for a broken file.
+pub fn detect_format(path: &Path) -> ImageResult<Format> {
+    if let Some(format) = sniff(path).map_err(ImageError::IoError)? {
+        return Ok(format);
+    }
+
+    match extension(path).as_deref() {
+        Some("heic") | Some("heif") | Some("avif") => Ok(Format::Heif),
+        Some("svg") => Ok(Format::Svg),
+        Some("pdf") => Ok(Format::Pdf),
+        Some(ext) if VIDEO_EXTENSIONS.contains(&ext) => Ok(Format::Video),
+        _ => ImageFormat::from_path(path)
+            .map(Format::Image)
+            .map_err(|_| ImageError::Unsupported(ImageFormatHint::Unknown.into())),
+    }
+}
+
+// Does this file look like an image we can decode?  This only looks at the
+// contents of the file, so it doesn't matter what it's called.
+pub fn is_image(path: &Path) -> io::Result<bool> {
+    Ok(match sniff(path)? {
+        Some(Format::Image(format)) => SUPPORTED_FORMATS.contains(&format),
+        Some(Format::Heif) => cfg!(feature = "heif"),
+        Some(Format::Svg) => true,
+        Some(Format::Pdf) => cfg!(feature = "pdf"),
+        Some(Format::Video) => cfg!(feature = "video"),
+        None => false,
+    })
+}
+
+// Get the pixels of an image, as RGBA bytes.
+//
+// There's different code for fetching bytes from GIF images and videos,
+// because they have multiple frames, and we want a selection of frames.
+pub fn get_bytes(path: &Path, options: &DecodeOptions) -> ImageResult<Decoded> {
+    if path == Path::new(STDIN) {
+        return get_bytes_from_stdin(options);
+    }
+
+    if let Some(url) = path.to_str().filter(|p| download::is_url(p)) {
+        let bytes = download::download(url, options.max_download_size)?;
+        return get_bytes_from_memory(&bytes, options);
+    }
+
+    let format = if options.video { Format::Video } else { detect_format(path)? };
+
+    let img = match format {
+        Format::Image(ImageFormat::Gif) => return get_bytes_for_gif(File::open(path)?, options),
+        Format::Image(ImageFormat::Png) => return get_bytes_for_png(&std::fs::read(path)?, options),
+        Format::Video => {
+            let max_frames = options.max_frames.unwrap_or(video::DEFAULT_MAX_FRAMES);
+            let frames = video::get_frames(path, max_frames, options.sample_every)?;
+
+            // The frames are evenly spaced through the video, so they all
+            // get the same weight.
+            let frames = frames.into_iter().map(|image| Frame::new(image, 0)).collect();
+            return get_bytes_for_frames(frames, options);
+        }
+        Format::Image(ImageFormat::Ico) => ico::decode(&std::fs::read(path)?)?,
+        Format::Image(ImageFormat::Jpeg) => cmyk_jpeg::decode(&std::fs::read(path)?)?,
+        Format::Image(ImageFormat::Tiff) => return get_bytes_for_tiff(&std::fs::read(path)?, options),
+        Format::Heif => heif::decode(&std::fs::read(path)?)?,
+        Format::Svg => svg::decode(&std::fs::read(path)?, options.svg_size)?,
+        Format::Pdf => pdf::decode(&std::fs::read(path)?, options.page.unwrap_or(1), options.pdf_dpi)?,
+        Format::Image(ImageFormat::WebP) => return get_bytes_for_webp(&std::fs::read(path)?, options),
+        Format::Image(format) if SUPPORTED_FORMATS.contains(&format) => {
+            image::load(BufReader::new(File::open(path)?), format)?
+        }
+        Format::Image(format) => {
+            return Err(ImageError::Unsupported(ImageFormatHint::Exact(format).into()));
+        }
+    };
+
+    get_bytes_for_image(img, options)
+}
+
+// There's no file name to go on, so we can only use the contents to work
+// out what sort of image this is.
+fn get_bytes_from_stdin(options: &DecodeOptions) -> ImageResult<Decoded> {
+    let mut stdin = io::stdin().lock();
+
+    // If nothing is being piped in, we'd wait for input forever.
+    if stdin.is_terminal() {
+        return Err(ImageError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "stdin is a terminal; pipe an image in, e.g. `cat image.png | dominant_colours -`",
+        )));
+    }
+
+    let mut bytes = Vec::new();
+    stdin.read_to_end(&mut bytes)?;
+
+    get_bytes_from_memory(&bytes, options)
+}
+
+// Get the pixels of an image we've already read into memory.
+pub fn get_bytes_from_memory(bytes: &[u8], options: &DecodeOptions) -> ImageResult<Decoded> {
+    let format = sniff_bytes(bytes).ok_or_else(|| ImageError::Unsupported(ImageFormatHint::Unknown.into()))?;
+
+    let img = match format {
+        Format::Image(ImageFormat::Gif) => return get_bytes_for_gif(Cursor::new(bytes), options),
+        Format::Image(ImageFormat::Png) => return get_bytes_for_png(bytes, options),
+
+        // We read videos with ffmpeg, which needs a file.
+        Format::Video => {
+            return Err(ImageError::Decoding(DecodingError::new(
+                ImageFormatHint::Name(String::from("video")),
+                "Videos can only be read from a file",
+            )));
+        }
+        Format::Image(ImageFormat::Ico) => ico::decode(bytes)?,
+        Format::Image(ImageFormat::Jpeg) => cmyk_jpeg::decode(bytes)?,
+        Format::Image(ImageFormat::Tiff) => return get_bytes_for_tiff(bytes, options),
+        Format::Heif => heif::decode(bytes)?,
+        Format::Svg => svg::decode(bytes, options.svg_size)?,
+        Format::Pdf => pdf::decode(bytes, options.page.unwrap_or(1), options.pdf_dpi)?,
+        Format::Image(ImageFormat::WebP) => return get_bytes_for_webp(bytes, options),
+        Format::Image(format) if SUPPORTED_FORMATS.contains(&format) => image::load_from_memory_with_format(bytes, format)?,
+        Format::Image(format) => {
+            return Err(ImageError::Unsupported(ImageFormatHint::Exact(format).into()));
         }
     };
 
+    get_bytes_for_image(img, options)
+}
+
+fn get_bytes_for_image(img: DynamicImage, options: &DecodeOptions) -> ImageResult<Decoded> {
+    let (img, clamped_crop) = match options.crop {
+        Some(crop) => {
+            let (region, clamped) = crop_region(crop, img.width(), img.height())?;
+            let cropped = img.crop_imm(region.x, region.y, region.width, region.height);
+            (cropped, clamped.then_some(region))
+        }
+        None => (img, None),
+    };
+
     // Resize the image after we open it.  For this tool I'd rather get a good answer
     // quickly than a great answer slower.
     //
-    // The choice of max dimension is arbitrary.  Making it smaller means you get
-    // faster results, but possibly at the loss of quality.
+    // The choice of max dimension is arbitrary (you can change it with
+    // --max-dimension).  Making it smaller means you get faster results,
+    // but possibly at the loss of quality.
     //
     // The nearest neighbour algorithm produces images that don't look as good,
     // but it's much much faster and the loss of quality is unlikely to be
@@ -28,22 +354,17 @@ pub fn get_bytes_for_image(path: &str) -> Vec<u8> {
     // of magnitude) than in debug mode.
     //
     // See https://docs.rs/image/0.23.14/image/imageops/enum.FilterType.html
-    let resized_img = img.resize(400, 400, FilterType::Nearest);
+    let resized_img = resize(img, options.max_dimension);
 
-    resized_img.into_rgba8().into_raw()
+    Ok(Decoded {
+        bytes: pixels(resized_img, options),
+        frame_count: 1,
+        frames_skipped: 0,
+        clamped_crop,
+    })
 }
 
-pub fn get_bytes_for_gif(path: &str) -> Vec<u8> {
-    let f = match File::open(path) {
-        Ok(im) => im,
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(1);
-        }
-    };
-
-    let decoder = GifDecoder::new(f).ok().unwrap();
-
+fn get_bytes_for_gif<R: Read>(reader: R, options: &DecodeOptions) -> ImageResult<Decoded> {
     // If the GIF is animated, we want to make sure we look at multiple
     // frames when choosing the dominant colour.
     //
@@ -53,7 +374,107 @@ pub fn get_bytes_for_gif(path: &str) -> Vec<u8> {
     //
     // For that reason, we select a sample of up to 50 frames and use those
     // as the basis for analysis.
-    let frames: Vec<Frame> = decoder.into_frames().collect_frames().unwrap();
+    let frames = composite_gif_frames(reader)?;
+
+    get_bytes_for_frames(frames, options)
+}
+
+// Animated PNGs (APNG) get the same treatment as animated GIFs.  An APNG
+// is a valid PNG, so other tools only see the first frame, but it has an
+// `acTL` chunk which says how many frames it has.
+fn get_bytes_for_png(bytes: &[u8], options: &DecodeOptions) -> ImageResult<Decoded> {
+    let is_animated = png::Decoder::new(bytes)
+        .read_info()
+        .is_ok_and(|reader| reader.info().animation_control.is_some());
+
+    if is_animated {
+        get_bytes_for_frames(composite_apng_frames(bytes)?, options)
+    } else {
+        get_bytes_for_image(image::load_from_memory_with_format(bytes, ImageFormat::Png)?, options)
+    }
+}
+
+// Animated WebPs get the same treatment as animated GIFs.
+fn get_bytes_for_webp(bytes: &[u8], options: &DecodeOptions) -> ImageResult<Decoded> {
+    if webp::is_animated(bytes)? {
+        get_bytes_for_frames(webp::get_frames(bytes)?, options)
+    } else {
+        get_bytes_for_image(webp::decode(bytes)?, options)
+    }
+}
+
+fn get_bytes_for_tiff(bytes: &[u8], options: &DecodeOptions) -> ImageResult<Decoded> {
+    // If there are several pages, we crop and resize each one as we go, so
+    // we never hold more than one page at full size.  We resize to the full
+    // --max-dimension, not the smaller size we use for frames, in case
+    // --frames picks out a single page.
+    let mut clamped_crop = None;
+    let mut is_first_page = true;
+
+    let shrink = |image: RgbaImage| -> ImageResult<RgbaImage> {
+        let image = match options.crop {
+            Some(crop) => {
+                let (region, clamped) = crop_region(crop, image.width(), image.height())?;
+
+                if is_first_page && clamped {
+                    clamped_crop = Some(region);
+                }
+
+                image::imageops::crop_imm(&image, region.x, region.y, region.width, region.height).to_image()
+            }
+            None => image,
+        };
+
+        is_first_page = false;
+
+        Ok(resize(DynamicImage::ImageRgba8(image), options.max_dimension).into_rgba8())
+    };
+
+    let mut pages = tiff_pages::decode(bytes, options.page, shrink)?;
+
+    if pages.len() == 1 {
+        get_bytes_for_image(DynamicImage::ImageRgba8(pages.remove(0)), options)
+    } else {
+        // Unlike the frames of an animation, every page is equally
+        // important, so they all get the same weight.
+        let options = DecodeOptions { crop: None, ..options.clone() };
+        let decoded = get_bytes_for_frames(pages.into_iter().map(|image| Frame::new(image, 0)).collect(), &options)?;
+
+        Ok(Decoded { clamped_crop, ..decoded })
+    }
+}
+
+// Get the pixels from a series of frames, e.g. from an animated GIF, an
+// animated WebP, the pages of a TIFF, or a video.
+fn get_bytes_for_frames(frames: Vec<Frame>, options: &DecodeOptions) -> ImageResult<Decoded> {
+    // The frames of an animation are all the same size, but the pages of a
+    // TIFF might not be, so we work out the region for each frame.  We only
+    // report the region of the first one if it was clamped.
+    let (frames, clamped_crop) = match (options.crop, frames.first()) {
+        (Some(crop), Some(first)) => {
+            let (first_region, clamped) = crop_region(crop, first.image.width(), first.image.height())?;
+            let cropped = frames
+                .iter()
+                .map(|frame| {
+                    let (region, _) = crop_region(crop, frame.image.width(), frame.image.height())?;
+                    let image = image::imageops::crop_imm(&frame.image, region.x, region.y, region.width, region.height);
+                    Ok(Frame { image: image.to_image(), delay: frame.delay })
+                })
+                .collect::<ImageResult<Vec<Frame>>>()?;
+            (cropped, clamped.then_some(first_region))
+        }
+        _ => (frames, None),
+    };
+
+    let frames = frames::select(frames, options.frames);
+
+    // A lot of GIFs pause on a single frame by repeating it many times.
+    // Those repeats slow us down, and they bias the palette towards
+    // whatever's on screen during the pause, so we drop them.
+    let frame_count = frames.len();
+
+    let frames = if options.dedupe_frames { dedupe(frames) } else { frames };
+    let frames_skipped = frame_count - frames.len();
 
     // How this works: it tells us we should be looking at the nth frame.
     // Examples:
@@ -68,17 +489,21 @@ pub fn get_bytes_for_gif(path: &str) -> Vec<u8> {
     // I'm sure there's a more idiomatic way to do this, but it was late
     // when I wrote this and it seems to work.
     //
-    let nth_frame = if frames.len() <= 50 {
+    // We only do this if you didn't pick the frames yourself with --frames.
+    let nth_frame = if frames.len() <= 50 || options.frames != FrameSelection::Auto {
         1
     } else {
-        ((frames.len() as f32) / (25 as f32)) as i32
+        ((frames.len() as f32) / 25.0) as i32
     };
 
-    let selected_frames = frames
+    let selected_frames: Vec<&Frame> = frames
         .iter()
         .enumerate()
         .filter(|(i, _)| (*i as f32 / nth_frame as f32).floor() == (*i as f32 / nth_frame as f32))
-        .map(|(_, frame)| frame);
+        .map(|(_, frame)| frame)
+        .collect();
+
+    let selected_frames = frames::limit(selected_frames, options.max_frames);
 
     // Now we go through the frames and extract all the pixels.  The k-means
     // process doesn't care about position, so we can concatenate the pixels
@@ -87,24 +512,440 @@ pub fn get_bytes_for_gif(path: &str) -> Vec<u8> {
     // As with non-GIF images, we resize the images down before loading them.
     // We resize to a smaller frame in GIFs because if there are multiple
     // frames, we don't care as much about individual frames, and we want
-    // to avoid a large Vec<u8> in memory.
-    let resize = if frames.len() == 1 { 400 } else { 100 };
-
-    selected_frames
-        .map(|frame| {
-            DynamicImage::ImageRgba8(frame.buffer().clone())
-                .resize(resize, resize, FilterType::Nearest)
-                .into_rgba8()
-                .into_raw()
-        })
-        .into_iter()
+    // to avoid a large Vec<u8> in memory -- a quarter of --max-dimension.
+    let max_dimension = if frames.len() == 1 {
+        options.max_dimension
+    } else {
+        options.max_dimension.map(|n| (n / 4).max(1))
+    };
+
+    // A frame which is on screen for longer gets more of the pixels, so a
+    // colour which flashes up briefly doesn't count as much as one you
+    // see for most of the animation.
+    let bytes = frames::weighted_pixels(
+        selected_frames
+            .iter()
+            .map(|frame| {
+                let image = DynamicImage::ImageRgba8(frame.image.clone());
+                (pixels(resize(image, max_dimension), options), frame.delay)
+            })
+            .collect(),
+    );
+
+    // Parts of the canvas that no frame ever draws on are left transparent,
+    // and we don't want them to count as black.  But if the whole GIF is
+    // transparent, there's nothing else to look at -- and if we're drawing
+    // it on a background, that's what you'd see there.  With
+    // --include-transparent, you asked for them, like any other
+    // transparent pixel.
+    if options.keep_undrawn {
+        return Ok(Decoded {
+            bytes,
+            frame_count: selected_frames.len(),
+            frames_skipped,
+            clamped_crop,
+        });
+    }
+
+    let drawn: Vec<u8> = bytes
+        .chunks_exact(4)
+        .filter(|px| px[3] != 0)
         .flatten()
-        .collect()
+        .copied()
+        .collect();
+
+    Ok(Decoded {
+        bytes: if drawn.is_empty() { bytes } else { drawn },
+        frame_count: selected_frames.len(),
+        frames_skipped,
+        clamped_crop,
+    })
+}
+
+// Resize an image to fit in a `max_dimension` square, unless we were
+// asked not to resize it (with --no-resize).
+fn resize(img: DynamicImage, max_dimension: Option<u32>) -> DynamicImage {
+    match max_dimension {
+        Some(n) => img.resize(n, n, FilterType::Nearest),
+        None => img,
+    }
+}
+
+// Fit the --crop region to an image of this size.  If there's nothing
+// left to look at, that's an error, because there's no sensible answer.
+fn crop_region(crop: Crop, width: u32, height: u32) -> ImageResult<(Region, bool)> {
+    let (region, clamped) = crop.region(width, height);
+
+    if region.width == 0 || region.height == 0 {
+        return Err(ImageError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("--crop is outside the image, which is only {}x{} pixels", width, height),
+        )));
+    }
+
+    Ok((region, clamped))
+}
+
+// Decoders give us images in lots of different layouts -- greyscale, RGB
+// without alpha, 16 bits per channel, and so on -- but the rest of the code
+// reads the pixels as a flat slice of RGBA bytes.  This converts any image
+// into that layout.
+fn rgba_bytes(img: DynamicImage) -> Vec<u8> {
+    let bytes = img.into_rgba8().into_raw();
+    debug_assert_eq!(bytes.len() % 4, 0);
+    bytes
+}
+
+// Get the pixels we want to look at: usually that's all of them, but with
+// --mode=background, it's only the ones around the edge -- see
+// `sampling::border` -- and with --center-weight, more of the ones near the
+// centre -- see `sampling::center_weighted`.  This runs after we crop and
+// resize, so the edge and the centre are of the part of the image you
+// asked us to look at.
+fn pixels(img: DynamicImage, options: &DecodeOptions) -> Vec<u8> {
+    match options.border_width {
+        Some(width) => sampling::border(&img.into_rgba8(), width),
+        None if options.center_weight > 0.0 => sampling::center_weighted(&img.into_rgba8(), options.center_weight, options.seed),
+        None => rgba_bytes(img),
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+// Drop any frame which is the same (or nearly the same) as the last
+// frame we kept.  We don't give the frame we kept the duplicates' delay,
+// because then a long pause would count for just as much as if we'd kept
+// every frame -- and the point of dropping them is to stop a pause from
+// taking over the palette.  Use --no-dedupe-frames to weight the pause by
+// how long it's on screen.
+//
+// Comparing every pixel would be slow, so we compare small thumbnails
+// instead.  Two frames are near-duplicates if their thumbnails differ
+// by less than one level per channel on average.
+fn dedupe(frames: Vec<Frame>) -> Vec<Frame> {
+    let fingerprint = |frame: &Frame| image::imageops::thumbnail(&frame.image, 16, 16).into_raw();
+
+    let mut kept: Vec<Frame> = Vec::new();
+    let mut last_fingerprint: Option<Vec<u8>> = None;
+
+    for frame in frames {
+        let this_fingerprint = fingerprint(&frame);
+
+        let is_duplicate = match &last_fingerprint {
+            Some(last) => {
+                let total_difference: u64 = last
+                    .iter()
+                    .zip(&this_fingerprint)
+                    .map(|(a, b)| (*a as i64 - *b as i64).unsigned_abs())
+                    .sum();
+
+                total_difference < this_fingerprint.len() as u64
+            }
+            None => false,
+        };
+
+        if !is_duplicate {
+            last_fingerprint = Some(this_fingerprint);
+            kept.push(frame);
+        }
+    }
+
+    kept
+}
+
+// Where the previous frame was drawn, and what we need to do to clear it up
+// before drawing the next frame.
+enum Disposal {
+    Background { left: u32, top: u32, width: u32, height: u32 },
+    Previous(RgbaImage),
+}
+
+// Decode every frame of a GIF as it would appear on screen.
+//
+// Animated GIFs often only store the part of each frame that changed,
+// so we draw each frame onto a persistent canvas, skipping transparent
+// pixels and clearing up according to the frame's disposal method.
+// The decoder handles local vs global colour tables for us.
+//
+// See https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+fn composite_gif_frames<R: Read>(reader: R) -> ImageResult<Vec<Frame>> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+
+    let mut decoder = options.read_info(reader).map_err(gif_error)?;
+
+    let mut canvas = RgbaImage::new(decoder.width() as u32, decoder.height() as u32);
+    let mut frames = Vec::new();
+    let mut disposal: Option<Disposal> = None;
+
+    while let Some(frame) = decoder.read_next_frame().map_err(gif_error)? {
+        match disposal.take() {
+            Some(Disposal::Background { left, top, width, height }) => {
+                for y in top..(top + height).min(canvas.height()) {
+                    for x in left..(left + width).min(canvas.width()) {
+                        canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    }
+                }
+            }
+            Some(Disposal::Previous(previous)) => canvas = previous,
+            None => (),
+        }
+
+        let (left, top) = (frame.left as u32, frame.top as u32);
+        let (width, height) = (frame.width as u32, frame.height as u32);
+
+        disposal = match frame.dispose {
+            DisposalMethod::Background => Some(Disposal::Background { left, top, width, height }),
+            DisposalMethod::Previous => Some(Disposal::Previous(canvas.clone())),
+            DisposalMethod::Keep | DisposalMethod::Any => None,
+        };
+
+        for (i, px) in frame.buffer.chunks_exact(4).enumerate() {
+            let x = left + (i as u32 % width);
+            let y = top + (i as u32 / width);
+
+            if px[3] != 0 && x < canvas.width() && y < canvas.height() {
+                canvas.put_pixel(x, y, Rgba([px[0], px[1], px[2], px[3]]));
+            }
+        }
+
+        // GIF delays are in hundredths of a second.
+        frames.push(Frame::new(canvas.clone(), frame.delay as u32 * 10));
+    }
+
+    Ok(frames)
+}
+
+// Decode every frame of an APNG as it would appear on screen.
+//
+// This works like GIFs: each frame is drawn onto a persistent canvas,
+// then cleared up according to its dispose op.  Each frame also has a
+// blend op, which says whether to replace the pixels underneath, or
+// draw over them (so transparent parts of the frame leave them alone).
+//
+// See https://wiki.mozilla.org/APNG_Specification
+fn composite_apng_frames(bytes: &[u8]) -> ImageResult<Vec<Frame>> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(png_error)?;
+
+    let info = reader.info();
+    let mut canvas = RgbaImage::new(info.width, info.height);
+    let frame_count = info.animation_control.map_or(0, |actl| actl.num_frames);
+
+    // If the default image doesn't have its own fcTL chunk, it isn't part
+    // of the animation (it's a placeholder for apps that don't support
+    // APNG), so we skip it.
+    let mut skip_default_image = info.frame_control.is_none();
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let mut frames = Vec::new();
+    let mut disposal: Option<Disposal> = None;
+
+    while frames.len() < frame_count as usize {
+        let output = reader.next_frame(&mut buffer).map_err(png_error)?;
+
+        if skip_default_image {
+            skip_default_image = false;
+            continue;
+        }
+
+        let control = reader
+            .info()
+            .frame_control
+            .ok_or_else(|| png_error("APNG frame is missing its fcTL chunk"))?;
+
+        match disposal.take() {
+            Some(Disposal::Background { left, top, width, height }) => {
+                for y in top..(top + height).min(canvas.height()) {
+                    for x in left..(left + width).min(canvas.width()) {
+                        canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    }
+                }
+            }
+            Some(Disposal::Previous(previous)) => canvas = previous,
+            None => (),
+        }
+
+        let (left, top) = (control.x_offset, control.y_offset);
+        let (width, height) = (control.width, control.height);
+
+        // If the first frame asks us to go back to the previous canvas,
+        // there isn't one, so we clear it instead.
+        disposal = match control.dispose_op {
+            png::DisposeOp::Previous if !frames.is_empty() => Some(Disposal::Previous(canvas.clone())),
+            png::DisposeOp::Previous | png::DisposeOp::Background => {
+                Some(Disposal::Background { left, top, width, height })
+            }
+            png::DisposeOp::None => None,
+        };
+
+        let channels = output.color_type.samples();
+        let pixels = buffer[..output.line_size * height as usize]
+            .chunks_exact(output.line_size)
+            .flat_map(|row| row[..width as usize * channels].chunks_exact(channels));
+
+        for (i, px) in pixels.enumerate() {
+            let x = left + (i as u32 % width);
+            let y = top + (i as u32 / width);
+
+            if x >= canvas.width() || y >= canvas.height() {
+                continue;
+            }
+
+            let px = match px {
+                [l] => Rgba([*l, *l, *l, 255]),
+                [l, a] => Rgba([*l, *l, *l, *a]),
+                [r, g, b] => Rgba([*r, *g, *b, 255]),
+                [r, g, b, a] => Rgba([*r, *g, *b, *a]),
+                _ => unreachable!("PNGs have 1 to 4 channels"),
+            };
+
+            match control.blend_op {
+                png::BlendOp::Source => canvas.put_pixel(x, y, px),
+                png::BlendOp::Over => canvas.get_pixel_mut(x, y).blend(&px),
+            }
+        }
+
+        // APNG delays are a fraction of a second, and a denominator of 0
+        // means hundredths.
+        let delay_den = if control.delay_den == 0 { 100 } else { control.delay_den as u32 };
+        frames.push(Frame::new(canvas.clone(), control.delay_num as u32 * 1000 / delay_den));
+    }
+
+    Ok(frames)
+}
+
+fn png_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(ImageFormat::Png), e))
+}
+
+fn gif_error(e: gif::DecodingError) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(ImageFormat::Gif), e))
 }
 
 #[cfg(test)]
 mod test {
-    use crate::get_bytes;
+    use std::fs::{self, File};
+    use std::path::Path;
+
+    use image::ImageFormat;
+
+    use crate::frames::FrameSelection;
+    use crate::get_bytes::{self, DecodeOptions, Format};
+
+    #[test]
+    fn it_recognises_images_by_their_contents() {
+        assert!(get_bytes::is_image(Path::new("./src/tests/red.png")).unwrap());
+        assert!(get_bytes::is_image(Path::new("./src/tests/noise.jpg")).unwrap());
+        assert!(get_bytes::is_image(Path::new("./src/tests/favicon.ico")).unwrap());
+        assert!(get_bytes::is_image(Path::new("./src/tests/solid_colour.bmp")).unwrap());
+
+        assert!(!get_bytes::is_image(Path::new("./src/tests/malformed.txt.png")).unwrap());
+        assert!(!get_bytes::is_image(Path::new("./src/tests/solid_colour.ppm")).unwrap());
+        assert!(get_bytes::is_image(Path::new("./src/tests/purple.webp")).unwrap());
+        assert_eq!(
+            get_bytes::is_image(Path::new("./src/tests/solid_colour.heic")).unwrap(),
+            cfg!(feature = "heif")
+        );
+    }
+
+    #[test]
+    fn it_detects_the_format_from_the_contents() {
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/animated_squares_gif.png")).unwrap(),
+            Format::Image(ImageFormat::Gif)
+        );
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/noise_jpg_no_extension")).unwrap(),
+            Format::Image(ImageFormat::Jpeg)
+        );
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/red_png.gif")).unwrap(),
+            Format::Image(ImageFormat::Png)
+        );
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/solid_colour.heic")).unwrap(),
+            Format::Heif
+        );
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/solid_colour.avif")).unwrap(),
+            Format::Heif
+        );
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/video/two_colours.mkv")).unwrap(),
+            Format::Video
+        );
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/red_rect.svg")).unwrap(),
+            Format::Svg
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_the_extension() {
+        assert_eq!(
+            get_bytes::detect_format(Path::new("./src/tests/malformed.txt.png")).unwrap(),
+            Format::Image(ImageFormat::Png)
+        );
+        assert!(get_bytes::detect_format(Path::new("./README.md")).is_err());
+    }
+
+    #[test]
+    fn it_only_sniffs_xml_with_an_svg_root_as_an_svg() {
+        assert!(get_bytes::is_svg(b"<svg xmlns=\"http://www.w3.org/2000/svg\">"));
+        assert!(get_bytes::is_svg(b"<?xml version=\"1.0\"?>\n<!-- a comment -->\n<svg>"));
+        assert!(get_bytes::is_svg(b"<?xml version=\"1.0\"?><!DOCTYPE svg PUBLIC \"-//W3C//DTD SVG 1.1//EN\">"));
+
+        assert!(!get_bytes::is_svg(b"<?xml version=\"1.0\"?>\n<plist version=\"1.0\">"));
+        assert!(!get_bytes::is_svg(b"<?xml version=\"1.0\"?>\n<!-- a comment which never ends"));
+        assert!(!get_bytes::is_svg(b"<html><svg>"));
+    }
+
+    fn decode_options() -> DecodeOptions {
+        DecodeOptions {
+            dedupe_frames: true,
+            video: false,
+            frames: FrameSelection::Auto,
+            max_frames: None,
+            sample_every: None,
+            max_download_size: 1_000_000,
+            crop: None,
+            max_dimension: Some(400),
+            keep_undrawn: false,
+            svg_size: 512,
+            page: None,
+            pdf_dpi: 150,
+            border_width: None,
+            center_weight: 0.0,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn it_decodes_the_same_pixels_from_memory() {
+        for path in ["./src/tests/noise.jpg", "./src/tests/animated_squares.gif", "./src/tests/favicon.ico", "./src/tests/purple.webp"] {
+            let from_file = get_bytes::get_bytes(Path::new(path), &decode_options()).unwrap();
+            let from_memory = get_bytes::get_bytes_from_memory(&fs::read(path).unwrap(), &decode_options()).unwrap();
+
+            assert_eq!(from_file.bytes, from_memory.bytes, "path = {}", path);
+            assert_eq!(from_file.frame_count, from_memory.frame_count, "path = {}", path);
+        }
+    }
+
+    // This WebP has stripes of opaque red and fully transparent blue.  We
+    // don't do anything with the alpha channel yet, but we shouldn't lose it.
+    #[test]
+    fn it_keeps_the_alpha_channel_of_a_webp() {
+        let decoded = get_bytes::get_bytes(Path::new("./src/tests/transparent.webp"), &decode_options()).unwrap();
+        let pixels: Vec<&[u8]> = decoded.bytes.chunks_exact(4).collect();
+
+        assert!(pixels.contains(&&[255, 0, 0, 255][..]));
+        assert!(pixels.contains(&&[0, 0, 255, 0][..]));
+    }
 
     // This image comes from https://stacks.wellcomecollection.org/peering-through-mri-scans-of-fruit-and-veg-part-1-a2e8b07bde6f
     //
@@ -113,6 +954,52 @@ mod test {
     // processed correctly.
     #[test]
     fn it_gets_bytes_for_mri_fruit() {
-        get_bytes::get_bytes_for_gif("./src/tests/garlic.gif");
+        assert!(get_bytes::get_bytes_for_gif(File::open("./src/tests/garlic.gif").unwrap(), &decode_options()).is_ok());
+    }
+
+    // This GIF draws a red square in one corner, and then a series of small
+    // blue patches with transparent borders, using local colour tables.
+    // Most of the canvas is never drawn on.
+    #[test]
+    fn it_composites_delta_frames() {
+        let decoded = get_bytes::get_bytes_for_gif(File::open("./src/tests/delta_frames.gif").unwrap(), &decode_options()).unwrap();
+
+        let pixels: Vec<&[u8]> = decoded.bytes.chunks_exact(4).collect();
+
+        assert!(pixels.iter().all(|px| *px == [255, 0, 0, 255] || *px == [0, 0, 255, 255]));
+        assert!(pixels.contains(&[255, 0, 0, 255].as_slice()));
+        assert!(pixels.contains(&[0, 0, 255, 255].as_slice()));
+    }
+
+    // This APNG starts with a red frame, then draws three small blue squares
+    // with transparent borders over it, putting the canvas back after each.
+    #[test]
+    fn it_composites_apng_frames() {
+        let frames = get_bytes::composite_apng_frames(&fs::read("./src/tests/animated_squares_apng.png").unwrap()).unwrap();
+
+        assert_eq!(frames.len(), 4);
+
+        // The transparent border of the first square leaves the red
+        // underneath, and the square is gone by the next frame.
+        assert_eq!(frames[1].image.get_pixel(10, 10).0, [0, 0, 255, 255]);
+        assert_eq!(frames[1].image.get_pixel(5, 5).0, [255, 0, 0, 255]);
+        assert_eq!(frames[2].image.get_pixel(10, 10).0, [255, 0, 0, 255]);
+
+        for frame in &frames {
+            assert!(frame.image.pixels().all(|px| px.0 == [255, 0, 0, 255] || px.0 == [0, 0, 255, 255]));
+        }
+    }
+
+    // This GIF has 20 identical red frames, then a single blue frame.
+    #[test]
+    fn it_skips_duplicate_frames() {
+        let decoded = get_bytes::get_bytes_for_gif(File::open("./src/tests/red_pause.gif").unwrap(), &decode_options()).unwrap();
+        assert_eq!(decoded.frame_count, 2);
+        assert_eq!(decoded.frames_skipped, 19);
+
+        let options = DecodeOptions { dedupe_frames: false, ..decode_options() };
+        let decoded = get_bytes::get_bytes_for_gif(File::open("./src/tests/red_pause.gif").unwrap(), &options).unwrap();
+        assert_eq!(decoded.frame_count, 21);
+        assert_eq!(decoded.frames_skipped, 0);
     }
 }
@@ -0,0 +1,125 @@
+use clap::ValueEnum;
+use palette::{FromColor, Lch, Srgb};
+
+use crate::distance::{to_srgb, GREY_CHROMA};
+
+// With --harmony, we build a small colour scheme around the most dominant
+// colour, by rotating its hue around the colour wheel.  We rotate the hue
+// in LCh rather than HSL, so the derived colours keep the same lightness
+// and chroma (colourfulness) as the original, and look like they belong
+// together -- in HSL, a yellow and a blue with the same "lightness" look
+// very different.
+//
+// See https://en.wikipedia.org/wiki/Color_scheme#Harmonious_colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Harmony {
+    // The opposite hue.
+    Complementary,
+
+    // The hues 30° either side.
+    Analogous,
+
+    // The hues a third of the way round the colour wheel either side.
+    Triadic,
+
+    // The hues 30° either side of the opposite hue.
+    SplitComplementary,
+}
+
+impl Harmony {
+    // How far to rotate the hue for each derived colour, in degrees.
+    fn angles(&self) -> &'static [f32] {
+        match self {
+            Harmony::Complementary => &[180.0],
+            Harmony::Analogous => &[-30.0, 30.0],
+            Harmony::Triadic => &[120.0, 240.0],
+            Harmony::SplitComplementary => &[150.0, 210.0],
+        }
+    }
+}
+
+pub struct Scheme {
+    pub colours: Vec<Srgb<u8>>,
+
+    // Whether the colour was grey, so we gave you a lightness ramp rather
+    // than rotating the hue.
+    pub is_lightness_ramp: bool,
+}
+
+// Derive the colours for a harmony from `c`, not including `c` itself.
+//
+// If `c` is grey, there's no hue to rotate, so instead we give you the same
+// number of colours spaced out in lightness, wrapping round from white to
+// black, e.g. a mid-grey's complement is a white or a black.
+pub fn derive(c: Srgb<u8>, harmony: Harmony) -> Scheme {
+    let lch = Lch::from_color(c.into_format::<f32>());
+    let angles = harmony.angles();
+
+    if lch.chroma < GREY_CHROMA {
+        let step = 100.0 / (angles.len() + 1) as f32;
+
+        let colours = (1..=angles.len())
+            .map(|i| to_srgb(Lch::new((lch.l + step * i as f32) % 100.0, lch.chroma, lch.hue)))
+            .collect();
+
+        return Scheme { colours, is_lightness_ramp: true };
+    }
+
+    let colours = angles
+        .iter()
+        .map(|angle| to_srgb(Lch::new(lch.l, lch.chroma, lch.hue + *angle)))
+        .collect();
+
+    Scheme { colours, is_lightness_ramp: false }
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::distance::to_lab;
+    use crate::format::hsl_components;
+    use crate::harmony::{derive, Harmony};
+
+    #[test]
+    fn it_finds_cyan_as_the_complement_of_red() {
+        let red = Srgb::new(255, 0, 0);
+
+        let scheme = derive(red, Harmony::Complementary);
+        assert_eq!(scheme.colours.len(), 1);
+        assert!(!scheme.is_lightness_ramp);
+
+        let complement = scheme.colours[0];
+
+        let (hue, _, _) = hsl_components(complement);
+        assert!((170.0..=210.0).contains(&hue), "hue = {}", hue);
+
+        let (lightness, complement_lightness) = (to_lab(red).l, to_lab(complement).l);
+        assert!((lightness - complement_lightness).abs() < 1.0, "{} vs {}", lightness, complement_lightness);
+    }
+
+    #[test]
+    fn it_derives_a_colour_for_each_angle() {
+        let c = Srgb::new(0x1f, 0x6f, 0xeb);
+
+        assert_eq!(derive(c, Harmony::Complementary).colours.len(), 1);
+        assert_eq!(derive(c, Harmony::Analogous).colours.len(), 2);
+        assert_eq!(derive(c, Harmony::Triadic).colours.len(), 2);
+        assert_eq!(derive(c, Harmony::SplitComplementary).colours.len(), 2);
+
+        // The split complements are either side of the complement.
+        let complement = hsl_components(derive(c, Harmony::Complementary).colours[0]).0;
+        let split: Vec<f32> = derive(c, Harmony::SplitComplementary).colours.iter().map(|c| hsl_components(*c).0).collect();
+        assert!(split[0] < complement && complement < split[1], "{:?} vs {}", split, complement);
+    }
+
+    #[test]
+    fn it_gives_a_lightness_ramp_for_a_grey() {
+        let scheme = derive(Srgb::new(0x80, 0x80, 0x80), Harmony::Triadic);
+
+        assert!(scheme.is_lightness_ramp);
+
+        let lightness: Vec<f32> = scheme.colours.iter().map(|c| to_lab(*c).l.round()).collect();
+        assert_eq!(lightness, vec![87.0, 20.0]);
+    }
+}
@@ -0,0 +1,73 @@
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageResult};
+
+// HEIF is the format used for photos on iPhones (usually with a .heic
+// extension).  The `image` crate can't read it, so we use libheif, which
+// is a C library -- that's why it's behind the `heif` feature.
+//
+// AVIF is the same container with AV1 instead of HEVC inside, and libheif
+// reads both (as long as it was built with an AV1 decoder, e.g. dav1d).
+//
+// See https://github.com/strukturag/libheif
+
+#[cfg(feature = "heif")]
+pub fn decode(bytes: &[u8]) -> ImageResult<DynamicImage> {
+    use image::RgbaImage;
+    use libheif_rs::{ColorSpace, HeifContext, RgbChroma};
+
+    let context = HeifContext::read_from_bytes(bytes).map_err(|e| format_error(e.message))?;
+
+    // A HEIF file can contain several images -- e.g. a burst of photos, or
+    // a thumbnail -- but there's always one primary image, which is the one
+    // you see in the Photos app.
+    let handle = context
+        .primary_image_handle()
+        .map_err(|e| format_error(e.message))?;
+
+    // We ask libheif for 8-bit RGBA, and it converts from whatever's in the
+    // file, including the 10-bit images taken by newer iPhones.
+    let image = handle
+        .decode(ColorSpace::Rgb(RgbChroma::Rgba), None)
+        .map_err(|e| format_error(e.message))?;
+
+    let plane = image
+        .planes()
+        .interleaved
+        .ok_or_else(|| format_error("Decoded image has no RGBA channel"))?;
+
+    // This is bits per pixel, not per channel, so 8-bit RGBA is 32 bits.
+    if plane.bits_pre_pixel != 32 {
+        return Err(format_error("Decoded image is not 8 bits per channel"));
+    }
+
+    // Each row may be padded, so we copy it across one row at a time.
+    let row_length = plane.width as usize * 4;
+
+    let rgba: Vec<u8> = plane
+        .data
+        .chunks(plane.stride)
+        .take(plane.height as usize)
+        .flat_map(|row| &row[..row_length])
+        .copied()
+        .collect();
+
+    RgbaImage::from_raw(plane.width, plane.height, rgba)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| format_error("Decoded image is the wrong size"))
+}
+
+#[cfg(not(feature = "heif"))]
+pub fn decode(_bytes: &[u8]) -> ImageResult<DynamicImage> {
+    Err(format_error(
+        "This build of dominant_colours doesn't support HEIF or AVIF images; rebuild with `--features heif`",
+    ))
+}
+
+// Some of libheif's messages end with a newline, which would leave a
+// blank line after our error.
+fn format_error(message: impl Into<String>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Name(String::from("HEIF")),
+        message.into().trim_end().to_string(),
+    ))
+}
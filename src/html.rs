@@ -0,0 +1,102 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use palette::Srgb;
+
+use crate::contrast::text_colour;
+use crate::format::OutputFormat;
+use crate::hex;
+
+// With --format=html, we print a page which shows each colour as a big
+// swatch, with its hex, RGB and HSL values and how much of the image it
+// covers, so you can share a palette with somebody who doesn't live in a
+// terminal.
+//
+// The page is self-contained: the CSS is inline and there are no images,
+// fonts or scripts, so it works when you open it straight from disk.
+//
+// The text on each swatch is black or white, whichever has more contrast
+// with the swatch -- see `contrast::text_colour`.
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222222; background: #ffffff; }
+h1 { font-size: 1.5em; font-weight: normal; }
+.swatches { display: flex; flex-wrap: wrap; gap: 1em; }
+.swatch { display: flex; flex-direction: column; justify-content: flex-end; box-sizing: border-box; width: 12em; height: 12em; padding: 1em; border-radius: 0.5em; box-shadow: 0 0 0 1px rgba(0, 0, 0, 0.1); }
+.swatch .hex { font-size: 1.5em; font-weight: bold; }
+footer { margin-top: 2em; color: #666666; font-size: 0.9em; }";
+
+// `parameters` are the settings we used to find the colours, e.g. the
+// seed, which we print in the footer so you can get the same colours again.
+pub fn page(path: &Path, colours: &[Srgb<u8>], proportions: Option<&[f32]>, parameters: &[(&str, String)]) -> String {
+    let name = escape(&path.display().to_string());
+
+    let mut html = String::new();
+
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(html, "<html lang=\"en\">").unwrap();
+    writeln!(html, "<head>").unwrap();
+    writeln!(html, "<meta charset=\"utf-8\">").unwrap();
+    writeln!(html, "<title>Dominant colours of {}</title>", name).unwrap();
+    writeln!(html, "<style>\n{}\n</style>", STYLE).unwrap();
+    writeln!(html, "</head>").unwrap();
+    writeln!(html, "<body>").unwrap();
+    writeln!(html, "<h1>Dominant colours of {}</h1>", name).unwrap();
+    writeln!(html, "<div class=\"swatches\">").unwrap();
+
+    for (i, c) in colours.iter().enumerate() {
+        writeln!(html, "<div class=\"swatch\" style=\"background: {}; color: {}\">", hex(*c), hex(text_colour(*c))).unwrap();
+        writeln!(html, "<span class=\"hex\">{}</span>", hex(*c)).unwrap();
+        writeln!(html, "<span>{}</span>", OutputFormat::Rgb.colour(*c)).unwrap();
+        writeln!(html, "<span>{}</span>", OutputFormat::Hsl.colour(*c)).unwrap();
+
+        if let Some(proportions) = proportions {
+            writeln!(html, "<span>{:.1}%</span>", proportions[i] * 100.0).unwrap();
+        }
+
+        writeln!(html, "</div>").unwrap();
+    }
+
+    let parameters: Vec<String> = parameters.iter().map(|(key, value)| format!("{} {}", key, escape(value))).collect();
+    let with = if parameters.is_empty() { String::new() } else { format!(", with {}", parameters.join(" and ")) };
+
+    writeln!(html, "</div>").unwrap();
+    writeln!(html, "<footer>Generated by dominant_colours from {}{}.</footer>", name, with).unwrap();
+    writeln!(html, "</body>").unwrap();
+    writeln!(html, "</html>").unwrap();
+
+    html
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use palette::Srgb;
+
+    use crate::html::{escape, page};
+
+    #[test]
+    fn it_escapes_the_path() {
+        assert_eq!(escape("<b>Tom & Jerry's \"pic\"</b>"), "&lt;b&gt;Tom &amp; Jerry&#39;s &quot;pic&quot;&lt;/b&gt;");
+
+        let html = page(Path::new("<script>.png"), &[Srgb::new(0, 0, 255)], None, &[]);
+        assert!(!html.contains("<script>"), "html = {}", html);
+    }
+
+    #[test]
+    fn it_leaves_out_the_percentage_if_we_dont_know_it() {
+        let html = page(Path::new("blue.png"), &[Srgb::new(0, 0, 255)], None, &[("seed", String::from("0"))]);
+
+        assert!(html.contains("<span>hsl(240.0, 100%, 50%)</span>\n</div>"), "html = {}", html);
+        assert!(html.contains("<footer>Generated by dominant_colours from blue.png, with seed 0.</footer>"), "html = {}", html);
+    }
+}
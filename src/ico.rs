@@ -0,0 +1,123 @@
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult};
+
+// An ICO file is a container for several images, usually the same icon at
+// different sizes.  The `image` crate can decode them, but it picks the
+// entry with the most bits per pixel rather than the biggest one, and the
+// small entries are often dithered down to a handful of colours.
+//
+// So we read the directory ourselves, pick the largest entry, and only
+// hand that entry to the decoder.
+//
+// See https://en.wikipedia.org/wiki/ICO_(file_format)
+
+const HEADER_SIZE: usize = 6;
+const ENTRY_SIZE: usize = 16;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Clone, Copy, Debug)]
+struct DirEntry {
+    width: u32,
+    height: u32,
+    bits_per_pixel: u16,
+    offset: usize,
+    size: usize,
+}
+
+pub fn decode(bytes: &[u8]) -> ImageResult<DynamicImage> {
+    let entries = read_directory(bytes)?;
+    let index = best_entry(&entries).ok_or_else(|| format_error("No icons in the ICO file"))?;
+    let entry = &entries[index];
+
+    let data = bytes
+        .get(entry.offset..entry.offset + entry.size)
+        .ok_or_else(|| format_error("Icon data runs past the end of the file"))?;
+
+    // Large icons are usually stored as embedded PNGs; the rest are BMPs
+    // without the file header, which we can only decode as part of an ICO.
+    // We build a new ICO file containing just the entry we want.
+    if data.starts_with(PNG_SIGNATURE) {
+        image::load_from_memory_with_format(data, ImageFormat::Png)
+    } else {
+        let mut ico = Vec::with_capacity(HEADER_SIZE + ENTRY_SIZE + data.len());
+        ico.extend_from_slice(&bytes[..4]);
+        ico.extend_from_slice(&1u16.to_le_bytes());
+
+        let dir_entry = &bytes[HEADER_SIZE + index * ENTRY_SIZE..][..ENTRY_SIZE];
+        ico.extend_from_slice(&dir_entry[..12]);
+        ico.extend_from_slice(&((HEADER_SIZE + ENTRY_SIZE) as u32).to_le_bytes());
+        ico.extend_from_slice(data);
+
+        image::load_from_memory_with_format(&ico, ImageFormat::Ico)
+    }
+}
+
+fn read_directory(bytes: &[u8]) -> ImageResult<Vec<DirEntry>> {
+    if bytes.len() < HEADER_SIZE || bytes[0..2] != [0, 0] || bytes[2..4] != [1, 0] {
+        return Err(format_error("Invalid ICO header"));
+    }
+
+    let count = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+
+    (0..count)
+        .map(|i| {
+            let entry = bytes
+                .get(HEADER_SIZE + i * ENTRY_SIZE..HEADER_SIZE + (i + 1) * ENTRY_SIZE)
+                .ok_or_else(|| format_error("ICO directory runs past the end of the file"))?;
+
+            // A width or height of 0 means 256 pixels.
+            let dimension = |b: u8| if b == 0 { 256 } else { b as u32 };
+
+            Ok(DirEntry {
+                width: dimension(entry[0]),
+                height: dimension(entry[1]),
+                bits_per_pixel: u16::from_le_bytes([entry[6], entry[7]]),
+                size: u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize,
+                offset: u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]) as usize,
+            })
+        })
+        .collect()
+}
+
+// Pick the entry with the most pixels; if two entries are the same size,
+// prefer the one with more bits per pixel (e.g. 32-bit over paletted).
+fn best_entry(entries: &[DirEntry]) -> Option<usize> {
+    entries
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, e)| (e.width * e.height, e.bits_per_pixel))
+        .map(|(i, _)| i)
+}
+
+fn format_error(message: &'static str) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(ImageFormat::Ico), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ico::{best_entry, DirEntry};
+
+    fn entry(width: u32, height: u32, bits_per_pixel: u16) -> DirEntry {
+        DirEntry {
+            width,
+            height,
+            bits_per_pixel,
+            offset: 0,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn it_picks_the_largest_entry() {
+        let entries = [entry(16, 16, 32), entry(48, 48, 8), entry(32, 32, 32)];
+
+        assert_eq!(best_entry(&entries), Some(1));
+    }
+
+    #[test]
+    fn it_prefers_more_bits_per_pixel_for_the_same_size() {
+        let entries = [entry(32, 32, 8), entry(32, 32, 32), entry(32, 32, 4)];
+
+        assert_eq!(best_entry(&entries), Some(1));
+    }
+}
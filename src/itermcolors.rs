@@ -0,0 +1,97 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::terminal_colours::{background_and_foreground, CursorAndSelection};
+
+// With --format=itermcolors, we print the terminal colours as an iTerm2
+// colour preset, which you can import in Preferences > Profiles > Colors.
+//
+// It's an XML property list, which maps names like "Ansi 0 Color" to a
+// dictionary with each component as a float from 0 to 1.  We write each
+// float with as many digits as it takes to get the same value back, so
+// importing the preset gives exactly the colours we printed.
+//
+// See https://iterm2.com/documentation-preferences-profiles-colors.html
+
+pub fn preset(terminal_colours: &[Srgb<u8>], light: bool, overrides: &CursorAndSelection) -> String {
+    let (background, foreground) = background_and_foreground(terminal_colours, light);
+    let (cursor, selection) = overrides.pick(terminal_colours, background, foreground);
+
+    let mut colours: Vec<(String, Srgb<u8>)> = terminal_colours
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (format!("Ansi {} Color", i), *c))
+        .collect();
+
+    colours.push((String::from("Background Color"), background));
+    colours.push((String::from("Cursor Color"), cursor));
+    colours.push((String::from("Foreground Color"), foreground));
+    colours.push((String::from("Selection Color"), selection));
+
+    let mut plist = String::new();
+
+    writeln!(plist, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+    writeln!(
+        plist,
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">"
+    )
+    .unwrap();
+    writeln!(plist, "<plist version=\"1.0\">").unwrap();
+    writeln!(plist, "<dict>").unwrap();
+
+    for (name, c) in colours {
+        writeln!(plist, "\t<key>{}</key>", name).unwrap();
+        writeln!(plist, "\t<dict>").unwrap();
+        writeln!(plist, "\t\t<key>Alpha Component</key>").unwrap();
+        writeln!(plist, "\t\t<real>1</real>").unwrap();
+        writeln!(plist, "\t\t<key>Blue Component</key>").unwrap();
+        writeln!(plist, "\t\t<real>{}</real>", component(c.blue)).unwrap();
+        writeln!(plist, "\t\t<key>Color Space</key>").unwrap();
+        writeln!(plist, "\t\t<string>sRGB</string>").unwrap();
+        writeln!(plist, "\t\t<key>Green Component</key>").unwrap();
+        writeln!(plist, "\t\t<real>{}</real>", component(c.green)).unwrap();
+        writeln!(plist, "\t\t<key>Red Component</key>").unwrap();
+        writeln!(plist, "\t\t<real>{}</real>", component(c.red)).unwrap();
+        writeln!(plist, "\t</dict>").unwrap();
+    }
+
+    writeln!(plist, "</dict>").unwrap();
+    writeln!(plist, "</plist>").unwrap();
+
+    plist
+}
+
+// Rust prints floats with the fewest digits that round-trip, so this is
+// the exact value of the division.
+fn component(value: u8) -> String {
+    format!("{}", value as f64 / 255.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::itermcolors::{component, preset};
+    use crate::terminal_colours::{red_ramp, CursorAndSelection};
+
+    #[test]
+    fn it_writes_components_that_round_trip() {
+        assert_eq!(component(0), "0");
+        assert_eq!(component(255), "1");
+
+        for value in 0..=255u8 {
+            let parsed: f64 = component(value).parse().unwrap();
+            assert_eq!((parsed * 255.0).round() as u8, value);
+            assert_eq!(parsed, value as f64 / 255.0);
+        }
+    }
+
+    #[test]
+    fn it_includes_the_special_colours() {
+        let colours = red_ramp(16);
+        let plist = preset(&colours, false, &CursorAndSelection { cursor: None, selection: None });
+
+        for name in ["Ansi 0 Color", "Ansi 15 Color", "Background Color", "Cursor Color", "Foreground Color", "Selection Color"] {
+            assert!(plist.contains(&format!("\t<key>{}</key>\n", name)), "plist = {}", plist);
+        }
+    }
+}
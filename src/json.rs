@@ -0,0 +1,376 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use palette::Srgb;
+use serde::{Serialize, Serializer};
+
+use crate::contrast;
+use crate::hex;
+use crate::names;
+
+// All our JSON goes through serde, pretty-printed with four spaces, e.g.
+//
+//      {
+//          "path": "cats.jpg",
+//          "seed": 0
+//      }
+//
+// and with a newline at the end, like our other formats.
+pub fn to_string<T: Serialize>(value: &T) -> String {
+    let mut json = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+
+    value.serialize(&mut serializer).expect("our JSON is always valid");
+    json.push(b'\n');
+
+    String::from_utf8(json).expect("serde_json always writes UTF-8")
+}
+
+// A string in double quotes, escaped for JSON, e.g. for the name of a
+// colour scheme.  YAML and TOML strings in double quotes use the same
+// escapes, so this works for them too.
+pub fn string(s: &str) -> String {
+    serde_json::to_string(s).expect("a string is always valid JSON")
+}
+
+// A JSON object whose keys stay in the order we give them, e.g. the paths
+// in the order we looked at the images.
+pub struct OrderedMap<K, V>(pub Vec<(K, V)>);
+
+impl<K: Serialize, V: Serialize> Serialize for OrderedMap<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+// A JSON object which maps each path to its list of colours, e.g.
+//
+//      {
+//          "cats.jpg": [
+//              "#d0c6b2",
+//              "#3f3336"
+//          ]
+//      }
+//
+pub fn palettes(palettes: &[(PathBuf, Vec<Srgb<u8>>)]) -> String {
+    let map = palettes
+        .iter()
+        .map(|(path, colours)| (path.to_string_lossy(), colours.iter().map(|c| hex(*c)).collect::<Vec<_>>()))
+        .collect();
+
+    to_string(&OrderedMap(map))
+}
+
+// Round a number to this many decimal places, so we don't print float
+// noise like 0.73160005.
+fn round(n: f32, places: i32) -> f32 {
+    let scale = 10f32.powi(places);
+    (n * scale).round() / scale
+}
+
+// One colour, with whatever we know about it -- see `Annotations`.  We
+// leave out the fields we don't know or weren't asked for.
+#[derive(Serialize)]
+struct Colour {
+    hex: String,
+    rgb: [u8; 3],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proportion: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contrast_white: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contrast_black: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recommended_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contrast_against: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    derived: Option<bool>,
+}
+
+impl Colour {
+    fn new(c: Srgb<u8>, proportion: Option<f32>) -> Colour {
+        Colour {
+            hex: hex(c),
+            rgb: [c.red, c.green, c.blue],
+            proportion: proportion.map(|p| round(p, 4)),
+            name: None,
+            contrast_white: None,
+            contrast_black: None,
+            recommended_text: None,
+            contrast_against: None,
+            derived: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Image<'a> {
+    path: Cow<'a, str>,
+    seed: u64,
+    colours: Vec<Colour>,
+}
+
+// A JSON object describing the colours we found in one image, e.g.
+//
+//      {
+//          "path": "cats.jpg",
+//          "seed": 0,
+//          "colours": [
+//              {
+//                  "hex": "#d0c6b2",
+//                  "rgb": [
+//                      208,
+//                      198,
+//                      178
+//                  ],
+//                  "proportion": 0.7316
+//              },
+//              ...
+//          ]
+//      }
+//
+// The proportion is the fraction of the pixels closest to that colour.
+// We leave it out if we don't know it, e.g. for terminal colours.
+pub fn result(path: &Path, seed: u64, colours: &[Srgb<u8>], proportions: Option<&[f32]>, annotations: Annotations) -> String {
+    to_string(&result_object(path, seed, colours, proportions, annotations))
+}
+
+// The path of an image, its colours, and their proportions (if we know them).
+pub type ImageColours = (PathBuf, Vec<Srgb<u8>>, Option<Vec<f32>>);
+
+// A JSON array with an object for each image, as in `result`.
+pub fn results(results: &[ImageColours], seed: u64, annotations: Annotations) -> String {
+    let objects: Vec<Image> = results
+        .iter()
+        .map(|(path, colours, proportions)| result_object(path, seed, colours, proportions.as_deref(), annotations))
+        .collect();
+
+    to_string(&objects)
+}
+
+// What else to say about each colour, besides its hex, RGB and proportion:
+//
+//   - with `names`, the nearest CSS named colour, e.g. `"name": "tomato"`
+//     -- see `names::nearest`
+//   - with `contrast`, the contrast ratio with white and black text, and
+//     which of them to use, e.g. `"recommended_text": "#ffffff"`
+//   - with `contrast_against`, the contrast ratio with that colour
+//   - with `observed`, whether each colour is one we found in the image,
+//     or one we derived from them with --harmony, e.g. `"derived": true`;
+//     the first `observed` colours are the ones we found
+//
+// See `contrast` for how we work out the ratios.
+#[derive(Clone, Copy, Default)]
+pub struct Annotations {
+    pub names: bool,
+    pub contrast: bool,
+    pub contrast_against: Option<Srgb<u8>>,
+    pub observed: Option<usize>,
+}
+
+fn result_object<'a>(path: &'a Path, seed: u64, colours: &[Srgb<u8>], proportions: Option<&[f32]>, annotations: Annotations) -> Image<'a> {
+    let colours = colours
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            // The derived colours aren't in the image, so they don't
+            // have a proportion.
+            let mut colour = Colour::new(*c, proportions.and_then(|p| p.get(i)).copied());
+
+            if annotations.names {
+                colour.name = Some(names::nearest(*c).name);
+            }
+
+            if annotations.contrast {
+                let (white, black) = contrast::against_white_and_black(*c);
+
+                colour.contrast_white = Some(round(white, 2));
+                colour.contrast_black = Some(round(black, 2));
+                colour.recommended_text = Some(hex(contrast::text_colour(*c)));
+            }
+
+            if let Some(other) = annotations.contrast_against {
+                colour.contrast_against = Some(round(contrast::ratio(*c, other), 2));
+            }
+
+            if let Some(observed) = annotations.observed {
+                colour.derived = Some(i >= observed);
+            }
+
+            colour
+        })
+        .collect();
+
+    Image { path: path.to_string_lossy(), seed, colours }
+}
+
+// The key for a role, and the colour we picked for it and its proportion
+// (if there was one).
+pub type RoleColour<'a> = (&'a str, Option<(Srgb<u8>, f32)>);
+
+#[derive(Serialize)]
+struct Roles<'a> {
+    path: Cow<'a, str>,
+    seed: u64,
+    roles: OrderedMap<&'a str, Option<Colour>>,
+}
+
+// A JSON object with the colour we picked for each role with --mode=roles,
+// and how much of the image it covers, e.g.
+//
+//      {
+//          "path": "cats.jpg",
+//          "seed": 0,
+//          "roles": {
+//              "vibrant": {
+//                  "hex": "#e8431f",
+//                  "rgb": [...],
+//                  "proportion": 0.3
+//              },
+//              "dark_vibrant": null,
+//              ...
+//          }
+//      }
+//
+// A role is null if there wasn't a colour for it.
+pub fn roles<'a>(path: &'a Path, seed: u64, roles: &[RoleColour<'a>]) -> String {
+    let roles = roles
+        .iter()
+        .map(|(key, colour)| (*key, colour.map(|(c, proportion)| Colour::new(c, Some(proportion)))))
+        .collect();
+
+    to_string(&Roles { path: path.to_string_lossy(), seed, roles: OrderedMap(roles) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use palette::Srgb;
+    use serde_json::{json, Value};
+
+    use crate::json::{palettes, result, results, roles, string, Annotations};
+
+    fn parse(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn it_quotes_strings() {
+        assert_eq!(string(r#"C:\wallpapers\"cats".jpg"#), r#""C:\\wallpapers\\\"cats\".jpg""#);
+        assert_eq!(string("tab\there"), "\"tab\\there\"");
+    }
+
+    #[test]
+    fn it_maps_paths_to_colours() {
+        let json = palettes(&[
+            (PathBuf::from("red.png"), vec![Srgb::new(255, 0, 0)]),
+            (PathBuf::from("mixed.png"), vec![Srgb::new(255, 0, 0), Srgb::new(0, 0, 255)]),
+        ]);
+
+        assert_eq!(
+            json,
+            "{\n    \"red.png\": [\n        \"#ff0000\"\n    ],\n    \"mixed.png\": [\n        \"#ff0000\",\n        \"#0000ff\"\n    ]\n}\n"
+        );
+    }
+
+    #[test]
+    fn it_describes_one_image() {
+        let json = result(Path::new("red.png"), 0, &[Srgb::new(255, 0, 0), Srgb::new(0, 0, 255)], None, Annotations::default());
+
+        assert_eq!(
+            parse(&json),
+            json!({
+                "path": "red.png",
+                "seed": 0,
+                "colours": [
+                    {"hex": "#ff0000", "rgb": [255, 0, 0]},
+                    {"hex": "#0000ff", "rgb": [0, 0, 255]}
+                ]
+            })
+        );
+        assert!(json.starts_with("{\n    \"path\": \"red.png\",\n    \"seed\": 0,\n"), "json = {}", json);
+        assert!(json.ends_with("\n}\n"), "json = {}", json);
+
+        let json = result(Path::new("red.png"), 0, &[Srgb::new(255, 0, 0), Srgb::new(0, 0, 255)], Some(&[0.75, 0.25]), Annotations::default());
+
+        assert_eq!(parse(&json)["colours"][0], json!({"hex": "#ff0000", "rgb": [255, 0, 0], "proportion": 0.75}));
+    }
+
+    // We round the proportions, so there's no float noise in the output.
+    #[test]
+    fn it_rounds_the_proportions() {
+        let json = result(Path::new("red.png"), 0, &[Srgb::new(255, 0, 0)], Some(&[0.731_600_05]), Annotations::default());
+
+        assert!(json.contains("\"proportion\": 0.7316\n"), "json = {}", json);
+    }
+
+    #[test]
+    fn it_names_the_colours() {
+        let annotations = Annotations { names: true, ..Annotations::default() };
+        let json = result(Path::new("red.png"), 0, &[Srgb::new(255, 0, 0)], Some(&[1.0]), annotations);
+
+        assert_eq!(parse(&json)["colours"][0], json!({"hex": "#ff0000", "rgb": [255, 0, 0], "proportion": 1.0, "name": "red"}));
+    }
+
+    #[test]
+    fn it_includes_the_contrast() {
+        let annotations = Annotations { contrast: true, contrast_against: Some(Srgb::new(255, 255, 255)), ..Annotations::default() };
+        let json = result(Path::new("blue.png"), 0, &[Srgb::new(0, 0, 255)], None, annotations);
+
+        assert!(json.contains("\"contrast_white\": 8.59,\n"), "json = {}", json);
+        assert!(json.contains("\"contrast_black\": 2.44,\n"), "json = {}", json);
+        assert!(json.contains("\"recommended_text\": \"#ffffff\",\n"), "json = {}", json);
+        assert!(json.contains("\"contrast_against\": 8.59\n"), "json = {}", json);
+    }
+
+    #[test]
+    fn it_marks_the_derived_colours() {
+        let annotations = Annotations { observed: Some(1), ..Annotations::default() };
+        let json = result(Path::new("red.png"), 0, &[Srgb::new(255, 0, 0), Srgb::new(0, 255, 255)], Some(&[1.0]), annotations);
+
+        assert_eq!(
+            parse(&json)["colours"],
+            json!([
+                {"hex": "#ff0000", "rgb": [255, 0, 0], "proportion": 1.0, "derived": false},
+                {"hex": "#00ffff", "rgb": [0, 255, 255], "derived": true}
+            ])
+        );
+    }
+
+    #[test]
+    fn it_describes_several_images() {
+        let json = results(&[(PathBuf::from("red.png"), vec![Srgb::new(255, 0, 0)], None)], 7, Annotations::default());
+
+        assert_eq!(
+            parse(&json),
+            json!([{"path": "red.png", "seed": 7, "colours": [{"hex": "#ff0000", "rgb": [255, 0, 0]}]}])
+        );
+
+        assert_eq!(results(&[], 0, Annotations::default()), "[]\n");
+    }
+
+    #[test]
+    fn it_describes_the_roles() {
+        let json = roles(Path::new("red.png"), 0, &[("vibrant", Some((Srgb::new(255, 0, 0), 1.0))), ("dark_vibrant", None)]);
+
+        assert_eq!(
+            parse(&json),
+            json!({
+                "path": "red.png",
+                "seed": 0,
+                "roles": {
+                    "vibrant": {"hex": "#ff0000", "rgb": [255, 0, 0], "proportion": 1.0},
+                    "dark_vibrant": null
+                }
+            })
+        );
+
+        // The roles are always in the same order.
+        assert!(json.find("\"vibrant\"").unwrap() < json.find("\"dark_vibrant\"").unwrap(), "json = {}", json);
+    }
+}
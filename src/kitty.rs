@@ -0,0 +1,70 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::hex;
+use crate::terminal_colours::{background_and_foreground, CursorAndSelection};
+
+// With --format=kitty, we print the terminal colours as a kitty theme,
+// which you can `include` from kitty.conf:
+//
+//      foreground #ffffff
+//      background #000000
+//      cursor #ffffff
+//      selection_background #000055
+//      color0 #000000
+//      ...
+//      color15 #ffffff
+//
+// With --terminal-colours=256, we carry on up to color255.
+//
+// See https://sw.kovidgoyal.net/kitty/conf/#color-scheme
+
+pub fn theme(terminal_colours: &[Srgb<u8>], light: bool, overrides: &CursorAndSelection) -> String {
+    let (background, foreground) = background_and_foreground(terminal_colours, light);
+    let (cursor, selection) = overrides.pick(terminal_colours, background, foreground);
+
+    let mut conf = String::new();
+
+    writeln!(conf, "foreground {}", hex(foreground)).unwrap();
+    writeln!(conf, "background {}", hex(background)).unwrap();
+    writeln!(conf, "cursor {}", hex(cursor)).unwrap();
+    writeln!(conf, "selection_background {}", hex(selection)).unwrap();
+
+    for (i, c) in terminal_colours.iter().enumerate() {
+        writeln!(conf, "color{} {}", i, hex(*c)).unwrap();
+    }
+
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::kitty::theme;
+    use crate::terminal_colours::{red_ramp, CursorAndSelection};
+
+    #[test]
+    fn it_derives_the_cursor_and_selection() {
+        let conf = theme(&red_ramp(16), false, &CursorAndSelection { cursor: None, selection: None });
+
+        assert!(
+            conf.starts_with("foreground #f00000\nbackground #000000\ncursor #f00000\nselection_background #200000\ncolor0 #000000\n"),
+            "conf = {}",
+            conf
+        );
+        assert!(conf.ends_with("color15 #f00000\n"), "conf = {}", conf);
+    }
+
+    #[test]
+    fn it_uses_the_cursor_and_selection_you_pick() {
+        let overrides = CursorAndSelection {
+            cursor: Some(Srgb::new(0, 255, 0)),
+            selection: Some(Srgb::new(0, 0, 255)),
+        };
+        let conf = theme(&red_ramp(16), false, &overrides);
+
+        assert!(conf.contains("\ncursor #00ff00\nselection_background #0000ff\n"), "conf = {}", conf);
+    }
+}
@@ -3,29 +3,315 @@
 #[macro_use]
 extern crate clap;
 
-use kmeans_colors::get_kmeans_hamerly;
+use std::fmt::Write;
+use std::io::{self, IsTerminal, Write as _};
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::ValueEnum;
+use clap_complete::Shell;
+use palette::Srgb;
 use rand::random;
-use palette::{FromColor, IntoColor, Pixel, Lab, Srgb, Srgba};
 
+use crate::clipboard::{Clipboard, CopyError, Selection, SystemClipboard};
+use crate::clustering::{Clustering, ColourCount, Pixels, SortOrder, Space};
+use crate::crop::Crop;
+use crate::diagnostics::{Diagnostics, Run};
+use crate::distance::Distance;
+use crate::errors::Error;
+use crate::exclude::Exclusion;
+use crate::export::ExportFormat;
+use crate::format::OutputFormat;
+use crate::frames::FrameSelection;
+use crate::get_bytes::DecodeOptions;
+use crate::harmony::Harmony;
+use crate::preview::When;
+use crate::quantize::Algorithm;
+use crate::roles::Mode;
+use crate::template::Template;
+use crate::terminal_colours::SlotCount;
+
+mod alacritty;
+mod apply;
+mod background;
+mod base16;
 mod cli;
+mod clipboard;
+mod clustering;
+mod cmyk_jpeg;
+mod compare;
+mod contrast;
+mod crop;
+mod css;
+mod diagnostics;
+mod distance;
+mod download;
+mod errors;
+mod exclude;
+mod export;
+mod format;
+mod frames;
 mod get_bytes;
+mod harmony;
+mod heif;
+mod html;
+mod kitty;
+mod ico;
+mod itermcolors;
+mod json;
+mod names;
+mod output;
+mod pdf;
+mod preview;
+mod quantize;
+mod roles;
+mod sampling;
+mod shell;
+mod svg;
+mod table;
+mod swatch_image;
+mod template;
 mod terminal_colours;
+mod tiff_pages;
+mod tmux;
+mod video;
+mod wal;
+mod walk;
+mod webp;
+mod wezterm;
+mod windows_terminal;
+mod xresources;
+
+// The settings for finding the dominant colours in an image.  With
+// --compare we look at two images, and they should both get the same
+// settings, so we gather them up in one place.
+struct Options {
+    seed: u64,
+    colour_count: ColourCount,
+    max_auto_colours: usize,
+    runs: usize,
+    max_iterations: usize,
+    converge: f32,
+    sample_size: usize,
+    sample_rate: f64,
+    exclude: Vec<Exclusion>,
+    exclude_white: Option<f32>,
+    exclude_black: Option<f32>,
+    min_saturation: Option<f32>,
+    min_saturation_fallback: f32,
+    alpha_threshold: Option<u8>,
+    background: Option<Srgb<u8>>,
+    min_percentage: f32,
+    min_distance: Option<f32>,
+    pad_colours: bool,
+    keep_biggest: Option<usize>,
+    mode: Mode,
+    max_exact_colours: usize,
+    space: Space,
+    sort: SortOrder,
+    algorithm: Algorithm,
+    average: bool,
+    terminal_colours: bool,
+    decode: DecodeOptions,
+}
+
+// The colours we found in an image, and what fraction of the pixels we
+// looked at are closest to each one.
+struct DominantColours {
+    colours: Vec<Srgb<u8>>,
+    proportions: Vec<f32>,
+}
 
 fn main() {
+    #[cfg(target_os = "linux")]
+    if std::env::args().nth(1).as_deref() == Some(clipboard::DAEMON_ARG) {
+        clipboard::serve(std::env::args().nth(2).unwrap_or_default());
+        return;
+    }
+
     let matches = cli::app().get_matches();
 
-    let path = matches
-        .get_one::<String>("PATH")
-        .expect("`path` is required");
+    if let Some(("completions", sub_matches)) = matches.subcommand() {
+        let shell = *sub_matches
+            .get_one::<Shell>("SHELL")
+            .expect("`shell` is required");
+
+        clap_complete::generate(shell, &mut cli::app(), "dominant_colours", &mut std::io::stdout());
+        return;
+    }
+
+    let path = matches.get_one::<String>("PATH").map(Path::new);
+
+    let walk_options = walk::WalkOptions {
+        recursive: matches.get_flag("recursive"),
+        hidden: matches.get_flag("hidden"),
+        pattern: matches.get_one::<glob::Pattern>("PATTERN").cloned(),
+    };
+
+    let json = matches
+        .get_flag("json");
+
+    let format = *matches
+        .get_one::<OutputFormat>("FORMAT")
+        .expect("`format` is required");
+
+    let wal = matches
+        .get_flag("wal");
 
-    let terminal_colours = matches
-        .get_flag("terminal-colours");
+    // --wal needs a full set of terminal colours.
+    let terminal_slots = match matches.get_one::<SlotCount>("terminal-colours") {
+        Some(slots) => Some(*slots),
+        None if wal => Some(SlotCount::Ansi),
+        None => None,
+    };
+
+    let terminal_colours = terminal_slots.is_some();
+
+    // These only change the terminal colours, so without them they'd do
+    // nothing at all.  --max-brightness isn't here, because old scripts
+    // might pass it without --terminal-colours, and it never complained.
+    let terminal_only = [
+        ("--brightness", "BRIGHTNESS"),
+        ("--saturation", "SATURATION"),
+        ("--min-brightness", "MIN-BRIGHTNESS"),
+        ("--min-contrast", "MIN-CONTRAST"),
+        ("--keep-neutrals", "keep-neutrals"),
+    ];
+
+    if !terminal_colours {
+        if let Some((flag, _)) = terminal_only.iter().find(|(_, id)| matches.value_source(id) == Some(ValueSource::CommandLine)) {
+            Error::Usage(cli::app().error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                format!("{} only works with --terminal-colours or --wal", flag),
+            ))
+            .exit();
+        }
+    }
 
     let random_seed = matches
         .get_flag("random-seed");
 
-    let max_brightness = matches
-        .get_flag("max-brightness");
+    // --max-brightness is from before you could pick how bright, so we
+    // keep it working for old scripts.
+    let brightness = if matches.get_flag("max-brightness") {
+        100
+    } else {
+        *matches
+            .get_one::<u8>("BRIGHTNESS")
+            .expect("`brightness` is required")
+    };
+
+    let saturation = *matches
+        .get_one::<u8>("SATURATION")
+        .expect("`saturation` is required");
+
+    let keep_neutrals = matches
+        .get_flag("keep-neutrals");
+
+    let min_brightness = *matches
+        .get_one::<u8>("MIN-BRIGHTNESS")
+        .expect("`min-brightness` is required");
+
+    let adjustment = terminal_colours::Adjustment {
+        brightness: brightness as f32 / 100.0,
+        saturation: saturation as f32 / 100.0,
+        min_lightness: min_brightness as f32,
+    };
+
+    let min_contrast = *matches
+        .get_one::<f32>("MIN-CONTRAST")
+        .expect("`min-contrast` is required");
+
+    let show_index = matches
+        .get_flag("show-index");
+
+    let osc4 = matches
+        .get_flag("osc4");
+
+    let preview = matches
+        .get_flag("preview");
+
+    let apply = matches
+        .get_flag("apply");
+
+    let verbose = matches
+        .get_flag("verbose");
+
+    let quiet = matches
+        .get_flag("quiet");
+
+    let average = matches
+        .get_flag("average");
+
+    let video = matches
+        .get_flag("video");
+
+    let output_path = matches.get_one::<String>("OUTPUT");
+
+    let no_clobber = matches
+        .get_flag("no-clobber");
+
+    // The swatches are only for terminals, so we never write them to a file.
+    let no_palette = matches
+        .get_flag("no-palette") || output_path.is_some();
+
+    let show_percentage = matches
+        .get_flag("show-percentage");
+
+    let contrast_against = matches
+        .get_one::<Srgb<u8>>("CONTRAST-AGAINST")
+        .copied();
+
+    let annotations = json::Annotations {
+        names: matches.get_flag("names"),
+        contrast: matches.get_flag("contrast"),
+        contrast_against,
+        observed: None,
+    };
+
+    let harmony = matches
+        .get_one::<Harmony>("HARMONY")
+        .copied();
+
+    let template = matches
+        .get_one::<Template>("TEMPLATE");
+
+    let dedupe_frames = !matches
+        .get_flag("no-dedupe-frames");
+
+    let distance = *matches
+        .get_one::<Distance>("DISTANCE")
+        .expect("`distance` is required");
+
+    let space = *matches
+        .get_one::<Space>("SPACE")
+        .expect("`space` is required");
+
+    let sort = *matches
+        .get_one::<SortOrder>("SORT")
+        .expect("`sort` is required");
+
+    let algorithm = *matches
+        .get_one::<Algorithm>("ALGORITHM")
+        .expect("`algorithm` is required");
+
+    let mode = *matches
+        .get_one::<Mode>("MODE")
+        .expect("`mode` is required");
+
+    // Only k-means uses random numbers, so with the other algorithms (or
+    // no algorithm at all) a seed does nothing -- which is worth saying,
+    // if you asked for one.
+    if random_seed || matches.value_source("SEED") == Some(ValueSource::CommandLine) {
+        if mode == Mode::Exact {
+            eprintln!("warning: --mode=exact counts every colour, so ignoring --seed and --random-seed");
+        } else if algorithm.deterministic().is_some() {
+            eprintln!(
+                "warning: --algorithm={} always finds the same colours, so ignoring --seed and --random-seed",
+                algorithm.to_possible_value().expect("no skipped variants").get_name()
+            );
+        }
+    }
 
     let seed: u64 = if random_seed { random() } else {
         *matches
@@ -33,317 +319,4467 @@ fn main() {
             .expect("`seed` is required")
     };
 
-    let colour_count = *matches
-        .get_one::<usize>("MAX-COLOURS")
-        .expect("`max-colours` is required");
+    // The roles need a wider choice of colours than we'd usually print,
+    // so they look for more unless you say otherwise.
+    let colour_count = if mode == Mode::Roles && matches.value_source("MAX-COLOURS") != Some(ValueSource::CommandLine) {
+        ColourCount::Fixed(roles::DEFAULT_COLOUR_COUNT)
+    } else {
+        *matches
+            .get_one::<ColourCount>("MAX-COLOURS")
+            .expect("`max-colours` is required")
+    };
+
+    let sample_size = *matches
+        .get_one::<usize>("SAMPLE-SIZE")
+        .expect("`sample-size` is required");
+
+    let sample_rate = *matches
+        .get_one::<f64>("SAMPLE-RATE")
+        .expect("`sample-rate` is required");
+
+    let exclude: Vec<Exclusion> = matches
+        .get_many::<Exclusion>("EXCLUDE")
+        .map(|exclusions| exclusions.copied().collect())
+        .unwrap_or_default();
+
+    let background = matches
+        .get_one::<Srgb<u8>>("BACKGROUND")
+        .copied();
+
+    let exclude_white = matches
+        .get_one::<f32>("EXCLUDE-WHITE")
+        .copied();
+
+    let exclude_black = matches
+        .get_one::<f32>("EXCLUDE-BLACK")
+        .copied();
+
+    let min_saturation = matches
+        .get_one::<f32>("MIN-SATURATION")
+        .copied();
+
+    let min_saturation_fallback = *matches
+        .get_one::<f32>("MIN-SATURATION-FALLBACK")
+        .expect("`min-saturation-fallback` is required");
+
+    // If we're drawing the image on a background, the transparent pixels
+    // count as the background, so we keep them.
+    let alpha_threshold = if matches.get_flag("include-transparent") || background.is_some() {
+        None
+    } else {
+        matches.get_one::<u8>("ALPHA-THRESHOLD").copied()
+    };
+
+    let min_percentage = *matches
+        .get_one::<f32>("MIN-PERCENTAGE")
+        .expect("`min-percentage` is required");
+
+    let min_distance = matches
+        .get_one::<f32>("MIN-DISTANCE")
+        .copied();
+
+    let max_auto_colours = *matches
+        .get_one::<usize>("MAX-AUTO-COLOURS")
+        .expect("`max-auto-colours` is required");
+
+    let runs = *matches
+        .get_one::<usize>("RUNS")
+        .expect("`runs` is required");
+
+    let max_iterations = *matches
+        .get_one::<usize>("MAX-ITERATIONS")
+        .expect("`max-iterations` is required");
+
+    let converge = *matches
+        .get_one::<f32>("CONVERGENCE")
+        .expect("`convergence` is required");
+
+    let frames = *matches
+        .get_one::<FrameSelection>("FRAMES")
+        .expect("`frames` is required");
+
+    let max_frames = matches
+        .get_one::<usize>("MAX-FRAMES")
+        .copied();
 
-    let colour_count: usize = if terminal_colours && 16 > colour_count { 16 } else { colour_count };
+    let sample_every = matches
+        .get_one::<f64>("SAMPLE-EVERY")
+        .copied();
 
-    // There's different code for fetching bytes from GIF images because
-    // GIFs are often animated, and we want a selection of frames.
-    let img_bytes = if path.to_lowercase().ends_with(".gif") {
-        get_bytes::get_bytes_for_gif(&path)
+    let max_download_size = *matches
+        .get_one::<u64>("MAX-DOWNLOAD-SIZE")
+        .expect("`max-download-size` is required");
+
+    let crop = matches
+        .get_one::<Crop>("CROP")
+        .copied();
+
+    let max_dimension = if matches.get_flag("no-resize") {
+        None
     } else {
-        get_bytes::get_bytes_for_image(&path)
+        matches.get_one::<u32>("MAX-DIMENSION").copied()
     };
 
-    // This is based on code from the kmeans-colors binary, but with a bunch of
-    // the options stripped out.
-    // See https://github.com/okaneco/kmeans-colors/blob/0.5.0/src/bin/kmeans_colors/app.rs
-    let lab: Vec<Lab> = Srgba::from_raw_slice(&img_bytes)
-        .iter()
-        .map(|x| x.into_format::<_, f32>().into_color())
-        .collect();
+    let svg_size = *matches
+        .get_one::<u32>("SVG-SIZE")
+        .expect("`svg-size` is required");
+
+    let page = matches
+        .get_one::<usize>("PAGE")
+        .copied();
+
+    let pdf_dpi = *matches
+        .get_one::<u32>("PDF-DPI")
+        .expect("`pdf-dpi` is required");
+
+    let options = Options {
+        seed,
+        colour_count,
+        max_auto_colours,
+        runs,
+        max_iterations,
+        converge,
+        sample_size,
+        sample_rate,
+        exclude,
+        exclude_white,
+        exclude_black,
+        min_saturation,
+        min_saturation_fallback,
+        alpha_threshold,
+        background,
+        min_percentage,
+        min_distance,
+        pad_colours: matches.get_flag("pad-colours"),
+        keep_biggest: (mode == Mode::Background && matches.value_source("MAX-COLOURS") != Some(ValueSource::CommandLine)).then_some(1),
+        mode,
+        max_exact_colours: *matches.get_one::<usize>("MAX-EXACT-COLOURS").expect("`max-exact-colours` is required"),
+        space,
+        sort,
+        algorithm,
+        average,
+        terminal_colours,
+        decode: DecodeOptions {
+            dedupe_frames,
+            video,
+            frames,
+            max_frames,
+            sample_every,
+            max_download_size,
+            crop,
+            max_dimension,
+            keep_undrawn: background.is_some() || matches.get_flag("include-transparent"),
+            svg_size,
+            page,
+            pdf_dpi,
+            border_width: (mode == Mode::Background)
+                .then(|| *matches.get_one::<f64>("BORDER-WIDTH").expect("`border-width` is required")),
+            center_weight: *matches.get_one::<f64>("CENTER-WEIGHT").expect("`center-weight` is required"),
+            seed,
+        },
+    };
+
+    // --format=gpl and --format=ase are another way to write --export.
+    let export_format = match matches.get_one::<ExportFormat>("EXPORT") {
+        Some(export_format) => Some(*export_format),
+        None if format == OutputFormat::Gpl => Some(ExportFormat::Gpl),
+        None if format == OutputFormat::Ase => Some(ExportFormat::Ase),
+        None => None,
+    };
+
+    // Binary formats would make a mess of the terminal, so they have to go
+    // to a file (or be piped somewhere).
+    if let Some(export_format) = export_format {
+        if !export_format.is_text() && output_path.is_none() && !quiet && io::stdout().is_terminal() {
+            Error::Usage(cli::app().error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--export=ase is a binary format, so it needs --output <FILE> unless you pipe it somewhere",
+            ))
+            .exit();
+        }
+    }
+
+    // The roles are picked from the clusters and their sizes, so they
+    // don't make sense for the other ways of printing colours.
+    if mode == Mode::Roles
+        && (terminal_colours
+            || osc4
+            || average
+            || export_format.is_some()
+            || template.is_some()
+            || matches.contains_id("COMPARE")
+            || !matches!(format, OutputFormat::Text | OutputFormat::Json))
+    {
+        Error::Usage(cli::app().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--mode=roles only works with --format=text or --format=json, not with --terminal-colours, --wal, --osc4, --average, --export, --template or --compare",
+        ))
+        .exit();
+    }
+
+    // The average blends every pixel together, so there's nothing to count.
+    if mode == Mode::Exact && average {
+        Error::Usage(cli::app().error(clap::error::ErrorKind::ArgumentConflict, "--mode=exact doesn't work with --average"))
+            .exit();
+    }
+
+    // --mode=background only looks at the edge, which is exactly the part
+    // that --center-weight looks at less.
+    if mode == Mode::Background && matches.value_source("CENTER-WEIGHT") == Some(ValueSource::CommandLine) {
+        Error::Usage(cli::app().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--center-weight doesn't work with --mode=background, which only looks at the edge of the image",
+        ))
+        .exit();
+    }
+
+    // The derived colours are printed after the dominant colours, which
+    // only makes sense in a list of colours.
+    if harmony.is_some()
+        && (terminal_colours
+            || osc4
+            || json
+            || mode == Mode::Roles
+            || export_format.is_some()
+            || template.is_some()
+            || matches.contains_id("COMPARE")
+            || !matches!(format, OutputFormat::Text | OutputFormat::Rgb | OutputFormat::Hsl | OutputFormat::Json))
+    {
+        Error::Usage(cli::app().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--harmony only works with --format=text, --format=rgb, --format=hsl or --format=json, not with --terminal-colours, --wal, --osc4, --json, --mode=roles, --export, --template or --compare",
+        ))
+        .exit();
+    }
+
+    // The preview goes after the colours, so it would end up at the end
+    // of a config file or a JSON document.
+    if matches.get_flag("preview") && format != OutputFormat::Text {
+        Error::Usage(cli::app().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--preview only works with --format=text, because it's printed after the colours",
+        ))
+        .exit();
+    }
+
+    // These only have slots for the 16 ANSI colours.
+    if terminal_slots == Some(SlotCount::Xterm)
+        && (wal || matches!(format, OutputFormat::WindowsTerminal | OutputFormat::Itermcolors))
+    {
+        Error::Usage(cli::app().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--terminal-colours=256 doesn't work with --wal, --format=windows-terminal or --format=itermcolors, which only have 16 colours",
+        ))
+        .exit();
+    }
+
+    let terminal_palette = match matches.get_one::<String>("TERMINAL-PALETTE") {
+        Some(palette_path) => or_exit(terminal_colours::read_palette(Path::new(palette_path))),
+        None => terminal_colours::ANSI_COLOUR_TABLE,
+    };
+
+    // The terminal colours don't match up with the clusters, so they don't
+    // have proportions.
+    let map_colours = |dominant: DominantColours| {
+        if terminal_colours {
+            let colours = terminal_colours::create_terminal_colour(dominant.colours, &terminal_palette, adjustment, distance, min_contrast, keep_neutrals);
+
+            let colours = if terminal_slots == Some(SlotCount::Xterm) {
+                terminal_colours::extend_to_256(&colours)
+            } else {
+                colours
+            };
+
+            (colours, None)
+        } else {
+            (dominant.colours, Some(dominant.proportions))
+        }
+    };
+
+    // With --show-percentage, we add the proportion after each colour,
+    // e.g. `#ff0000 61.4%`; with --names, the nearest named colour, e.g.
+    // `#f4624e tomato (ΔE 3.2)`; and with --contrast, the text colour to
+    // use on it, e.g. `#1f6feb → text #ffffff (4.6:1)`.
+    let label = |c: Srgb<u8>, proportion: Option<f32>| {
+        let mut label = match proportion {
+            Some(p) if show_percentage => format!("{} {:.1}%", format.colour(c), p * 100.0),
+            _ => format.colour(c),
+        };
+
+        if annotations.names {
+            write!(label, " {}", names::nearest(c).label()).unwrap();
+        }
+
+        if annotations.contrast {
+            let text = contrast::text_colour(c);
+            write!(label, " → text {} ({})", hex(text), contrast::format_ratio(contrast::ratio(c, text))).unwrap();
+        }
+
+        if let Some(other) = annotations.contrast_against {
+            let ratio = contrast::ratio(c, other);
+            let warning = if ratio < contrast::MIN_RATIO { format!(", below {}", contrast::format_ratio(contrast::MIN_RATIO)) } else { String::new() };
+
+            write!(label, " → {} ({}{})", hex(other), contrast::format_ratio(ratio), warning).unwrap();
+        }
+
+        label
+    };
+
+    // If we've been given a directory or a list of files, we look at every
+    // image, rather than a single image.
+    let walk = match (matches.get_one::<String>("FILES-FROM"), path) {
+        (Some(list_path), _) => Some(or_exit(
+            walk::read_file_list(Path::new(list_path)).map_err(|error| Error::Io {
+                path: PathBuf::from(list_path),
+                error,
+            }),
+        )),
+        (None, Some(path)) if path.is_dir() => Some(walk::find_images(path, &walk_options)),
+        (None, _) => None,
+    };
+
+    if let Some(walk) = walk {
+        // The options which only make sense for one image: flags we've
+        // already looked at, then the ids of arguments which might be set.
+        let single_image_flags = [
+            ("--wal", wal),
+            ("--osc4", osc4),
+            ("--apply", apply),
+            ("--preview", preview),
+            ("--export", export_format.is_some()),
+            ("--mode=roles", mode == Mode::Roles),
+        ];
+        let single_image_args = [
+            ("--compare", "COMPARE"),
+            ("--copy", "COPY"),
+            ("--harmony", "HARMONY"),
+            ("--require-distinct", "REQUIRE-DISTINCT"),
+            ("--save-swatch", "SAVE-SWATCH"),
+        ];
+
+        if single_image_flags.iter().any(|(_, used)| *used)
+            || format.single_image_only()
+            || single_image_args.iter().any(|(_, id)| matches.contains_id(id))
+        {
+            let formats = OutputFormat::value_variants()
+                .iter()
+                .filter(|f| f.single_image_only())
+                .map(|f| format!("--format={}", f.to_possible_value().expect("every format has a name").get_name()));
+
+            let names: Vec<String> = single_image_flags
+                .iter()
+                .map(|(flag, _)| flag.to_string())
+                .chain(formats)
+                .chain(single_image_args.iter().map(|(flag, _)| flag.to_string()))
+                .collect();
+
+            Error::Usage(cli::app().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!(
+                    "{} and {} only work with a single image, not a directory or a list of files",
+                    names[..names.len() - 1].join(", "),
+                    names[names.len() - 1]
+                ),
+            ))
+            .exit();
+        }
+
+        // If anything goes wrong, we exit with the highest of the exit
+        // codes, so a decode error isn't hidden by an IO error.
+        let mut exit_code = 0;
+
+        for (path, error) in walk.errors {
+            let e = Error::Io { path, error };
+            eprintln!("{}", e);
+            exit_code = exit_code.max(e.exit_code());
+        }
+
+        // If we can't read one of the images, we carry on with the rest.
+        let mut palettes = Vec::new();
+
+        for image in walk.images {
+            match get_dominant_colours(&image, &options) {
+                Ok((dominant, diagnostics)) => {
+                    if verbose {
+                        eprintln!("{}:", image.display());
+                        eprint!("{}", diagnostics);
+                    }
+
+                    let (colours, proportions) = map_colours(dominant);
+                    palettes.push((image, colours, proportions));
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit_code = exit_code.max(e.exit_code());
+                }
+            }
+        }
+
+        let output = if format == OutputFormat::Json {
+            json::results(&palettes, options.seed, annotations)
+        } else if let Some(delimiter) = format.table_delimiter() {
+            table::table(&palettes, delimiter, !matches.get_flag("no-header"))
+        } else if json {
+            let palettes: Vec<_> = palettes.into_iter().map(|(image, colours, _)| (image, colours)).collect();
+            json::palettes(&palettes)
+        } else {
+            let mut output = String::new();
+
+            for (i, (image, colours, proportions)) in palettes.iter().enumerate() {
+                let proportion = |i: usize| proportions.as_ref().map(|p| p[i]);
+
+                if let Some(template) = template {
+                    for (j, c) in colours.iter().enumerate() {
+                        writeln!(output, "{}", template.render(*c, j, proportion(j), image)).unwrap();
+                    }
+                } else if no_palette {
+                    for (j, c) in colours.iter().enumerate() {
+                        writeln!(output, "{}: {}", image.display(), label(*c, proportion(j))).unwrap();
+                    }
+                } else {
+                    if i > 0 {
+                        writeln!(output).unwrap();
+                    }
+
+                    writeln!(output, "{}", image.display()).unwrap();
+                    for (j, c) in colours.iter().enumerate() {
+                        writeln!(output, "{}", swatch(*c, &label(*c, proportion(j)))).unwrap();
+                    }
+                }
+            }
+
+            output
+        };
+
+        write_output(output.as_bytes(), output_path, quiet, no_clobber);
+
+        if walk.skipped > 0 {
+            eprintln!("skipped {} file(s) which aren't images", walk.skipped);
+        }
+
+        if exit_code != 0 {
+            std::process::exit(exit_code);
+        }
+
+        return;
+    }
+
+    let path = path.expect("`path` is required without --files-from");
+
+    let (dominant, diagnostics) = or_exit(get_dominant_colours(path, &options));
+
+    if verbose {
+        eprint!("{}", diagnostics);
+    }
+
+    if let Some(other_path) = matches.get_one::<String>("COMPARE") {
+        let mut output = String::new();
+
+        let (other, other_diagnostics) = or_exit(get_dominant_colours(Path::new(other_path), &options));
+
+        if verbose {
+            eprint!("{}", other_diagnostics);
+        }
+
+        let comparison = compare::compare(&dominant.colours, &other.colours);
+
+        for pair in &comparison.pairs {
+            if no_palette {
+                writeln!(output, "{} {} {:.2}", hex(pair.colour), hex(pair.other_colour), pair.distance).unwrap();
+            } else {
+                writeln!(
+                    output,
+                    "{} {} {:.2}",
+                    swatch(pair.colour, &hex(pair.colour)),
+                    swatch(pair.other_colour, &hex(pair.other_colour)),
+                    pair.distance
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(output, "similarity: {:.3}", comparison.similarity).unwrap();
+        write_output(output.as_bytes(), output_path, quiet, no_clobber);
+
+        // So the comparison can be used in a shell `if`, we use a
+        // different exit code if the images aren't similar enough.
+        if let Some(threshold) = matches.get_one::<f32>("THRESHOLD") {
+            if comparison.similarity < *threshold {
+                std::process::exit(errors::CHECK_FAILED);
+            }
+        }
+
+        return;
+    }
+
+    // Check this before we map to terminal colours, which may repeat
+    // a colour several times.
+    let too_few_distinct = match matches.get_one::<usize>("REQUIRE-DISTINCT") {
+        Some(required) => distance::count_distinct(&dominant.colours) < *required,
+        None => false,
+    };
+
+    // The preview is only for looking at in a terminal, so we don't print
+    // it anywhere the swatches wouldn't go, and by default we only print it
+    // if stdout is a terminal.
+    let show_preview = preview
+        && !no_palette
+        && !quiet
+        && match *matches.get_one::<When>("COLOR").expect("`color` is required") {
+            When::Auto => io::stdout().is_terminal(),
+            When::Always => true,
+            When::Never => false,
+        };
+
+    let mut preview_output = None;
+
+    let output: Vec<u8> = if wal {
+        let terminal = terminal_colours::create_terminal_colour(dominant.colours.clone(), &terminal_palette, adjustment, distance, min_contrast, keep_neutrals);
+        let wallpaper = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        wal::colors_json(&wallpaper, &dominant.colours, &terminal).into_bytes()
+    } else {
+        let (rgb, proportions) = map_colours(dominant);
+
+        if let Some(selection) = matches.get_one::<Selection>("COPY") {
+            copy_to_clipboard(&mut SystemClipboard, &rgb, *selection);
+        }
+
+        if apply {
+            let target = *matches.get_one::<apply::Target>("APPLY-TARGET").expect("`apply-target` is required");
+
+            or_exit(apply::apply(&rgb, matches.get_flag("light-theme"), target));
+        }
+
+        if show_preview {
+            preview_output = Some(preview::preview(&rgb));
+        }
+
+        if let Some(swatch_path) = matches.get_one::<String>("SAVE-SWATCH") {
+            let block_size = *matches.get_one::<u32>("SWATCH-BLOCK-SIZE").expect("`swatch-block-size` is required");
+
+            or_exit(swatch_image::save(Path::new(swatch_path), &rgb, block_size));
+        }
+
+        // Palettes and colour schemes are named after the image, unless
+        // you pick a name.
+        let name_from = |id: &str| match matches.get_one::<String>(id) {
+            Some(name) => name.clone(),
+            None => path
+                .file_stem()
+                .filter(|_| path != Path::new(get_bytes::STDIN))
+                .map_or_else(|| String::from(export::DEFAULT_PALETTE_NAME), |stem| stem.to_string_lossy().into_owned()),
+        };
+
+        // With --harmony, we derive more colours from the most dominant
+        // colour, which is the first one unless you picked a --sort.
+        let derived = match harmony {
+            Some(harmony) if !rgb.is_empty() => {
+                let most_dominant = match &proportions {
+                    Some(p) => (0..p.len()).fold(0, |best, i| if p[i] > p[best] { i } else { best }),
+                    None => 0,
+                };
+
+                let scheme = harmony::derive(rgb[most_dominant], harmony);
+
+                if scheme.is_lightness_ramp {
+                    eprintln!(
+                        "warning: the most dominant colour {} is grey, so it has no hue to rotate; printing a lightness ramp instead",
+                        hex(rgb[most_dominant])
+                    );
+                }
+
+                scheme.colours
+            }
+            _ => Vec::new(),
+        };
+
+        let cursor_and_selection = terminal_colours::CursorAndSelection {
+            cursor: matches.get_one::<Srgb<u8>>("CURSOR-COLOUR").copied(),
+            selection: matches.get_one::<Srgb<u8>>("SELECTION-COLOUR").copied(),
+        };
+
+        if let Some(export_format) = export_format {
+            let name = name_from("PALETTE-NAME");
+
+            let swatch_name = matches.get_one::<String>("SWATCH-NAME").expect("`swatch-name` is required");
+
+            export_format.encode(&rgb, &name, swatch_name)
+        } else if mode == Mode::Roles {
+            let proportions = proportions.expect("roles always have proportions");
+
+            let chosen: Vec<_> = roles::ROLES
+                .iter()
+                .zip(roles::choose(&rgb, &proportions))
+                .map(|(role, i)| (role, i.map(|i| (rgb[i], proportions[i]))))
+                .collect();
+
+            if format == OutputFormat::Json || json {
+                let keyed: Vec<json::RoleColour> = chosen.iter().map(|(role, colour)| (role.key, *colour)).collect();
+
+                json::roles(path, options.seed, &keyed).into_bytes()
+            } else {
+                let mut lines = String::new();
+
+                // Pad the names so the colours line up, e.g.
+                //
+                //      Vibrant       #e8431f
+                //      Light Vibrant -
+                //
+                for (role, colour) in chosen {
+                    let colour = match colour {
+                        Some((c, p)) if no_palette => label(c, Some(p)),
+                        Some((c, p)) => swatch(c, &label(c, Some(p))),
+                        None => String::from("-"),
+                    };
+
+                    writeln!(lines, "{:<13} {}", role.name, colour).unwrap();
+                }
+
+                lines.into_bytes()
+            }
+        } else if format == OutputFormat::Json {
+            let annotations = json::Annotations { observed: harmony.map(|_| rgb.len()), ..annotations };
+            let colours: Vec<_> = rgb.iter().chain(&derived).copied().collect();
+
+            json::result(path, options.seed, &colours, proportions.as_deref(), annotations).into_bytes()
+        } else if let Some(delimiter) = format.table_delimiter() {
+            table::table(&[(path.to_path_buf(), rgb, proportions)], delimiter, !matches.get_flag("no-header")).into_bytes()
+        } else if format == OutputFormat::Xresources {
+            let prefix = matches.get_one::<String>("XRESOURCES-PREFIX").expect("`prefix` is required");
+
+            xresources::resources(&rgb, prefix, matches.get_flag("light-theme")).into_bytes()
+        } else if format == OutputFormat::Alacritty {
+            alacritty::colours_toml(&rgb, matches.get_flag("light-theme")).into_bytes()
+        } else if format == OutputFormat::Kitty {
+            kitty::theme(&rgb, matches.get_flag("light-theme"), &cursor_and_selection).into_bytes()
+        } else if format == OutputFormat::WindowsTerminal {
+            let name = name_from("SCHEME-NAME");
+
+            windows_terminal::scheme(&rgb, &name, matches.get_flag("light-theme"), &cursor_and_selection).into_bytes()
+        } else if format == OutputFormat::Wezterm {
+            let name = name_from("SCHEME-NAME");
+
+            wezterm::scheme(&rgb, &name, matches.get_flag("light-theme"), &cursor_and_selection).into_bytes()
+        } else if format == OutputFormat::Itermcolors {
+            itermcolors::preset(&rgb, matches.get_flag("light-theme"), &cursor_and_selection).into_bytes()
+        } else if format == OutputFormat::Html {
+            let max_colours = match options.colour_count {
+                ColourCount::Fixed(n) => n.to_string(),
+                ColourCount::Auto => String::from("auto"),
+            };
+
+            html::page(path, &rgb, proportions.as_deref(), &[("seed", options.seed.to_string()), ("max colours", max_colours)]).into_bytes()
+        } else if matches!(format, OutputFormat::Sh | OutputFormat::Bash) {
+            let prefix = matches.get_one::<String>("VAR-PREFIX").expect("`var-prefix` is required");
+
+            shell::variables(&rgb, prefix, format == OutputFormat::Bash).into_bytes()
+        } else if format == OutputFormat::Base16 {
+            let name = name_from("SCHEME-NAME");
+
+            base16::scheme(&rgb, &name).into_bytes()
+        } else if format == OutputFormat::Tmux {
+            let roles = tmux::Roles {
+                background: matches.get_one::<usize>("BACKGROUND-INDEX").copied(),
+                accent: matches.get_one::<usize>("ACCENT-INDEX").copied(),
+            };
+
+            match tmux::config(&rgb, proportions.as_deref(), &roles) {
+                Ok(conf) => conf.into_bytes(),
+                Err(message) => Error::Usage(cli::app().error(clap::error::ErrorKind::ValueValidation, message)).exit(),
+            }
+        } else if format == OutputFormat::Css {
+            let selector = matches.get_one::<String>("CSS-SELECTOR").expect("`css-selector` is required");
+            let prefix = matches.get_one::<String>("CSS-PREFIX").expect("`css-prefix` is required");
+
+            css::stylesheet(&rgb, selector, prefix).into_bytes()
+        } else if format == OutputFormat::Scss {
+            let prefix = matches.get_one::<String>("CSS-PREFIX").expect("`css-prefix` is required");
+
+            css::scss_variables(&rgb, prefix, matches.get_flag("scss-map")).into_bytes()
+        } else if format == OutputFormat::CssGradient {
+            let angle = *matches.get_one::<f32>("GRADIENT-ANGLE").expect("`gradient-angle` is required");
+            let proportions = proportions.as_deref().filter(|_| !matches.get_flag("gradient-even"));
+
+            css::linear_gradient(&rgb, proportions, angle).into_bytes()
+        } else if json {
+            json::palettes(&[(path.to_path_buf(), rgb)]).into_bytes()
+        } else if let Some(template) = template {
+            let mut lines = String::new();
+
+            for (i, c) in rgb.into_iter().enumerate() {
+                writeln!(lines, "{}", template.render(c, i, proportions.as_ref().map(|p| p[i]), path)).unwrap();
+            }
+
+            lines.into_bytes()
+        } else if osc4 {
+            // With --osc4, we only print the escape sequences and nothing
+            // else, so you can send the output straight to a terminal.
+            rgb.iter()
+                .enumerate()
+                .map(|(i, c)| terminal_colours::osc4(i, *c))
+                .collect::<String>()
+                .into_bytes()
+        } else {
+            let mut lines = String::new();
+
+            for (i, c) in rgb.iter().copied().enumerate() {
+                if show_index {
+                    write!(lines, "{} ", i).unwrap();
+                }
+
+                let label = label(c, proportions.as_ref().map(|p| p[i]));
+
+                if no_palette {
+                    writeln!(lines, "{}", label).unwrap();
+                } else {
+                    writeln!(lines, "{}", swatch(c, &label)).unwrap();
+                }
+            }
+
+            // The derived colours aren't in the image, so they don't have
+            // a proportion, and we mark them so you can tell them apart.
+            for (i, c) in derived.into_iter().enumerate() {
+                if show_index {
+                    write!(lines, "{} ", rgb.len() + i).unwrap();
+                }
+
+                let label = format!("{} (derived)", label(c, None));
+
+                if no_palette {
+                    writeln!(lines, "{}", label).unwrap();
+                } else {
+                    writeln!(lines, "{}", swatch(c, &label)).unwrap();
+                }
+            }
+
+            lines.into_bytes()
+        }
+    };
+
+    write_output(&output, output_path, quiet, no_clobber);
+
+    if let Some(preview_output) = preview_output {
+        write_output(format!("\n{}", preview_output).as_bytes(), None, quiet, no_clobber);
+    }
+
+    if too_few_distinct {
+        std::process::exit(errors::CHECK_FAILED);
+    }
+}
+
+// Print the output, or write it to a file if the user asked for --output.
+// With --quiet, we don't print anything, but we still write to the file.
+fn write_output(output: &[u8], output_path: Option<&String>, quiet: bool, no_clobber: bool) {
+    let result = match output_path {
+        Some(output_path) => output::write_atomically(Path::new(output_path), output, no_clobber),
+        None if quiet => Ok(()),
+        None => std::io::stdout().write_all(output),
+    };
+
+    if let Err(error) = result {
+        let path = PathBuf::from(output_path.map_or("<stdout>", |p| p.as_str()));
+        Error::Io { path, error }.exit();
+    }
+}
+
+// Not having a clipboard (e.g. over SSH) isn't worth failing over, because
+// the colours are still printed -- but asking for a colour we didn't find is.
+fn copy_to_clipboard(clipboard: &mut impl Clipboard, colours: &[Srgb<u8>], selection: Selection) {
+    match clipboard::copy(clipboard, colours, selection) {
+        Ok(()) => (),
+        Err(CopyError::Unavailable(e)) => eprintln!("warning: couldn't copy to the clipboard: {}", e),
+        Err(CopyError::OutOfRange { n, colour_count }) => Error::Usage(cli::app().error(
+            clap::error::ErrorKind::ValueValidation,
+            format!("--copy={}, but only found {} colour(s)", n, colour_count),
+        ))
+        .exit(),
+    }
+}
+
+// If we can't read the image, there's nothing else to do.
+fn or_exit<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => e.exit(),
+    }
+}
+
+fn get_dominant_colours(path: &Path, options: &Options) -> Result<(DominantColours, Diagnostics), Error> {
+    let mut diagnostics = Diagnostics::new();
+    let seed = options.seed;
+
+    let decoded = diagnostics
+        .time("decoding", || get_bytes::get_bytes(path, &options.decode))
+        .map_err(|e| Error::from_image_error(path, e))?;
+
+    diagnostics.pixel_count = decoded.bytes.len() / 4;
+    diagnostics.frame_count = decoded.frame_count;
+    diagnostics.frames_skipped = decoded.frames_skipped;
+
+    if let Some(region) = decoded.clamped_crop {
+        eprintln!(
+            "warning: {}: --crop goes outside the image, so only looking at the part inside it ({})",
+            path.display(),
+            region
+        );
+    }
+
+    // We drop transparent pixels (or draw them on the background) and
+    // excluded colours before anything else, so they don't count towards
+    // the average or the sample.
+    let bytes = match (options.background, options.alpha_threshold) {
+        (Some(background), _) => diagnostics.time("compositing", || background::composite(&decoded.bytes, background)),
+        (None, Some(alpha_threshold)) => {
+            diagnostics.time("dropping transparent pixels", || exclude::drop_transparent(&decoded.bytes, alpha_threshold))
+        }
+        (None, None) => decoded.bytes,
+    };
+
+    // If there's nothing left, there's no sensible answer -- and the
+    // clustering library doesn't cope with an empty image.
+    if bytes.is_empty() {
+        return Err(Error::Io {
+            path: path.to_path_buf(),
+            error: io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "every pixel is transparent; use --include-transparent to look at them anyway",
+            ),
+        });
+    }
+
+    let bytes = if options.exclude.is_empty() {
+        bytes
+    } else {
+        diagnostics.time("excluding", || match exclude::exclude(&bytes, &options.exclude) {
+            Some(kept) => kept,
+            None => {
+                eprintln!("warning: {}: --exclude removed every pixel, so ignoring it", path.display());
+                bytes
+            }
+        })
+    };
+
+    // Unlike --exclude, these are about the pixels rather than particular
+    // colours, so if there's nothing left, we can't just ignore them.
+    let bytes = if options.exclude_white.is_some() || options.exclude_black.is_some() {
+        let kept = diagnostics.time("excluding by lightness", || {
+            exclude::drop_by_lightness(&bytes, options.exclude_white, options.exclude_black)
+        });
+
+        if kept.is_empty() {
+            return Err(Error::Io {
+                path: path.to_path_buf(),
+                error: io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--exclude-white or --exclude-black removed every pixel; try a higher --exclude-white=L or a lower --exclude-black=L",
+                ),
+            });
+        }
+
+        kept
+    } else {
+        bytes
+    };
+
+    // If hardly any pixels are saturated enough, the colours would come from
+    // a handful of stray pixels, so we look at all of them instead.
+    let bytes = match options.min_saturation {
+        Some(min_saturation) => {
+            let kept = diagnostics.time("excluding by saturation", || exclude::drop_unsaturated(&bytes, min_saturation));
+
+            if kept.is_empty() || (kept.len() as f32) * 100.0 < options.min_saturation_fallback * bytes.len() as f32 {
+                eprintln!(
+                    "warning: {}: only {:.1}% of the pixels have a saturation of at least {}, so looking at all of them",
+                    path.display(),
+                    kept.len() as f32 * 100.0 / bytes.len() as f32,
+                    min_saturation
+                );
+                bytes
+            } else {
+                kept
+            }
+        }
+        None => bytes,
+    };
+
+    // The average doesn't need k-means, and it's cheap enough that we can
+    // look at every pixel rather than a random sample -- so it doesn't
+    // depend on the seed.
+    if options.average {
+        diagnostics.sampled_pixel_count = bytes.len() / 4;
+
+        let average = diagnostics.time("averaging", || clustering::average(&bytes));
+        let dominant = DominantColours { colours: vec![average], proportions: vec![1.0] };
+        return Ok((dominant, diagnostics));
+    }
+
+    // Counting the exact colours is fast and doesn't use random numbers,
+    // so, like the average, it looks at every pixel.
+    if options.mode == Mode::Exact {
+        let pixel_count = bytes.len() / 4;
+        diagnostics.sampled_pixel_count = pixel_count;
+
+        let colour_count = match options.colour_count {
+            ColourCount::Fixed(colour_count) => Some(colour_count),
+            ColourCount::Auto => None,
+        };
+
+        let counted = diagnostics.time("counting colours", || quantize::exact(&bytes, colour_count, options.max_exact_colours));
+
+        return match counted {
+            Some(best) => Ok((dominant_colours(best, pixel_count, options), diagnostics)),
+            None => Err(Error::Io {
+                path: path.to_path_buf(),
+                error: io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "there are more than {} different colours, which is too many for --mode=exact; try the default --mode=colours, which groups similar colours together, or a higher --max-exact-colours",
+                        options.max_exact_colours
+                    ),
+                ),
+            }),
+        };
+    }
+
+    // Median cut and octree always find the same colours, so they always
+    // look at the same sample, whatever the --seed.
+    let seed = if options.algorithm.deterministic().is_some() { 0 } else { seed };
+
+    let img_bytes = diagnostics.time("sampling", || {
+        let bytes = if options.sample_rate < 1.0 {
+            // If the image is tiny, we might not keep any pixels, and then
+            // there's nothing to cluster -- so we look at all of them instead.
+            match sampling::sample_rate(&bytes, options.sample_rate, seed) {
+                Some(sampled) => sampled,
+                None => {
+                    eprintln!(
+                        "warning: {}: --sample-rate={} didn't keep any pixels, so looking at all of them",
+                        path.display(),
+                        options.sample_rate
+                    );
+                    bytes
+                }
+            }
+        } else {
+            bytes
+        };
+
+        sampling::sample(bytes, options.sample_size, seed)
+    });
+
+    diagnostics.sampled_pixel_count = img_bytes.len() / 4;
+
+    // Median cut and octree don't use random numbers, so they don't need
+    // the rest of the k-means setup -- but they still look at the sample,
+    // not every pixel, so a huge image doesn't take forever.
+    if let Some(quantize) = options.algorithm.deterministic() {
+        let pixel_count = img_bytes.len() / 4;
+
+        let colour_count = match options.colour_count {
+            ColourCount::Fixed(colour_count) => colour_count,
+            ColourCount::Auto => {
+                let errors: Vec<f32> = diagnostics.time("choosing colour count", || {
+                    (1..=options.max_auto_colours).map(|k| quantize(&img_bytes, k).error).collect()
+                });
+                let colour_count = clustering::choose_elbow(&errors, pixel_count);
+
+                diagnostics.auto_colour_errors = errors;
+                diagnostics.chosen_colour_count = Some(colour_count);
+                colour_count
+            }
+        };
+
+        let colour_count = if options.terminal_colours && 16 > colour_count { 16 } else { colour_count };
+
+        let best = diagnostics.time("quantizing", || quantize(&img_bytes, colour_count));
+
+        return Ok((dominant_colours(best, pixel_count, options), diagnostics));
+    }
+
+    // This is based on code from the kmeans-colors binary, but with a bunch of
+    // the options stripped out.
+    // See https://github.com/okaneco/kmeans-colors/blob/0.5.0/src/bin/kmeans_colors/app.rs
+    let pixels = diagnostics.time("converting", || Pixels::new(&img_bytes, options.space));
+
+    let (max_iterations, converge) = (options.max_iterations, options.converge);
+
+    let colour_count = match options.colour_count {
+        ColourCount::Fixed(colour_count) => colour_count,
+        ColourCount::Auto => {
+            let (colour_count, errors) = diagnostics.time("choosing colour count", || {
+                pixels.choose_colour_count(options.max_auto_colours, max_iterations, converge, seed)
+            });
+
+            diagnostics.auto_colour_errors = errors;
+            diagnostics.chosen_colour_count = Some(colour_count);
+            colour_count
+        }
+    };
+
+    let colour_count: usize = if options.terminal_colours && 16 > colour_count { 16 } else { colour_count };
+
+    // k-means is sensitive to the initial choice of centroids, so we may
+    // run it several times and keep the result with the lowest error (the
+    // within-cluster sum of squares), i.e. the clusters which fit the pixels
+    // best.  Each run gets a seed derived from the base seed, so a fixed
+    // --seed still gives reproducible output.
+    let clusterings = diagnostics.time("clustering", || {
+        (0..options.runs)
+            .map(|run| {
+                let run_seed = seed.wrapping_add(run as u64);
+                (run_seed, pixels.kmeans(colour_count, max_iterations, converge, run_seed))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    for (seed, clustering) in &clusterings {
+        diagnostics.runs.push(Run { seed: *seed, score: clustering.score, error: clustering.error });
+    }
+
+    // Ties go to the earliest run.
+    let best = clusterings
+        .into_iter()
+        .map(|(_, clustering)| clustering)
+        .reduce(|best, this| if this.error < best.error { this } else { best })
+        .expect("`runs` is at least 1");
+
+    // The library stops when either the centroids have stopped moving
+    // (the score is below `converge`) or it runs out of iterations.
+    diagnostics.converged = Some(best.score <= converge);
+
+    Ok((dominant_colours(best, pixels.len(), options), diagnostics))
+}
+
+// Merge colours which look the same, drop the tiny clusters and sort the
+// rest, then work out what fraction of the pixels each one covers.
+fn dominant_colours(mut best: Clustering, pixel_count: usize, options: &Options) -> DominantColours {
+    // If the image has fewer colours than we asked for, we may get empty
+    // clusters, or several clusters with the same colour.  Each line should
+    // be a different colour, so we drop and merge them -- unless you asked
+    // for exactly --max-colours lines with --pad-colours.  Exact colours
+    // are always different, even if they look the same.
+    let min_distance = if options.pad_colours || options.mode == Mode::Exact {
+        options.min_distance
+    } else {
+        best.drop_empty_clusters();
+        Some(options.min_distance.map_or(clustering::DUPLICATE_DISTANCE, |d| d.max(clustering::DUPLICATE_DISTANCE)))
+    };
+
+    if let Some(min_distance) = min_distance {
+        best.merge_close_colours(min_distance);
+    }
+
+    best.drop_small_clusters(options.min_percentage);
+
+    // With --mode=background, you only want the background colour, unless
+    // you asked for more with --max-colours.
+    if let Some(keep) = options.keep_biggest {
+        best.sort(SortOrder::Size);
+        best.centroids.truncate(keep);
+        best.counts.truncate(keep);
+    }
+
+    best.sort(options.sort);
+
+    // This is a fraction of every pixel we clustered, so if --min-percentage
+    // dropped any clusters, the rest add up to less than 1.  Transparent and
+    // excluded pixels were dropped before clustering, so they don't count.
+    let mut proportions: Vec<f32> = best.counts.iter().map(|count| *count as f32 / pixel_count as f32).collect();
+    let mut colours = best.centroids;
+
+    // With --pad-colours, we repeat the last colour until there are as many
+    // as you asked for, and the repeats don't cover any of the image.
+    if let (true, ColourCount::Fixed(colour_count)) = (options.pad_colours, options.colour_count) {
+        if let Some(last) = colours.last().copied() {
+            colours.resize(colour_count.max(colours.len()), last);
+            proportions.resize(colours.len(), 0.0);
+        }
+    }
+
+    DominantColours { colours, proportions }
+}
+
+fn hex(c: Srgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue)
+}
+
+// Parse a colour like `#ff0000` (the # is optional).
+fn parse_hex(s: &str) -> Option<Srgb<u8>> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Some(Srgb::new(channel(0), channel(2), channel(4)))
+}
+
+// This uses ANSI escape sequences and Unicode block elements to print
+// a label (usually the hex string) which is coloured to match.
+// See https://alexwlchan.net/2021/04/coloured-squares/
+fn swatch(c: Srgb<u8>, label: &str) -> String {
+    format!("\x1B[38;2;{};{};{}m▇ {}\x1B[0m", c.red, c.green, c.blue, label)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str;
+
+    use assert_cmd::assert::OutputAssertExt;
+    use assert_cmd::Command;
+
+    // Note: for the purposes of these tests, I mostly trust the k-means code
+    // provided by the external library.
+
+    #[test]
+    fn it_prints_the_colour_with_ansi_escape_codes() {
+        let output = get_success(&["./src/tests/red.png", "--max-colours=1"]);
+
+        assert_eq!(output.exit_code, 0);
+
+        assert_eq!(output.stdout, "\u{1b}[38;2;255;0;0m▇ #ff0000\u{1b}[0m\n");
+
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_can_look_at_png_images() {
+        let output = get_success(&["./src/tests/red.png", "--max-colours=1"]);
+        assert_eq!(output.exit_code, 0);
+    }
+
+    #[test]
+    fn it_can_look_at_jpeg_images() {
+        let output = get_success(&["./src/tests/noise.jpg", "--max-colours=1"]);
+        assert_eq!(output.exit_code, 0);
+    }
+
+    #[test]
+    fn it_can_look_at_static_gif_images() {
+        let output = get_success(&["./src/tests/yellow.gif", "--max-colours=1"]);
+        assert_eq!(output.exit_code, 0);
+    }
+
+    #[test]
+    fn it_can_look_at_tiff_images() {
+        let output = get_success(&["./src/tests/green.tiff", "--max-colours=1"]);
+        assert_eq!(output.exit_code, 0);
+    }
+
+    // This TIFF has two pages: an 8x8 red page compressed with LZW, and a
+    // 4x6 blue page compressed with Deflate.
+    #[test]
+    fn it_looks_at_every_page_of_a_tiff() {
+        let output = get_success(&["./src/tests/red_and_blue_pages.tiff", "--space=rgb", "--no-palette", "--verbose"]);
+
+        let colours: Vec<&str> = output.stdout.lines().filter(|line| line.starts_with('#')).collect();
+        assert_eq!(colours.len(), 2, "stdout = {:?}", output.stdout);
+        assert!(colours.contains(&"#ff0000"), "stdout = {:?}", output.stdout);
+        assert!(colours.contains(&"#0000ff"), "stdout = {:?}", output.stdout);
+        assert!(output.stderr.contains("frames sampled: 2\n"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_looks_at_a_single_page_of_a_tiff() {
+        let output = get_success(&["./src/tests/red_and_blue_pages.tiff", "--page=1", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#ff0000\n");
+
+        let output = get_success(&["./src/tests/red_and_blue_pages.tiff", "--page=2", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#0000ff\n");
+    }
+
+    #[test]
+    fn it_explains_if_the_tiff_page_is_out_of_range() {
+        let output = get_failure(&["./src/tests/red_and_blue_pages.tiff", "--page=3"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stderr, "./src/tests/red_and_blue_pages.tiff: --page=3 is out of range; this TIFF has 2 pages\n");
+    }
+
+    // We can't read CCITT fax compression, which is common in scanned
+    // documents, so we change the first page's compression tag from LZW (5)
+    // to Group 4 fax (4).
+    #[test]
+    fn it_explains_if_the_tiff_compression_is_unsupported() {
+        let mut bytes = std::fs::read("./src/tests/red_and_blue_pages.tiff").unwrap();
+
+        let lzw_tag = [0x03, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00];
+        let i = bytes.windows(lzw_tag.len()).position(|w| w == lzw_tag).unwrap();
+        bytes[i + 8] = 0x04;
+
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd.args(["-"]).write_stdin(bytes).unwrap_err().as_output().unwrap().to_owned();
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert!(stderr.contains("Unsupported TIFF compression Fax4"), "stderr = {:?}", stderr);
+    }
+
+    // These JPEGs are all a solid CMYK (0%, 60%, 100%, 20%), which is
+    // roughly #cc5200, stored the three different ways: Adobe's inverted
+    // CMYK, Adobe's YCCK, and plain CMYK with no Adobe marker.
+    #[test]
+    fn it_can_look_at_cmyk_jpegs() {
+        for fixture in ["cmyk.jpg", "ycck.jpg", "cmyk_no_adobe_marker.jpg"] {
+            let path = format!("./src/tests/{}", fixture);
+            let output = get_success(&[&path, "--space=rgb", "--no-palette"]);
+
+            let hex = output.stdout.trim();
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as i32;
+
+            assert!(
+                (channel(1) - 0xcc).abs() <= 2 && (channel(3) - 0x52).abs() <= 2 && channel(5) <= 2,
+                "{}: stdout = {:?}",
+                fixture,
+                output.stdout
+            );
+        }
+    }
+
+    #[test]
+    fn it_says_if_it_cant_decode_a_cmyk_jpeg() {
+        let mut bytes = std::fs::read("./src/tests/cmyk.jpg").unwrap();
+        bytes.truncate(120);
+
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd.args(["-"]).write_stdin(bytes).unwrap_err().as_output().unwrap().to_owned();
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert!(stderr.contains("CMYK JPEG"), "stderr = {:?}", stderr);
+    }
+
+    // This is a Matroska video with four 16x16 MJPEG frames, alternating
+    // red and blue.  JPEG compression means the colours might not be exact.
+    //
+    // It's in its own directory, so the tests which look at every image in
+    // ./src/tests don't need ffmpeg.
+    #[test]
+    #[cfg(feature = "video")]
+    fn it_can_look_at_videos() {
+        let output = get_success(&["./src/tests/video/two_colours.mkv", "--max-colours=2", "--space=rgb", "--no-palette"]);
+
+        let colours: Vec<(u8, u8, u8)> = output
+            .stdout
+            .lines()
+            .map(|hex| {
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+                (channel(1), channel(3), channel(5))
+            })
+            .collect();
+
+        assert_eq!(colours.len(), 2, "stdout = {:?}", output.stdout);
+        assert!(colours.iter().any(|(r, g, b)| *r > 240 && *g < 16 && *b < 16), "stdout = {:?}", output.stdout);
+        assert!(colours.iter().any(|(r, g, b)| *r < 16 && *g < 16 && *b > 240), "stdout = {:?}", output.stdout);
+    }
+
+    // With a tiny interval, we take frames from throughout the video, and
+    // --max-frames spreads them out so we still see both colours.
+    #[test]
+    #[cfg(feature = "video")]
+    fn it_can_sample_a_video_at_an_interval() {
+        let output = get_success(&[
+            "./src/tests/video/two_colours.mkv",
+            "--sample-every=1ms",
+            "--max-frames=4",
+            "--max-colours=2",
+            "--space=rgb",
+            "--no-palette",
+        ]);
+
+        assert_eq!(output.stdout.lines().count(), 2, "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_rejects_a_bad_sample_interval() {
+        let output = get_failure(&["./src/tests/video/two_colours.mkv", "--sample-every=often"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("must be a length of time"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    #[cfg(not(feature = "video"))]
+    fn it_explains_if_video_support_is_missing() {
+        let output = get_failure(&["./src/tests/video/two_colours.mkv"]);
+
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "");
+        assert!(output.stderr.contains("rebuild with `--features video`"), "stderr = {:?}", output.stderr);
+    }
+
+    // This is a copy of animated_squares.gif, but with a .png extension.
+    // We should still treat it as an animated GIF, and look at every frame.
+    #[test]
+    fn it_detects_a_gif_with_the_wrong_extension() {
+        let output = get_success(&["./src/tests/animated_squares_gif.png", "--space=rgb", "--no-palette", "--verbose"]);
+
+        let mut lines: Vec<&str> = output.stdout.lines().collect();
+        lines.sort_unstable();
+
+        assert_eq!(lines, vec!["#0000ff", "#ff0000"]);
+        assert!(output.stderr.contains("frames sampled: 8\n"), "stderr = {:?}", output.stderr);
+    }
+
+    // This is a copy of red.png, but with a .gif extension.  We shouldn't
+    // try to read it with the GIF decoder.
+    #[test]
+    fn it_detects_a_png_with_the_wrong_extension() {
+        let output = get_success(&["./src/tests/red_png.gif", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+    }
+
+    // This is a copy of noise.jpg, without an extension.
+    #[test]
+    fn it_detects_an_image_without_an_extension() {
+        let with_extension = get_success(&["./src/tests/noise.jpg"]);
+        let without_extension = get_success(&["./src/tests/noise_jpg_no_extension"]);
+
+        assert_eq!(with_extension.stdout, without_extension.stdout);
+    }
+
+    // These images are all solid colours, but stored in different pixel
+    // layouts.  They're 7x5 pixels, so with three bytes per pixel the
+    // buffer isn't a multiple of four bytes long.
+    #[test]
+    fn it_can_look_at_greyscale_images() {
+        let output = get_success(&["./src/tests/grey.png", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#808080\n");
+    }
+
+    #[test]
+    fn it_can_look_at_greyscale_images_with_alpha() {
+        let output = get_success(&["./src/tests/grey_alpha.png", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#404040\n");
+    }
+
+    #[test]
+    fn it_can_look_at_rgb_images_without_alpha() {
+        let output = get_success(&["./src/tests/rgb_no_alpha.png", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#123456\n");
+    }
+
+    #[test]
+    fn it_can_look_at_16_bit_greyscale_images() {
+        let output = get_success(&["./src/tests/sixteen_bit_grey.png", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#404040\n");
+    }
+
+    #[test]
+    fn it_can_look_at_palette_images() {
+        let output = get_success(&["./src/tests/palette.png", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#abcdef\n");
+    }
+
+    // This JPEG was #3366cc before it was converted to YCbCr, and it
+    // comes back as #3266cc.
+    #[test]
+    fn it_can_look_at_rgb_jpegs() {
+        let output = get_success(&["./src/tests/rgb.jpg", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#3266cc\n");
+    }
+
+    // This is a red circle on a transparent background.  The transparent
+    // pixels are black underneath, but we shouldn't count them.
+    #[test]
+    fn it_ignores_transparent_pixels() {
+        let output = get_success(&["./src/tests/red_circle.png", "--no-palette"]);
+        assert_eq!(output.stdout, "#ff0000\n");
+
+        let output = get_success(&["./src/tests/red_circle.png", "--no-palette", "--include-transparent"]);
+
+        let mut lines: Vec<&str> = output.stdout.lines().collect();
+        lines.sort_unstable();
+
+        assert_eq!(lines, vec!["#000000", "#ff0000"]);
+    }
+
+    // This image is red at 50% opacity, so it looks pink on a white
+    // background and dark red on a black one.
+    #[test]
+    fn it_draws_transparent_pixels_on_the_background() {
+        let output = get_success(&["./src/tests/half_transparent_red.png", "--max-colours=1", "--no-palette", "--background=#ffffff"]);
+        assert_eq!(output.stdout, "#ff7f7f\n");
+
+        let output = get_success(&["./src/tests/half_transparent_red.png", "--max-colours=1", "--no-palette", "--background=#000000"]);
+        assert_eq!(output.stdout, "#800000\n");
+    }
+
+    // Most of the canvas in this GIF is never drawn on, so on a white
+    // background, that part is white.
+    #[test]
+    fn it_draws_the_undrawn_parts_of_a_gif_on_the_background() {
+        let output = get_success(&["./src/tests/delta_frames.gif", "--max-colours=3", "--no-palette", "--space=rgb", "--background=#ffffff"]);
+
+        let mut lines: Vec<&str> = output.stdout.lines().collect();
+        lines.sort_unstable();
+
+        assert_eq!(lines, vec!["#0000ff", "#ff0000", "#ffffff"]);
+    }
+
+    // With --include-transparent, the undrawn parts of the canvas count,
+    // the same as the transparent pixels in a still image.
+    #[test]
+    fn it_includes_the_undrawn_parts_of_a_gif_if_you_ask() {
+        let output = get_success(&["./src/tests/delta_frames.gif", "--max-colours=3", "--no-palette", "--space=rgb", "--include-transparent"]);
+
+        let mut lines: Vec<&str> = output.stdout.lines().collect();
+        lines.sort_unstable();
+
+        assert_eq!(lines, vec!["#000000", "#0000ff", "#ff0000"]);
+    }
+
+    #[test]
+    fn it_fails_if_the_background_is_invalid() {
+        let output = get_failure(&["./src/tests/red.png", "--background=white"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("\"white\" isn't a hex colour like #ffffff"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_you_use_background_with_alpha_threshold() {
+        let output = get_failure(&["./src/tests/red.png", "--background=#ffffff", "--alpha-threshold=10"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("cannot be used with"), "stderr = {:?}", output.stderr);
+    }
+
+    // The corner of the red circle is completely transparent.
+    #[test]
+    fn it_fails_if_every_pixel_is_transparent() {
+        let output = get_failure(&["./src/tests/red_circle.png", "--crop=0,0,4,4"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(
+            output.stderr,
+            "./src/tests/red_circle.png: every pixel is transparent; use --include-transparent to look at them anyway\n"
+        );
+    }
+
+    #[test]
+    fn it_only_drops_pixels_below_the_alpha_threshold() {
+        let output = get_success(&["./src/tests/red_circle.png", "--crop=0,0,4,4", "--alpha-threshold=0", "--no-palette"]);
+        assert_eq!(output.stdout, "#000000\n");
+    }
+
+    // This image is (0x1234, 0x5678, 0x9abc) at 16 bits per channel.
+    #[test]
+    fn it_can_look_at_16_bit_images() {
+        let output = get_success(&["./src/tests/sixteen_bit.png", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#12569a\n");
+    }
+
+    // These images are #123456 and #808080 stored at 16 bits per channel
+    // (0x1212, 0x3434, 0x5656 and 0x8080), so they should match their
+    // 8-bit counterparts exactly.
+    #[test]
+    fn it_converts_16_bit_images_to_the_same_colour_as_8_bit() {
+        let eight_bit = get_success(&["./src/tests/rgb_no_alpha.png", "--space=rgb", "--no-palette"]);
+        let sixteen_bit = get_success(&["./src/tests/sixteen_bit_rgba.png", "--space=rgb", "--no-palette"]);
+
+        assert_eq!(eight_bit.stdout, "#123456\n");
+        assert_eq!(sixteen_bit.stdout, eight_bit.stdout);
+
+        let output = get_success(&["./src/tests/sixteen_bit_grey_alpha.png", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#808080\n");
+    }
+
+    // This TIFF is (0.2, 0.4, 0.8) as 32-bit floats.
+    #[test]
+    fn it_can_look_at_float_tiffs() {
+        let output = get_success(&["./src/tests/float.tiff", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#3366cc\n");
+    }
+
+    // This TIFF is (1.5, 0.4, -0.2) as 32-bit floats, so the values outside
+    // 0.0 to 1.0 get clamped.
+    #[test]
+    fn it_clamps_hdr_float_tiffs() {
+        let output = get_success(&["./src/tests/hdr_float.tiff", "--space=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "#ff6600\n");
+    }
+
+    // These HEIC images were created with libheif (using x265 in lossless
+    // mode): a solid #3366cc, and a solid 10-bit (1023, 512, 0).
+    #[test]
+    #[cfg(feature = "heif")]
+    fn it_can_look_at_heic_images() {
+        let output = get_success(&["./src/tests/solid_colour.heic", "--space=rgb", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#3266cc\n");
+    }
+
+    #[test]
+    #[cfg(feature = "heif")]
+    fn it_converts_10_bit_heic_images_to_8_bit() {
+        let output = get_success(&["./src/tests/ten_bit.heic", "--space=rgb", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff8000\n");
+    }
+
+    #[test]
+    #[cfg(not(feature = "heif"))]
+    fn it_explains_if_heic_support_is_missing() {
+        let output = get_failure(&["./src/tests/solid_colour.heic"]);
+
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "");
+        assert!(output.stderr.contains("rebuild with `--features heif`"), "stderr = {:?}", output.stderr);
+    }
+
+    // These AVIF images were created with ravif: a solid #3366cc (8-bit),
+    // and a solid #ff8000 (10-bit).
+    #[test]
+    #[cfg(feature = "heif")]
+    fn it_can_look_at_avif_images() {
+        let output = get_success(&["./src/tests/solid_colour.avif", "--space=rgb", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#3266cc\n");
+    }
+
+    #[test]
+    #[cfg(feature = "heif")]
+    fn it_converts_10_bit_avif_images_to_8_bit() {
+        let output = get_success(&["./src/tests/ten_bit.avif", "--space=rgb", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff8000\n");
+    }
+
+    // We pipe in the first 280 bytes of solid_colour.avif, which cuts off
+    // the middle of the image data.  (A truncated file in src/tests would
+    // break the tests that look at every image in the directory.)
+    #[test]
+    fn it_fails_cleanly_on_a_truncated_avif() {
+        let mut bytes = std::fs::read("./src/tests/solid_colour.avif").unwrap();
+        bytes.truncate(280);
+
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd.args(["-"]).write_stdin(bytes).unwrap_err().as_output().unwrap().to_owned();
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert!(stderr.starts_with("<stdin>: "), "stderr = {:?}", stderr);
+        assert_eq!(stderr.lines().count(), 1, "stderr = {:?}", stderr);
+    }
+
+    #[test]
+    fn it_draws_an_svg() {
+        let output = get_success(&["./src/tests/red_rect.svg", "--max-colours=1", "--no-palette", "--verbose"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+
+        // The SVG is 100x50, so we draw it at 512x256, then resize that
+        // to 400x200.
+        assert!(output.stderr.contains("pixels: 80000\n"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_draws_an_svg_at_the_svg_size() {
+        let output = get_success(&["./src/tests/red_rect.svg", "--svg-size=20", "--no-resize", "--no-palette", "--verbose"]);
+
+        assert!(output.stderr.contains("pixels: 200\n"), "stderr = {:?}", output.stderr);
+    }
+
+    // The parts of an SVG we don't draw on are transparent, so we leave
+    // them out rather than counting them as black.
+    #[test]
+    fn it_ignores_the_transparent_parts_of_an_svg() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><circle cx="5" cy="5" r="3" fill="#0000ff"/></svg>"##;
+
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd.args(["-", "--no-palette"]).write_stdin(svg).unwrap();
+
+        assert_eq!(str::from_utf8(&output.stdout).unwrap(), "#0000ff\n");
+    }
+
+    // (A broken SVG in src/tests would break the tests that look at every
+    // image in the directory.)
+    #[test]
+    fn it_fails_cleanly_on_a_broken_svg() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="10" height="10""#;
+
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd.args(["-"]).write_stdin(svg).unwrap_err().as_output().unwrap().to_owned();
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert!(stderr.starts_with("<stdin>: "), "stderr = {:?}", stderr);
+        assert_eq!(stderr.lines().count(), 1, "stderr = {:?}", stderr);
+    }
+
+    // This PDF has a single 2x1 inch page, filled with #3366cc.
+    #[test]
+    #[cfg(feature = "pdf")]
+    fn it_draws_a_pdf() {
+        let output = get_success(&["./src/tests/solid_colour.pdf", "--max-colours=1", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#3366cc\n");
+    }
+
+    #[test]
+    #[cfg(feature = "pdf")]
+    fn it_draws_a_pdf_at_the_dpi() {
+        let output = get_success(&["./src/tests/solid_colour.pdf", "--pdf-dpi=72", "--no-resize", "--no-palette", "--verbose"]);
+
+        assert!(output.stderr.contains("pixels: 10368\n"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    #[cfg(feature = "pdf")]
+    fn it_explains_if_the_page_is_out_of_range() {
+        let output = get_failure(&["./src/tests/solid_colour.pdf", "--page=3"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stderr, "./src/tests/solid_colour.pdf: --page=3 is out of range; this PDF has 1 page\n");
+    }
+
+    // We add an encryption dictionary to solid_colour.pdf, as an update
+    // at the end of the file.  The empty password doesn't match it, so
+    // the PDF needs a password to open.
+    #[test]
+    #[cfg(feature = "pdf")]
+    fn it_explains_if_a_pdf_is_password_protected() {
+        let mut bytes = std::fs::read("./src/tests/solid_colour.pdf").unwrap();
+
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        let prev_xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+
+        let encrypt_offset = bytes.len();
+        bytes.extend(format!("5 0 obj\n<< /Filter /Standard /V 1 /R 2 /O <{0}> /U <{0}> /P -4 >>\nendobj\n", "00".repeat(32)).bytes());
+
+        let xref_offset = bytes.len();
+        bytes.extend(
+            format!(
+                "xref\n5 1\n{:010} 00000 n \ntrailer\n<< /Size 6 /Root 1 0 R /Prev {} /Encrypt 5 0 R /ID [<{id}> <{id}>] >>\nstartxref\n{}\n%%EOF\n",
+                encrypt_offset,
+                prev_xref,
+                xref_offset,
+                id = "00112233445566778899aabbccddeeff",
+            )
+            .bytes(),
+        );
+
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd.args(["-"]).write_stdin(bytes).unwrap_err().as_output().unwrap().to_owned();
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert!(stderr.contains("This PDF is password-protected"), "stderr = {:?}", stderr);
+    }
+
+    #[test]
+    #[cfg(not(feature = "pdf"))]
+    fn it_explains_if_pdf_support_is_missing() {
+        let output = get_failure(&["./src/tests/solid_colour.pdf"]);
+
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "");
+        assert!(output.stderr.contains("rebuild with `--features pdf`"), "stderr = {:?}", output.stderr);
+    }
+
+    // This icon has three entries: a 16x16 red BMP, a 32x32 paletted green BMP,
+    // and a 48x48 blue PNG.  We should only look at the largest one.
+    #[test]
+    fn it_looks_at_the_largest_image_in_an_ico_file() {
+        let output = get_success(&["./src/tests/favicon.ico", "--max-colours=1", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#0000ff\n");
+    }
+
+    #[test]
+    fn it_omits_the_escape_codes_with_no_palette() {
+        let output = get_success(&["./src/tests/red.png", "--max-colours=1", "--no-palette"]);
+
+        assert_eq!(output.exit_code, 0);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_defaults_to_five_colours() {
+        let output = get_success(&["./src/tests/noise.jpg"]);
+
+        assert_eq!(
+            output.stdout.matches("\n").count(),
+            5,
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    // With the default seed, the output should be exactly the same every
+    // time: the biggest cluster first, with ties broken by hex value.
+    #[test]
+    fn it_sorts_the_colours_by_cluster_size() {
+        let output = get_success(&["./src/tests/noise.jpg", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#739177\n#75819c\n#86b463\n#9a6661\n#83509d\n");
+
+        let output = get_success(&["./src/tests/red_and_blue.png", "--max-colours=2", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#0000ff\n#ff0000\n");
+    }
+
+    #[test]
+    fn it_sorts_the_colours_by_luminance() {
+        let output = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sort=luminance"]);
+
+        assert_eq!(output.stdout, "#86b463\n#739177\n#75819c\n#9a6661\n#83509d\n");
+    }
+
+    #[test]
+    fn it_sorts_the_colours_by_hue() {
+        let output = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sort=hue"]);
+
+        assert_eq!(output.stdout, "#9a6661\n#86b463\n#739177\n#75819c\n#83509d\n");
+    }
+
+    #[test]
+    fn it_accepts_dominance_as_another_name_for_size() {
+        let size = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sort=size"]);
+        let dominance = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sort=dominance"]);
+
+        assert_eq!(size.stdout, dominance.stdout);
+    }
+
+    #[test]
+    fn it_can_skip_sorting_the_colours() {
+        let sorted = get_success(&["./src/tests/noise.jpg", "--no-palette"]);
+        let unsorted = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sort=none"]);
+
+        let mut sorted_lines: Vec<&str> = sorted.stdout.lines().collect();
+        let mut unsorted_lines: Vec<&str> = unsorted.stdout.lines().collect();
+        assert_ne!(sorted_lines, unsorted_lines);
+
+        sorted_lines.sort_unstable();
+        unsorted_lines.sort_unstable();
+        assert_eq!(sorted_lines, unsorted_lines);
+    }
+
+    #[test]
+    fn it_samples_at_a_rate_reproducibly() {
+        let first = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sample-rate=0.05", "--seed=1"]);
+        let second = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sample-rate=0.05", "--seed=1"]);
+        let other_seed = get_success(&["./src/tests/noise.jpg", "--no-palette", "--sample-rate=0.05", "--seed=2"]);
+
+        assert_eq!(first.stdout, second.stdout);
+        assert_ne!(first.stdout, other_seed.stdout);
+    }
+
+    #[test]
+    fn it_looks_at_every_pixel_if_the_sample_rate_keeps_none() {
+        let output = get_success(&["./src/tests/red.png", "--no-palette", "--sample-rate=0.000001"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+        assert_eq!(
+            output.stderr,
+            "warning: ./src/tests/red.png: --sample-rate=0.000001 didn't keep any pixels, so looking at all of them\n"
+        );
+    }
+
+    #[test]
+    fn it_excludes_colours() {
+        let output = get_success(&["./src/tests/animated_squares.gif", "--no-palette", "--exclude", "#ff0000"]);
+
+        assert_eq!(output.stdout, "#0000ff\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_applies_every_exclude() {
+        let output = get_success(&["./src/tests/noise.jpg", "--no-palette", "--exclude=#739177:20", "--exclude=#75819c:20"]);
+
+        assert!(!output.stdout.contains("#739177"), "stdout = {:?}", output.stdout);
+        assert!(!output.stdout.contains("#75819c"), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_ignores_exclude_if_it_would_remove_every_pixel() {
+        let output = get_success(&["./src/tests/red.png", "--no-palette", "--exclude=#ff0000"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+        assert_eq!(output.stderr, "warning: ./src/tests/red.png: --exclude removed every pixel, so ignoring it\n");
+    }
+
+    #[test]
+    fn it_lets_you_choose_the_max_colours() {
+        let output = get_success(&["./src/tests/noise.jpg", "--max-colours=8"]);
+
+        assert_eq!(
+            output.stdout.matches("\n").count(),
+            8,
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    #[test]
+    fn it_chooses_two_colours_automatically_for_an_animated_gif() {
+        let output = get_success(&["./src/tests/animated_squares.gif", "--max-colours=auto", "--verbose"]);
+
+        assert_eq!(output.stdout.lines().count(), 2, "stdout = {:?}", output.stdout);
+        assert!(output.stderr.contains("chose 2 colour(s)"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_chooses_every_colour_automatically_for_an_image_with_a_few_colours() {
+        let output = get_success(&["./src/tests/four_colours.gif", "--max-colours=auto", "--no-palette"]);
+
+        assert_eq!(output.stdout.lines().count(), 4, "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_chooses_one_colour_automatically_for_a_solid_image() {
+        let output = get_success(&["./src/tests/red.png", "--max-colours=auto"]);
+
+        assert_eq!(output.stdout.lines().count(), 1, "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_chooses_the_same_colour_count_with_the_same_seed() {
+        let chosen = || {
+            let output = get_success(&["./src/tests/noise.jpg", "--max-colours=auto", "--seed=7", "--verbose", "--no-palette"]);
+            let line = output.stderr.lines().find(|line| line.starts_with("chose ")).map(String::from);
+
+            (output.stdout, line)
+        };
+
+        let (stdout, line) = chosen();
+        assert!(line.is_some(), "stdout = {:?}", stdout);
+        assert_eq!(chosen(), (stdout, line));
+    }
+
+    #[test]
+    fn it_only_prints_each_colour_once() {
+        let output = get_success(&["./src/tests/red.png", "--max-colours=5", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+    }
+
+    #[test]
+    fn it_pads_the_colours_to_max_colours() {
+        let output = get_success(&["./src/tests/red.png", "--max-colours=3", "--no-palette", "--show-percentage", "--pad-colours"]);
+
+        assert_eq!(output.stdout, "#ff0000 100.0%\n#ff0000 0.0%\n#ff0000 0.0%\n");
+    }
+
+    #[test]
+    fn it_merges_colours_closer_than_the_min_distance() {
+        let output = get_success(&["./src/tests/two_reds_and_blue.png", "--max-colours=3", "--no-palette"]);
+        assert_eq!(output.stdout.lines().count(), 3, "stdout = {:?}", output.stdout);
+
+        let output = get_success(&["./src/tests/two_reds_and_blue.png", "--max-colours=3", "--no-palette", "--show-percentage", "--min-distance=5"]);
+        assert_eq!(output.stdout, "#ff0000 66.8%\n#0000ff 33.2%\n");
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_an_invalid_min_distance() {
+        let output = get_failure(&["./src/tests/red.png", "--min-distance=0"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("must be a number greater than 0"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_excludes_a_white_background() {
+        let output = get_success(&["./src/tests/red_product_on_white.png", "--no-palette", "--show-percentage"]);
+        assert_eq!(output.stdout, "#ffffff 91.0%\n#c8102e 9.0%\n");
+
+        let output = get_success(&["./src/tests/red_product_on_white.png", "--no-palette", "--show-percentage", "--exclude-white"]);
+        assert_eq!(output.stdout, "#c8102e 100.0%\n");
+    }
+
+    #[test]
+    fn it_fails_if_excluding_white_and_black_removes_every_pixel() {
+        let output = get_failure(&["./src/tests/red.png", "--exclude-white=40", "--exclude-black=60"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(
+            output.stderr,
+            "./src/tests/red.png: --exclude-white or --exclude-black removed every pixel; try a higher --exclude-white=L or a lower --exclude-black=L\n"
+        );
+    }
+
+    #[test]
+    fn it_finds_an_accent_colour_among_greys() {
+        let output = get_success(&["./src/tests/grey_with_blue_accent.png", "--no-palette"]);
+        assert_eq!(output.stdout, "#96928c\n#808080\n#1e50dc\n");
+
+        let output = get_success(&["./src/tests/grey_with_blue_accent.png", "--no-palette", "--min-saturation=20"]);
+        assert_eq!(output.stdout, "#1e50dc\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_falls_back_to_every_pixel_if_too_few_are_saturated() {
+        let output = get_success(&["./src/tests/grey_with_blue_accent.png", "--no-palette", "--min-saturation=20", "--min-saturation-fallback=5"]);
+
+        assert_eq!(output.stdout, "#96928c\n#808080\n#1e50dc\n");
+        assert_eq!(
+            output.stderr,
+            "warning: ./src/tests/grey_with_blue_accent.png: only 4.0% of the pixels have a saturation of at least 20, so looking at all of them\n"
+        );
+    }
+
+    // The fixture has six blocks of colour, one for each role.
+    #[test]
+    fn it_picks_a_colour_for_each_role() {
+        let output = get_success(&["./src/tests/colourful.png", "--mode=roles", "--no-palette"]);
+
+        assert_eq!(
+            output.stdout,
+            "Vibrant       #e8431f\n\
+             Dark Vibrant  #0b3a8c\n\
+             Light Vibrant #7fe0f5\n\
+             Muted         #7a8c99\n\
+             Dark Muted    #3b3a33\n\
+             Light Muted   #d9cfc0\n"
+        );
+    }
+
+    #[test]
+    fn it_picks_a_saturated_vibrant_role_and_an_unsaturated_muted_role() {
+        let output = get_success(&["./src/tests/colourful.png", "--mode=roles", "--format=json"]);
+
+        let result: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        let hsl = |key: &str| {
+            let rgb: Vec<u8> = result["roles"][key]["rgb"].as_array().unwrap().iter().map(|v| v.as_u64().unwrap() as u8).collect();
+            crate::format::hsl_components(palette::Srgb::new(rgb[0], rgb[1], rgb[2]))
+        };
+
+        let (_, saturation, lightness) = hsl("vibrant");
+        assert!(saturation >= 35.0, "result = {}", result);
+        assert!((30.0..=70.0).contains(&lightness), "result = {}", result);
+
+        let (_, muted_saturation, _) = hsl("muted");
+        assert!(muted_saturation <= 40.0, "result = {}", result);
+        assert_ne!(result["roles"]["vibrant"], result["roles"]["muted"]);
+    }
+
+    #[test]
+    fn it_prints_a_dash_for_a_role_with_no_colour() {
+        let output = get_success(&["./src/tests/red.png", "--mode=roles", "--no-palette"]);
+        assert_eq!(output.stdout, "Vibrant       #ff0000\nDark Vibrant  -\nLight Vibrant -\nMuted         -\nDark Muted    -\nLight Muted   -\n");
+
+        let output = get_success(&["./src/tests/red.png", "--mode=roles", "--format=json"]);
+        let result: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        assert_eq!(result["roles"]["vibrant"]["hex"], "#ff0000");
+        assert!(result["roles"]["muted"].is_null(), "result = {}", result);
+    }
+
+    #[test]
+    fn it_counts_the_exact_colours() {
+        let output = get_success(&["./src/tests/four_colours.gif", "--mode=exact", "--show-percentage", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#12a4f0 40.0%\n#f0c018 30.0%\n#2d2d2d 20.0%\n#e8e8e3 10.0%\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_warns_that_exact_mode_ignores_the_seed() {
+        let output = get_success(&["./src/tests/four_colours.gif", "--mode=exact", "--seed=1", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#12a4f0\n#f0c018\n#2d2d2d\n#e8e8e3\n");
+        assert_eq!(
+            output.stderr,
+            "warning: --mode=exact counts every colour, so ignoring --seed and --random-seed\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_there_are_too_many_exact_colours() {
+        let output = get_failure(&["./src/tests/noise.jpg", "--mode=exact", "--max-exact-colours=100"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, "");
+        assert!(output.stderr.contains("there are more than 100 different colours"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_prints_the_complementary_colour_after_the_dominant_colours() {
+        let output = get_success(&["./src/tests/red.png", "--harmony=complementary", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff0000\n#008ca1 (derived)\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_marks_the_derived_colours_in_json() {
+        let output = get_success(&["./src/tests/red.png", "--harmony=triadic", "--format=json"]);
+
+        let json: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        let colours = json["colours"].as_array().unwrap();
+
+        let derived: Vec<bool> = colours.iter().map(|c| c["derived"].as_bool().unwrap()).collect();
+        assert_eq!(derived, vec![false, true, true]);
+
+        assert!(colours[0]["proportion"].is_number());
+        assert!(colours[1]["proportion"].is_null());
+    }
+
+    #[test]
+    fn it_prints_a_lightness_ramp_for_a_grey_image() {
+        let output = get_success(&["./src/tests/grey.png", "--harmony=complementary", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#808080\n#0d0d0d (derived)\n");
+        assert_eq!(
+            output.stderr,
+            "warning: the most dominant colour #808080 is grey, so it has no hue to rotate; printing a lightness ramp instead\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_you_use_a_harmony_with_another_format() {
+        let output = get_failure(&["./src/tests/red.png", "--harmony=analogous", "--format=css"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--harmony only works with"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_finds_the_background_colour_around_the_edge() {
+        let output = get_success(&["./src/tests/blue_border_red_centre.png", "--no-palette"]);
+        assert_eq!(output.stdout.lines().next(), Some("#ff0000"));
+
+        let output = get_success(&["./src/tests/blue_border_red_centre.png", "--mode=background", "--no-palette"]);
+        assert_eq!(output.stdout, "#0000ff\n");
+    }
+
+    #[test]
+    fn it_finds_the_background_colour_with_a_wider_border() {
+        let output = get_success(&[
+            "./src/tests/blue_border_red_centre.png",
+            "--mode=background",
+            "--border-width=20%",
+            "--max-colours=2",
+            "--show-percentage",
+            "--no-palette",
+        ]);
+
+        assert_eq!(output.stdout, "#0000ff 56.2%\n#ff0000 43.8%\n");
+    }
+
+    #[test]
+    fn it_finds_the_background_colour_after_cropping() {
+        let output = get_success(&[
+            "./src/tests/blue_border_red_centre.png",
+            "--mode=background",
+            "--crop=15%,15%,70%,70%",
+            "--no-palette",
+        ]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+    }
+
+    #[test]
+    fn it_weights_the_pixels_near_the_center() {
+        // The red disc covers about 40% of the image, so it only wins if
+        // we give the centre enough weight.
+        for (weight, dominant) in [("0", "#0000ff"), ("0.5", "#0000ff"), ("0.75", "#ff0000"), ("1", "#ff0000")] {
+            let output = get_success(&[
+                "./src/tests/red_disc_blue_surround.png",
+                &format!("--center-weight={}", weight),
+                "--no-palette",
+            ]);
+
+            assert_eq!(output.stdout.lines().next(), Some(dominant), "weight = {}", weight);
+        }
+    }
+
+    #[test]
+    fn it_fails_if_you_use_center_weight_with_background_mode() {
+        let output = get_failure(&["./src/tests/red.png", "--mode=background", "--center-weight=0.5"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--center-weight doesn't work with --mode=background"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_you_use_roles_with_another_format() {
+        let output = get_failure(&["./src/tests/red.png", "--mode=roles", "--format=css"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--mode=roles only works with"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_prints_the_nearest_named_colour() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--no-palette", "--names"]);
+        assert_eq!(output.stdout, "#0000ff blue\n#ff0000 red\n");
+
+        let output = get_success(&["./src/tests/colourful.png", "--no-palette", "--max-colours=6", "--names", "--show-percentage"]);
+        assert!(output.stdout.starts_with("#e8431f 30.0% red (ΔE 4.4)\n"), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_includes_the_name_in_json() {
+        let output = get_success(&["./src/tests/red.png", "--format=json", "--names"]);
+
+        let result: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        assert_eq!(result["colours"][0]["name"], "red");
+    }
+
+    #[test]
+    fn it_suggests_a_text_colour_for_each_colour() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--no-palette", "--contrast"]);
+
+        assert_eq!(output.stdout, "#0000ff → text #ffffff (8.5:1)\n#ff0000 → text #000000 (5.2:1)\n");
+    }
+
+    #[test]
+    fn it_flags_colours_with_too_little_contrast() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--no-palette", "--contrast-against=#ffffff"]);
+
+        assert_eq!(output.stdout, "#0000ff → #ffffff (8.5:1)\n#ff0000 → #ffffff (3.9:1, below 4.5:1)\n");
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_contrast_and_contrast_against() {
+        let output = get_failure(&["./src/tests/red.png", "--contrast", "--contrast-against=#ffffff"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("cannot be used with"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_includes_the_contrast_in_json() {
+        let output = get_success(&["./src/tests/red.png", "--format=json", "--contrast"]);
+
+        let result: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        assert_eq!(result["colours"][0]["recommended_text"], "#000000");
+        assert_eq!(result["colours"][0]["contrast_white"], 4.0);
+        assert_eq!(result["colours"][0]["contrast_black"], 5.25);
+    }
+
+    #[test]
+    fn it_drops_colours_below_the_min_percentage() {
+        let output = get_success(&[
+            "./src/tests/animated_squares.gif",
+            "--max-colours=5",
+            "--min-percentage=10",
+            "--space=rgb",
+            "--no-palette",
+        ]);
+
+        let mut lines: Vec<&str> = output.stdout.lines().collect();
+        lines.sort_unstable();
+
+        assert_eq!(lines, vec!["#0000ff", "#ff0000"]);
+    }
+
+    #[test]
+    fn it_always_prints_at_least_one_colour() {
+        let output = get_success(&["./src/tests/noise.jpg", "--min-percentage=100", "--no-palette"]);
+
+        assert_eq!(output.stdout.lines().count(), 1, "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_finds_the_average_colour() {
+        let output = get_success(&["./src/tests/red.png", "--average", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+    }
+
+    #[test]
+    fn it_fails_if_you_ask_for_the_average_and_several_colours() {
+        for flag in ["--max-colours=3", "--terminal-colours", "--wal"] {
+            let output = get_failure(&["./src/tests/red.png", "--average", flag]);
+
+            assert_eq!(output.exit_code, 2);
+            assert!(output.stderr.contains("cannot be used with"), "flag = {}, stderr = {:?}", flag, output.stderr);
+        }
+    }
+
+    // If you average red and blue in sRGB, you get #800080, which is
+    // too dark; averaging in linear RGB gives the right answer.
+    #[test]
+    fn it_averages_in_linear_light() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--average", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#bc00bc\n");
+    }
+
+    #[test]
+    fn it_ignores_the_seed_for_the_average() {
+        let output1 = get_success(&["./src/tests/noise.jpg", "--average", "--seed=1", "--sample-size=10"]);
+        let output2 = get_success(&["./src/tests/noise.jpg", "--average", "--seed=2", "--sample-size=10"]);
+
+        assert_eq!(output1.stdout, output2.stdout);
+        assert_eq!(output1.stdout.lines().count(), 1);
+    }
+
+    #[test]
+    fn it_lets_you_choose_the_seed() {
+        let output = get_success(&["./src/tests/noise.jpg", "--max-colours=1", "--seed", "123456789"]);
+
+        assert!(output.stdout.contains("#85827f"));
+    }
+
+    #[test]
+    fn it_samples_pixels_reproducibly() {
+        let output1 = get_success(&["./src/tests/noise.jpg", "--sample-size=1000", "--seed=3", "--verbose"]);
+        let output2 = get_success(&["./src/tests/noise.jpg", "--sample-size=1000", "--seed=3"]);
+
+        assert_eq!(output1.stdout, output2.stdout);
+        assert!(output1.stderr.contains("pixels sampled: 1000\n"), "stderr = {:?}", output1.stderr);
+    }
+
+    #[test]
+    fn it_finds_both_colours_with_each_algorithm() {
+        for algorithm in ["kmeans", "median-cut", "octree"] {
+            let output = get_success(&["./src/tests/animated_squares.gif", "--no-palette", "--show-percentage", &format!("--algorithm={}", algorithm)]);
+
+            assert_eq!(output.stdout, "#0000ff 50.0%\n#ff0000 50.0%\n", "algorithm = {}", algorithm);
+        }
+    }
+
+    #[test]
+    fn it_uses_the_octree_algorithm() {
+        let output = get_success(&["./src/tests/animated_squares.gif", "--no-palette", "--algorithm=octree", "--verbose"]);
+
+        assert!(output.stderr.contains("\nquantizing: "), "stderr = {:?}", output.stderr);
+        assert!(!output.stderr.contains("\nclustering: "), "stderr = {:?}", output.stderr);
+        assert!(!output.stderr.contains("\nrun 1 "), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_warns_that_the_seed_does_nothing_without_kmeans() {
+        let output = get_success(&["./src/tests/noise.jpg", "--algorithm=median-cut", "--seed=7"]);
+        let expected = get_success(&["./src/tests/noise.jpg", "--algorithm=median-cut"]);
+
+        assert_eq!(output.stdout, expected.stdout);
+        assert_eq!(output.stderr, "warning: --algorithm=median-cut always finds the same colours, so ignoring --seed and --random-seed\n");
+        assert_eq!(expected.stderr, "");
+    }
+
+    #[test]
+    fn it_can_change_the_colours_with_more_iterations() {
+        let one = get_success(&["./src/tests/noise.jpg", "--no-palette", "--seed=3", "--max-iterations=1", "--verbose"]);
+        let many = get_success(&["./src/tests/noise.jpg", "--no-palette", "--seed=3", "--max-iterations=200", "--verbose"]);
+
+        assert_ne!(one.stdout, many.stdout);
+        assert!(one.stderr.contains("k-means stopped at the iteration limit without converging\n"), "stderr = {:?}", one.stderr);
+        assert!(many.stderr.contains("k-means converged\n"), "stderr = {:?}", many.stderr);
+    }
+
+    #[test]
+    fn it_lets_you_set_random_seed() {
+        let output1 = get_success(&["./src/tests/noise.jpg", "--random-seed"]);
+        let output2 = get_success(&["./src/tests/noise.jpg", "--random-seed"]);
+
+        assert_ne!(
+            output1.stdout,
+            output2.stdout
+        );
+    }
+
+    #[test]
+    fn it_is_deterministic_with_multiple_runs_and_a_fixed_seed() {
+        let output1 = get_success(&["./src/tests/noise.jpg", "--runs=5", "--seed=1"]);
+        let output2 = get_success(&["./src/tests/noise.jpg", "--runs=5", "--seed=1"]);
+
+        assert_eq!(output1.stdout, output2.stdout);
+    }
+
+    #[test]
+    fn it_prints_the_score_of_each_run_with_verbose() {
+        let output = get_success(&["./src/tests/noise.jpg", "--runs=3", "--verbose"]);
+
+        assert_eq!(
+            output.stderr.matches("): score").count(),
+            3,
+            "stderr = {:?}",
+            output.stderr
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_run_with_the_lowest_error() {
+        let output = get_success(&["./src/tests/noise.jpg", "--runs=5", "--seed=1", "--verbose"]);
+
+        let error = |line: &str| line.rsplit("error ").next().unwrap().parse::<f32>().unwrap();
+        let errors: Vec<f32> = output.stderr.lines().filter(|line| line.starts_with("run ")).map(error).collect();
+        let best = output.stderr.lines().find(|line| line.starts_with("best run: ")).expect("no best run");
+
+        assert_eq!(errors.len(), 5, "stderr = {:?}", output.stderr);
+        assert_eq!(error(best), errors.iter().copied().fold(f32::INFINITY, f32::min), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_prints_diagnostics_to_stderr_with_verbose() {
+        let quiet = get_success(&["./src/tests/animated_squares.gif", "--no-palette"]);
+        let verbose = get_success(&["./src/tests/animated_squares.gif", "--no-palette", "--verbose"]);
+
+        assert_eq!(quiet.stderr, "");
+        assert_ne!(verbose.stderr, "");
+        assert!(verbose.stderr.contains("frames sampled: 8"), "stderr = {:?}", verbose.stderr);
+
+        assert_eq!(quiet.stdout, verbose.stdout);
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_zero_runs() {
+        let output = get_failure(&["./src/tests/red.png", "--runs=0"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+    }
+
+    // The image created in the next two tests was created with the
+    // following command:
+    //
+    //      convert -delay 200 -loop 10 -dispose previous red.png blue.png red.png blue.png red.png blue.png red.png blue.png animated_squares.gif
+    //
+
+    #[test]
+    fn it_looks_at_multiple_frames_in_an_animated_gif() {
+        let output = get_success(&["./src/tests/animated_squares.gif"]);
+
+        assert_eq!(
+            output.stdout.matches("\n").count(),
+            2,
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    #[test]
+    fn it_looks_at_multiple_frames_in_an_animated_gif_uppercase() {
+        let output = get_success(&["./src/tests/animated_upper_squares.GIF"]);
+
+        assert_eq!(
+            output.stdout.matches("\n").count(),
+            2,
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    // This GIF is built from delta frames: a red square in one corner, then
+    // small blue patches drawn on top.  Most of the canvas is never drawn
+    // on, and shouldn't be reported as black.
+    #[test]
+    fn it_composites_the_frames_of_an_animated_gif() {
+        let output = get_success(&["./src/tests/delta_frames.gif", "--max-colours=3", "--no-palette", "--space=rgb"]);
+
+        assert_eq!(output.stdout, "#ff0000\n#0000ff\n");
+    }
+
+    // This GIF has 20 identical red frames, then a single blue frame.
+    #[test]
+    fn it_skips_duplicate_frames_in_an_animated_gif() {
+        let output = get_success(&["./src/tests/red_pause.gif", "--max-colours=2", "--no-palette", "--space=rgb", "--verbose"]);
+
+        let mut colours: Vec<&str> = output.stdout.lines().collect();
+        colours.sort();
+        assert_eq!(colours, vec!["#0000ff", "#ff0000"]);
+        assert!(output.stderr.contains("frames sampled: 2\n"), "stderr = {:?}", output.stderr);
+        assert!(output.stderr.contains("duplicate frames skipped: 19\n"), "stderr = {:?}", output.stderr);
+
+        let output = get_success(&["./src/tests/red_pause.gif", "--no-dedupe-frames", "--verbose"]);
+
+        assert!(output.stderr.contains("frames sampled: 21\n"), "stderr = {:?}", output.stderr);
+        assert!(output.stderr.contains("duplicate frames skipped: 0\n"), "stderr = {:?}", output.stderr);
+
+        // The duplicates don't pass their delay on to the frame we keep,
+        // so the pause doesn't take over the palette -- unless you ask
+        // for every frame.
+        let output = get_success(&["./src/tests/red_pause.gif", "--max-colours=2", "--no-palette", "--space=rgb", "--show-percentage", "--sort=luminance"]);
+        assert_eq!(output.stdout, "#ff0000 50.0%\n#0000ff 50.0%\n");
+
+        let output = get_success(&["./src/tests/red_pause.gif", "--max-colours=2", "--no-palette", "--space=rgb", "--show-percentage", "--no-dedupe-frames"]);
+        assert_eq!(output.stdout, "#ff0000 95.2%\n#0000ff 4.8%\n");
+    }
+
+    // This GIF shows a red frame for over ten minutes, then a blue frame
+    // with no delay.
+    #[test]
+    fn it_weights_frames_by_how_long_theyre_shown() {
+        let output = get_success(&["./src/tests/long_red_short_blue.gif", "--max-colours=1", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+    }
+
+    #[test]
+    fn it_only_looks_at_the_first_frame() {
+        let output = get_success(&["./src/tests/animated_squares.gif", "--frames=first", "--no-palette", "--space=rgb", "--verbose"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+        assert!(output.stderr.contains("frames sampled: 1\n"), "stderr = {:?}", output.stderr);
+    }
+
+    // Frames count from zero, and an index past the end is the last frame.
+    #[test]
+    fn it_looks_at_a_single_frame_by_index() {
+        let output = get_success(&["./src/tests/animated_squares.gif", "--frames=index:1", "--no-palette", "--space=rgb"]);
+        assert_eq!(output.stdout, "#0000ff\n");
+
+        let output = get_success(&["./src/tests/red_pause.gif", "--frames=index:100", "--no-palette", "--space=rgb"]);
+        assert_eq!(output.stdout, "#0000ff\n");
+    }
+
+    #[test]
+    fn it_looks_at_every_nth_frame() {
+        let output = get_success(&["./src/tests/red_pause.gif", "--frames=every:5", "--no-dedupe-frames", "--verbose"]);
+
+        assert!(output.stderr.contains("frames sampled: 5\n"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_caps_the_number_of_frames() {
+        let output = get_success(&["./src/tests/red_pause.gif", "--frames=all", "--no-dedupe-frames", "--max-frames=4", "--verbose"]);
+
+        assert!(output.stderr.contains("frames sampled: 4\n"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_the_frame_selection_is_invalid() {
+        let output = get_failure(&["./src/tests/animated_squares.gif", "--frames=last"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("should be auto, all, first, every:<N> or index:<N>"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_still_prints_16_colours_when_max_colours_and_terminal_colours_are_set() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--max-colours=20"]);
+
+        assert_eq!(output.exit_code, 0);
+
+        assert_eq!(
+            output.stdout.matches("\n").count(),
+            16,
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    // Notice the colours in the terminal_colours.png image is slightly different than the values defined in terminal_colours.rs.
+    // This is on purpose to test that slight variation gets handled.
+    #[test]
+    fn it_prints_the_ansi_terminal_colours_mapped_correctly() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--no-palette"]);
+
+        assert_eq!(output.exit_code, 0);
+
+        let expected_output = "\
+#000000
+#aa0000
+#00aa00
+#808000
+#0000aa
+#aa00aa
+#00aaaa
+#aaaaaa
+#555555
+#ff0000
+#00ff00
+#ffff00
+#0000ff
+#ff00ff
+#00ffff
+#ffffff
+";
+
+        assert_eq!(output.stdout, expected_output);
+
+        assert_eq!(output.stderr, "");
+    }
+
+    // By default, colours 1-6 get at least 3:1 contrast with the background,
+    // so the dark red and blue are lightened; everything else is as above.
+    #[test]
+    fn it_prints_the_ansi_terminal_colours_with_the_default_contrast() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--no-palette"]);
+
+        let expected_output = "\
+#000000
+#b41307
+#00aa00
+#808000
+#5d37e0
+#aa00aa
+#00aaaa
+#aaaaaa
+#555555
+#ff0000
+#00ff00
+#ffff00
+#0000ff
+#ff00ff
+#00ffff
+#ffffff
+";
+
+        assert_eq!(output.stdout, expected_output);
+        assert_eq!(output.stderr, "");
+    }
+
+    // The bright slots (9-15) are already as bright as they go, so
+    // --max-brightness gives the same colour for each normal and bright pair.
+    #[test]
+    fn it_brightens_the_terminal_colours() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--no-palette", "--max-brightness"]);
+
+        let lines: Vec<&str> = output.stdout.lines().collect();
+
+        assert_eq!(lines[..8], ["#000000", "#ff0000", "#00ff00", "#ffff00", "#0000ff", "#ff00ff", "#00ffff", "#ffffff"]);
+        assert_eq!(lines[8], "#555555");
+        assert_eq!(lines[1..8], lines[9..16]);
+
+        let brightness = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--no-palette", "--brightness=100"]);
+        assert_eq!(brightness.stdout, output.stdout);
+    }
+
+    #[test]
+    fn it_brightens_and_saturates_the_terminal_colours_part_of_the_way() {
+        use palette::FromColor;
+
+        // The value (brightness) and saturation of each slot, in HSV.
+        let slots = |args: &[&str]| -> Vec<(f32, f32)> {
+            let mut args = args.to_vec();
+            args.extend(["./src/tests/colourful.png", "--terminal-colours", "--min-contrast=1", "--no-palette"]);
+
+            get_success(&args)
+                .stdout
+                .lines()
+                .map(|line| {
+                    let hsv = palette::Hsv::from_color(crate::parse_hex(line).unwrap().into_format::<f32>());
+                    (hsv.value, hsv.saturation)
+                })
+                .collect()
+        };
+
+        let untouched = slots(&[]);
+
+        let (half, full) = (slots(&["--brightness=50"]), slots(&["--brightness=100"]));
+        for i in (1..=7).chain(9..=15) {
+            assert!(untouched[i].0 <= half[i].0 && half[i].0 <= full[i].0, "{}: {:?} {:?} {:?}", i, untouched[i], half[i], full[i]);
+            assert!((full[i].0 - 1.0).abs() < 0.01, "{}: {:?}", i, full[i]);
+        }
+
+        let (half, full) = (slots(&["--saturation=50"]), slots(&["--saturation=100"]));
+        for i in (1..=7).chain(9..=15) {
+            assert!(untouched[i].1 <= half[i].1 + 0.01 && half[i].1 <= full[i].1 + 0.01, "{}: {:?} {:?} {:?}", i, untouched[i], half[i], full[i]);
+        }
+
+        // The blacks are left alone.
+        assert_eq!(slots(&["--brightness=100", "--saturation=100"])[0], untouched[0]);
+        assert_eq!(slots(&["--brightness=100", "--saturation=100"])[8], untouched[8]);
+    }
+
+    #[test]
+    fn it_raises_dark_terminal_colours_to_the_min_brightness() {
+        let lightness = |min_brightness: &str| -> Vec<f32> {
+            get_success(&[
+                "./src/tests/dark_colours.png",
+                "--terminal-colours",
+                "--min-contrast=1",
+                "--no-palette",
+                &format!("--min-brightness={}", min_brightness),
+            ])
+            .stdout
+            .lines()
+            .map(|line| crate::distance::to_lab(crate::parse_hex(line).unwrap()).l)
+            .collect()
+        };
+
+        let (untouched, raised) = (lightness("0"), lightness("40"));
+
+        assert!(untouched[1..].iter().any(|l| *l < 40.0), "{:?}", untouched);
+
+        for i in (1..=6).chain(9..=14) {
+            assert!(raised[i] >= 40.0, "{}: {:?}", i, raised);
+        }
+
+        // The background is meant to be dark, so we leave it alone.
+        assert_eq!(raised[0], untouched[0]);
+        assert!(raised[0] < 40.0);
+    }
+
+    #[test]
+    fn it_fails_if_you_adjust_the_terminal_colours_without_them() {
+        for flag in ["--brightness=50", "--saturation=50", "--min-brightness=40", "--min-contrast=7", "--keep-neutrals"] {
+            let output = get_failure(&["./src/tests/red.png", flag]);
+
+            assert_eq!(output.exit_code, 2);
+            assert!(output.stderr.contains("only works with --terminal-colours or --wal"), "{}: stderr = {:?}", flag, output.stderr);
+        }
+
+        get_success(&["./src/tests/terminal_colours.png", "--wal", "--brightness=50"]);
+
+        // --max-brightness was always allowed on its own, so it still is.
+        get_success(&["./src/tests/red.png", "--max-brightness"]);
+    }
+
+    #[test]
+    fn it_fails_if_the_brightness_is_out_of_range() {
+        let output = get_failure(&["./src/tests/red.png", "--terminal-colours", "--brightness=101"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--brightness"), "stderr = {:?}", output.stderr);
+
+        let output = get_failure(&["./src/tests/red.png", "--terminal-colours", "--saturation=-5"]);
+
+        assert_eq!(output.exit_code, 2);
+
+        let output = get_failure(&["./src/tests/red.png", "--terminal-colours", "--min-brightness=150"]);
+
+        assert_eq!(output.exit_code, 2);
+    }
+
+    #[test]
+    fn it_keeps_the_neutral_terminal_colours() {
+        for path in ["./src/tests/terminal_colours.png", "./src/tests/dark_colours.png", "./src/tests/colourful.png"] {
+            for extra in [None, Some("--max-brightness"), Some("--min-brightness=60")] {
+                let mut args = vec![path, "--terminal-colours", "--no-palette", "--keep-neutrals"];
+                args.extend(extra);
+
+                let output = get_success(&args);
+                let lines: Vec<&str> = output.stdout.lines().collect();
+
+                assert_eq!(
+                    (lines[0], lines[7], lines[8], lines[15]),
+                    ("#000000", "#aaaaaa", "#555555", "#ffffff"),
+                    "{:?}",
+                    args
+                );
+            }
+        }
+
+        // The colours still come from the image.
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--no-palette", "--keep-neutrals"]);
+        let lines: Vec<&str> = output.stdout.lines().collect();
+
+        assert_eq!(lines[1..7], ["#aa0000", "#00aa00", "#808000", "#0000aa", "#aa00aa", "#00aaaa"]);
+
+        // It works with a custom palette too, which has the standard neutrals.
+        let output = get_success(&[
+            "./src/tests/dark_colours.png",
+            "--terminal-colours",
+            "--no-palette",
+            "--keep-neutrals",
+            "--terminal-palette=./src/tests/swapped_red_blue.toml",
+        ]);
+        let lines: Vec<&str> = output.stdout.lines().collect();
+
+        assert_eq!((lines[0], lines[7], lines[8], lines[15]), ("#000000", "#aaaaaa", "#555555", "#ffffff"));
+    }
+
+    #[test]
+    fn it_previews_the_terminal_colours() {
+        let plain = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1"]);
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--preview", "--color=always"]);
+
+        // The preview comes after the usual output, with a blank line
+        // in between.
+        let preview = output.stdout.strip_prefix(&plain.stdout).unwrap();
+        let lines: Vec<&str> = preview.lines().collect();
+
+        assert_eq!(lines.len(), 8, "{:?}", lines);
+        assert_eq!(lines[0], "");
+        assert_eq!(lines[1].matches("███").count(), 8);
+        assert_eq!(lines[2].matches("███").count(), 8);
+        assert_eq!(lines[3], "");
+        assert_eq!(lines[4], "\x1B[48;2;0;0;0m\x1B[38;2;170;170;170m The quick brown fox jumps over the lazy dog \x1B[0m");
+        assert_eq!(
+            lines[5],
+            "\x1B[48;2;0;0;0m\x1B[38;2;170;0;0m red\x1B[38;2;0;170;0m green\x1B[38;2;128;128;0m yellow\x1B[38;2;0;0;170m blue\x1B[38;2;170;0;170m magenta\x1B[38;2;0;170;170m cyan \x1B[0m"
+        );
+        assert!(lines[6].starts_with("\x1B[48;2;0;0;0m\x1B[1m\x1B[38;2;255;255;255m"), "{:?}", lines[6]);
+        assert!(lines[7].starts_with("\x1B[48;2;0;0;0m\x1B[1m\x1B[38;2;255;0;0m red"), "{:?}", lines[7]);
+
+        assert_eq!(output.stderr, "");
+    }
+
+    // The tests don't run in a terminal, so by default there's no preview.
+    #[test]
+    fn it_only_previews_the_terminal_colours_in_a_terminal() {
+        let plain = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours"]);
+
+        for args in [
+            vec!["--preview"],
+            vec!["--preview", "--color=never"],
+            vec!["--preview", "--color=always", "--no-palette"],
+        ] {
+            let mut args = args;
+            args.extend(["./src/tests/terminal_colours.png", "--terminal-colours"]);
+
+            let output = get_success(&args);
+
+            if args.contains(&"--no-palette") {
+                assert!(!output.stdout.contains("\x1B"), "{:?}", args);
+            } else {
+                assert_eq!(output.stdout, plain.stdout, "{:?}", args);
+            }
+        }
+    }
+
+    // We don't use the default target here, because if you run the tests
+    // in a terminal, that really would change its colours.  Under the tests,
+    // stdout is always a pipe.
+    #[test]
+    fn it_refuses_to_apply_the_terminal_colours_without_a_terminal() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--terminal-colours", "--apply", "--apply-target=1"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "/dev/fd/1: there's no terminal to apply the colours to; use --osc4 to print the escape sequences instead\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_the_apply_target_is_invalid() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--terminal-colours", "--apply", "--apply-target=stdout"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("must be `tty` or the number of a file descriptor"), "stderr = {:?}", output.stderr);
+
+        let output = get_failure(&["./src/tests/red.png", "--apply"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--terminal-colours"), "stderr = {:?}", output.stderr);
+    }
+
+    // --wal only prints the colours, so it would never get to send them.
+    #[test]
+    fn it_fails_if_you_apply_the_colours_with_wal() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--wal", "--terminal-colours", "--apply"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--wal"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_you_preview_without_terminal_colours() {
+        let output = get_failure(&["./src/tests/red.png", "--preview"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--terminal-colours"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_you_preview_with_another_format() {
+        for format in ["json", "xresources", "kitty", "css"] {
+            let output = get_failure(&["./src/tests/terminal_colours.png", "--terminal-colours", "--preview", "--color=always", "--format", format]);
+
+            assert_eq!(output.exit_code, 2);
+            assert_eq!(output.stdout, "");
+            assert!(output.stderr.contains("--preview only works with --format=text"), "{}: stderr = {:?}", format, output.stderr);
+        }
+    }
+
+    // The preview would end up in the middle of the JSON.
+    #[test]
+    fn it_fails_if_you_preview_with_wal() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--wal", "--terminal-colours", "--preview", "--color=always"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--wal"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_prints_the_slot_number_of_each_terminal_colour() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--no-palette", "--show-index"]);
+
+        let lines: Vec<&str> = output.stdout.lines().collect();
+
+        assert_eq!(lines.len(), 16, "stdout = {:?}", output.stdout);
+        assert_eq!(lines[0], "0 #000000");
+        assert_eq!(lines[9], "9 #ff0000");
+        assert_eq!(lines[15], "15 #ffffff");
+    }
+
+    #[test]
+    fn it_prints_osc4_sequences_for_the_terminal_colours() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--osc4"]);
+
+        let expected_output = "\
+\x1b]4;0;rgb:00/00/00\x1b\\\
+\x1b]4;1;rgb:aa/00/00\x1b\\\
+\x1b]4;2;rgb:00/aa/00\x1b\\\
+\x1b]4;3;rgb:80/80/00\x1b\\\
+\x1b]4;4;rgb:00/00/aa\x1b\\\
+\x1b]4;5;rgb:aa/00/aa\x1b\\\
+\x1b]4;6;rgb:00/aa/aa\x1b\\\
+\x1b]4;7;rgb:aa/aa/aa\x1b\\\
+\x1b]4;8;rgb:55/55/55\x1b\\\
+\x1b]4;9;rgb:ff/00/00\x1b\\\
+\x1b]4;10;rgb:00/ff/00\x1b\\\
+\x1b]4;11;rgb:ff/ff/00\x1b\\\
+\x1b]4;12;rgb:00/00/ff\x1b\\\
+\x1b]4;13;rgb:ff/00/ff\x1b\\\
+\x1b]4;14;rgb:00/ff/ff\x1b\\\
+\x1b]4;15;rgb:ff/ff/ff\x1b\\\
+";
+
+        assert_eq!(output.stdout, expected_output);
+        assert_eq!(output.stderr, "");
+    }
+
+    // This palette swaps the red and blue targets, so the blue cluster
+    // should end up in the red slot (1) and vice versa (4).
+    #[test]
+    fn it_matches_against_a_custom_terminal_palette() {
+        let default = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--no-palette"]);
+        let custom = get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--min-contrast=1",
+            "--no-palette",
+            "--terminal-palette=./src/tests/swapped_red_blue.palette",
+        ]);
+
+        let default: Vec<&str> = default.stdout.lines().collect();
+        let custom: Vec<&str> = custom.stdout.lines().collect();
+
+        assert_eq!((default[1], default[4]), ("#aa0000", "#0000aa"));
+        assert_eq!((custom[1], custom[4]), ("#0000aa", "#aa0000"));
+        assert_eq!(custom.len(), 16);
+    }
+
+    #[test]
+    fn it_matches_against_a_custom_terminal_palette_in_toml() {
+        let plain = get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--min-contrast=1",
+            "--no-palette",
+            "--terminal-palette=./src/tests/swapped_red_blue.palette",
+        ]);
+        let toml = get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--min-contrast=1",
+            "--no-palette",
+            "--terminal-palette=./src/tests/swapped_red_blue.toml",
+        ]);
+
+        let lines: Vec<&str> = toml.stdout.lines().collect();
+
+        assert_eq!((lines[1], lines[4]), ("#0000aa", "#aa0000"));
+        assert_eq!(toml.stdout, plain.stdout);
+    }
+
+    // This image is all mid greys, so the closest colour to every slot is
+    // a grey which is barely different from the background.
+    #[test]
+    fn it_makes_sure_the_terminal_colours_have_enough_contrast() {
+        let slots = |min_contrast: &str| -> Vec<palette::Srgb<u8>> {
+            get_success(&[
+                "./src/tests/low_contrast_greys.png",
+                "--terminal-colours",
+                "--no-palette",
+                &format!("--min-contrast={}", min_contrast),
+            ])
+            .stdout
+            .lines()
+            .map(|line| crate::parse_hex(line).unwrap())
+            .collect()
+        };
+
+        let ratio = |c: &[palette::Srgb<u8>], i: usize| crate::contrast::ratio(c[i], c[0]);
+
+        let unadjusted = slots("1");
+        assert!(ratio(&unadjusted, 7) < 1.5, "{:?}", unadjusted);
+
+        let default = slots("4.5");
+        assert_eq!(default[0], unadjusted[0]);
+        assert!(ratio(&default, 7) >= 4.5, "{:?}", default);
+        assert!(ratio(&default, 15) >= 4.5, "{:?}", default);
+        for i in 1..=6 {
+            assert!(ratio(&default, i) >= 3.0, "{}: {:?}", i, default);
+        }
+
+        let higher = slots("7");
+        assert!(ratio(&higher, 15) >= 7.0, "{:?}", higher);
+    }
+
+    #[test]
+    fn it_fails_if_the_min_contrast_is_out_of_range() {
+        let output = get_failure(&["./src/tests/red.png", "--terminal-colours", "--min-contrast=30"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("must be between 1 and 21"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_the_toml_terminal_palette_is_invalid() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--terminal-colours", "--terminal-palette=./Cargo.toml"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "./Cargo.toml, line 1: expected a slot like `red = \"#aa0000\"`, but found \"[package]\"\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_the_terminal_palette_is_invalid() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--terminal-colours", "--terminal-palette=./README.md"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, "");
+        assert_eq!(output.stderr, "./README.md, line 1: invalid hex colour \"# dominant_colours\"\n");
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_osc4_without_terminal_colours() {
+        let output = get_failure(&["./src/tests/red.png", "--osc4"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--terminal-colours"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_finds_red_in_every_colour_space() {
+        for space in ["lab", "oklab", "rgb", "srgb", "hsv"] {
+            let output = get_success(&["./src/tests/red.png", "--max-colours=1", "--no-palette", &format!("--space={}", space)]);
+            assert_eq!(output.stdout, "#ff0000\n", "space = {}", space);
+        }
+    }
+
+    #[test]
+    fn it_finds_different_colours_in_different_colour_spaces() {
+        let lab = get_success(&["./src/tests/noise.jpg", "--no-palette", "--space=lab"]);
+        let rgb = get_success(&["./src/tests/noise.jpg", "--no-palette", "--space=rgb"]);
+        let hsv = get_success(&["./src/tests/noise.jpg", "--no-palette", "--space=hsv"]);
+        let oklab = get_success(&["./src/tests/noise.jpg", "--no-palette", "--colorspace=oklab", "--seed=0"]);
+
+        for output in [&lab, &rgb, &hsv, &oklab] {
+            assert_eq!(output.stdout.lines().count(), 5, "stdout = {:?}", output.stdout);
+            assert!(output.stdout.lines().all(|line| line.len() == 7 && line.starts_with('#')));
+        }
+
+        assert_ne!(lab.stdout, rgb.stdout);
+        assert_ne!(lab.stdout, hsv.stdout);
+        assert_ne!(rgb.stdout, hsv.stdout);
+        assert_ne!(lab.stdout, oklab.stdout);
+        assert_ne!(rgb.stdout, oklab.stdout);
+    }
+
+    #[test]
+    fn it_maps_terminal_colours_after_clustering_in_rgb() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--no-palette", "--space=rgb"]);
+
+        assert_eq!(output.stdout.lines().count(), 16, "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_maps_the_ansi_terminal_colours_the_same_with_euclidean_distance() {
+        let default = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--no-palette"]);
+        let euclidean = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--no-palette", "--distance=euclidean"]);
+
+        assert_eq!(default.stdout, euclidean.stdout);
+    }
+
+    // The fixture has a desaturated teal, a dark yellow, a grey, a dark red
+    // and a dark blue.  In RGB, the teal is closest to bright blue (slot 12),
+    // because it's about as light; in Lab, the dark blue is, because it's
+    // the same hue.
+    #[test]
+    fn it_maps_ambiguous_colours_by_how_they_look() {
+        let slot_12 = |metric: &str| {
+            let output = get_success(&["./src/tests/ambiguous_terminal_colours.png", "--terminal-colours", "--no-palette", "--show-index", metric]);
+
+            output.stdout.lines().nth(12).unwrap().to_owned()
+        };
+
+        assert_eq!(slot_12("--mapping-metric=rgb"), "12 #5f8a8b");
+        assert_eq!(slot_12("--mapping-metric=lab"), "12 #2a2a8a");
+        assert_eq!(slot_12("--mapping-metric=ciede2000"), "12 #2a2a8a");
+    }
+
+    #[test]
+    fn it_generates_256_terminal_colours() {
+        let sixteen = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--no-palette"]);
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours=256", "--no-palette"]);
+
+        let lines: Vec<&str> = output.stdout.lines().collect();
+        assert_eq!(lines.len(), 256);
+
+        // The first 16 are the same as without =256.
+        assert_eq!(lines[..16], sixteen.stdout.lines().collect::<Vec<&str>>()[..]);
+
+        // The grey ramp goes from dark to light.
+        let luminance: Vec<f32> = lines[232..]
+            .iter()
+            .map(|line| crate::contrast::relative_luminance(crate::parse_hex(line).unwrap()))
+            .collect();
+
+        assert!(luminance.windows(2).all(|w| w[0] < w[1]), "luminance = {:?}", luminance);
+    }
+
+    #[test]
+    fn it_prints_osc4_sequences_for_256_terminal_colours() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours=256", "--osc4"]);
+
+        assert_eq!(output.stdout.matches("\x1b]4;").count(), 256);
+        assert!(output.stdout.contains("\x1b]4;255;rgb:"), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_still_reads_the_path_after_terminal_colours() {
+        let output = get_success(&["--terminal-colours", "./src/tests/terminal_colours.png", "--no-palette"]);
+
+        assert_eq!(output.stdout.lines().count(), 16);
+    }
+
+    #[test]
+    fn it_fails_if_you_ask_for_256_colours_in_a_16_colour_format() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--terminal-colours=256", "--format=windows-terminal"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("which only have 16 colours"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_generates_shell_completions() {
+        let output = get_success(&["completions", "zsh"]);
+
+        assert!(output.stdout.contains("--terminal-colours"), "stdout = {:?}", output.stdout);
+        assert!(output.stdout.contains("--max-colours"), "stdout = {:?}", output.stdout);
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_still_requires_a_path() {
+        let output = get_failure(&["--max-colours=3"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+        assert!(output.stderr.contains("<PATH>"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_finds_an_image_completely_similar_to_itself() {
+        let output = get_success(&["./src/tests/red.png", "--compare=./src/tests/red.png", "--no-palette"]);
+
+        assert!(output.stdout.ends_with("similarity: 1.000\n"), "stdout = {:?}", output.stdout);
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_finds_red_and_blue_not_similar() {
+        let output = get_success(&["./src/tests/red.png", "--compare=./src/tests/blue.png", "--no-palette", "--space=rgb"]);
+
+        assert_eq!(output.stdout, "#ff0000 #0000ff 176.31\nsimilarity: 0.000\n");
+    }
+
+    #[test]
+    fn it_exits_with_3_if_the_images_are_not_similar_enough() {
+        let output = get_success(&["./src/tests/red.png", "--compare=./src/tests/red.png", "--threshold=0.9"]);
+        assert_eq!(output.exit_code, 0);
+
+        let output = get_failure(&["./src/tests/red.png", "--compare=./src/tests/blue.png", "--threshold=0.9"]);
+        assert_eq!(output.exit_code, 4);
+        assert!(output.stdout.ends_with("similarity: 0.000\n"), "stdout = {:?}", output.stdout);
+    }
+
+    // This compares to a checked-in copy of the JSON, but the wallpaper
+    // is an absolute path which depends on where the repo is checked out.
+    #[test]
+    fn it_prints_pywal_json() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--wal", "--min-contrast=1"]);
+
+        let wallpaper = std::fs::canonicalize("./src/tests/terminal_colours.png").unwrap();
+        let json = output.stdout.replace(wallpaper.to_str().unwrap(), "<wallpaper>");
+
+        assert_eq!(json, std::fs::read_to_string("./src/tests/terminal_colours.wal.json").unwrap());
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_prints_the_terminal_colours_as_xresources() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--format=xresources"]);
+
+        assert_eq!(output.stdout, std::fs::read_to_string("./src/tests/terminal_colours.Xresources").unwrap());
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn it_scopes_the_xresources_to_a_program() {
+        let output = get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--format=xresources",
+            "--prefix=URxvt",
+        ]);
+
+        assert!(output.stdout.starts_with("URxvt.foreground: #ffffff\n"), "stdout = {:?}", output.stdout);
+        assert!(output.stdout.lines().all(|line| line.starts_with("URxvt.")), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_prints_the_terminal_colours_as_an_alacritty_scheme() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--format=alacritty"]);
+
+        assert_eq!(output.stdout, std::fs::read_to_string("./src/tests/terminal_colours.alacritty.toml").unwrap());
+
+        let toml: toml::Table = output.stdout.parse().unwrap();
+        assert_eq!(toml["colors"]["normal"].as_table().unwrap().len(), 8);
+        assert_eq!(toml["colors"]["bright"].as_table().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn it_swaps_the_alacritty_background_for_a_light_theme() {
+        let output = get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--format=alacritty",
+            "--light-theme",
+        ]);
+
+        assert!(
+            output.stdout.starts_with("[colors.primary]\nbackground = \"#ffffff\"\nforeground = \"#000000\"\n"),
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    #[test]
+    fn it_prints_the_terminal_colours_as_a_kitty_theme() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--format=kitty"]);
+
+        assert_eq!(output.stdout, std::fs::read_to_string("./src/tests/terminal_colours.kitty.conf").unwrap());
+        assert!(!output.stdout.contains('\x1b'), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_uses_the_kitty_cursor_and_selection_colours_you_pick() {
+        let output = get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--format=kitty",
+            "--cursor-colour=#ff8800",
+            "--selection-colour=44475a",
+        ]);
+
+        assert!(output.stdout.contains("\ncursor #ff8800\nselection_background #44475a\n"), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_prints_the_terminal_colours_as_a_windows_terminal_scheme() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--format=windows-terminal"]);
+
+        let scheme: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        assert_eq!(scheme["name"], "terminal_colours");
+
+        let keys = [
+            "cursorColor", "selectionBackground", "background", "foreground",
+            "black", "red", "green", "yellow", "blue", "purple", "cyan", "white",
+            "brightBlack", "brightRed", "brightGreen", "brightYellow", "brightBlue", "brightPurple", "brightCyan", "brightWhite",
+        ];
+
+        for key in keys {
+            let value = scheme[key].as_str().unwrap_or_else(|| panic!("missing {}: {}", key, scheme));
+            let is_hex = value.len() == 7 && value.starts_with('#') && value[1..].chars().all(|c| c.is_ascii_hexdigit());
+            assert!(is_hex, "{} = {:?}", key, value);
+        }
+
+        assert_eq!(scheme["purple"], "#aa00aa");
+        assert_eq!(scheme["brightWhite"], "#ffffff");
+    }
+
+    #[test]
+    fn it_names_the_windows_terminal_scheme() {
+        let output = get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--format=windows-terminal",
+            "--scheme-name=Sunset",
+        ]);
+
+        assert!(output.stdout.starts_with("{\n    \"name\": \"Sunset\",\n"), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_writes_the_terminal_colours_as_an_itermcolors_file() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}.itermcolors", std::process::id()));
+
+        get_success(&[
+            "./src/tests/terminal_colours.png",
+            "--terminal-colours",
+            "--min-contrast=1",
+            "--format=itermcolors",
+            "--output-file",
+            out_path.to_str().unwrap(),
+        ]);
+
+        let preset = plist::Value::from_file(&out_path).unwrap();
+        std::fs::remove_file(&out_path).unwrap();
+
+        let preset = preset.as_dictionary().unwrap();
+        let component = |colour: &str, name: &str| {
+            let value = preset[colour].as_dictionary().unwrap()[name].as_real().unwrap();
+            (value * 255.0).round() as u8
+        };
+
+        // Slot 1 of terminal_colours.png is #aa0000.
+        assert_eq!(
+            (component("Ansi 1 Color", "Red Component"), component("Ansi 1 Color", "Green Component"), component("Ansi 1 Color", "Blue Component")),
+            (0xaa, 0x00, 0x00)
+        );
+
+        for name in ["Background Color", "Foreground Color", "Cursor Color", "Ansi 15 Color"] {
+            assert!(preset.contains_key(name), "missing {}", name);
+        }
+    }
+
+    #[test]
+    fn it_prints_the_terminal_colours_as_a_wezterm_scheme() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--min-contrast=1", "--format=wezterm"]);
+
+        assert_eq!(output.stdout, std::fs::read_to_string("./src/tests/terminal_colours.wezterm.toml").unwrap());
+
+        let toml: toml::Table = output.stdout.parse().unwrap();
+        assert_eq!(toml["colors"]["ansi"].as_array().unwrap().len(), 8);
+        assert_eq!(toml["colors"]["brights"].as_array().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn it_prints_variables_you_can_eval_in_sh() {
+        let output = get_success(&["./src/tests/red.png", "--format=sh"]);
+
+        assert_eq!(output.stdout, "DOMINANT_COLOUR_1='#ff0000'\nDOMINANT_COLOUR_1_R=255\nDOMINANT_COLOUR_1_G=0\nDOMINANT_COLOUR_1_B=0\n");
+        assert_eq!(eval_in("sh", &output.stdout, "echo \"$DOMINANT_COLOUR_1 $DOMINANT_COLOUR_1_R\""), "#ff0000 255\n");
+    }
+
+    #[test]
+    fn it_prints_an_array_you_can_eval_in_bash() {
+        let output = get_success(&["./src/tests/red.png", "--format=bash", "--var-prefix=WALLPAPER"]);
+
+        assert!(output.stdout.ends_with("\nWALLPAPERS=('#ff0000')\n"), "stdout = {:?}", output.stdout);
+        assert_eq!(eval_in("bash", &output.stdout, "echo \"${WALLPAPERS[0]} $WALLPAPER_1_G\""), "#ff0000 0\n");
+    }
+
+    // Run `script` through `eval` in this shell, then run `command` and
+    // return what it prints.
+    fn eval_in(shell: &str, script: &str, command: &str) -> String {
+        let output = std::process::Command::new(shell)
+            .args(["-c", &format!("eval \"$1\"; {}", command), shell, script])
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "stderr = {:?}", str::from_utf8(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn it_prints_a_base16_scheme() {
+        let output = get_success(&["./src/tests/terminal_colours.png", "--format=base16"]);
+
+        assert_eq!(output.stdout, std::fs::read_to_string("./src/tests/terminal_colours.base16.yaml").unwrap());
+
+        let yaml: serde_yaml::Mapping = serde_yaml::from_str(&output.stdout).unwrap();
+        assert_eq!(yaml["scheme"], "terminal_colours");
+        assert_eq!(yaml.len(), 18);
+
+        for i in 0..16 {
+            let value = yaml[format!("base{:02X}", i).as_str()].as_str().unwrap();
+            assert!(value.len() == 6 && value.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')), "base{:02X} = {:?}", i, value);
+        }
+    }
+
+    #[test]
+    fn it_prints_tmux_options() {
+        let output = get_success(&["./src/tests/red.png", "--format=tmux"]);
+
+        assert_eq!(
+            output.stdout,
+            "set -g status-style \"bg=#ff0000,fg=#000000\"\n\
+             set -g pane-border-style \"fg=#ff0000\"\n\
+             set -g pane-active-border-style \"fg=#000000\"\n\
+             set -g message-style \"bg=#000000,fg=#ffffff\"\n\
+             set -g window-status-current-style \"bg=#000000,fg=#ffffff\"\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_the_tmux_accent_index_is_out_of_range() {
+        let output = get_failure(&["./src/tests/red.png", "--format=tmux", "--accent-index=2"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.starts_with("error: --accent-index=2, but only found 1 colour(s)\n"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_you_ask_for_xresources_without_terminal_colours() {
+        let output = get_failure(&["./src/tests/terminal_colours.png", "--format=xresources"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--terminal-colours"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_writes_the_output_to_a_file() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}.json", std::process::id()));
+
+        let output = get_success(&["./src/tests/terminal_colours.png", "--wal", "--output", out_path.to_str().unwrap()]);
+        assert_eq!(output.stdout, "");
+
+        let json = std::fs::read_to_string(&out_path).unwrap();
+        std::fs::remove_file(&out_path).unwrap();
+
+        assert!(json.contains("\"color15\": \"#ffffff\"\n"), "json = {:?}", json);
+    }
+
+    // There's no terminal to draw the swatches in, so the file should be
+    // the same as the output with --no-palette.
+    #[test]
+    fn it_writes_the_output_to_a_file_without_escape_codes() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}_palette.txt", std::process::id()));
+
+        let output = get_success(&["./src/tests/noise.jpg", "--output-file", out_path.to_str().unwrap()]);
+        assert_eq!(output.stdout, "");
+
+        let written = std::fs::read_to_string(&out_path).unwrap();
+        std::fs::remove_file(&out_path).unwrap();
+
+        let expected = get_success(&["./src/tests/noise.jpg", "--no-palette"]);
+        assert_eq!(written, expected.stdout);
+    }
+
+    // /dev/stdout is a symlink to whatever stdout is (here, a pipe), so
+    // we have to write to it rather than replace it.
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn it_writes_the_output_to_dev_stdout() {
+        let output = get_success(&["./src/tests/red.png", "--output-file", "/dev/stdout"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_writes_the_output_through_a_symlink() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_symlink_output_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (target, link) = (dir.join("colours.txt"), dir.join("link.txt"));
+        std::fs::write(&target, "").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        get_success(&["./src/tests/red.png", "--output-file", link.to_str().unwrap()]);
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "#ff0000\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_writes_an_html_page() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}_palette.html", std::process::id()));
+
+        get_success(&["./src/tests/red_and_blue.png", "--format=html", "--seed=7", "--output-file", out_path.to_str().unwrap()]);
+
+        let html = std::fs::read_to_string(&out_path).unwrap();
+        std::fs::remove_file(&out_path).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>\n"), "html = {}", html);
+        assert!(html.contains("<div class=\"swatch\" style=\"background: #ff0000; color: #000000\">\n<span class=\"hex\">#ff0000</span>\n"), "html = {}", html);
+        assert!(html.contains("<div class=\"swatch\" style=\"background: #0000ff; color: #ffffff\">\n<span class=\"hex\">#0000ff</span>\n"), "html = {}", html);
+        assert!(html.contains("<span>50.0%</span>"), "html = {}", html);
+        assert!(html.contains("red_and_blue.png, with seed 7 and max colours 5.</footer>"), "html = {}", html);
+        assert!(!html.contains("http"), "html = {}", html);
+    }
+
+    #[test]
+    fn it_wont_replace_a_file_with_no_clobber() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}_clobber.txt", std::process::id()));
+        std::fs::write(&out_path, "existing").unwrap();
+
+        let output = get_failure(&["./src/tests/red.png", "--output-file", out_path.to_str().unwrap(), "--no-clobber"]);
+
+        let contents = std::fs::read_to_string(&out_path).unwrap();
+        std::fs::remove_file(&out_path).unwrap();
+
+        assert_eq!(output.exit_code, 1);
+        assert!(output.stderr.contains("this file already exists"), "stderr = {:?}", output.stderr);
+        assert_eq!(contents, "existing");
+    }
+
+    #[test]
+    fn it_exports_a_gimp_palette() {
+        let output = get_success(&["./src/tests/red.png", "--space=rgb", "--export=gpl"]);
+
+        assert_eq!(output.stdout, "GIMP Palette\nName: red\nColumns: 1\n#\n255   0   0\t#ff0000\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    // This is a snapshot of the whole file, because GIMP is picky about
+    // the whitespace.
+    #[test]
+    fn it_exports_a_gimp_palette_with_format_gpl() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}.gpl", std::process::id()));
+
+        let output = get_success(&[
+            "./src/tests/red_and_blue.png",
+            "--format=gpl",
+            "--palette-name=Brand colours",
+            "--output-file",
+            out_path.to_str().unwrap(),
+        ]);
+        assert_eq!(output.stdout, "");
+
+        let gpl = std::fs::read_to_string(&out_path).unwrap();
+        std::fs::remove_file(&out_path).unwrap();
+
+        assert_eq!(
+            gpl,
+            "GIMP Palette\nName: Brand colours\nColumns: 2\n#\n  0   0 255\t#0000ff\n255   0   0\t#ff0000\n"
+        );
+    }
+
+    #[test]
+    fn it_uses_a_default_palette_name_for_stdin() {
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd
+            .args(["-", "--format=gpl"])
+            .write_stdin(std::fs::read("./src/tests/red.png").unwrap())
+            .unwrap();
+
+        assert!(
+            str::from_utf8(&output.stdout).unwrap().starts_with("GIMP Palette\nName: dominant_colours\n"),
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    #[test]
+    fn it_exports_an_ase_palette_to_a_file() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}.ase", std::process::id()));
+
+        let output = get_success(&["./src/tests/red.png", "--export=ase", "--output", out_path.to_str().unwrap()]);
+        assert_eq!(output.stdout, "");
+
+        let ase = std::fs::read(&out_path).unwrap();
+        std::fs::remove_file(&out_path).unwrap();
+
+        assert!(ase.starts_with(b"ASEF"), "ase = {:?}", ase);
+    }
+
+    // We won't print an ASE palette to a terminal, but we can pipe it
+    // somewhere (as we do in tests).
+    #[test]
+    fn it_pipes_an_ase_palette_to_stdout() {
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd
+            .args(["./src/tests/red.png", "--format=ase", "--swatch-name=cover-{index}"])
+            .unwrap();
+
+        assert!(output.stdout.starts_with(b"ASEF"), "stdout = {:?}", output.stdout);
+
+        let name: Vec<u8> = "cover-1".encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect();
+        assert!(output.stdout.windows(name.len()).any(|w| w == name), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_rejects_an_unknown_swatch_name_placeholder() {
+        let output = get_failure(&["./src/tests/red.png", "--format=ase", "--swatch-name=cover-{n}"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("can only use the placeholders {index} and {hex}"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_looks_at_every_image_in_a_directory() {
+        let output = get_success(&["./src/tests", "--max-colours=1", "--no-palette"]);
+
+        for fixture in ["red.png", "noise.jpg", "green.tiff", "garlic.gif", "favicon.ico"] {
+            let prefix = format!("./src/tests/{}: #", fixture);
+            assert!(output.stdout.contains(&prefix), "{} missing from stdout = {:?}", fixture, output.stdout);
+        }
+
+        assert!(!output.stdout.contains("malformed.txt.png"), "stdout = {:?}", output.stdout);
+        assert!(output.stderr.contains("file(s) which aren't images\n"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_groups_the_colours_for_each_image() {
+        let output = get_success(&["./src/tests", "--max-colours=1"]);
+
+        assert!(
+            output.stdout.contains("./src/tests/green.tiff\n\u{1b}[38;2;"),
+            "stdout = {:?}",
+            output.stdout
+        );
+    }
+
+    #[test]
+    fn it_prints_a_directory_as_json() {
+        let output = get_success(&["./src/tests", "--max-colours=1", "--space=rgb", "--json"]);
+
+        assert!(output.stdout.starts_with("{\n"), "stdout = {:?}", output.stdout);
+        assert!(output.stdout.contains("    \"./src/tests/red.png\": [\n        \"#ff0000\"\n    ],\n"), "stdout = {:?}", output.stdout);
+        assert!(output.stdout.ends_with("\n}\n"), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_prints_a_single_image_as_json() {
+        let output = get_success(&["./src/tests/red.png", "--space=rgb", "--json"]);
+
+        assert_eq!(output.stdout, "{\n    \"./src/tests/red.png\": [\n        \"#ff0000\"\n    ]\n}\n");
+    }
+
+    // Check a JSON object from --format=json has the path, the seed, and
+    // each colour as a hex string and matching RGB values.
+    fn assert_is_json_result(result: &serde_json::Value, path: &str, seed: u64) {
+        assert_eq!(result["path"], path, "result = {}", result);
+        assert_eq!(result["seed"], seed, "result = {}", result);
+
+        let colours = result["colours"].as_array().unwrap();
+        assert!(!colours.is_empty(), "result = {}", result);
+
+        for colour in colours {
+            let hex = colour["hex"].as_str().unwrap();
+            let rgb: Vec<u64> = colour["rgb"].as_array().unwrap().iter().map(|c| c.as_u64().unwrap()).collect();
+
+            assert_eq!(hex, format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]), "result = {}", result);
+        }
+    }
+
+    #[test]
+    fn it_prints_a_json_document() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--format=json", "--seed=3"]);
+
+        let result: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        assert_is_json_result(&result, "./src/tests/red_and_blue.png", 3);
+
+        let hexes: Vec<&str> = result["colours"].as_array().unwrap().iter().map(|c| c["hex"].as_str().unwrap()).collect();
+        assert_eq!(hexes.len(), 2);
+        assert!(hexes.contains(&"#ff0000"), "hexes = {:?}", hexes);
+        assert!(hexes.contains(&"#0000ff"), "hexes = {:?}", hexes);
+        assert_eq!(result["colours"][0]["rgb"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn it_includes_the_proportion_of_each_colour_in_json() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--format=json"]);
+
+        let result: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+
+        for colour in result["colours"].as_array().unwrap() {
+            let proportion = colour["proportion"].as_f64().unwrap();
+            assert!((proportion - 0.5).abs() < 0.01, "result = {}", result);
+        }
+    }
+
+    #[test]
+    fn it_prints_a_directory_as_a_json_document() {
+        let output = get_success(&["./src/tests", "--pattern", "red*.png", "--max-colours=1", "--format=json"]);
+
+        let results: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+        let results = results.as_array().unwrap();
+
+        assert!(results.len() >= 2, "stdout = {:?}", output.stdout);
+        assert_is_json_result(&results[0], results[0]["path"].as_str().unwrap(), 0);
+        assert!(results.iter().any(|r| r["path"] == "./src/tests/red.png"), "stdout = {:?}", output.stdout);
+    }
+
+    // Parse the percentages from the output of --show-percentage.
+    fn get_percentages(stdout: &str) -> Vec<f32> {
+        stdout
+            .lines()
+            .map(|line| line.rsplit(' ').next().unwrap().trim_end_matches('%').parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn it_shows_the_percentage_of_each_colour() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--show-percentage", "--no-palette"]);
+
+        let percentages = get_percentages(&output.stdout);
+        assert_eq!(percentages.len(), 2);
+        assert!(percentages.iter().all(|p| (p - 50.0).abs() < 1.0), "stdout = {:?}", output.stdout);
+
+        let output = get_success(&["./src/tests/large_blue_and_red.png", "--show-percentage", "--no-palette"]);
+        assert_eq!(output.stdout, "#0000ff 75.0%\n#ff0000 25.0%\n");
+
+        let output = get_success(&["./src/tests/red.png", "--show-percentage"]);
+        assert_eq!(output.stdout, "\u{1b}[38;2;255;0;0m▇ #ff0000 100.0%\u{1b}[0m\n");
+    }
+
+    // The transparent pixels around the red circle aren't part of the
+    // image, so the red is all of it.
+    #[test]
+    fn it_leaves_transparent_pixels_out_of_the_percentage() {
+        let output = get_success(&["./src/tests/red_circle.png", "--show-percentage", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff0000 100.0%\n");
+    }
+
+    #[test]
+    fn it_shows_percentages_that_add_up_to_100() {
+        let output = get_success(&["./src/tests/noise.jpg", "--max-colours=7", "--show-percentage", "--no-palette"]);
+
+        let percentages = get_percentages(&output.stdout);
+        let total: f32 = percentages.iter().sum();
+
+        assert_eq!(percentages.len(), 7);
+        assert!((total - 100.0).abs() < 0.5, "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_fails_if_you_show_percentages_for_terminal_colours() {
+        let output = get_failure(&["./src/tests/red.png", "--show-percentage", "--terminal-colours"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("cannot be used with"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_prints_the_colours_with_a_template() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--template", "{index}. {hex} {HEX} {r},{g},{b} {percent}"]);
+
+        assert_eq!(output.stdout, "1. #0000ff #0000FF 0,0,255 50.0\n2. #ff0000 #FF0000 255,0,0 50.0\n");
+        assert_eq!(output.stderr, "");
+
+        let output = get_success(&["./src/tests/red.png", "--template", "{{\"h\": {h}, \"s\": {s}, \"l\": {l}}}"]);
+        assert_eq!(output.stdout, "{\"h\": 0.0, \"s\": 100, \"l\": 50}\n");
+    }
+
+    #[test]
+    fn it_prints_a_directory_with_a_template() {
+        let output = get_success(&["./src/tests", "--pattern", "red.png", "--template", "{path} {hex}"]);
+
+        assert_eq!(output.stdout, "./src/tests/red.png #ff0000\n");
+    }
+
+    #[test]
+    fn it_fails_if_the_template_has_an_unknown_placeholder() {
+        let output = get_failure(&["./src/tests/red.png", "--template", "{hex} {red}"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("unknown placeholder {red}; the placeholders are {hex}, {HEX}"), "stderr = {:?}", output.stderr);
+        assert_eq!(output.stdout, "");
+    }
+
+    #[test]
+    fn it_prints_a_csv_table() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--format=csv"]);
+
+        let mut reader = csv::Reader::from_reader(output.stdout.as_bytes());
+        assert_eq!(reader.headers().unwrap(), vec!["path", "index", "hex", "r", "g", "b", "percentage"]);
+
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], vec!["./src/tests/red_and_blue.png", "1", "#0000ff", "0", "0", "255", "50.0"]);
+        assert_eq!(rows[1], vec!["./src/tests/red_and_blue.png", "2", "#ff0000", "255", "0", "0", "50.0"]);
+    }
+
+    #[test]
+    fn it_prints_a_tsv_table_without_a_header() {
+        let output = get_success(&["./src/tests/red.png", "--format=tsv", "--no-header"]);
+
+        assert_eq!(output.stdout, "./src/tests/red.png\t1\t#ff0000\t255\t0\t0\t100.0\n");
+    }
+
+    // A path with a comma has to be quoted, and errors for other files
+    // go to stderr rather than into the table.
+    #[test]
+    fn it_prints_a_csv_table_for_a_directory() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_{}_csv", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("./src/tests/red.png", dir.join("red, dark.png")).unwrap();
+        let red = std::fs::read("./src/tests/red.png").unwrap();
+        std::fs::write(dir.join("truncated.png"), &red[..red.len() / 2]).unwrap();
+
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd.args([dir.to_str().unwrap(), "--format=csv"]).unwrap_err().as_output().unwrap().to_owned();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let stdout = str::from_utf8(&output.stdout).unwrap();
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+
+        let mut reader = csv::Reader::from_reader(stdout.as_bytes());
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+
+        assert_eq!(rows.len(), 1, "stdout = {:?}", stdout);
+        assert_eq!(&rows[0][0], dir.join("red, dark.png").to_str().unwrap());
+        assert_eq!(&rows[0][2], "#ff0000");
+
+        assert!(stderr.contains("truncated.png"), "stderr = {:?}", stderr);
+        assert!(!stdout.contains("truncated"), "stdout = {:?}", stdout);
+    }
+
+    #[test]
+    fn it_prints_rgb_values() {
+        let output = get_success(&["./src/tests/red.png", "--format=rgb", "--no-palette"]);
+        assert_eq!(output.stdout, "rgb(255, 0, 0)\n");
+
+        let output = get_success(&["./src/tests/red.png", "--format=rgb"]);
+        assert_eq!(output.stdout, "\u{1b}[38;2;255;0;0m▇ rgb(255, 0, 0)\u{1b}[0m\n");
+    }
+
+    #[test]
+    fn it_prints_hsl_values() {
+        let output = get_success(&["./src/tests/red.png", "--format=hsl", "--no-palette"]);
+        assert_eq!(output.stdout, "hsl(0.0, 100%, 50%)\n");
+
+        let output = get_success(&["./src/tests/red.png", "--format=hsl"]);
+        assert_eq!(output.stdout, "\u{1b}[38;2;255;0;0m▇ hsl(0.0, 100%, 50%)\u{1b}[0m\n");
+    }
+
+    #[test]
+    fn it_saves_a_swatch_image() {
+        let out_path = std::env::temp_dir().join(format!("dominant_colours_{}_swatch.png", std::process::id()));
+
+        let output = get_success(&[
+            "./src/tests/red_and_blue.png",
+            "--no-palette",
+            "--swatch-block-size=10",
+            "--save-swatch",
+            out_path.to_str().unwrap(),
+        ]);
+
+        let swatch = image::open(&out_path).unwrap().to_rgb8();
+        std::fs::remove_file(&out_path).unwrap();
+
+        assert_eq!(swatch.dimensions(), (20, 10));
+
+        // The swatch is drawn in the same order as the colours we print.
+        let printed: Vec<&str> = output.stdout.lines().collect();
+        let drawn: Vec<String> = [5, 15]
+            .iter()
+            .map(|x| {
+                let [r, g, b] = swatch.get_pixel(*x, 5).0;
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            })
+            .collect();
+
+        assert_eq!(printed, drawn);
+    }
+
+    #[test]
+    fn it_explains_if_the_swatch_directory_does_not_exist() {
+        let output = get_failure(&["./src/tests/red.png", "--save-swatch", "./does/not/exist/swatch.png"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(
+            output.stderr,
+            "./does/not/exist/swatch.png: couldn't save the swatch, because the directory ./does/not/exist doesn't exist\n"
+        );
+        assert_eq!(output.stdout, "");
+    }
+
+    #[test]
+    fn it_prints_css_custom_properties() {
+        let output = get_success(&["./src/tests/red.png", "--format=css"]);
+
+        assert_eq!(output.stdout, ":root {\n    --dominant-colour-1: #ff0000;\n}\n");
+    }
+
+    #[test]
+    fn it_prints_css_with_a_custom_prefix_and_selector() {
+        let output = get_success(&[
+            "./src/tests/red.png",
+            "--format=css",
+            "--css-prefix=brand",
+            "--css-selector=.theme-dark",
+        ]);
+
+        assert_eq!(output.stdout, ".theme-dark {\n    --brand-1: #ff0000;\n}\n");
+    }
+
+    #[test]
+    fn it_rejects_a_bad_css_prefix() {
+        let output = get_failure(&["./src/tests/red.png", "--format=css", "--css-prefix=my brand"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("only use letters, numbers, hyphens and underscores"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_prints_scss_variables() {
+        let output = get_success(&["./src/tests/red.png", "--format=scss"]);
+
+        assert_eq!(output.stdout, "$dominant-colour-1: #ff0000;\n");
+    }
+
+    #[test]
+    fn it_prints_scss_variables_and_a_map() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--format=scss", "--scss-map", "--css-prefix=brand"]);
+
+        assert_eq!(
+            output.stdout,
+            "$brand-1: #0000ff;\n$brand-2: #ff0000;\n\n$brands: (1: #0000ff, 2: #ff0000);\n"
+        );
+    }
+
+    #[test]
+    fn it_prints_a_css_gradient() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--format=css-gradient"]);
+
+        assert_eq!(output.stdout, "background: linear-gradient(90deg, #0000ff 0% 50%, #ff0000 50% 100%);\n");
+    }
+
+    #[test]
+    fn it_prints_a_css_gradient_with_even_stops_and_an_angle() {
+        let output = get_success(&[
+            "./src/tests/colourful.png",
+            "--max-colours=4",
+            "--format=css-gradient",
+            "--gradient-even",
+            "--gradient-angle=-45deg",
+        ]);
+
+        let stops: Vec<&str> = output.stdout.split(", ").skip(1).map(|stop| stop.split_once(' ').unwrap().1).collect();
+
+        assert!(output.stdout.starts_with("background: linear-gradient(-45deg, #"), "stdout = {:?}", output.stdout);
+        assert_eq!(stops, vec!["0% 25%", "25% 50%", "50% 75%", "75% 100%);\n"]);
+    }
+
+    #[test]
+    fn it_rejects_a_bad_gradient_angle() {
+        let output = get_failure(&["./src/tests/red.png", "--format=css-gradient", "--gradient-angle=left"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("should be an angle in degrees"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_rejects_an_scss_prefix_which_starts_with_a_digit() {
+        let output = get_failure(&["./src/tests/red.png", "--format=scss", "--css-prefix=1brand"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("should start with a letter or underscore"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_doesnt_mix_the_json_formats() {
+        let output = get_failure(&["./src/tests/red.png", "--format=json", "--json"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("cannot be used with"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_only_looks_at_files_matching_the_pattern() {
+        let output = get_success(&["./src/tests", "--pattern", "red.*", "--space=rgb", "--no-palette"]);
+
+        assert_eq!(output.stdout, "./src/tests/red.png: #ff0000\n");
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_an_invalid_pattern() {
+        let output = get_failure(&["./src/tests", "--pattern", "[*.png"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("Invalid value '[*.png' for '--pattern <GLOB>'"), "stderr = {:?}", output.stderr);
+    }
+
+    // An XML declaration doesn't make a file an SVG -- lots of other files
+    // are XML too.
+    #[test]
+    fn it_skips_xml_files_which_arent_svgs() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_xml_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::copy("./src/tests/red.png", dir.join("red.png")).unwrap();
+        std::fs::write(dir.join("a.xml"), "<?xml version=\"1.0\"?>\n<plist version=\"1.0\"><dict/></plist>\n").unwrap();
+        std::fs::write(
+            dir.join("b.svg"),
+            "<?xml version=\"1.0\"?>\n<!-- drawn by hand -->\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"10\"><rect width=\"10\" height=\"10\" fill=\"#0000ff\"/></svg>\n",
+        )
+        .unwrap();
+
+        let output = get_success(&[dir.to_str().unwrap(), "--no-palette", "--max-colours=1"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut lines: Vec<&str> = output.stdout.lines().map(|line| line.rsplit(' ').next().unwrap()).collect();
+        lines.sort();
+
+        assert_eq!(lines, vec!["#0000ff", "#ff0000"], "stdout = {:?}", output.stdout);
+        assert_eq!(output.stderr, "skipped 1 file(s) which aren't images\n");
+    }
+
+    #[test]
+    fn it_skips_hidden_directories_unless_asked() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_hidden_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(".thumbnails")).unwrap();
+
+        std::fs::copy("./src/tests/red.png", dir.join("red.png")).unwrap();
+        std::fs::copy("./src/tests/red.png", dir.join(".thumbnails/red.png")).unwrap();
+
+        let output = get_success(&[dir.to_str().unwrap(), "--recursive", "--no-palette"]);
+        let with_hidden = get_success(&[dir.to_str().unwrap(), "--recursive", "--hidden", "--no-palette"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output.stdout.lines().count(), 1, "stdout = {:?}", output.stdout);
+        assert_eq!(with_hidden.stdout.lines().count(), 2, "stdout = {:?}", with_hidden.stdout);
+    }
+
+    // We don't follow symlinks to directories, so a symlink loop can't
+    // keep us walking forever.
+    #[test]
+    #[cfg(unix)]
+    fn it_doesnt_get_stuck_in_a_symlink_loop() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_loop_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::copy("./src/tests/red.png", dir.join("red.png")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("loop")).unwrap();
+
+        let output = get_success(&[dir.to_str().unwrap(), "--recursive", "--no-palette"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output.stdout.lines().count(), 1, "stdout = {:?}", output.stdout);
+    }
+
+    // red.png is 32x32, which we usually scale up to 400x400.
+    #[test]
+    fn it_resizes_the_image_to_the_max_dimension() {
+        let output = get_success(&["./src/tests/red.png", "--max-dimension=10", "--verbose"]);
+        assert!(output.stderr.starts_with("pixels: 100\n"), "stderr = {:?}", output.stderr);
+
+        let output = get_success(&["./src/tests/red.png", "--no-resize", "--verbose"]);
+        assert!(output.stderr.starts_with("pixels: 1024\n"), "stderr = {:?}", output.stderr);
+    }
+
+    // This is a 4000x3000 image: the left quarter is red, and the rest
+    // is blue.  If we looked at every pixel this would take minutes in
+    // a debug build.
+    #[test]
+    fn it_finds_the_colours_of_a_large_image_quickly() {
+        let start = std::time::Instant::now();
+        let output = get_success(&["./src/tests/large_blue_and_red.png", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#0000ff\n#ff0000\n");
+        assert!(start.elapsed().as_secs() < 30, "took {:?}", start.elapsed());
+    }
+
+    // The left half of this image is red, and the right half is blue.
+    #[test]
+    fn it_only_looks_at_the_cropped_region() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--crop", "0,0,10,20", "--no-palette"]);
+        assert_eq!(output.stdout, "#ff0000\n");
+
+        let output = get_success(&["./src/tests/red_and_blue.png", "--crop", "50%,0%,50%,100%", "--no-palette"]);
+        assert_eq!(output.stdout, "#0000ff\n");
+    }
+
+    #[test]
+    fn it_clamps_a_crop_outside_the_image() {
+        let output = get_success(&["./src/tests/red_and_blue.png", "--crop", "15,0,100,100", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#0000ff\n");
+        assert_eq!(
+            output.stderr,
+            "warning: ./src/tests/red_and_blue.png: --crop goes outside the image, so only looking at the part inside it (15,0,5,20)\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_the_crop_leaves_no_pixels() {
+        let output = get_failure(&["./src/tests/red_and_blue.png", "--crop", "20,0,10,10"]);
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(
+            output.stderr,
+            "./src/tests/red_and_blue.png: --crop is outside the image, which is only 20x20 pixels\n"
+        );
+    }
+
+    #[test]
+    fn it_reads_a_list_of_files() {
+        let list_path = std::env::temp_dir().join(format!("dominant_colours_{}.txt", std::process::id()));
+        std::fs::write(
+            &list_path,
+            "./src/tests/red.png\n\n# this file doesn't exist\n./src/tests/missing.png\n./src/tests/red_png.gif\n",
+        )
+        .unwrap();
+
+        let output = get_failure(&["--files-from", list_path.to_str().unwrap(), "--space=rgb", "--no-palette"]);
+        std::fs::remove_file(&list_path).unwrap();
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, "./src/tests/red.png: #ff0000\n./src/tests/red_png.gif: #ff0000\n");
+        assert!(output.stderr.starts_with("./src/tests/missing.png: "), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_reads_a_list_of_files_from_stdin() {
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd
+            .args(["--files-from", "-", "--space=rgb", "--no-palette"])
+            .write_stdin("./src/tests/red.png\n./src/tests/red_and_blue.png\n")
+            .unwrap();
+
+        assert_eq!(
+            str::from_utf8(&output.stdout).unwrap(),
+            "./src/tests/red.png: #ff0000\n./src/tests/red_and_blue.png: #0000ff\n./src/tests/red_and_blue.png: #ff0000\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_files_from_and_a_path() {
+        let output = get_failure(&["./src/tests/red.png", "--files-from", "-"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(
+            output.stderr.contains("The argument '[PATH]' cannot be used with '--files-from <FILE>'"),
+            "stderr = {:?}",
+            output.stderr
+        );
+    }
+
+    #[test]
+    fn it_carries_on_if_an_image_in_a_directory_is_broken() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::copy("./src/tests/red.png", dir.join("1.png")).unwrap();
+        std::fs::write(dir.join("2.png"), b"\x89PNG\r\n\x1a\nthis isn't really a PNG").unwrap();
+        std::fs::copy("./src/tests/red.png", dir.join("3.png")).unwrap();
+
+        let output = get_failure(&[dir.to_str().unwrap(), "--space=rgb", "--no-palette"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output.exit_code, 3);
+        assert!(output.stdout.contains("1.png: #ff0000\n"), "stdout = {:?}", output.stdout);
+        assert!(output.stdout.contains("3.png: #ff0000\n"), "stdout = {:?}", output.stdout);
+        assert!(output.stderr.contains("2.png: "), "stderr = {:?}", output.stderr);
+    }
+
+    // We don't walk ./src, because that would find the video fixtures,
+    // which need ffmpeg.
+    #[test]
+    fn it_only_looks_in_subdirectories_if_recursive() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_recursive_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::copy("./src/tests/red.png", dir.join("nested").join("red.png")).unwrap();
+
+        let output = get_success(&[dir.to_str().unwrap(), "--max-colours=1", "--no-palette"]);
+        assert_eq!(output.stdout, "");
+
+        let output = get_success(&[dir.to_str().unwrap(), "--max-colours=1", "--no-palette", "--recursive"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(output.stdout.contains("red.png: #ff0000"), "stdout = {:?}", output.stdout);
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_a_threshold_without_compare() {
+        let output = get_failure(&["./src/tests/red.png", "--threshold=0.9"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("--compare <OTHER_PATH>"), "stderr = {:?}", output.stderr);
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_an_invalid_max_colours() {
+        let output = get_failure(&["./src/tests/red.png", "--max-colours=NaN"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "error: Invalid value 'NaN' for '--max-colours <MAX-COLOURS>': invalid digit found in string\n\nFor more information try '--help'\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_you_ask_for_no_colours() {
+        for algorithm in ["kmeans", "median-cut", "octree"] {
+            let output = get_failure(&["./src/tests/red.png", "--max-colours=0", "--algorithm", algorithm]);
+
+            assert_eq!(output.exit_code, 2);
+            assert_eq!(output.stdout, "");
+            assert_eq!(
+                output.stderr,
+                "error: Invalid value '0' for '--max-colours <MAX-COLOURS>': must be at least 1, or `auto`\n\nFor more information try '--help'\n"
+            );
+        }
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_an_invalid_exclude_colour() {
+        let output = get_failure(&["./src/tests/red.png", "--exclude=#ff00"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "error: Invalid value '#ff00' for '--exclude <COLOUR>': \"#ff00\" isn't a hex colour like #ff00ff\n\nFor more information try '--help'\n"
+        );
+    }
+
+    #[test]
+    fn it_fails_if_you_pass_an_invalid_seed() {
+        let output = get_failure(&["./src/tests/noise.jpg", "--seed=NaN"]);
+
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "error: Invalid value 'NaN' for '--seed <SEED>': invalid digit found in string\n\nFor more information try '--help'\n"
+        );
+    }
 
-    let max_iterations = 20;
-    let converge = 1.0;
-    let verbose = false;
+    #[test]
+    fn it_fails_if_you_pass_an_invalid_max_iterations() {
+        let output = get_failure(&["./src/tests/noise.jpg", "--max-iterations=0"]);
 
-    let result = get_kmeans_hamerly(colour_count, max_iterations, converge, verbose, &lab, seed).centroids;
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+        assert!(
+            output.stderr.starts_with("error: Invalid value '0' for '--max-iterations <N>': 0 is not in 1.."),
+            "stderr = {:?}",
+            output.stderr
+        );
+    }
 
-    let srgb_colors = result
-        .iter()
-        .map(|x| Srgb::from_color(*x).into_format())
-        .collect();
+    #[test]
+    fn it_fails_if_you_pass_an_invalid_convergence() {
+        let output = get_failure(&["./src/tests/noise.jpg", "--convergence=-1"]);
 
-    let rgb = if terminal_colours {
-        terminal_colours::create_terminal_colour(srgb_colors, max_brightness)
-    } else {
-        srgb_colors
-    };
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "error: Invalid value '-1' for '--convergence <SCORE>': must be a number greater than 0\n\nFor more information try '--help'\n"
+        );
+    }
 
-    // This uses ANSI escape sequences and Unicode block elements to print
-    // a palette of hex strings which are coloured to match.
-    // See https://alexwlchan.net/2021/04/coloured-squares/
-    for c in rgb {
-        let display_value = format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue);
+    #[test]
+    fn it_fails_if_you_pass_an_nonexistent_file() {
+        let output = get_failure(&["./doesnotexist.jpg"]);
 
-        if matches.get_flag("no-palette") {
-            println!("{}", display_value);
-        } else {
-            println!(
-                "\x1B[38;2;{};{};{}m▇ {}\x1B[0m",
-                c.red, c.green, c.blue, display_value
-            );
-        }
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, "");
+        assert_eq!(output.stderr, "./doesnotexist.jpg: No such file or directory (os error 2)\n");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::str;
-
-    use assert_cmd::assert::OutputAssertExt;
-    use assert_cmd::Command;
+    #[test]
+    fn it_fails_if_you_pass_an_nonexistent_gif() {
+        let output = get_failure(&["./doesnotexist.gif"]);
 
-    // Note: for the purposes of these tests, I mostly trust the k-means code
-    // provided by the external library.
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, "");
+        assert_eq!(output.stderr, "./doesnotexist.gif: No such file or directory (os error 2)\n");
+    }
 
     #[test]
-    fn it_prints_the_colour_with_ansi_escape_codes() {
-        let output = get_success(&["./src/tests/red.png", "--max-colours=1"]);
-
-        assert_eq!(output.exit_code, 0);
+    fn it_fails_if_you_pass_a_non_image_file() {
+        let output = get_failure(&["./README.md"]);
 
-        assert!(
-            output.stdout == "\u{1b}[38;2;255;0;0m▇ #ff0000\u{1b}[0m\n"
-                || output.stdout == "\u{1b}[38;2;254;0;0m▇ #fe0000\u{1b}[0m\n",
-            "stdout = {:?}",
-            output.stdout
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "./README.md: The image format could not be determined\n\
+             hint: dominant_colours didn't recognise the contents of the file; it can read JPEG, PNG, GIF, TIFF, BMP, ICO, WebP and SVG images\n"
         );
-
-        assert_eq!(output.stderr, "");
     }
 
     #[test]
-    fn it_can_look_at_png_images() {
-        let output = get_success(&["./src/tests/red.png", "--max-colours=1"]);
-        assert_eq!(output.exit_code, 0);
+    fn it_fails_if_you_pass_an_unsupported_image_format() {
+        let output = get_failure(&["./src/tests/solid_colour.ppm"]);
+
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "./src/tests/solid_colour.ppm: The image format Pnm is not supported\n\
+             hint: dominant_colours can read JPEG, PNG, GIF, TIFF, BMP, ICO, WebP and SVG images\n"
+        );
     }
 
+    // These are a 24-bit and a 32-bit BMP, both solid #123456.
     #[test]
-    fn it_can_look_at_jpeg_images() {
-        let output = get_success(&["./src/tests/noise.jpg", "--max-colours=1"]);
-        assert_eq!(output.exit_code, 0);
+    fn it_looks_at_bmp_images() {
+        for path in ["./src/tests/solid_colour.bmp", "./src/tests/thirty_two_bit.bmp"] {
+            let output = get_success(&[path, "--no-palette"]);
+
+            assert_eq!(output.stdout, "#123456\n", "path = {}", path);
+        }
     }
 
     #[test]
-    fn it_can_look_at_static_gif_images() {
-        let output = get_success(&["./src/tests/yellow.gif", "--max-colours=1"]);
-        assert_eq!(output.exit_code, 0);
+    fn it_looks_at_a_lossy_webp() {
+        let output = get_success(&["./src/tests/purple.webp", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#7a81fe\n");
     }
 
     #[test]
-    fn it_can_look_at_tiff_images() {
-        let output = get_success(&["./src/tests/green.tiff", "--max-colours=1"]);
-        assert_eq!(output.exit_code, 0);
+    fn it_looks_at_a_lossless_webp() {
+        let output = get_success(&["./src/tests/lossless.webp", "--no-palette"]);
+
+        assert_eq!(output.stdout, "#123456\n");
     }
 
+    // This APNG has a red frame, then three frames with small blue squares
+    // drawn over the red.
     #[test]
-    fn it_omits_the_escape_codes_with_no_palette() {
-        let output = get_success(&["./src/tests/red.png", "--max-colours=1", "--no-palette"]);
+    fn it_looks_at_multiple_frames_in_an_apng() {
+        let output = get_success(&["./src/tests/animated_squares_apng.png", "--no-palette", "--verbose"]);
 
-        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.stdout, "#ff0000\n#0000ff\n");
+        assert!(output.stderr.contains("frames sampled: 4"), "stderr = {:?}", output.stderr);
+    }
 
-        assert!(
-            output.stdout == "#ff0000\n" || output.stdout == "#fe0000\n",
-            "stdout = {:?}",
-            output.stdout
-        );
+    // This animated WebP has four frames: solid red, blue, red, blue.
+    #[test]
+    fn it_looks_at_multiple_frames_in_an_animated_webp() {
+        let output = get_success(&["./src/tests/alternating_colours.webp", "--no-palette", "--verbose"]);
 
-        assert_eq!(output.stderr, "");
+        assert_eq!(output.stdout, "#0000ff\n#ff0000\n");
+        assert!(output.stderr.contains("frames sampled: 4"), "stderr = {:?}", output.stderr);
     }
 
     #[test]
-    fn it_defaults_to_five_colours() {
-        let output = get_success(&["./src/tests/noise.jpg"]);
+    fn it_fails_if_you_pass_a_malformed_image() {
+        let output = get_failure(&["./src/tests/malformed.txt.png"]);
 
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "");
         assert_eq!(
-            output.stdout.matches("\n").count(),
-            5,
-            "stdout = {:?}",
-            output.stdout
+            output.stderr,
+            "./src/tests/malformed.txt.png: Format error decoding Png: Invalid PNG signature.\n"
         );
     }
 
     #[test]
-    fn it_lets_you_choose_the_max_colours() {
-        let output = get_success(&["./src/tests/noise.jpg", "--max-colours=8"]);
+    fn it_fails_if_you_pass_a_malformed_ico() {
+        let output = get_failure(&["./src/tests/malformed.txt.ico"]);
 
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "");
         assert_eq!(
-            output.stdout.matches("\n").count(),
-            8,
-            "stdout = {:?}",
-            output.stdout
+            output.stderr,
+            "./src/tests/malformed.txt.ico: Format error decoding Ico: Invalid ICO header\n"
         );
     }
 
+    // On Linux, removing the display means there's no clipboard to copy to,
+    // so we can check this warns rather than failing.
     #[test]
-    fn it_lets_you_choose_the_seed() {
-        let output = get_success(&["./src/tests/noise.jpg", "--max-colours=1", "--seed", "123456789"]);
+    #[cfg(target_os = "linux")]
+    fn it_warns_if_there_is_no_clipboard() {
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd
+            .args(["./src/tests/red.png", "--no-palette", "--copy"])
+            .env_remove("DISPLAY")
+            .env_remove("WAYLAND_DISPLAY")
+            .unwrap();
 
-        assert_eq!(output.stdout.contains("#85827f"), true);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(str::from_utf8(&output.stdout).unwrap(), "#ff0000\n");
+
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+        assert!(stderr.starts_with("warning: couldn't copy to the clipboard: "), "stderr = {:?}", stderr);
     }
 
+    // The background process which holds the clipboard isn't looking at an
+    // image, so it shouldn't print anything, even if there's no clipboard.
     #[test]
-    fn it_lets_you_set_random_seed() {
-        let output1 = get_success(&["./src/tests/noise.jpg", "--random-seed"]);
-        let output2 = get_success(&["./src/tests/noise.jpg", "--random-seed"]);
+    #[cfg(target_os = "linux")]
+    fn it_runs_the_clipboard_daemon_quietly() {
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd
+            .args([crate::clipboard::DAEMON_ARG, "#ff0000"])
+            .env_remove("DISPLAY")
+            .env_remove("WAYLAND_DISPLAY")
+            .unwrap();
 
-        assert_ne!(
-            output1.stdout,
-            output2.stdout
-        );
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(output.stdout, b"");
+        assert_eq!(output.stderr, b"");
     }
 
-    // The image created in the next two tests was created with the
-    // following command:
-    //
-    //      convert -delay 200 -loop 10 -dispose previous red.png blue.png red.png blue.png red.png blue.png red.png blue.png animated_squares.gif
-    //
-
     #[test]
-    fn it_looks_at_multiple_frames_in_an_animated_gif() {
-        let output = get_success(&["./src/tests/animated_squares.gif"]);
+    fn it_fails_if_you_copy_a_colour_that_doesnt_exist() {
+        let output = get_failure(&["./src/tests/red.png", "--max-colours=1", "--copy=2"]);
 
-        assert_eq!(
-            output.stdout.matches("\n").count(),
-            2,
-            "stdout = {:?}",
-            output.stdout
-        );
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.stdout, "");
+        assert!(output.stderr.starts_with("error: --copy=2, but only found 1 colour(s)\n"), "stderr = {:?}", output.stderr);
     }
 
     #[test]
-    fn it_looks_at_multiple_frames_in_an_animated_gif_uppercase() {
-        let output = get_success(&["./src/tests/animated_upper_squares.GIF"]);
+    fn it_only_accepts_a_number_or_all_for_copy() {
+        let output = get_failure(&["./src/tests/red.png", "--copy=first"]);
 
-        assert_eq!(
-            output.stdout.matches("\n").count(),
-            2,
-            "stdout = {:?}",
-            output.stdout
-        );
+        assert_eq!(output.exit_code, 2);
+        assert!(output.stderr.contains("must be a number or `all`"), "stderr = {:?}", output.stderr);
     }
 
     #[test]
-    fn it_still_prints_16_colours_when_max_colours_and_terminal_colours_are_set() {
-        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--max-colours=20"]);
-
-        assert_eq!(output.exit_code, 0);
+    fn it_fails_the_check_if_there_are_too_few_distinct_colours() {
+        let output = get_failure(&["./src/tests/red.png", "--quiet", "--require-distinct", "2"]);
 
-        assert_eq!(
-            output.stdout.matches("\n").count(),
-            16,
-            "stdout = {:?}",
-            output.stdout
-        );
+        assert_eq!(output.exit_code, 4);
+        assert_eq!(output.stdout, "");
+        assert_eq!(output.stderr, "");
     }
 
-    // Notice the colours in the terminal_colours.png image is slightly different than the values defined in terminal_colours.rs.
-    // This is on purpose to test that slight variation gets handled.
     #[test]
-    fn it_prints_the_ansi_terminal_colours_mapped_correctly() {
-        let output = get_success(&["./src/tests/terminal_colours.png", "--terminal-colours", "--no-palette"]);
+    fn it_passes_the_check_if_there_are_enough_distinct_colours() {
+        let output = get_success(&["./src/tests/noise.jpg", "--quiet", "--require-distinct", "2"]);
 
         assert_eq!(output.exit_code, 0);
+        assert_eq!(output.stdout, "");
+    }
 
-        let expected_output = "\
-#000000
-#aa0000
-#00aa00
-#808000
-#0000aa
-#aa00aa
-#00aaaa
-#aaaaaa
-#555555
-#ff0000
-#00ff00
-#ffff00
-#0000ff
-#ff00ff
-#00ffff
-#ffffff
-";
+    #[test]
+    fn it_prints_the_colours_if_the_check_fails_without_quiet() {
+        let output = get_failure(&["./src/tests/red.png", "--no-palette", "--require-distinct=2"]);
 
-        assert_eq!(output.stdout, expected_output);
+        assert_eq!(output.exit_code, 4);
+        assert_eq!(output.stdout, "#ff0000\n");
+    }
 
+    #[test]
+    fn it_reads_an_image_from_stdin() {
+        let output = get_success_with_stdin(&["-", "--no-palette"], "./src/tests/red.png");
+
+        assert_eq!(output.stdout, "#ff0000\n");
         assert_eq!(output.stderr, "");
     }
 
     #[test]
-    fn it_fails_if_you_pass_an_invalid_max_colours() {
-        let output = get_failure(&["./src/tests/red.png", "--max-colours=NaN"]);
+    fn it_reads_an_animated_gif_from_stdin() {
+        let output = get_success_with_stdin(&["-", "--no-palette", "--verbose"], "./src/tests/animated_squares.gif");
 
-        assert_eq!(output.exit_code, 2);
-        assert_eq!(output.stdout, "");
-        assert_eq!(
-            output.stderr,
-            "error: Invalid value 'NaN' for '--max-colours <MAX-COLOURS>': invalid digit found in string\n\nFor more information try '--help'\n"
-        );
+        assert_eq!(output.stdout, "#0000ff\n#ff0000\n");
+        assert!(output.stderr.contains("frames sampled: 8"), "stderr = {:?}", output.stderr);
     }
 
     #[test]
-    fn it_fails_if_you_pass_an_invalid_seed() {
-        let output = get_failure(&["./src/tests/noise.jpg", "--seed=NaN"]);
+    fn it_fails_if_stdin_is_not_an_image() {
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd
+            .args(["-"])
+            .write_stdin(std::fs::read("./src/tests/malformed.txt.png").unwrap())
+            .unwrap_err()
+            .as_output()
+            .unwrap()
+            .to_owned();
 
-        assert_eq!(output.exit_code, 2);
-        assert_eq!(output.stdout, "");
+        assert_eq!(output.status.code(), Some(3));
         assert_eq!(
-            output.stderr,
-            "error: Invalid value 'NaN' for '--seed <SEED>': invalid digit found in string\n\nFor more information try '--help'\n"
+            str::from_utf8(&output.stderr).unwrap(),
+            "<stdin>: The image format could not be determined\n\
+             hint: dominant_colours didn't recognise the contents of the file; it can read JPEG, PNG, GIF, TIFF, BMP, ICO, WebP and SVG images\n"
         );
     }
 
+    // Serve a single HTTP response on a local port, and return the URL.
+    fn serve_once(status: &str, body: Vec<u8>) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let status = status.to_owned();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            // Read the request headers, which end with a blank line.
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+
+            // The client may hang up early (e.g. if the body is too big),
+            // so we don't mind if these writes fail.
+            let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len());
+            let _ = stream.write_all(&body);
+        });
+
+        format!("http://127.0.0.1:{}", port)
+    }
+
     #[test]
-    fn it_fails_if_you_pass_an_nonexistent_file() {
-        let output = get_failure(&["./doesnotexist.jpg"]);
+    fn it_downloads_an_image_from_a_url() {
+        // The query string and the .gif extension shouldn't matter; we only
+        // look at the contents.
+        let url = serve_once("200 OK", std::fs::read("./src/tests/red.png").unwrap()) + "/cover.gif?width=300";
 
-        assert_eq!(output.exit_code, 1);
-        assert_eq!(output.stdout, "");
-        assert_eq!(output.stderr, "No such file or directory (os error 2)\n");
+        let output = get_success(&[&url, "--no-palette"]);
+
+        assert_eq!(output.stdout, "#ff0000\n");
+        assert_eq!(output.stderr, "");
     }
 
     #[test]
-    fn it_fails_if_you_pass_an_nonexistent_gif() {
-        let output = get_failure(&["./doesnotexist.gif"]);
+    fn it_downloads_an_animated_gif_from_a_url() {
+        let url = serve_once("200 OK", std::fs::read("./src/tests/animated_squares.gif").unwrap()) + "/squares";
 
-        assert_eq!(output.exit_code, 1);
-        assert_eq!(output.stdout, "");
-        assert_eq!(output.stderr, "No such file or directory (os error 2)\n");
+        let output = get_success(&[&url, "--no-palette", "--verbose"]);
+
+        assert_eq!(output.stdout, "#0000ff\n#ff0000\n");
+        assert!(output.stderr.contains("frames sampled: 8"), "stderr = {:?}", output.stderr);
     }
 
     #[test]
-    fn it_fails_if_you_pass_a_non_image_file() {
-        let output = get_failure(&["./README.md"]);
+    fn it_fails_if_the_server_returns_an_error() {
+        let url = serve_once("404 Not Found", b"not found".to_vec()) + "/missing.png";
+
+        let output = get_failure(&[&url]);
 
         assert_eq!(output.exit_code, 1);
         assert_eq!(output.stdout, "");
-        assert_eq!(
-            output.stderr,
-            "The file extension `.\"md\"` was not recognized as an image format\n"
-        );
+        assert_eq!(output.stderr, format!("{}: the server responded with HTTP 404 Not Found\n", url));
     }
 
     #[test]
-    fn it_fails_if_you_pass_an_unsupported_image_format() {
-        let output = get_failure(&["./src/tests/purple.webp"]);
+    fn it_fails_if_it_cant_connect_to_the_server() {
+        // Bind to a port and then close it, so nothing is listening there.
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("http://127.0.0.1:{}/red.png", port);
+
+        let output = get_failure(&[&url]);
 
         assert_eq!(output.exit_code, 1);
-        assert_eq!(output.stdout, "");
-        assert_eq!(output.stderr, "The image format WebP is not supported\n");
+        assert!(
+            output.stderr.starts_with(&format!("{}: couldn't download the image: ", url)),
+            "stderr = {:?}",
+            output.stderr
+        );
+        assert_eq!(output.stderr.lines().count(), 1);
     }
 
     #[test]
-    fn it_fails_if_you_pass_a_malformed_image() {
-        let output = get_failure(&["./src/tests/malformed.txt.png"]);
+    fn it_fails_if_the_download_is_too_big() {
+        let url = serve_once("200 OK", std::fs::read("./src/tests/red.png").unwrap()) + "/red.png";
+
+        let output = get_failure(&[&url, "--max-download-size=100"]);
 
         assert_eq!(output.exit_code, 1);
-        assert_eq!(output.stdout, "");
         assert_eq!(
             output.stderr,
-            "Format error decoding Png: Invalid PNG signature.\n"
+            format!("{}: the image is bigger than 100 bytes; use --max-download-size to allow bigger downloads\n", url)
+        );
+    }
+
+    #[test]
+    fn it_fails_if_the_download_is_not_an_image() {
+        let url = serve_once("200 OK", b"<html>not an image</html>".to_vec()) + "/page.html";
+
+        let output = get_failure(&[&url]);
+
+        assert_eq!(output.exit_code, 3);
+        assert!(
+            output.stderr.starts_with(&format!("{}: The image format could not be determined\n", url)),
+            "stderr = {:?}",
+            output.stderr
         );
     }
 
@@ -370,6 +4806,24 @@ mod tests {
         }
     }
 
+    fn get_success_with_stdin(args: &[&str], stdin_path: &str) -> DcOutput {
+        let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
+        let output = cmd
+            .args(args)
+            .write_stdin(std::fs::read(stdin_path).unwrap())
+            .unwrap()
+            .assert()
+            .success()
+            .get_output()
+            .to_owned();
+
+        DcOutput {
+            exit_code: output.status.code().unwrap(),
+            stdout: str::from_utf8(&output.stdout).unwrap().to_owned(),
+            stderr: str::from_utf8(&output.stderr).unwrap().to_owned(),
+        }
+    }
+
     fn get_failure(args: &[&str]) -> DcOutput {
         let mut cmd = Command::cargo_bin("dominant_colours").unwrap();
         let output = cmd.args(args).unwrap_err().as_output().unwrap().to_owned();
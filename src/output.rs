@@ -0,0 +1,89 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+// Write the output to a file with --output.
+//
+// We write to a temporary file in the same directory, then rename it into
+// place, so a build tool watching the file never sees it half-written --
+// the rename either happens or it doesn't.  The temporary file has to be in
+// the same directory, because you can't rename across filesystems.
+//
+// We only do that for regular files, though.  Renaming over anything else
+// would replace it rather than write to it: a symlink would become a plain
+// file rather than updating the file it points to, and /dev/stdout or a FIFO
+// would become a file that nothing reads.  For those, we write in place.
+//
+// With --no-clobber, we refuse to replace a file that already exists.
+pub fn write_atomically(path: &Path, contents: &[u8], no_clobber: bool) -> io::Result<()> {
+    if no_clobber && path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "this file already exists; remove it or leave out --no-clobber to replace it",
+        ));
+    }
+
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_file() => return fs::File::create(path)?.write_all(contents),
+        _ => (),
+    }
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "this isn't a path to a file"))?;
+
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+
+    let result = fs::write(&temp_path, contents).and_then(|_| fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::output::write_atomically;
+
+    #[test]
+    fn it_replaces_a_file_unless_no_clobber() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_output_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("palette.txt");
+
+        write_atomically(&path, b"#ff0000\n", false).unwrap();
+        write_atomically(&path, b"#0000ff\n", false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"#0000ff\n");
+
+        let err = write_atomically(&path, b"#00ff00\n", true).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"#0000ff\n");
+
+        // We don't leave any temporary files behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_writes_through_a_symlink() {
+        let dir = std::env::temp_dir().join(format!("dominant_colours_output_symlink_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let (target, link) = (dir.join("palette.txt"), dir.join("link.txt"));
+        fs::write(&target, b"#ff0000\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomically(&link, b"#0000ff\n", false).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"#0000ff\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageResult};
+
+// PDFs are documents rather than images, so we draw one page (the first,
+// unless you pick another with --page) at --pdf-dpi, then treat it like
+// any other image.  We draw the page on white, like a PDF viewer does.
+//
+// We draw the page with hayro, which is written in Rust but brings in a
+// lot of code -- that's why it's behind the `pdf` feature.
+//
+// See https://github.com/LaurenzV/hayro
+
+#[cfg(feature = "pdf")]
+pub fn decode(bytes: &[u8], page_number: usize, dpi: u32) -> ImageResult<DynamicImage> {
+    use std::io;
+
+    use hayro::hayro_interpret::InterpreterSettings;
+    use hayro::hayro_syntax::{DecryptionError, LoadPdfError, Pdf};
+    use hayro::vello_cpu::color::palette::css::WHITE;
+    use hayro::vello_cpu::peniko::ImageAlphaType;
+    use hayro::{PixmapSettings, RenderCache, RenderSettings};
+    use image::RgbaImage;
+
+    let pdf = Pdf::new(bytes.to_vec()).map_err(|e| match e {
+        LoadPdfError::Decryption(DecryptionError::PasswordProtected) => {
+            format_error("This PDF is password-protected, and dominant_colours can't read password-protected PDFs")
+        }
+        LoadPdfError::Decryption(_) => format_error("This PDF is encrypted in a way dominant_colours can't read"),
+        LoadPdfError::Invalid => format_error("This PDF is invalid or damaged"),
+    })?;
+
+    // Pages count from 1, like they do in a PDF viewer.
+    let pages = pdf.pages();
+
+    let page = page_number.checked_sub(1).and_then(|i| pages.get(i)).ok_or_else(|| {
+        ImageError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--page={} is out of range; this PDF has {} page{}",
+                page_number,
+                pages.len(),
+                if pages.len() == 1 { "" } else { "s" }
+            ),
+        ))
+    })?;
+
+    // PDF sizes are in points, which are 1/72 of an inch.
+    let scale = dpi as f32 / 72.0;
+    let (width, height) = page.render_dimensions();
+
+    if width * scale > u16::MAX as f32 || height * scale > u16::MAX as f32 {
+        return Err(format_error(format!("This page is too large to draw at {} DPI", dpi)));
+    }
+
+    let pixmap = hayro::render(
+        page,
+        &RenderCache::new(),
+        &InterpreterSettings::default(),
+        &RenderSettings::default(),
+        &PixmapSettings { x_scale: scale, y_scale: scale, bg_color: WHITE },
+    );
+
+    let (width, height) = (pixmap.width() as u32, pixmap.height() as u32);
+
+    RgbaImage::from_raw(width, height, pixmap.take_rgba8(ImageAlphaType::Alpha))
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| format_error("Drawn page is the wrong size"))
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn decode(_bytes: &[u8], _page_number: usize, _dpi: u32) -> ImageResult<DynamicImage> {
+    Err(format_error(
+        "This build of dominant_colours doesn't support PDFs; rebuild with `--features pdf`",
+    ))
+}
+
+fn format_error(message: impl Into<String>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Name(String::from("PDF")),
+        message.into(),
+    ))
+}
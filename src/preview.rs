@@ -0,0 +1,104 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use palette::Srgb;
+
+// With --preview, we show you what the terminal colours would look like
+// before you put them in your terminal's config: a block for each of the
+// 16 slots, then some text in each colour on the background (colour 0),
+// the same as you'd see in a terminal using the scheme.
+//
+// This uses 24-bit ANSI escape sequences, like the swatches, so it works
+// even if your terminal's own palette is different.
+// See https://en.wikipedia.org/wiki/ANSI_escape_code#24-bit
+
+// When to print the preview, with --color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum When {
+    // Only if stdout is a terminal, because the escape sequences would be
+    // noise anywhere else.
+    Auto,
+    Always,
+    Never,
+}
+
+const SAMPLE_TEXT: &str = "The quick brown fox jumps over the lazy dog";
+
+// The names we print in each colour, for colours 1-6 and 9-14.
+const COLOUR_NAMES: [&str; 6] = ["red", "green", "yellow", "blue", "magenta", "cyan"];
+
+pub fn preview(colours: &[Srgb<u8>]) -> String {
+    let mut output = String::new();
+
+    // The blocks, in two rows of 8 like most terminals' colour pickers.
+    for row in colours[..16].chunks(8) {
+        for c in row {
+            write!(output, "{}███", foreground(*c)).unwrap();
+        }
+        writeln!(output, "\x1B[0m").unwrap();
+    }
+
+    writeln!(output).unwrap();
+
+    let background = background(colours[0]);
+
+    // Normal text, then the same again in bold with the bright colours,
+    // which is how most terminals draw bold text.
+    for (bold, offset) in [(false, 0), (true, 8)] {
+        let bold = if bold { "\x1B[1m" } else { "" };
+
+        writeln!(output, "{}{}{} {} \x1B[0m", background, bold, foreground(colours[offset + 7]), SAMPLE_TEXT).unwrap();
+
+        write!(output, "{}{}", background, bold).unwrap();
+        for (i, name) in COLOUR_NAMES.iter().enumerate() {
+            write!(output, "{} {}", foreground(colours[offset + i + 1]), name).unwrap();
+        }
+        writeln!(output, " \x1B[0m").unwrap();
+    }
+
+    output
+}
+
+fn foreground(c: Srgb<u8>) -> String {
+    format!("\x1B[38;2;{};{};{}m", c.red, c.green, c.blue)
+}
+
+fn background(c: Srgb<u8>) -> String {
+    format!("\x1B[48;2;{};{};{}m", c.red, c.green, c.blue)
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::preview::preview;
+    use crate::terminal_colours::ANSI_COLOUR_TABLE;
+
+    #[test]
+    fn it_previews_the_terminal_colours() {
+        let colours: Vec<Srgb<u8>> = ANSI_COLOUR_TABLE.iter().map(|[r, g, b]| Srgb::new(*r, *g, *b)).collect();
+
+        let output = preview(&colours);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 7, "{:?}", lines);
+
+        assert!(lines[0].starts_with("\x1B[38;2;0;0;0m███\x1B[38;2;170;0;0m███"), "{:?}", lines[0]);
+        assert_eq!(lines[0].matches("███").count(), 8);
+        assert!(lines[1].starts_with("\x1B[38;2;85;85;85m███"), "{:?}", lines[1]);
+        assert_eq!(lines[2], "");
+
+        assert_eq!(lines[3], "\x1B[48;2;0;0;0m\x1B[38;2;170;170;170m The quick brown fox jumps over the lazy dog \x1B[0m");
+        assert!(lines[4].starts_with("\x1B[48;2;0;0;0m\x1B[38;2;170;0;0m red\x1B[38;2;0;170;0m green"), "{:?}", lines[4]);
+
+        assert_eq!(lines[5], "\x1B[48;2;0;0;0m\x1B[1m\x1B[38;2;255;255;255m The quick brown fox jumps over the lazy dog \x1B[0m");
+        assert!(lines[6].ends_with("\x1B[38;2;85;255;255m cyan \x1B[0m"), "{:?}", lines[6]);
+    }
+
+    #[test]
+    fn it_only_previews_the_first_16_of_256_colours() {
+        let colours = vec![Srgb::new(0x10, 0x20, 0x30); 256];
+
+        assert_eq!(preview(&colours).matches("███").count(), 16);
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use clap::ValueEnum;
+use palette::{Pixel, Srgb, Srgba};
+
+use crate::clustering::Clustering;
+
+// The algorithms we can use to find the dominant colours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    // k-means clustering, which finds colours that are a good average of
+    // the pixels around them -- see `clustering::Pixels::kmeans`.
+    Kmeans,
+
+    // Median cut, which keeps splitting the box of colours with the widest
+    // range in half, so small-but-distinct colours get a box of their own.
+    // See https://en.wikipedia.org/wiki/Median_cut
+    MedianCut,
+
+    // An octree, which puts each pixel in a tree of ever-smaller cubes of
+    // RGB space, then merges the smallest cubes until there are few enough.
+    // See https://en.wikipedia.org/wiki/Octree#Color_quantization
+    Octree,
+}
+
+impl Algorithm {
+    // The algorithms other than k-means don't use random numbers, so they
+    // always give the same colours for the same pixels.  This returns the
+    // function to run them, or None for k-means.
+    pub fn deterministic(&self) -> Option<fn(&[u8], usize) -> Clustering> {
+        match self {
+            Algorithm::Kmeans => None,
+            Algorithm::MedianCut => Some(median_cut),
+            Algorithm::Octree => Some(octree),
+        }
+    }
+}
+
+// Like k-means, we ignore the alpha channel -- transparent pixels have
+// already been dropped by the time we get here.
+fn rgb_pixels(img_bytes: &[u8]) -> Vec<[u8; 3]> {
+    Srgba::<u8>::from_raw_slice(img_bytes)
+        .iter()
+        .map(|p| [p.red, p.green, p.blue])
+        .collect()
+}
+
+// Find up to `colour_count` colours in these RGBA bytes with median cut.
+//
+// We keep every pixel in one list, and each box is a range of it, so
+// splitting a box is just sorting its range and picking a midpoint.
+pub fn median_cut(img_bytes: &[u8], colour_count: usize) -> Clustering {
+    let mut pixels = rgb_pixels(img_bytes);
+    let mut boxes: Vec<Range<usize>> = Vec::new();
+    boxes.push(0..pixels.len());
+
+    while boxes.len() < colour_count {
+        // Find the box and channel with the widest range.  A box where
+        // every pixel is the same colour has a range of 0, so we'll never
+        // split it, and if that's every box, we're done.
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, range)| (0..3).map(move |channel| (i, channel, range.clone())))
+            .map(|(i, channel, range)| {
+                let values = pixels[range].iter().map(|p| p[channel]);
+                let spread = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                (i, channel, spread)
+            })
+            .fold(None, |best: Option<(usize, usize, u8)>, this| match best {
+                Some(best) if best.2 >= this.2 => Some(best),
+                _ => Some(this),
+            });
+
+        let (i, channel) = match widest {
+            Some((i, channel, spread)) if spread > 0 => (i, channel),
+            _ => break,
+        };
+
+        let range = boxes[i].clone();
+        let slice = &mut pixels[range.clone()];
+        slice.sort_unstable_by_key(|p| p[channel]);
+
+        // We move the split to the nearest change in value, so pixels of
+        // the same colour always end up in the same box.
+        let mid = slice.len() / 2;
+        let below = slice.partition_point(|p| p[channel] < slice[mid][channel]);
+        let above = slice.partition_point(|p| p[channel] <= slice[mid][channel]);
+
+        let split = if below == 0 {
+            above
+        } else if above == slice.len() || mid - below <= above - mid {
+            below
+        } else {
+            above
+        };
+
+        boxes[i] = range.start..range.start + split;
+        boxes.push(range.start + split..range.end);
+    }
+
+    let groups: Vec<&[[u8; 3]]> = boxes.into_iter().map(|range| &pixels[range]).collect();
+    let centroids: Vec<[u8; 3]> = groups.iter().map(|group| mean(group.iter())).collect();
+
+    let error = groups
+        .iter()
+        .zip(&centroids)
+        .flat_map(|(group, centroid)| group.iter().map(move |p| squared_distance(p, centroid)))
+        .sum();
+
+    Clustering {
+        score: 0.0,
+        error,
+        centroids: centroids.iter().map(|[r, g, b]| Srgb::new(*r, *g, *b)).collect(),
+        counts: groups.iter().map(|group| group.len()).collect(),
+    }
+}
+
+// A cube of RGB space.  The root is every colour, and each level down
+// splits the cube in half along each channel, using one more bit of each
+// value -- so the nodes on level 8 are single colours.
+//
+// Every node keeps the sum and count of the pixels inside it, so when we
+// merge a node's children, it already knows their average.
+#[derive(Default)]
+struct Node {
+    children: [Option<usize>; 8],
+    sum: [u64; 3],
+    count: usize,
+    is_leaf: bool,
+}
+
+// Find up to `colour_count` colours in these RGBA bytes with an octree.
+pub fn octree(img_bytes: &[u8], colour_count: usize) -> Clustering {
+    let pixels = rgb_pixels(img_bytes);
+    let mut nodes = vec![Node::default()];
+    let mut levels: Vec<Vec<usize>> = vec![Vec::new(); 8];
+    let mut leaf_count = 0;
+
+    for p in &pixels {
+        let mut node = 0;
+
+        for level in 0..8 {
+            add(&mut nodes[node], p);
+
+            let child = child_index(p, level);
+            node = match nodes[node].children[child] {
+                Some(i) => i,
+                None => {
+                    nodes.push(Node { is_leaf: level == 7, ..Node::default() });
+                    let i = nodes.len() - 1;
+                    nodes[node].children[child] = Some(i);
+
+                    if level == 7 {
+                        leaf_count += 1;
+                    } else {
+                        levels[level + 1].push(i);
+                    }
+                    i
+                }
+            };
+        }
+
+        add(&mut nodes[node], p);
+    }
+
+    let colour_count = colour_count.max(1);
+
+    // We merge the deepest nodes first, and on each level, the nodes with
+    // the fewest pixels, so the big colours are kept intact for longest.
+    // Merging a node replaces every leaf under it with one leaf, so we skip
+    // any node which would leave us with fewer colours than we asked for.
+    for level in (0..8).rev() {
+        let mut candidates = if level == 0 { vec![0] } else { levels[level].clone() };
+        candidates.sort_by_key(|i| (nodes[*i].count, *i));
+
+        for i in candidates {
+            let leaves_under = count_leaves(&nodes, i);
+
+            if leaf_count - leaves_under + 1 >= colour_count {
+                nodes[i].children = [None; 8];
+                nodes[i].is_leaf = true;
+                leaf_count = leaf_count - leaves_under + 1;
+            }
+        }
+    }
+
+    // Go through the leaves in a fixed order, so the output doesn't depend
+    // on the order of the pixels.
+    let mut leaves = Vec::new();
+    let mut stack = vec![0];
+
+    while let Some(i) = stack.pop() {
+        if nodes[i].is_leaf {
+            leaves.push(i);
+        } else {
+            stack.extend(nodes[i].children.iter().rev().flatten());
+        }
+    }
+
+    // If every merge would have left too few leaves (e.g. eight colours,
+    // one in each corner of the cube, and we want five), we're left with
+    // too many.  We finish off by merging the smallest group of pixels into
+    // the nearest one, until there are few enough.
+    let mut groups: Vec<([u64; 3], usize)> = leaves.iter().map(|i| (nodes[*i].sum, nodes[*i].count)).collect();
+    let mut group_of: Vec<usize> = (0..leaves.len()).collect();
+    let mut alive: Vec<usize> = (0..leaves.len()).collect();
+
+    while alive.len() > colour_count {
+        let smallest = *alive.iter().min_by_key(|g| (groups[**g].1, **g)).expect("there are groups left");
+        let centroid = |g: usize| average(groups[g].0, groups[g].1);
+
+        let nearest = *alive
+            .iter()
+            .filter(|g| **g != smallest)
+            .min_by(|a, b| {
+                let (da, db) = (squared_distance(&centroid(smallest), &centroid(**a)), squared_distance(&centroid(smallest), &centroid(**b)));
+                da.total_cmp(&db).then(a.cmp(b))
+            })
+            .expect("there are at least two groups left");
+
+        let (sum, count) = groups[smallest];
+        for (total, value) in groups[nearest].0.iter_mut().zip(sum) {
+            *total += value;
+        }
+        groups[nearest].1 += count;
+
+        for g in group_of.iter_mut().filter(|g| **g == smallest) {
+            *g = nearest;
+        }
+        alive.retain(|g| *g != smallest);
+    }
+
+    let centroids: Vec<[u8; 3]> = alive.iter().map(|g| average(groups[*g].0, groups[*g].1)).collect();
+
+    // Which group each leaf ended up in, indexed by node, so we can look it
+    // up straight away for each pixel.
+    let mut group_of_node: Vec<Option<usize>> = vec![None; nodes.len()];
+
+    for (leaf, node) in leaves.iter().enumerate() {
+        group_of_node[*node] = alive.iter().position(|g| *g == group_of[leaf]);
+    }
+
+    // Now put each pixel in the leaf that it ends up in when we walk down
+    // the tree, and that leaf's group.
+    let mut error = 0.0;
+
+    for p in &pixels {
+        let mut node = 0;
+        let mut level = 0;
+
+        while !nodes[node].is_leaf {
+            node = nodes[node].children[child_index(p, level)].expect("every pixel has a path to a leaf");
+            level += 1;
+        }
+
+        let group = group_of_node[node].expect("every leaf is in a group");
+        error += squared_distance(p, &centroids[group]);
+    }
+
+    Clustering {
+        score: 0.0,
+        error,
+        centroids: centroids.iter().map(|[r, g, b]| Srgb::new(*r, *g, *b)).collect(),
+        counts: alive.iter().map(|g| groups[*g].1).collect(),
+    }
+}
+
+fn count_leaves(nodes: &[Node], i: usize) -> usize {
+    if nodes[i].is_leaf {
+        1
+    } else {
+        nodes[i].children.iter().flatten().map(|child| count_leaves(nodes, *child)).sum()
+    }
+}
+
+fn average(sum: [u64; 3], count: usize) -> [u8; 3] {
+    sum.map(|total| ((total as f64) / (count.max(1) as f64)).round() as u8)
+}
+
+fn add(node: &mut Node, p: &[u8; 3]) {
+    for (total, value) in node.sum.iter_mut().zip(p) {
+        *total += *value as u64;
+    }
+    node.count += 1;
+}
+
+// Which child of a node on this level the colour goes in, using bit
+// (7 - level) of each channel.
+fn child_index(p: &[u8; 3], level: usize) -> usize {
+    let bit = |value: u8| ((value >> (7 - level)) & 1) as usize;
+
+    (bit(p[0]) << 2) | (bit(p[1]) << 1) | bit(p[2])
+}
+
+fn mean<'a>(pixels: impl Iterator<Item = &'a [u8; 3]>) -> [u8; 3] {
+    let (sum, count) = pixels.fold(([0u64; 3], 0), |(mut sum, count), p| {
+        for (total, value) in sum.iter_mut().zip(p) {
+            *total += *value as u64;
+        }
+        (sum, count + 1)
+    });
+
+    average(sum, count)
+}
+
+// With --mode=exact, we don't find colours at all -- we count how many
+// pixels have each exact colour, and keep the `colour_count` most common
+// (or all of them, if it's None).  This is what you want for pixel art and
+// flat designs, where k-means would invent colours in between.
+//
+// Ties go to the lowest hex value, so it's the same every time.  We ignore
+// the alpha channel, like the other algorithms, so the same colour at two
+// different opacities is still one colour.
+//
+// If the image has more than `max_unique` colours, this isn't the right
+// tool (and the count would use a lot of memory), so we stop and return
+// None.
+pub fn exact(img_bytes: &[u8], colour_count: Option<usize>, max_unique: usize) -> Option<Clustering> {
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+
+    for p in Srgba::<u8>::from_raw_slice(img_bytes) {
+        *counts.entry([p.red, p.green, p.blue]).or_insert(0) += 1;
+
+        if counts.len() > max_unique {
+            return None;
+        }
+    }
+
+    let mut counts: Vec<([u8; 3], usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    counts.truncate(colour_count.unwrap_or(usize::MAX));
+
+    Some(Clustering {
+        score: 0.0,
+        error: 0.0,
+        centroids: counts.iter().map(|([r, g, b], _)| Srgb::new(*r, *g, *b)).collect(),
+        counts: counts.iter().map(|(_, count)| *count).collect(),
+    })
+}
+
+// The squared distance in RGB, with each channel from 0 to 255.
+fn squared_distance(p: &[u8; 3], q: &[u8; 3]) -> f32 {
+    p.iter().zip(q).map(|(a, b)| (*a as f32 - *b as f32).powi(2)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::clustering::SortOrder;
+    use crate::quantize::{exact, median_cut, octree};
+
+    fn bytes(colours: &[([u8; 3], usize)]) -> Vec<u8> {
+        colours
+            .iter()
+            .flat_map(|([r, g, b], count)| std::iter::repeat_n([*r, *g, *b, 255], *count))
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn it_finds_each_colour_exactly() {
+        let img_bytes = bytes(&[([255, 0, 0], 30), ([0, 0, 255], 10), ([0, 255, 0], 1)]);
+
+        for (name, algorithm) in [("median-cut", median_cut as fn(&[u8], usize) -> _), ("octree", octree)] {
+            let mut clustering = algorithm(&img_bytes, 5);
+            clustering.sort(SortOrder::Size);
+
+            assert_eq!(
+                clustering.centroids,
+                vec![Srgb::new(255, 0, 0), Srgb::new(0, 0, 255), Srgb::new(0, 255, 0)],
+                "algorithm = {}",
+                name
+            );
+            assert_eq!(clustering.counts, vec![30, 10, 1], "algorithm = {}", name);
+            assert_eq!(clustering.error, 0.0, "algorithm = {}", name);
+        }
+    }
+
+    #[test]
+    fn it_merges_colours_to_get_few_enough() {
+        let img_bytes = bytes(&[([250, 0, 0], 10), ([254, 0, 0], 10), ([0, 0, 255], 20)]);
+
+        for (name, algorithm) in [("median-cut", median_cut as fn(&[u8], usize) -> _), ("octree", octree)] {
+            let mut clustering = algorithm(&img_bytes, 2);
+            clustering.sort(SortOrder::Size);
+
+            assert_eq!(clustering.centroids.len(), 2, "algorithm = {}", name);
+            assert_eq!(clustering.counts, vec![20, 20], "algorithm = {}", name);
+            assert!(clustering.centroids.contains(&Srgb::new(252, 0, 0)), "algorithm = {}", name);
+        }
+    }
+
+    #[test]
+    fn it_counts_the_exact_colours() {
+        let img_bytes = bytes(&[([0, 0, 255], 10), ([255, 0, 0], 30), ([1, 0, 0], 10), ([0, 255, 0], 1)]);
+
+        let clustering = exact(&img_bytes, Some(3), 100).unwrap();
+
+        assert_eq!(clustering.centroids, vec![Srgb::new(255, 0, 0), Srgb::new(0, 0, 255), Srgb::new(1, 0, 0)]);
+        assert_eq!(clustering.counts, vec![30, 10, 10]);
+
+        assert_eq!(exact(&img_bytes, None, 100).unwrap().counts, vec![30, 10, 10, 1]);
+    }
+
+    #[test]
+    fn it_stops_counting_if_there_are_too_many_colours() {
+        let img_bytes = bytes(&[([0, 0, 255], 10), ([255, 0, 0], 30), ([0, 255, 0], 1)]);
+
+        assert!(exact(&img_bytes, Some(1), 3).is_some());
+        assert!(exact(&img_bytes, Some(1), 2).is_none());
+    }
+}
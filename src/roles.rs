@@ -0,0 +1,172 @@
+use clap::ValueEnum;
+use palette::Srgb;
+
+use crate::format::hsl_components;
+
+// What to print, with --mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    // The dominant colours, biggest first.
+    Colours,
+
+    // A colour for each of the roles below, for theming an app.
+    Roles,
+
+    // The most common exact colours, without any clustering -- see
+    // `quantize::exact`.
+    Exact,
+
+    // The colour around the edge of the image -- see `sampling::border`.
+    Background,
+}
+
+// With --mode=roles, rather than printing the most common colours, we
+// pick a colour for each of six named roles -- Vibrant, Dark Vibrant and
+// so on -- the same as Android's Palette API, so you can theme an app
+// around an image.
+//
+// Each role has a range of saturation and lightness (in HSL) that it will
+// accept, and a target within that range.  We score every colour in range
+// by how close it is to the targets and how much of the image it covers,
+// and the best colour gets the role.  Each colour can only fill one role,
+// so we pick them in order, and a role with no colour in range is empty.
+//
+// We use the same ranges and weights as Android, so you get roughly the
+// same colours as an Android app would.
+//
+// See https://developer.android.com/reference/androidx/palette/graphics/Target
+
+// How many colours we look for in the image before picking the roles,
+// unless you pass --max-colours.  This is the same as Android's default;
+// it's more than we'd normally print, so there's a good chance of finding
+// a colour for the rarer roles.
+pub const DEFAULT_COLOUR_COUNT: usize = 16;
+
+// How much the saturation, lightness and size of each colour count
+// towards its score.
+const SATURATION_WEIGHT: f32 = 0.24;
+const LIGHTNESS_WEIGHT: f32 = 0.52;
+const POPULATION_WEIGHT: f32 = 0.24;
+
+// The smallest, ideal and largest value that a role will accept, as a
+// fraction from 0 to 1.
+struct Range {
+    min: f32,
+    target: f32,
+    max: f32,
+}
+
+impl Range {
+    fn contains(&self, value: f32) -> bool {
+        self.min <= value && value <= self.max
+    }
+}
+
+pub struct Role {
+    // How we print the role in the text output, e.g. `Dark Vibrant`.
+    pub name: &'static str,
+
+    // How we write the role as a JSON key, e.g. `dark_vibrant`.
+    pub key: &'static str,
+
+    saturation: Range,
+    lightness: Range,
+}
+
+const VIBRANT: Range = Range { min: 0.35, target: 1.0, max: 1.0 };
+const MUTED: Range = Range { min: 0.0, target: 0.3, max: 0.4 };
+
+const DARK: Range = Range { min: 0.0, target: 0.26, max: 0.45 };
+const NORMAL: Range = Range { min: 0.3, target: 0.5, max: 0.7 };
+const LIGHT: Range = Range { min: 0.55, target: 0.74, max: 1.0 };
+
+pub const ROLES: [Role; 6] = [
+    Role { name: "Vibrant", key: "vibrant", saturation: VIBRANT, lightness: NORMAL },
+    Role { name: "Dark Vibrant", key: "dark_vibrant", saturation: VIBRANT, lightness: DARK },
+    Role { name: "Light Vibrant", key: "light_vibrant", saturation: VIBRANT, lightness: LIGHT },
+    Role { name: "Muted", key: "muted", saturation: MUTED, lightness: NORMAL },
+    Role { name: "Dark Muted", key: "dark_muted", saturation: MUTED, lightness: DARK },
+    Role { name: "Light Muted", key: "light_muted", saturation: MUTED, lightness: LIGHT },
+];
+
+// Pick a colour for each role, in the same order as `ROLES`.  For each
+// colour, we return its index in `colours`, so you can find its proportion.
+pub fn choose(colours: &[Srgb<u8>], proportions: &[f32]) -> Vec<Option<usize>> {
+    let largest = proportions.iter().copied().fold(0.0, f32::max);
+
+    let mut taken = vec![false; colours.len()];
+    let mut chosen = Vec::new();
+
+    for role in &ROLES {
+        let best = (0..colours.len())
+            .filter(|i| !taken[*i])
+            .filter_map(|i| {
+                let (_, saturation, lightness) = hsl_components(colours[i]);
+                let (saturation, lightness) = (saturation / 100.0, lightness / 100.0);
+
+                if !role.saturation.contains(saturation) || !role.lightness.contains(lightness) {
+                    return None;
+                }
+
+                let score = SATURATION_WEIGHT * (1.0 - (saturation - role.saturation.target).abs())
+                    + LIGHTNESS_WEIGHT * (1.0 - (lightness - role.lightness.target).abs())
+                    + POPULATION_WEIGHT * (proportions[i] / largest);
+
+                Some((i, score))
+            })
+            .fold(None, |best: Option<(usize, f32)>, (i, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((i, score)),
+            });
+
+        if let Some((i, _)) = best {
+            taken[i] = true;
+        }
+
+        chosen.push(best.map(|(i, _)| i));
+    }
+
+    chosen
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::roles::{choose, ROLES};
+
+    #[test]
+    fn it_picks_a_colour_for_each_role() {
+        let colours = [
+            Srgb::new(0x7a, 0x8c, 0x99),
+            Srgb::new(0xe8, 0x43, 0x1f),
+            Srgb::new(0x0b, 0x3a, 0x8c),
+            Srgb::new(0x7f, 0xe0, 0xf5),
+            Srgb::new(0x3b, 0x3a, 0x33),
+            Srgb::new(0xd9, 0xcf, 0xc0),
+        ];
+
+        let chosen = choose(&colours, &[0.3, 0.25, 0.15, 0.1, 0.1, 0.1]);
+
+        let names: Vec<&str> = ROLES.iter().map(|role| role.name).collect();
+        assert_eq!(names, vec!["Vibrant", "Dark Vibrant", "Light Vibrant", "Muted", "Dark Muted", "Light Muted"]);
+        assert_eq!(chosen, vec![Some(1), Some(2), Some(3), Some(0), Some(4), Some(5)]);
+    }
+
+    #[test]
+    fn it_uses_each_colour_once() {
+        // This orange is the only vibrant colour, and it's closest to
+        // Vibrant, so the other vibrant roles are empty.
+        let chosen = choose(&[Srgb::new(0xe8, 0x43, 0x1f)], &[1.0]);
+
+        assert_eq!(chosen, vec![Some(0), None, None, None, None, None]);
+    }
+
+    #[test]
+    fn it_prefers_bigger_colours() {
+        let (small, big) = (Srgb::new(0xe8, 0x43, 0x1f), Srgb::new(0xe0, 0x48, 0x28));
+
+        assert_eq!(choose(&[small, big], &[0.1, 0.9])[0], Some(1));
+        assert_eq!(choose(&[small, big], &[0.9, 0.1])[0], Some(0));
+    }
+}
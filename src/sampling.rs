@@ -0,0 +1,283 @@
+use image::RgbaImage;
+use rand::seq::index;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// Pick a uniform random sample of (at most) `sample_size` pixels from
+// a flat buffer of RGBA bytes.
+//
+// A big image has millions of pixels, and converting all of them to Lab
+// and clustering them uses a lot of memory for very little benefit -- a
+// few hundred thousand pixels give an essentially identical palette.
+//
+// Images which are already small enough are returned unchanged, and the
+// sample is seeded so a fixed --seed gives reproducible output.
+pub fn sample(bytes: Vec<u8>, sample_size: usize, seed: u64) -> Vec<u8> {
+    let pixel_count = bytes.len() / 4;
+
+    if pixel_count <= sample_size {
+        return bytes;
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    // Keep the pixels in their original order; it doesn't matter to
+    // k-means, but it makes the sample easier to reason about.
+    let mut indices = index::sample(&mut rng, pixel_count, sample_size).into_vec();
+    indices.sort_unstable();
+
+    indices
+        .into_iter()
+        .flat_map(|i| bytes[i * 4..i * 4 + 4].iter().copied())
+        .collect()
+}
+
+// Keep each pixel with probability `rate`, so e.g. a rate of 0.05 looks at
+// roughly 5% of the pixels, however big the image is.
+//
+// This runs after we've dropped any pixels we don't want (e.g. transparent
+// ones), so the rate applies to the pixels we'd otherwise look at.  For an
+// animated GIF, the frames are already joined together, so each frame is
+// sampled at the same rate.
+//
+// Returns None if we didn't keep any pixels, so the caller can decide
+// what to do instead.
+pub fn sample_rate(bytes: &[u8], rate: f64, seed: u64) -> Option<Vec<u8>> {
+    if bytes.is_empty() {
+        return Some(vec![]);
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let sampled: Vec<u8> = bytes
+        .chunks_exact(4)
+        .filter(|_| rng.gen_bool(rate))
+        .flatten()
+        .copied()
+        .collect();
+
+    if sampled.is_empty() {
+        None
+    } else {
+        Some(sampled)
+    }
+}
+
+// With --mode=background, we only look at a band around the edge of the
+// image, because that's usually where the background is -- e.g. in a
+// product photo, the product fills the middle and the backdrop is all
+// around it.
+//
+// The band down the left and right is `width` percent of the image's
+// width, and the band along the top and bottom is the same percentage of
+// its height, but each is always at least one pixel.
+pub const DEFAULT_BORDER_WIDTH: &str = "5%";
+
+pub fn border(image: &RgbaImage, width: f64) -> Vec<u8> {
+    let band = |size: u32| ((size as f64 * width / 100.0).round() as u32).max(1);
+    let (left, top) = (band(image.width()), band(image.height()));
+    let right = image.width().saturating_sub(left);
+    let bottom = image.height().saturating_sub(top);
+
+    image
+        .enumerate_pixels()
+        .filter(|(x, y, _)| *x < left || *x >= right || *y < top || *y >= bottom)
+        .flat_map(|(_, _, px)| px.0)
+        .collect()
+}
+
+// With --center-weight, we look at more of the pixels near the centre of
+// the image, because that's usually where the subject of a photo is, and
+// the edges are often sky or pavement or blurred background.
+//
+// We keep each pixel with a probability that falls off with its distance
+// from the centre, along a Gaussian curve: a weight of 0 keeps every pixel,
+// and a weight of 1 keeps the pixels in the very centre and hardly any in
+// the corners.  The distance is measured as a fraction of the distance to
+// the edge, so the falloff is the same in a wide image as in a tall one.
+//
+// This runs on each frame of an animation separately, so every frame is
+// weighted around its own centre.
+const CENTER_FALLOFF: f64 = 0.5;
+
+pub fn center_weighted(image: &RgbaImage, weight: f64, seed: u64) -> Vec<u8> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let (center_x, center_y) = (image.width() as f64 / 2.0, image.height() as f64 / 2.0);
+
+    let kept: Vec<u8> = image
+        .enumerate_pixels()
+        .filter(|(x, y, _)| {
+            let dx = (*x as f64 + 0.5 - center_x) / center_x;
+            let dy = (*y as f64 + 0.5 - center_y) / center_y;
+            let falloff = (-(dx * dx + dy * dy) / (2.0 * CENTER_FALLOFF * CENTER_FALLOFF)).exp();
+
+            rng.gen_bool(1.0 - weight + weight * falloff)
+        })
+        .flat_map(|(_, _, px)| px.0)
+        .collect();
+
+    // A tiny image might not have a pixel close enough to the centre to be
+    // sure of keeping it, but we always want something to look at.
+    if kept.is_empty() {
+        image.as_raw().clone()
+    } else {
+        kept
+    }
+}
+
+pub fn parse_center_weight(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(weight) if (0.0..=1.0).contains(&weight) => Ok(weight),
+        Ok(_) => Err(String::from("must be between 0 and 1")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Parse --border-width, as a percentage with or without the `%`.  A band
+// wider than 50% would cover the whole image.
+pub fn parse_border_width(s: &str) -> Result<f64, String> {
+    match s.strip_suffix('%').unwrap_or(s).parse::<f64>() {
+        Ok(width) if width > 0.0 && width <= 50.0 => Ok(width),
+        Ok(_) => Err(String::from("must be greater than 0% and at most 50%")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate <= 1.0 => Ok(rate),
+        Ok(_) => Err(String::from("must be greater than 0 and at most 1")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::sampling::{border, center_weighted, parse_border_width, parse_center_weight, parse_rate, sample, sample_rate};
+
+    fn pixels(count: u8) -> Vec<u8> {
+        (0..count).flat_map(|i| [i, i, i, 255]).collect()
+    }
+
+    #[test]
+    fn it_only_looks_at_the_border() {
+        // A 20x10 image with a 1-pixel black edge and a white middle; 5% of
+        // the width and height rounds to 1 pixel.
+        let image = RgbaImage::from_fn(20, 10, |x, y| {
+            if x == 0 || x == 19 || y == 0 || y == 9 {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        });
+
+        let pixels = border(&image, 5.0);
+        assert_eq!(pixels.len() / 4, 20 * 10 - 18 * 8);
+        assert!(pixels.chunks_exact(4).all(|px| px == [0, 0, 0, 255]));
+
+        // The band is always at least one pixel wide, and a band of 50%
+        // covers the whole image.
+        assert_eq!(border(&image, 0.1).len() / 4, 20 * 10 - 18 * 8);
+        assert_eq!(border(&image, 50.0).len() / 4, 20 * 10);
+    }
+
+    #[test]
+    fn it_weights_the_pixels_near_the_center() {
+        // The middle of the image covers about a third of the pixels, but
+        // with a weight of 1, we keep more of the middle than the rest.
+        let image = RgbaImage::from_fn(100, 100, |x, y| {
+            if (20..80).contains(&x) && (20..80).contains(&y) {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        });
+
+        assert_eq!(center_weighted(&image, 0.0, 0), image.as_raw().clone());
+        assert_eq!(center_weighted(&image, 1.0, 1), center_weighted(&image, 1.0, 1));
+
+        let kept = center_weighted(&image, 1.0, 0);
+        let red = kept.chunks_exact(4).filter(|px| px[0] == 255).count();
+        let blue = kept.len() / 4 - red;
+
+        assert!(red > blue, "red = {}, blue = {}", red, blue);
+
+        // A tiny image always keeps something.
+        let tiny = RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 255]));
+        assert!(!center_weighted(&tiny, 1.0, 0).is_empty());
+    }
+
+    #[test]
+    fn it_parses_a_center_weight() {
+        assert_eq!(parse_center_weight("0"), Ok(0.0));
+        assert_eq!(parse_center_weight("0.5"), Ok(0.5));
+        assert_eq!(parse_center_weight("1"), Ok(1.0));
+
+        assert!(parse_center_weight("-0.1").is_err());
+        assert!(parse_center_weight("1.5").is_err());
+        assert!(parse_center_weight("lots").is_err());
+    }
+
+    #[test]
+    fn it_parses_a_border_width() {
+        assert_eq!(parse_border_width("5"), Ok(5.0));
+        assert_eq!(parse_border_width("12.5%"), Ok(12.5));
+        assert_eq!(parse_border_width("50%"), Ok(50.0));
+
+        assert!(parse_border_width("0").is_err());
+        assert!(parse_border_width("51%").is_err());
+        assert!(parse_border_width("wide").is_err());
+    }
+
+    #[test]
+    fn it_leaves_small_images_alone() {
+        assert_eq!(sample(pixels(10), 10, 0), pixels(10));
+        assert_eq!(sample(pixels(10), 100, 0), pixels(10));
+    }
+
+    #[test]
+    fn it_samples_whole_pixels() {
+        let sampled = sample(pixels(100), 10, 0);
+
+        assert_eq!(sampled.len(), 40);
+        assert!(sampled.chunks_exact(4).all(|px| px[0] == px[1] && px[1] == px[2] && px[3] == 255));
+    }
+
+    #[test]
+    fn it_is_reproducible_for_a_fixed_seed() {
+        assert_eq!(sample(pixels(100), 10, 1), sample(pixels(100), 10, 1));
+        assert_ne!(sample(pixels(100), 10, 1), sample(pixels(100), 10, 2));
+    }
+
+    #[test]
+    fn it_samples_at_a_rate() {
+        assert_eq!(sample_rate(&pixels(100), 1.0, 0), Some(pixels(100)));
+
+        let sampled = sample_rate(&pixels(200), 0.5, 0).unwrap();
+        let count = sampled.len() / 4;
+
+        assert!((50..150).contains(&count), "count = {}", count);
+        assert!(sampled.chunks_exact(4).all(|px| px[0] == px[1] && px[1] == px[2] && px[3] == 255));
+
+        assert_eq!(sample_rate(&pixels(200), 0.5, 1), sample_rate(&pixels(200), 0.5, 1));
+        assert_ne!(sample_rate(&pixels(200), 0.5, 1), sample_rate(&pixels(200), 0.5, 2));
+    }
+
+    #[test]
+    fn it_returns_none_if_no_pixels_survive() {
+        assert_eq!(sample_rate(&pixels(1), 0.000001, 0), None);
+        assert_eq!(sample_rate(&[], 0.5, 0), Some(vec![]));
+    }
+
+    #[test]
+    fn it_parses_the_rate() {
+        assert_eq!(parse_rate("0.05"), Ok(0.05));
+        assert_eq!(parse_rate("1"), Ok(1.0));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("1.5").is_err());
+        assert!(parse_rate("NaN").is_err());
+    }
+}
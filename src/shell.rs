@@ -0,0 +1,109 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::hex;
+
+// With --format=sh, we print the colours as shell variable assignments, so
+// you can load them into a script with
+// `eval "$(dominant_colours pic.png --format=sh)"`:
+//
+//      DOMINANT_COLOUR_1='#d0c6b2'
+//      DOMINANT_COLOUR_1_R=208
+//      DOMINANT_COLOUR_1_G=198
+//      DOMINANT_COLOUR_1_B=178
+//
+// This is plain POSIX sh.  With --format=bash, we also print an array of
+// all the colours, which you can loop over:
+//
+//      DOMINANT_COLOURS=('#d0c6b2' '#3f3336')
+//
+// Every string is single-quoted, so nothing in it is expanded by the shell.
+// The colours are numbered from 1 in the order we print them, the same as
+// --format=css.
+//
+// See https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_02_02_02
+
+pub const DEFAULT_PREFIX: &str = "DOMINANT_COLOUR";
+
+pub fn variables(colours: &[Srgb<u8>], prefix: &str, include_array: bool) -> String {
+    let mut sh = String::new();
+
+    for (i, c) in colours.iter().enumerate() {
+        let name = format!("{}_{}", prefix, i + 1);
+
+        writeln!(sh, "{}={}", name, quote(&hex(*c))).unwrap();
+        writeln!(sh, "{}_R={}", name, c.red).unwrap();
+        writeln!(sh, "{}_G={}", name, c.green).unwrap();
+        writeln!(sh, "{}_B={}", name, c.blue).unwrap();
+    }
+
+    if include_array {
+        let values: Vec<String> = colours.iter().map(|c| quote(&hex(*c))).collect();
+
+        writeln!(sh, "{}S=({})", prefix, values.join(" ")).unwrap();
+    }
+
+    sh
+}
+
+// Quote a string so the shell reads it back exactly.  Nothing is special
+// inside single quotes, but they can't contain a single quote, so we end
+// the quoted string, add an escaped quote, and start a new one.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// Parse the start of the variable names with --var-prefix.  It has to be
+// a valid shell variable name, so it can't start with a digit.
+pub fn parse_prefix(s: &str) -> Result<String, String> {
+    let is_valid = s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !is_valid {
+        return Err(String::from(
+            "should start with a letter or underscore, and only use letters, numbers and underscores, e.g. WALLPAPER",
+        ));
+    }
+
+    Ok(s.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::shell::{parse_prefix, quote, variables};
+
+    #[test]
+    fn it_prints_variables() {
+        let colours = [Srgb::new(255, 0, 0), Srgb::new(0, 255, 0)];
+
+        assert_eq!(
+            variables(&colours, "BRAND", false),
+            "BRAND_1='#ff0000'\nBRAND_1_R=255\nBRAND_1_G=0\nBRAND_1_B=0\n\
+             BRAND_2='#00ff00'\nBRAND_2_R=0\nBRAND_2_G=255\nBRAND_2_B=0\n"
+        );
+
+        assert!(variables(&colours, "BRAND", true).ends_with("\nBRANDS=('#ff0000' '#00ff00')\n"));
+    }
+
+    #[test]
+    fn it_quotes_strings() {
+        assert_eq!(quote("#ff0000"), "'#ff0000'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn it_parses_a_prefix() {
+        assert_eq!(parse_prefix("WALLPAPER"), Ok(String::from("WALLPAPER")));
+        assert_eq!(parse_prefix("_my_colour2"), Ok(String::from("_my_colour2")));
+
+        assert!(parse_prefix("").is_err());
+        assert!(parse_prefix("1COLOUR").is_err());
+        assert!(parse_prefix("MY-COLOUR").is_err());
+        assert!(parse_prefix("MY COLOUR").is_err());
+        assert!(parse_prefix("$(rm -rf ~)").is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageResult, RgbaImage};
+use resvg::tiny_skia::{Pixmap, Size, Transform};
+use resvg::usvg;
+
+// SVGs are vector images, so they don't have any pixels until we draw
+// them.  We draw them with resvg, scaled to fit in a `size` square (which
+// you can change with --svg-size), then treat them like any other image.
+//
+// We don't draw text or embedded raster images, which would need a lot
+// more dependencies, and are rare in the sort of icons and logos you'd
+// look at with this tool.
+//
+// See https://github.com/linebender/resvg
+
+pub fn decode(bytes: &[u8], size: u32) -> ImageResult<DynamicImage> {
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default()).map_err(format_error)?;
+
+    let target = Size::from_wh(size as f32, size as f32).expect("--svg-size is at least 1");
+    let scaled = tree.size().scale_to(target).to_int_size();
+
+    let mut pixmap = Pixmap::new(scaled.width(), scaled.height()).ok_or_else(|| format_error("Image is too large to draw"))?;
+
+    let transform = Transform::from_scale(
+        scaled.width() as f32 / tree.size().width(),
+        scaled.height() as f32 / tree.size().height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // resvg draws with premultiplied alpha, but everything else expects
+    // straight alpha.
+    let img = RgbaImage::from_raw(scaled.width(), scaled.height(), pixmap.take_demultiplied())
+        .ok_or_else(|| format_error("Drawn image is the wrong size"))?;
+
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+fn format_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Name(String::from("SVG")), e))
+}
@@ -0,0 +1,60 @@
+use std::io;
+use std::path::Path;
+
+use image::{ImageFormat, Rgb, RgbImage};
+use palette::Srgb;
+
+use crate::errors::Error;
+
+// With --save-swatch, we draw the colours as a strip of square blocks,
+// left to right in the order we print them, and save it as a PNG -- so
+// with the default --sort=size, the most dominant colour is on the left.
+
+pub const DEFAULT_BLOCK_SIZE: &str = "64";
+
+pub fn draw(colours: &[Srgb<u8>], block_size: u32) -> RgbImage {
+    let width = block_size * colours.len().max(1) as u32;
+
+    RgbImage::from_fn(width, block_size, |x, _| match colours.get((x / block_size) as usize) {
+        Some(c) => Rgb([c.red, c.green, c.blue]),
+        None => Rgb([0, 0, 0]),
+    })
+}
+
+pub fn save(path: &Path, colours: &[Srgb<u8>], block_size: u32) -> Result<(), Error> {
+    let io_error = |error| Error::Io { path: path.to_path_buf(), error };
+
+    // Otherwise the error is just "No such file or directory", which
+    // doesn't say which directory is missing.
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if !parent.is_dir() {
+            return Err(io_error(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("couldn't save the swatch, because the directory {} doesn't exist", parent.display()),
+            )));
+        }
+    }
+
+    draw(colours, block_size)
+        .save_with_format(path, ImageFormat::Png)
+        .map_err(|e| match e {
+            image::ImageError::IoError(error) => io_error(error),
+            e => io_error(io::Error::other(e)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::swatch_image::draw;
+
+    #[test]
+    fn it_draws_a_block_for_each_colour() {
+        let swatch = draw(&[Srgb::new(255, 0, 0), Srgb::new(0, 0, 255)], 4);
+
+        assert_eq!(swatch.dimensions(), (8, 4));
+        assert_eq!(swatch.get_pixel(3, 3).0, [255, 0, 0]);
+        assert_eq!(swatch.get_pixel(4, 0).0, [0, 0, 255]);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::hex;
+use crate::json::ImageColours;
+
+// With --format=csv or tsv, we print a table for spreadsheets, with a row
+// for every colour in every image, e.g.
+//
+//      path,index,hex,r,g,b,percentage
+//      cats.jpg,1,#d0c6b2,208,198,178,73.2
+//      cats.jpg,2,#3f3336,63,51,54,26.8
+//
+// There's always a path column, even for a single image, so the columns
+// are the same however you run it.  The index counts from 1, and the
+// percentage is empty if we don't know it (e.g. for terminal colours).
+//
+// Paths can contain commas, quotes and even newlines, so we leave the
+// quoting to the `csv` crate.
+
+const HEADER: [&str; 7] = ["path", "index", "hex", "r", "g", "b", "percentage"];
+
+pub fn table(images: &[ImageColours], delimiter: u8, include_header: bool) -> String {
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(vec![]);
+
+    if include_header {
+        writer.write_record(HEADER).unwrap();
+    }
+
+    for (path, colours, proportions) in images {
+        let path = path.to_string_lossy();
+
+        for (i, c) in colours.iter().enumerate() {
+            let percentage = match proportions {
+                Some(p) => format!("{:.1}", p[i] * 100.0),
+                None => String::new(),
+            };
+
+            writer
+                .write_record([
+                    path.as_ref(),
+                    &(i + 1).to_string(),
+                    &hex(*c),
+                    &c.red.to_string(),
+                    &c.green.to_string(),
+                    &c.blue.to_string(),
+                    &percentage,
+                ])
+                .unwrap();
+        }
+    }
+
+    // We're writing to a Vec and every field is a valid string, so neither
+    // of these can fail.
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use palette::Srgb;
+
+    use crate::table::table;
+
+    #[test]
+    fn it_prints_a_row_for_each_colour() {
+        let images = [
+            (PathBuf::from("red.png"), vec![Srgb::new(255, 0, 0)], Some(vec![1.0])),
+            (PathBuf::from("mixed.png"), vec![Srgb::new(0, 0, 255), Srgb::new(255, 0, 0)], None),
+        ];
+
+        assert_eq!(
+            table(&images, b',', true),
+            "path,index,hex,r,g,b,percentage\nred.png,1,#ff0000,255,0,0,100.0\nmixed.png,1,#0000ff,0,0,255,\nmixed.png,2,#ff0000,255,0,0,\n"
+        );
+
+        assert_eq!(table(&images[..1], b'\t', false), "red.png\t1\t#ff0000\t255\t0\t0\t100.0\n");
+    }
+
+    #[test]
+    fn it_quotes_awkward_paths() {
+        let images = [(PathBuf::from("red, \"dark\".png"), vec![Srgb::new(255, 0, 0)], None)];
+
+        assert_eq!(table(&images, b',', false), "\"red, \"\"dark\"\".png\",1,#ff0000,255,0,0,\n");
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use palette::Srgb;
+
+use crate::format::hsl_components;
+use crate::hex;
+
+// With --template, we print one line per colour, filling in placeholders
+// like `{hex}` or `{r}`, so you can get whatever format another tool wants:
+//
+//      $ dominant_colours cats.jpg --template "{hex} {r},{g},{b} {percent}"
+//      #d0c6b2 208,198,178 73.2
+//      #3f3336 63,51,54 26.8
+//
+// As in Rust's format strings, `{{` and `}}` are literal braces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template(Vec<Part>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placeholder {
+    Hex,
+    UpperHex,
+    Red,
+    Green,
+    Blue,
+    Hue,
+    Saturation,
+    Lightness,
+    Index,
+    Percent,
+    Path,
+}
+
+const PLACEHOLDERS: &[(&str, Placeholder)] = &[
+    ("hex", Placeholder::Hex),
+    ("HEX", Placeholder::UpperHex),
+    ("r", Placeholder::Red),
+    ("g", Placeholder::Green),
+    ("b", Placeholder::Blue),
+    ("h", Placeholder::Hue),
+    ("s", Placeholder::Saturation),
+    ("l", Placeholder::Lightness),
+    ("index", Placeholder::Index),
+    ("percent", Placeholder::Percent),
+    ("path", Placeholder::Path),
+];
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut is_closed = false;
+
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            is_closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+
+                    if !is_closed {
+                        return Err(format!("unclosed {{{}; {}", name, usage()));
+                    }
+
+                    let placeholder = PLACEHOLDERS
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, p)| *p)
+                        .ok_or_else(|| format!("unknown placeholder {{{}}}; {}", name, usage()))?;
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(placeholder));
+                }
+                '}' => return Err(format!("unmatched }}; {}", usage())),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template(parts))
+    }
+
+    // Fill in the template for the colour at `index` (counting from 0).
+    // If we don't know the proportion, `{percent}` is left empty.
+    pub fn render(&self, c: Srgb<u8>, index: usize, proportion: Option<f32>, path: &Path) -> String {
+        let mut line = String::new();
+        let (h, s, l) = hsl_components(c);
+
+        for part in &self.0 {
+            match part {
+                Part::Literal(text) => line.push_str(text),
+                Part::Placeholder(Placeholder::Hex) => line.push_str(&hex(c)),
+                Part::Placeholder(Placeholder::UpperHex) => line.push_str(&hex(c).to_uppercase()),
+                Part::Placeholder(Placeholder::Red) => write!(line, "{}", c.red).unwrap(),
+                Part::Placeholder(Placeholder::Green) => write!(line, "{}", c.green).unwrap(),
+                Part::Placeholder(Placeholder::Blue) => write!(line, "{}", c.blue).unwrap(),
+                Part::Placeholder(Placeholder::Hue) => write!(line, "{:.1}", h).unwrap(),
+                Part::Placeholder(Placeholder::Saturation) => write!(line, "{:.0}", s).unwrap(),
+                Part::Placeholder(Placeholder::Lightness) => write!(line, "{:.0}", l).unwrap(),
+                Part::Placeholder(Placeholder::Index) => write!(line, "{}", index + 1).unwrap(),
+                Part::Placeholder(Placeholder::Percent) => {
+                    if let Some(p) = proportion {
+                        write!(line, "{:.1}", p * 100.0).unwrap();
+                    }
+                }
+                Part::Placeholder(Placeholder::Path) => write!(line, "{}", path.display()).unwrap(),
+            }
+        }
+
+        line
+    }
+}
+
+fn usage() -> String {
+    let names: Vec<String> = PLACEHOLDERS.iter().map(|(name, _)| format!("{{{}}}", name)).collect();
+
+    format!(
+        "the placeholders are {} and {}, and {{{{ and }}}} are literal braces",
+        names[..names.len() - 1].join(", "),
+        names[names.len() - 1]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use palette::Srgb;
+
+    use crate::template::Template;
+
+    fn render(template: &str, c: Srgb<u8>) -> String {
+        Template::parse(template).unwrap().render(c, 0, Some(0.614), Path::new("cats.jpg"))
+    }
+
+    #[test]
+    fn it_fills_in_the_placeholders() {
+        let c = Srgb::new(18, 52, 86);
+
+        assert_eq!(render("{hex} {HEX}", c), "#123456 #123456");
+        assert_eq!(render("{r},{g},{b}", Srgb::new(255, 0, 10)), "255,0,10");
+        assert_eq!(render("hsl({h}, {s}%, {l}%)", c), "hsl(210.0, 65%, 20%)");
+        assert_eq!(render("{index}: {percent}% of {path}", c), "1: 61.4% of cats.jpg");
+        assert_eq!(render("{HEX}", Srgb::new(171, 205, 239)), "#ABCDEF");
+    }
+
+    #[test]
+    fn it_leaves_out_an_unknown_percentage() {
+        let template = Template::parse("{hex} {percent}").unwrap();
+
+        assert_eq!(template.render(Srgb::new(255, 0, 0), 0, None, Path::new("-")), "#ff0000 ");
+    }
+
+    #[test]
+    fn it_escapes_braces() {
+        let c = Srgb::new(255, 0, 0);
+
+        assert_eq!(render("{{hex}} = {hex}", c), "{hex} = #ff0000");
+        assert_eq!(render("{{{hex}}}", c), "{#ff0000}");
+        assert_eq!(render("no placeholders", c), "no placeholders");
+    }
+
+    #[test]
+    fn it_rejects_unknown_placeholders() {
+        let err = Template::parse("{hex} {red}").unwrap_err();
+
+        assert_eq!(
+            err,
+            "unknown placeholder {red}; the placeholders are {hex}, {HEX}, {r}, {g}, {b}, {h}, {s}, {l}, {index}, {percent} and {path}, and {{ and }} are literal braces"
+        );
+
+        assert!(Template::parse("{hex").unwrap_err().starts_with("unclosed {hex;"));
+        assert!(Template::parse("hex}").unwrap_err().starts_with("unmatched }"));
+    }
+}
@@ -1,7 +1,19 @@
-use palette::Srgb;
+use std::path::Path;
+
+use clap::ValueEnum;
+use palette::{FromColor, Hsv, Lab, Lch, Mix, Srgb};
+
+use crate::background;
+use crate::contrast;
+use crate::distance::{to_lab, to_srgb, Distance, GREY_CHROMA};
+use crate::errors::Error;
+use crate::parse_hex;
+
+// The 16 colours we match against, in the order of the terminal's slots.
+pub type TerminalPalette = [[u8; 3]; 16];
 
 // https://en.wikipedia.org/wiki/ANSI_escape_code#Colors
-const ANSI_COLOUR_TABLE: [[u8; 3]; 16] = [
+pub const ANSI_COLOUR_TABLE: TerminalPalette = [
     // Darker colours
     [0, 0, 0], // Black
     [170, 0, 0], // Red
@@ -23,47 +35,651 @@ const ANSI_COLOUR_TABLE: [[u8; 3]; 16] = [
     [255, 255, 255], // Bright White
 ];
 
-// Takes a vector of colours in the Srgb<u8> format and returns a vector of colours
-// in the same format, but mapped to the closest colour in the ANSI colour table.
-pub fn create_terminal_colour(colours: Vec<Srgb<u8>>, max_brightness : bool) -> Vec<Srgb<u8>> {
-    let colours : Vec<[u8; 3]> = colours.iter().map(|rgb| [rgb.red, rgb.green, rgb.blue]).collect();
+// How many of the terminal's colours to generate, with --terminal-colours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SlotCount {
+    // The 16 ANSI colours.
+    #[value(name = "16")]
+    Ansi,
+
+    // The 16 ANSI colours, then the rest of xterm's 256 colours -- see
+    // `extend_to_256`.
+    #[value(name = "256")]
+    Xterm,
+}
 
+// The lowest contrast ratio we allow between colour 0 (the background) and
+// the coloured text in slots 1-6.  This is what WCAG asks for large text,
+// which is lower than for normal text, because otherwise dark blues and
+// reds would have to be much lighter than anyone expects.
+const MIN_COLOUR_CONTRAST: f32 = 3.0;
+
+// The default for --min-contrast, which is what WCAG AA asks for normal
+// text.  See https://www.w3.org/TR/WCAG21/#contrast-minimum
+pub const DEFAULT_MIN_CONTRAST: &str = "4.5";
+
+// With --brightness and --saturation, how far to push every slot except
+// the two blacks (0 and 8) towards full brightness and saturation, as a fraction
+// from 0 (leave them alone) to 1 (as bright or saturated as they can be).
+//
+// With --min-brightness, the lightest that colours 1-6 and 9-14 are
+// allowed to be, as L* in Lab from 0 to 100 -- see `raise_lightness`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Adjustment {
+    pub brightness: f32,
+    pub saturation: f32,
+    pub min_lightness: f32,
+}
+
+// The black, white and greys: the background (0), the foreground (7) and
+// their bright versions (8 and 15).  With --keep-neutrals, these keep the
+// palette's own colours, rather than taking a tint from the image.
+const NEUTRAL_SLOTS: [usize; 4] = [0, 7, 8, 15];
+
+// Takes a vector of colours in the Srgb<u8> format and returns a vector of colours
+// in the same format, but mapped to the closest colour in the palette (usually
+// the ANSI colour table).
+//
+// If `keep_neutrals` is set, the neutral slots are copied from the palette
+// as-is, and we don't adjust them afterwards.
+//
+// A low-contrast image can give you a background and foreground which are
+// almost the same, so afterwards we make sure colours 7 and 15 have at
+// least `min_contrast` with colour 0, and colours 1-6 have at least
+// `MIN_COLOUR_CONTRAST` (or `min_contrast`, if that's lower) -- see
+// `ensure_contrast`.
+pub fn create_terminal_colour(colours: Vec<Srgb<u8>>, palette: &TerminalPalette, adjustment: Adjustment, distance: Distance, min_contrast: f32, keep_neutrals: bool) -> Vec<Srgb<u8>> {
     let mut result: Vec<Srgb<u8>> = Vec::new();
 
-    // If max_brightness is set, replace 8 > i > 0 with i + 8
-    let colour_table: Vec<[u8; 3]> = if max_brightness {
-        ANSI_COLOUR_TABLE.iter().enumerate().map(|(i, &x)| if i > 0 && i < 8 { ANSI_COLOUR_TABLE[i + 8] } else { x }).collect()
-    } else { ANSI_COLOUR_TABLE.to_vec() };
+    let is_pinned = |i: usize| keep_neutrals && NEUTRAL_SLOTS.contains(&i);
 
-    for unix_colour in colour_table {
+    for (i, unix_colour) in palette.iter().enumerate() {
 
         // Find the colour with the closest distance to the ANSI colour table
-        let mut smallest_distance = f64::MAX;
-        let mut closest_colour = [0,0,0];
-        for colour in &colours {
-            let distance = colour_distance(unix_colour, *colour);
-            if distance < smallest_distance {
-                smallest_distance = distance;
-                closest_colour = *colour;
-            }
+        let target = Srgb::new(unix_colour[0], unix_colour[1], unix_colour[2]);
+
+        if is_pinned(i) {
+            result.push(target);
+            continue;
+        }
+
+        let closest_colour = match distance.nearest(target, &colours) {
+            Some(i) => colours[i],
+            None => Srgb::new(0, 0, 0),
+        };
+        result.push(closest_colour);
+    }
+
+    for i in (1..=7).chain(9..=15).filter(|i| !is_pinned(*i)) {
+        result[i] = adjust(result[i], adjustment);
+    }
+
+    // Colour 0 is the background, which is meant to be dark, and 7/15
+    // are the foreground, which `ensure_contrast` takes care of.
+    for i in (1..=6).chain(9..=14) {
+        result[i] = raise_lightness(result[i], adjustment.min_lightness);
+    }
+
+    if !keep_neutrals {
+        for i in [7, 15] {
+            let foreground = ensure_contrast(result[i], result[0], min_contrast);
+            result[0] = ensure_contrast(result[0], foreground, min_contrast);
+            result[i] = foreground;
         }
-        result.push(Srgb::new(closest_colour[0], closest_colour[1], closest_colour[2]));
+    }
+
+    for i in 1..=6 {
+        result[i] = ensure_contrast(result[i], result[0], min_contrast.min(MIN_COLOUR_CONTRAST));
     }
 
     result
 }
 
-// This function calculates the distance between two colours in the RGB colour space.
-// It uses a formula that takes into account the human perception of colour differences.
-fn colour_distance(c1: [u8; 3], c2: [u8; 3]) -> f64 {
-    let r = c1[0] as f64 - c2[0] as f64;
-    let g = c1[1] as f64 - c2[1] as f64;
-    let b = c1[2] as f64 - c2[2] as f64;
+// Push a colour towards full brightness and saturation in HSV, by moving
+// each one part of the way from where it is to 100%.  We scale rather than
+// set them to a fixed value, so a colour which was brighter than another
+// stays brighter, unless you go all the way.
+//
+// We use HSV rather than LCh, because the brightest a colour can be in
+// LCh is L* = 100, which is white whatever the hue; an HSV value of 100%
+// is the brightest colour with the same hue and saturation.
+//
+// A grey doesn't have a hue, so we don't saturate it -- otherwise it would
+// turn red, because that's where HSV puts hue 0.
+fn adjust(c: Srgb<u8>, adjustment: Adjustment) -> Srgb<u8> {
+    let mut hsv = Hsv::from_color(c.into_format::<f32>());
+
+    hsv.value += (1.0 - hsv.value) * adjustment.brightness;
+
+    if Lch::from_color(c.into_format::<f32>()).chroma >= GREY_CHROMA {
+        hsv.saturation += (1.0 - hsv.saturation) * adjustment.saturation;
+    }
+
+    Srgb::from_color(hsv).into_format()
+}
+
+// Raise a colour's lightness to `min_lightness` if it's darker, keeping its
+// hue, and as much of its chroma as fits in sRGB -- a very dark blue can be
+// much more colourful than a lighter one.  Colours which are already light
+// enough are left alone, so unlike --brightness, this only fixes the
+// colours which are too dark to read.
+fn raise_lightness(c: Srgb<u8>, min_lightness: f32) -> Srgb<u8> {
+    let lch = Lch::from_color(c.into_format::<f32>());
+
+    if lch.l >= min_lightness {
+        return c;
+    }
+
+    // Rounding to 8 bits can leave us just under the floor, so we aim a
+    // little higher until we're over it.
+    let mut target = min_lightness;
+
+    loop {
+        let raised = to_srgb(Lch::new(target, lch.chroma, lch.hue));
+
+        if to_lab(raised).l >= min_lightness || target >= 100.0 {
+            return raised;
+        }
+
+        target = (target + 0.1).min(100.0);
+    }
+}
+
+// Move the lightness of `c` away from `other` until they have at least
+// `min_ratio` contrast, or it's as light (or dark) as it can be.  We keep
+// a* and b* the same, so the colour keeps its hue -- until L* gets to 0 or
+// 100, and then we take away its chroma instead, because only black and
+// white are as dark and as light as a colour can be.  Otherwise a reddish
+// background could never get to 21:1 against white.
+//
+// We go 1 L* (or 1 unit of chroma) at a time, so this always finishes.
+fn ensure_contrast(c: Srgb<u8>, other: Srgb<u8>, min_ratio: f32) -> Srgb<u8> {
+    let step = if contrast::relative_luminance(c) >= contrast::relative_luminance(other) { 1.0 } else { -1.0 };
+    let limit = if step > 0.0 { 100.0 } else { 0.0 };
+
+    let mut lab = to_lab(c);
+    let mut adjusted = c;
+
+    while contrast::ratio(adjusted, other) < min_ratio {
+        let chroma = lab.a.hypot(lab.b);
+
+        if lab.l != limit {
+            lab.l = (lab.l + step).clamp(0.0, 100.0);
+        } else if chroma > 0.0 {
+            let scale = (chroma - 1.0).max(0.0) / chroma;
+
+            lab.a *= scale;
+            lab.b *= scale;
+        } else {
+            break;
+        }
+
+        adjusted = Srgb::from_color(lab).into_format();
+    }
+
+    adjusted
+}
+
+// Parse --min-contrast, which has to be a ratio that two colours can have:
+// from 1 (any two colours) to 21 (black and white).
+pub fn parse_min_contrast(s: &str) -> Result<f32, String> {
+    match s.strip_suffix(":1").unwrap_or(s).parse::<f32>() {
+        Ok(ratio) if (1.0..=21.0).contains(&ratio) => Ok(ratio),
+        Ok(_) => Err(String::from("must be between 1 and 21")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// xterm's 256 colours are the 16 ANSI colours, then a 6×6×6 cube of colours
+// (16-231), then a ramp of 24 greys (232-255).  Usually the cube and the
+// ramp are fixed colours, so TUI apps which use them ignore your theme --
+// so with --terminal-colours=256, we build them from the 16 colours instead:
+//
+//   - the corners of the cube are colours 0-6 and 15 (black, red, green,
+//     yellow, blue, magenta, cyan and bright white), in the same places
+//     as in xterm's cube, and we blend between them to fill in the rest
+//   - the grey ramp goes from colour 0 to colour 15, leaving out the ends,
+//     which are already in the cube
+//
+// We blend in CIELAB, so the steps look even.  The ramp always goes from
+// dark to light, like xterm's, so if colour 0 is lighter than colour 15,
+// we go the other way.
+//
+// See https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit
+pub fn extend_to_256(terminal_colours: &[Srgb<u8>]) -> Vec<Srgb<u8>> {
+    let slot = |i: usize| {
+        let [r, g, b] = ANSI_COLOUR_TABLE[i];
+        to_lab(terminal_colours.get(i).copied().unwrap_or_else(|| Srgb::new(r, g, b)))
+    };
+
+    let to_srgb = |lab: Lab| Srgb::from_color(lab).into_format::<u8>();
+
+    let (black, white) = (slot(0), slot(15));
+    let (red, green, yellow, blue, magenta, cyan) = (slot(1), slot(2), slot(3), slot(4), slot(5), slot(6));
+
+    let mut colours = terminal_colours.to_vec();
+
+    for r in 0..6 {
+        for g in 0..6 {
+            for b in 0..6 {
+                let (r, g, b) = (r as f32 / 5.0, g as f32 / 5.0, b as f32 / 5.0);
+
+                // Blend along the red axis, then green, then blue.
+                let without_blue = black.mix(&red, r).mix(&green.mix(&yellow, r), g);
+                let with_blue = blue.mix(&magenta, r).mix(&cyan.mix(&white, r), g);
+
+                colours.push(to_srgb(without_blue.mix(&with_blue, b)));
+            }
+        }
+    }
+
+    let (dark, light) = if black.l <= white.l { (black, white) } else { (white, black) };
+
+    for i in 1..=24 {
+        colours.push(to_srgb(dark.mix(&light, i as f32 / 25.0)));
+    }
+
+    colours
+}
+
+// The names of the slots in a TOML palette, in the order of the slots.
+const SLOT_NAMES: [&str; 16] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    "bright_black", "bright_red", "bright_green", "bright_yellow", "bright_blue", "bright_magenta", "bright_cyan", "bright_white",
+];
+
+// Read a palette to use instead of the ANSI colour table, e.g. the colours
+// of your terminal theme.  The file has 16 hex colours, one per line, in
+// the order of the terminal's slots -- or if it ends in `.toml`, a colour
+// for each named slot, e.g. `bright_red = "#ff5555"`.
+pub fn read_palette(path: &Path) -> Result<TerminalPalette, Error> {
+    let text = std::fs::read_to_string(path).map_err(|error| Error::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    let is_toml = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    let parsed = if is_toml { parse_toml_palette(&text) } else { parse_palette(&text) };
+
+    parsed.map_err(|(line, message)| Error::Palette {
+        path: path.to_path_buf(),
+        line,
+        message,
+    })
+}
+
+// If the palette is invalid, this returns the line number and what's wrong.
+// Blank lines are ignored.
+fn parse_palette(text: &str) -> Result<TerminalPalette, (usize, String)> {
+    let mut palette = ANSI_COLOUR_TABLE;
+    let mut count = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if count == palette.len() {
+            return Err((i + 1, format!("expected {} colours, but found more", palette.len())));
+        }
+
+        let c = parse_hex(line).ok_or_else(|| (i + 1, format!("invalid hex colour {:?}", line)))?;
+        palette[count] = [c.red, c.green, c.blue];
+        count += 1;
+    }
+
+    if count < palette.len() {
+        let line_count = text.lines().count().max(1);
+        return Err((line_count, format!("expected {} colours, but only found {}", palette.len(), count)));
+    }
+
+    Ok(palette)
+}
+
+// A TOML palette only needs a tiny part of TOML, so rather than pull in
+// a TOML parser, we read it line by line: each line is blank, a comment,
+// or `name = "#rrggbb"`, optionally followed by a comment.  Every slot has
+// to be there exactly once, so you can't accidentally leave one out.
+fn parse_toml_palette(text: &str) -> Result<TerminalPalette, (usize, String)> {
+    let mut palette = ANSI_COLOUR_TABLE;
+    let mut seen = [false; 16];
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = || (i + 1, format!("expected a slot like `red = \"#aa0000\"`, but found {:?}", line));
+
+        let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+        let (name, value) = (name.trim(), value.trim());
+
+        let slot = SLOT_NAMES
+            .iter()
+            .position(|slot| *slot == name)
+            .ok_or_else(|| (i + 1, format!("unknown slot {:?}; the slots are {}", name, SLOT_NAMES.join(", "))))?;
+
+        if seen[slot] {
+            return Err((i + 1, format!("slot {:?} is set more than once", name)));
+        }
+
+        // The value is a quoted string, with maybe a comment after it.
+        let hex = value
+            .strip_prefix('"')
+            .and_then(|rest| rest.split_once('"'))
+            .filter(|(_, after)| after.trim().is_empty() || after.trim().starts_with('#'))
+            .map(|(hex, _)| hex)
+            .ok_or_else(invalid)?;
+
+        let c = parse_hex(hex).ok_or_else(|| (i + 1, format!("invalid hex colour {:?}", hex)))?;
+        palette[slot] = [c.red, c.green, c.blue];
+        seen[slot] = true;
+    }
+
+    let missing: Vec<&str> = SLOT_NAMES.iter().zip(seen).filter(|(_, seen)| !seen).map(|(name, _)| *name).collect();
+
+    if !missing.is_empty() {
+        let line_count = text.lines().count().max(1);
+        let verb = if missing.len() == 1 { "is" } else { "are" };
+        return Err((line_count, format!("expected all 16 slots, but {} {} missing", missing.join(", "), verb)));
+    }
+
+    Ok(palette)
+}
+
+// The background and foreground colours for a terminal scheme.  For a
+// dark theme that's colour 0 (black) on colour 15 (bright white), which is
+// what most terminal themes do; with `light`, we swap them round.
+//
+// Every scheme we write (X resources, Alacritty, kitty, Windows Terminal,
+// iTerm2 and WezTerm) and --apply picks them here, so they always agree.
+// If we don't have 16 colours, the missing ones come from the ANSI table.
+pub fn background_and_foreground(terminal_colours: &[Srgb<u8>], light: bool) -> (Srgb<u8>, Srgb<u8>) {
+    let colour = |i: usize| {
+        let [r, g, b] = ANSI_COLOUR_TABLE[i];
+        terminal_colours.get(i).copied().unwrap_or_else(|| Srgb::new(r, g, b))
+    };
+
+    if light {
+        (colour(15), colour(0))
+    } else {
+        (colour(0), colour(15))
+    }
+}
+
+// The cursor and selection colours for terminals which need them, from
+// --cursor-colour and --selection-colour if you picked them.
+pub struct CursorAndSelection {
+    pub cursor: Option<Srgb<u8>>,
+    pub selection: Option<Srgb<u8>>,
+}
+
+impl CursorAndSelection {
+    // If you didn't pick them, the cursor is the same as the foreground,
+    // and the selection is colour 4 (blue) at half strength on the
+    // background, so it's visible without hiding the text.
+    pub fn pick(&self, terminal_colours: &[Srgb<u8>], background: Srgb<u8>, foreground: Srgb<u8>) -> (Srgb<u8>, Srgb<u8>) {
+        let cursor = self.cursor.unwrap_or(foreground);
+
+        let selection = self.selection.unwrap_or_else(|| {
+            let [r, g, b] = ANSI_COLOUR_TABLE[4];
+            let blue = terminal_colours.get(4).copied().unwrap_or_else(|| Srgb::new(r, g, b));
+
+            let dimmed = background::composite(&[blue.red, blue.green, blue.blue, 128], background);
+            Srgb::new(dimmed[0], dimmed[1], dimmed[2])
+        });
+
+        (cursor, selection)
+    }
+}
+
+// An OSC 4 escape sequence, which tells the terminal to change one of its
+// palette colours (0-255).  If you print this to a terminal, it applies the
+// colour straight away.
+//
+// See https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Operating-System-Commands
+pub fn osc4(index: usize, colour: Srgb<u8>) -> String {
+    format!(
+        "\x1b]4;{};rgb:{:02x}/{:02x}/{:02x}\x1b\\",
+        index, colour.red, colour.green, colour.blue
+    )
+}
+
+// A ramp of reds for the scheme formats' tests, so each slot is easy to
+// tell apart: colour 0 is #000000, colour 1 is #100000, and so on.
+#[cfg(test)]
+pub fn red_ramp(count: u8) -> Vec<Srgb<u8>> {
+    (0..count).map(|i| Srgb::new(i * 16, 0, 0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::contrast;
+    use crate::distance::{to_lab, Distance};
+    use crate::format::hsl_components;
+    use crate::terminal_colours::{adjust, background_and_foreground, create_terminal_colour, ensure_contrast, extend_to_256, osc4, parse_min_contrast, parse_palette, parse_toml_palette, raise_lightness, Adjustment, ANSI_COLOUR_TABLE, SLOT_NAMES};
+
+    fn palette_text(colours: &[[u8; 3]]) -> String {
+        colours
+            .iter()
+            .map(|c| format!("#{:02x}{:02x}{:02x}\n", c[0], c[1], c[2]))
+            .collect()
+    }
+
+    #[test]
+    fn it_parses_a_palette() {
+        assert_eq!(parse_palette(&palette_text(&ANSI_COLOUR_TABLE)), Ok(ANSI_COLOUR_TABLE));
+
+        // The # is optional, and blank lines are ignored.
+        let text = palette_text(&ANSI_COLOUR_TABLE).replacen('#', "", 1).replacen('\n', "\n\n", 1);
+        assert_eq!(parse_palette(&text), Ok(ANSI_COLOUR_TABLE));
+    }
+
+    #[test]
+    fn it_rejects_a_palette_with_the_wrong_number_of_colours() {
+        let text = palette_text(&ANSI_COLOUR_TABLE[..15]);
+        assert_eq!(parse_palette(&text), Err((15, String::from("expected 16 colours, but only found 15"))));
+
+        let text = palette_text(&ANSI_COLOUR_TABLE) + "#123456\n";
+        assert_eq!(parse_palette(&text), Err((17, String::from("expected 16 colours, but found more"))));
+    }
+
+    #[test]
+    fn it_rejects_a_palette_with_invalid_hex() {
+        let text = palette_text(&ANSI_COLOUR_TABLE).replace("#aa0000", "#aa00zz");
+
+        assert_eq!(parse_palette(&text), Err((2, String::from("invalid hex colour \"#aa00zz\""))));
+    }
+
+    fn toml_text(colours: &[[u8; 3]]) -> String {
+        SLOT_NAMES
+            .iter()
+            .zip(colours)
+            .map(|(name, c)| format!("{} = \"#{:02x}{:02x}{:02x}\"\n", name, c[0], c[1], c[2]))
+            .collect()
+    }
+
+    #[test]
+    fn it_parses_a_toml_palette() {
+        assert_eq!(parse_toml_palette(&toml_text(&ANSI_COLOUR_TABLE)), Ok(ANSI_COLOUR_TABLE));
+
+        // The slots can be in any order, with comments and blank lines.
+        let mut lines: Vec<String> = toml_text(&ANSI_COLOUR_TABLE).lines().rev().map(String::from).collect();
+        lines.insert(0, String::from("# Reversed"));
+        lines[3].push_str("  # a comment");
+        assert_eq!(parse_toml_palette(&lines.join("\n")), Ok(ANSI_COLOUR_TABLE));
+    }
+
+    #[test]
+    fn it_rejects_an_invalid_toml_palette() {
+        let text = toml_text(&ANSI_COLOUR_TABLE[..15]);
+        assert_eq!(parse_toml_palette(&text), Err((15, String::from("expected all 16 slots, but bright_white is missing"))));
+
+        let text = toml_text(&ANSI_COLOUR_TABLE) + "red = \"#123456\"\n";
+        assert_eq!(parse_toml_palette(&text), Err((17, String::from("slot \"red\" is set more than once"))));
+
+        let text = toml_text(&ANSI_COLOUR_TABLE).replace("magenta =", "purple =");
+        assert!(parse_toml_palette(&text).unwrap_err().1.starts_with("unknown slot \"purple\""));
+
+        let text = toml_text(&ANSI_COLOUR_TABLE).replace("\"#aa0000\"", "#aa0000");
+        assert_eq!(
+            parse_toml_palette(&text),
+            Err((2, String::from("expected a slot like `red = \"#aa0000\"`, but found \"red = #aa0000\""))),
+        );
+
+        let text = toml_text(&ANSI_COLOUR_TABLE).replace("#aa0000", "#aa00zz");
+        assert_eq!(parse_toml_palette(&text), Err((2, String::from("invalid hex colour \"#aa00zz\""))));
+    }
+
+    #[test]
+    fn it_picks_the_background_and_foreground() {
+        let colours: Vec<Srgb<u8>> = ANSI_COLOUR_TABLE.iter().map(|[r, g, b]| Srgb::new(*r, *g, *b)).collect();
+
+        assert_eq!(background_and_foreground(&colours, false), (Srgb::new(0, 0, 0), Srgb::new(255, 255, 255)));
+        assert_eq!(background_and_foreground(&colours, true), (Srgb::new(255, 255, 255), Srgb::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn it_extends_the_ansi_colours_to_256() {
+        let ansi: Vec<Srgb<u8>> = ANSI_COLOUR_TABLE.iter().map(|[r, g, b]| Srgb::new(*r, *g, *b)).collect();
+        let colours = extend_to_256(&ansi);
+
+        assert_eq!(colours.len(), 256);
+        assert_eq!(colours[..16], ansi[..]);
+
+        // The corners of the cube are the ANSI colours.
+        assert_eq!(colours[16], ansi[0]);
+        assert_eq!(colours[16 + 36 * 5], ansi[1]);
+        assert_eq!(colours[16 + 6 * 5], ansi[2]);
+        assert_eq!(colours[16 + 5], ansi[4]);
+        assert_eq!(colours[231], ansi[15]);
+
+        // The grey ramp goes from dark to light, without the ends.
+        let lightness: Vec<f32> = colours[232..].iter().map(|c| to_lab(*c).l).collect();
+        assert!(lightness.windows(2).all(|w| w[0] < w[1]), "lightness = {:?}", lightness);
+        assert!(lightness[0] > 0.0 && lightness[23] < 100.0, "lightness = {:?}", lightness);
+    }
+
+    #[test]
+    fn it_builds_the_grey_ramp_from_dark_to_light() {
+        let mut ansi: Vec<Srgb<u8>> = ANSI_COLOUR_TABLE.iter().map(|[r, g, b]| Srgb::new(*r, *g, *b)).collect();
+        ansi.swap(0, 15);
+
+        let colours = extend_to_256(&ansi);
+
+        let lightness: Vec<f32> = colours[232..].iter().map(|c| to_lab(*c).l).collect();
+        assert!(lightness.windows(2).all(|w| w[0] < w[1]), "lightness = {:?}", lightness);
+    }
+
+    #[test]
+    fn it_moves_the_lightness_until_there_is_enough_contrast() {
+        let (dark, grey) = (Srgb::new(0x20, 0x20, 0x20), Srgb::new(0x40, 0x40, 0x40));
+
+        let lighter = ensure_contrast(grey, dark, 4.5);
+        assert!(contrast::ratio(lighter, dark) >= 4.5);
+        assert!(to_lab(lighter).l > to_lab(grey).l);
+
+        // If the other colour is lighter, we go darker instead.
+        let darker = ensure_contrast(dark, grey, 3.0);
+        assert!(to_lab(darker).l < to_lab(dark).l);
+
+        // We don't touch a colour which already has enough contrast.
+        assert_eq!(ensure_contrast(Srgb::new(255, 255, 255), dark, 4.5), Srgb::new(255, 255, 255));
+    }
 
-    // Apparently more pleasing to the human eyes
-    // https://stackoverflow.com/a/9085524/6802309
-    let r_mean = (c1[0] as f64 + c2[0] as f64) / 2.0;
-    (((512.0+r_mean)*r*r)/256.0 + 4.0*g*g + ((767.0-r_mean)*b*b)/256.0).sqrt()
+    // A dark red can't get to 21:1 against white by losing lightness alone,
+    // so it has to lose its chroma too, and end up black.
+    #[test]
+    fn it_takes_away_the_chroma_if_the_lightness_isnt_enough() {
+        let white = Srgb::new(255, 255, 255);
+
+        let background = ensure_contrast(Srgb::new(0x40, 0x08, 0x00), white, 21.0);
+        assert_eq!(background, Srgb::new(0, 0, 0));
+
+        let foreground = ensure_contrast(Srgb::new(0xfe, 0xff, 0xec), Srgb::new(0, 0, 0), 21.0);
+        assert_eq!(foreground, white);
+    }
+
+    #[test]
+    fn it_scales_the_brightness_and_saturation() {
+        let muddy = Srgb::new(0x80, 0x40, 0x40);
+
+        assert_eq!(adjust(muddy, Adjustment::default()), muddy);
+        assert_eq!(adjust(muddy, Adjustment { brightness: 0.5, saturation: 0.0, ..Adjustment::default() }), Srgb::new(0xc0, 0x60, 0x60));
+        assert_eq!(adjust(muddy, Adjustment { brightness: 1.0, saturation: 1.0, ..Adjustment::default() }), Srgb::new(0xff, 0x00, 0x00));
+    }
 
-    // Alternatively: return (r*r + g*g + b*b).sqrt();
-}
\ No newline at end of file
+    #[test]
+    fn it_doesnt_saturate_a_grey() {
+        let grey = Srgb::new(0x80, 0x80, 0x80);
+
+        assert_eq!(adjust(grey, Adjustment { brightness: 0.0, saturation: 1.0, ..Adjustment::default() }), grey);
+    }
+
+    #[test]
+    fn it_raises_a_dark_colour_to_the_min_lightness() {
+        let dark_blue = Srgb::new(0x0a, 0x10, 0x34);
+
+        let raised = raise_lightness(dark_blue, 40.0);
+        assert!(to_lab(raised).l >= 40.0, "{:?}", raised);
+        assert!(to_lab(raised).l < 41.0, "{:?}", raised);
+
+        // It's still blue.
+        let (hue, _, _) = hsl_components(raised);
+        let (dark_hue, _, _) = hsl_components(dark_blue);
+        assert!((hue - dark_hue).abs() < 15.0, "{} vs {}", hue, dark_hue);
+
+        // A black becomes a grey.
+        let raised = raise_lightness(Srgb::new(0, 0, 0), 40.0);
+        assert!(to_lab(raised).l >= 40.0 && raised.red == raised.green && raised.green == raised.blue, "{:?}", raised);
+    }
+
+    #[test]
+    fn it_leaves_a_light_colour_alone() {
+        let light_red = Srgb::new(0xe8, 0x43, 0x1f);
+
+        assert_eq!(raise_lightness(light_red, 40.0), light_red);
+        assert_eq!(raise_lightness(Srgb::new(0x0a, 0x10, 0x34), 0.0), Srgb::new(0x0a, 0x10, 0x34));
+    }
+
+    #[test]
+    fn it_keeps_the_neutrals_from_the_palette() {
+        let mut palette = ANSI_COLOUR_TABLE;
+        palette[0] = [0x1d, 0x1f, 0x21];
+        palette[15] = [0xfd, 0xf6, 0xe3];
+
+        let colours = vec![Srgb::new(0x80, 0x70, 0x60), Srgb::new(0xe8, 0x43, 0x1f)];
+        let adjustment = Adjustment { brightness: 1.0, saturation: 1.0, min_lightness: 50.0 };
+
+        let result = create_terminal_colour(colours, &palette, adjustment, Distance::Ciede2000, 21.0, true);
+
+        for i in [0, 7, 8, 15] {
+            assert_eq!(result[i], Srgb::new(palette[i][0], palette[i][1], palette[i][2]), "slot {}", i);
+        }
+
+        // The other slots still come from the image.
+        assert_ne!(result[1], Srgb::new(0xaa, 0x00, 0x00));
+    }
+
+    #[test]
+    fn it_parses_the_min_contrast() {
+        assert_eq!(parse_min_contrast("4.5"), Ok(4.5));
+        assert_eq!(parse_min_contrast("7:1"), Ok(7.0));
+        assert_eq!(parse_min_contrast("1"), Ok(1.0));
+
+        assert!(parse_min_contrast("0.5").is_err());
+        assert!(parse_min_contrast("22").is_err());
+        assert!(parse_min_contrast("high").is_err());
+    }
+
+    #[test]
+    fn it_creates_osc4_sequences() {
+        assert_eq!(osc4(3, Srgb::new(18, 52, 86)), "\x1b]4;3;rgb:12/34/56\x1b\\");
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::{self, Cursor, Read, Seek};
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ImageError, ImageFormat, ImageResult, RgbaImage};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::{ColorType, TiffError, TiffUnsupportedError};
+
+// A TIFF can hold several images ("pages"), e.g. a scanned document with
+// one page per sheet of paper.  The `image` crate only reads the first, so
+// we read TIFFs ourselves with the `tiff` crate.  We look at every page,
+// unless you pick one with --page.
+//
+// Pages can be different sizes, so we return each one as its own image.
+//
+// A scanned document can have hundreds of pages, so we don't want to hold
+// them all at full size.  If there's more than one page, we pass each one
+// through `shrink` as soon as we've decoded it (usually to crop and resize
+// it).  A single page is returned as-is, because it gets the same treatment
+// as any other image.
+//
+// Most TIFFs store each channel as an 8- or 16-bit integer, but scientific
+// and HDR images often use 32-bit floats.  Floats usually go from 0.0
+// (black) to 1.0 (full brightness), but HDR images can go above 1.0 (or
+// below 0.0), and we clamp those to the range we can display.
+//
+// See https://www.awaresystems.be/imaging/tiff/tifftags/sampleformat.html
+
+pub fn decode(
+    bytes: &[u8],
+    page: Option<usize>,
+    mut shrink: impl FnMut(RgbaImage) -> ImageResult<RgbaImage>,
+) -> ImageResult<Vec<RgbaImage>> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(tiff_error)?;
+
+    let mut pages = Vec::new();
+
+    // Pages count from 1, like they do in --page for PDFs.
+    let mut page_number = 1;
+
+    loop {
+        match page {
+            Some(p) if p == page_number => return Ok(vec![decode_page(&mut decoder)?]),
+            Some(_) => (),
+            None if page_number == 1 => pages.push(decode_page(&mut decoder)?),
+            None => pages.push(shrink(decode_page(&mut decoder)?)?),
+        }
+
+        if !decoder.more_images() {
+            break;
+        }
+
+        // Now we know there's more than one page, we can shrink the first.
+        if page.is_none() && page_number == 1 {
+            let first = pages.remove(0);
+            pages.push(shrink(first)?);
+        }
+
+        decoder.next_image().map_err(tiff_error)?;
+        page_number += 1;
+    }
+
+    match page {
+        Some(p) => Err(ImageError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--page={} is out of range; this TIFF has {} page{}",
+                p,
+                page_number,
+                if page_number == 1 { "" } else { "s" }
+            ),
+        ))),
+        None => Ok(pages),
+    }
+}
+
+fn decode_page<R: Read + Seek>(decoder: &mut Decoder<R>) -> ImageResult<RgbaImage> {
+    let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+    let color_type = decoder.colortype().map_err(tiff_error)?;
+
+    let unsupported = || format_error(format!("TIFFs with colour type {:?} aren't supported", color_type));
+
+    // Scale every sample to 8 bits, rounding the same way as `image`.
+    let samples: Vec<u8> = match decoder.read_image().map_err(tiff_error)? {
+        DecodingResult::U8(samples) => samples,
+        DecodingResult::U16(samples) => samples.iter().map(|s| ((*s as u32 + 128) / 257) as u8).collect(),
+        DecodingResult::F32(samples) => samples.iter().map(|s| (s.clamp(0.0, 1.0) * 255.0).round() as u8).collect(),
+        _ => return Err(unsupported()),
+    };
+
+    let rgba: Vec<u8> = match color_type {
+        ColorType::Gray(8 | 16 | 32) => samples.iter().flat_map(|l| [*l, *l, *l, 255]).collect(),
+        ColorType::GrayA(8 | 16 | 32) => samples.chunks_exact(2).flat_map(|px| [px[0], px[0], px[0], px[1]]).collect(),
+        ColorType::RGB(8 | 16 | 32) => samples.chunks_exact(3).flat_map(|px| [px[0], px[1], px[2], 255]).collect(),
+        ColorType::RGBA(8 | 16 | 32) => samples,
+
+        // TIFFs store CMYK as the amount of each ink, so 0 is no ink.
+        ColorType::CMYK(8) => samples
+            .chunks_exact(4)
+            .flat_map(|px| {
+                let channel = |c: u8| (((255 - c) as u32 * (255 - px[3]) as u32 + 127) / 255) as u8;
+                [channel(px[0]), channel(px[1]), channel(px[2]), 255]
+            })
+            .collect(),
+        _ => return Err(unsupported()),
+    };
+
+    RgbaImage::from_raw(width, height, rgba).ok_or_else(|| format_error("Decoded image is the wrong size"))
+}
+
+fn tiff_error(e: TiffError) -> ImageError {
+    match e {
+        TiffError::UnsupportedError(TiffUnsupportedError::UnsupportedCompressionMethod(method)) => format_error(format!(
+            "Unsupported TIFF compression {:?}; dominant_colours can read uncompressed, LZW, Deflate, PackBits and JPEG-compressed TIFFs",
+            method
+        )),
+        TiffError::UnsupportedError(TiffUnsupportedError::UnknownCompressionMethod) => {
+            format_error("Unsupported TIFF compression")
+        }
+        e => format_error(e),
+    }
+}
+
+fn format_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(ImageFormat::Tiff), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image::RgbaImage;
+
+    use crate::tiff_pages::decode;
+
+    #[test]
+    fn it_shrinks_every_page_of_a_multi_page_tiff() {
+        let bytes = fs::read("./src/tests/red_and_blue_pages.tiff").unwrap();
+
+        let mut shrunk = 0;
+        let pages = decode(&bytes, None, |image| {
+            shrunk += 1;
+            Ok(RgbaImage::from_pixel(1, 1, *image.get_pixel(0, 0)))
+        })
+        .unwrap();
+
+        assert_eq!(shrunk, 2);
+        assert!(pages.iter().all(|page| page.dimensions() == (1, 1)));
+    }
+
+    #[test]
+    fn it_doesnt_shrink_a_single_page() {
+        let bytes = fs::read("./src/tests/green.tiff").unwrap();
+
+        let pages = decode(&bytes, None, |_| panic!("a single page shouldn't be shrunk")).unwrap();
+        assert_eq!(pages.len(), 1);
+
+        let bytes = fs::read("./src/tests/red_and_blue_pages.tiff").unwrap();
+
+        let pages = decode(&bytes, Some(2), |_| panic!("a page picked with --page shouldn't be shrunk")).unwrap();
+        assert_eq!(pages.len(), 1);
+    }
+}
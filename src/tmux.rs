@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::distance::to_lab;
+use crate::hex;
+
+// With --format=tmux, we print `set -g` options which theme tmux's status
+// line and borders to match the image, so you can `tmux source-file` them.
+//
+// This has to squash the palette into two roles, so it's opinionated:
+//
+//   - The background of the status line is the most dominant colour.
+//   - The accent (for the current window, messages and the active pane's
+//     border) is the most saturated of the other colours.
+//   - The text on each is black or white, whichever is easier to read.
+//
+// You can pick either role yourself with --background-index or
+// --accent-index, which count from 1 in the order we'd print the colours.
+//
+// See https://man.openbsd.org/tmux#STYLES
+
+// Which colours to use for each role, if you picked them, counting from 1.
+pub struct Roles {
+    pub background: Option<usize>,
+    pub accent: Option<usize>,
+}
+
+pub fn config(colours: &[Srgb<u8>], proportions: Option<&[f32]>, roles: &Roles) -> Result<String, String> {
+    let pick = |flag: &str, n: usize| match colours.get(n.wrapping_sub(1)) {
+        Some(c) => Ok(*c),
+        None => Err(format!("--{}={}, but only found {} colour(s)", flag, n, colours.len())),
+    };
+
+    let background = match roles.background {
+        Some(n) => pick("background-index", n)?,
+        None => most_dominant(colours, proportions),
+    };
+
+    let accent = match roles.accent {
+        Some(n) => pick("accent-index", n)?,
+        None => most_saturated(colours, background),
+    };
+
+    let mut conf = String::new();
+
+    writeln!(conf, "set -g status-style \"bg={},fg={}\"", hex(background), hex(text_on(background))).unwrap();
+    writeln!(conf, "set -g pane-border-style \"fg={}\"", hex(background)).unwrap();
+    writeln!(conf, "set -g pane-active-border-style \"fg={}\"", hex(accent)).unwrap();
+    writeln!(conf, "set -g message-style \"bg={},fg={}\"", hex(accent), hex(text_on(accent))).unwrap();
+    writeln!(conf, "set -g window-status-current-style \"bg={},fg={}\"", hex(accent), hex(text_on(accent))).unwrap();
+
+    Ok(conf)
+}
+
+// If we don't know the proportions (e.g. for terminal colours), we use
+// the first colour.
+fn most_dominant(colours: &[Srgb<u8>], proportions: Option<&[f32]>) -> Srgb<u8> {
+    let i = match proportions {
+        Some(p) => (0..p.len()).fold(0, |best, i| if p[i] > p[best] { i } else { best }),
+        None => 0,
+    };
+
+    colours.get(i).copied().unwrap_or_else(|| Srgb::new(0, 0, 0))
+}
+
+// The colour with the highest chroma, other than the background.  If every
+// colour is the background, the accent is the text colour, so it stands out.
+fn most_saturated(colours: &[Srgb<u8>], background: Srgb<u8>) -> Srgb<u8> {
+    let chroma = |c: &Srgb<u8>| {
+        let lab = to_lab(*c);
+        (lab.a * lab.a + lab.b * lab.b).sqrt()
+    };
+
+    colours
+        .iter()
+        .filter(|c| **c != background)
+        .max_by(|a, b| chroma(a).total_cmp(&chroma(b)))
+        .copied()
+        .unwrap_or_else(|| text_on(background))
+}
+
+// Black or white, whichever is easier to read on this colour.
+fn text_on(c: Srgb<u8>) -> Srgb<u8> {
+    if to_lab(c).l > 50.0 {
+        Srgb::new(0, 0, 0)
+    } else {
+        Srgb::new(255, 255, 255)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::tmux::{config, Roles};
+
+    const NO_ROLES: Roles = Roles { background: None, accent: None };
+
+    #[test]
+    fn it_uses_the_most_dominant_and_most_saturated_colours() {
+        let colours = [Srgb::new(20, 30, 40), Srgb::new(240, 240, 230), Srgb::new(230, 64, 27)];
+        let conf = config(&colours, Some(&[0.2, 0.7, 0.1]), &NO_ROLES).unwrap();
+
+        assert_eq!(
+            conf,
+            "set -g status-style \"bg=#f0f0e6,fg=#000000\"\n\
+             set -g pane-border-style \"fg=#f0f0e6\"\n\
+             set -g pane-active-border-style \"fg=#e6401b\"\n\
+             set -g message-style \"bg=#e6401b,fg=#000000\"\n\
+             set -g window-status-current-style \"bg=#e6401b,fg=#000000\"\n"
+        );
+    }
+
+    #[test]
+    fn it_uses_the_roles_you_pick() {
+        let colours = [Srgb::new(20, 30, 40), Srgb::new(240, 240, 230), Srgb::new(230, 64, 27)];
+        let roles = Roles { background: Some(1), accent: Some(2) };
+        let conf = config(&colours, None, &roles).unwrap();
+
+        assert!(conf.starts_with("set -g status-style \"bg=#141e28,fg=#ffffff\"\n"), "conf = {}", conf);
+        assert!(conf.contains("set -g pane-active-border-style \"fg=#f0f0e6\"\n"), "conf = {}", conf);
+
+        let roles = Roles { background: None, accent: Some(4) };
+        assert_eq!(config(&colours, None, &roles), Err(String::from("--accent-index=4, but only found 3 colour(s)")));
+    }
+
+    #[test]
+    fn it_picks_an_accent_for_a_single_colour() {
+        let conf = config(&[Srgb::new(0, 0, 0)], Some(&[1.0]), &NO_ROLES).unwrap();
+
+        assert!(conf.contains("set -g pane-active-border-style \"fg=#ffffff\"\n"), "conf = {}", conf);
+    }
+}
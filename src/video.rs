@@ -0,0 +1,202 @@
+use std::path::Path;
+#[cfg(feature = "video")]
+use std::process::Command;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ImageError, ImageResult, RgbaImage};
+
+// We read videos by running ffmpeg, which has to be installed separately --
+// that's why this is behind the `video` feature.
+//
+// We ask ffprobe how long the video is, then ask ffmpeg for a handful of
+// frames spread through it, one at a time, already scaled down to
+// FRAME_SIZE x FRAME_SIZE and as raw RGBA pixels.  Then we treat them like
+// the frames of an animated GIF.
+//
+// Putting `-ss` before the input makes ffmpeg jump to the nearest keyframe
+// and only decode from there, so a long video doesn't have to be decoded
+// from the start.
+//
+// See https://ffmpeg.org/ffmpeg.html
+
+// This matches the size we use for the frames of an animated GIF.  We don't
+// preserve the aspect ratio, but that doesn't matter for finding colours.
+#[cfg(feature = "video")]
+const FRAME_SIZE: u32 = 100;
+
+// How many frames we look at if you don't pass --max-frames.
+pub const DEFAULT_MAX_FRAMES: usize = 25;
+
+#[cfg(feature = "video")]
+pub fn get_frames(path: &Path, max_frames: usize, sample_every: Option<f64>) -> ImageResult<Vec<RgbaImage>> {
+    let (codec, duration) = probe(path)?;
+
+    let timestamps = match sample_every {
+        Some(interval) => {
+            let every = (0..).map(|i| i as f64 * interval).take_while(|t| *t < duration).collect();
+            crate::frames::limit(every, Some(max_frames))
+        }
+
+        // Take the frame from the middle of each equal slice of the video,
+        // so we don't get the very first and last frames, which are often
+        // black.
+        None => (0..max_frames).map(|i| duration * (i as f64 + 0.5) / max_frames as f64).collect(),
+    };
+
+    let mut frames = Vec::new();
+
+    for timestamp in timestamps {
+        if let Some(frame) = get_frame(path, &codec, timestamp)? {
+            frames.push(frame);
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(format_error("ffmpeg didn't find any frames in the video"));
+    }
+
+    Ok(frames)
+}
+
+#[cfg(not(feature = "video"))]
+pub fn get_frames(_path: &Path, _max_frames: usize, _sample_every: Option<f64>) -> ImageResult<Vec<RgbaImage>> {
+    Err(format_error(
+        "This build of dominant_colours doesn't support videos; rebuild with `--features video`",
+    ))
+}
+
+// Ask ffprobe for the codec of the first video stream, and how long the
+// video is in seconds.  This only reads the headers, so it's quick even
+// for a long video.
+#[cfg(feature = "video")]
+fn probe(path: &Path) -> ImageResult<(String, f64)> {
+    let mut ffprobe = Command::new("ffprobe");
+    ffprobe
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=codec_name:format=duration", "-of", "default=noprint_wrappers=1"])
+        .arg(path);
+
+    let output = run(ffprobe, "read the video")?;
+    let output = String::from_utf8_lossy(&output);
+
+    let value = |key: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(|v| v.trim().to_owned())
+    };
+
+    let codec = value("codec_name").ok_or_else(|| format_error("ffprobe couldn't find a video stream"))?;
+
+    let duration = value("duration")
+        .and_then(|d| d.parse::<f64>().ok())
+        .filter(|d| *d > 0.0)
+        .ok_or_else(|| format_error("ffprobe couldn't work out how long the video is"))?;
+
+    Ok((codec, duration))
+}
+
+// Get the frame at this many seconds into the video, or None if there
+// isn't one (e.g. we asked for a time just past the last frame).
+#[cfg(feature = "video")]
+fn get_frame(path: &Path, codec: &str, timestamp: f64) -> ImageResult<Option<RgbaImage>> {
+    let mut ffmpeg = Command::new("ffmpeg");
+    ffmpeg
+        .args(["-v", "error", "-ss"])
+        .arg(format!("{:.3}", timestamp))
+        .arg("-i")
+        .arg(path)
+        .args(["-map", "0:v:0", "-frames:v", "1", "-vf"])
+        .arg(format!("scale={}:{}", FRAME_SIZE, FRAME_SIZE))
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-"]);
+
+    // If ffmpeg can't read the video, it's usually because it doesn't have
+    // a decoder for the codec, so we say which codec it was.
+    let output = run(ffmpeg, &format!("decode the {} video stream", codec))?;
+
+    let frame_length = (FRAME_SIZE * FRAME_SIZE * 4) as usize;
+
+    Ok(output
+        .get(..frame_length)
+        .map(|frame| RgbaImage::from_raw(FRAME_SIZE, FRAME_SIZE, frame.to_vec()).expect("frame is the right size")))
+}
+
+// Parse how often to take a frame with --sample-every, e.g. `2s`, as a
+// number of seconds.  A number without a unit is in seconds.
+pub fn parse_interval(s: &str) -> Result<f64, String> {
+    let (number, multiplier) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60.0)
+    } else if let Some(n) = s.strip_suffix('h') {
+        (n, 3600.0)
+    } else {
+        (s, 1.0)
+    };
+
+    number
+        .parse::<f64>()
+        .ok()
+        .map(|n| n * multiplier)
+        .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+        .ok_or_else(|| String::from("must be a length of time with an optional ms, s, m or h suffix, e.g. 2s"))
+}
+
+// Run one of the ffmpeg tools, and return what it printed to stdout.  If it
+// fails, the error says what we were trying to do.
+#[cfg(feature = "video")]
+fn run(mut command: Command, task: &str) -> ImageResult<Vec<u8>> {
+    use std::io::ErrorKind;
+
+    let program = command.get_program().to_string_lossy().into_owned();
+
+    let output = command.output().map_err(|e| {
+        if e.kind() == ErrorKind::NotFound {
+            format_error(format!(
+                "Couldn't find `{}`, which is needed to read videos; install ffmpeg (https://ffmpeg.org/) and make sure it's on your PATH",
+                program
+            ))
+        } else {
+            ImageError::IoError(e)
+        }
+    })?;
+
+    if !output.status.success() {
+        return Err(format_error(format!(
+            "{} couldn't {}: {}",
+            program,
+            task,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+fn format_error(message: impl Into<String>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Name(String::from("video")),
+        message.into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::video::parse_interval;
+
+    #[test]
+    fn it_parses_an_interval() {
+        assert_eq!(parse_interval("2s"), Ok(2.0));
+        assert_eq!(parse_interval("500ms"), Ok(0.5));
+        assert_eq!(parse_interval("1.5m"), Ok(90.0));
+        assert_eq!(parse_interval("1h"), Ok(3600.0));
+        assert_eq!(parse_interval("3"), Ok(3.0));
+
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("-1s").is_err());
+        assert!(parse_interval("s").is_err());
+        assert!(parse_interval("two seconds").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+use palette::Srgb;
+use serde::Serialize;
+
+use crate::distance::to_lab;
+use crate::hex;
+use crate::json::{self, OrderedMap};
+
+#[derive(Serialize)]
+struct ColorsJson<'a> {
+    wallpaper: Cow<'a, str>,
+    alpha: &'static str,
+    special: Special,
+    colors: OrderedMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct Special {
+    background: String,
+    foreground: String,
+    cursor: String,
+}
+
+// Build a JSON file in the same format as the colors.json file written
+// by pywal, so we can be dropped in as a replacement.
+//
+// See https://github.com/dylanaraps/pywal/blob/master/pywal/templates/colors.json
+pub fn colors_json(wallpaper: &Path, colours: &[Srgb<u8>], terminal_colours: &[Srgb<u8>]) -> String {
+    // The background is the darkest of the dominant colours, and the
+    // foreground the lightest.  pywal uses the foreground for the cursor.
+    let by_lightness = |a: &&Srgb<u8>, b: &&Srgb<u8>| to_lab(**a).l.total_cmp(&to_lab(**b).l);
+    let background = colours.iter().min_by(by_lightness).copied().unwrap_or_else(|| Srgb::new(0, 0, 0));
+    let foreground = colours.iter().max_by(by_lightness).copied().unwrap_or_else(|| Srgb::new(255, 255, 255));
+
+    json::to_string(&ColorsJson {
+        wallpaper: wallpaper.to_string_lossy(),
+        alpha: "100",
+        special: Special {
+            background: hex(background),
+            foreground: hex(foreground),
+            cursor: hex(foreground),
+        },
+        colors: OrderedMap(terminal_colours.iter().enumerate().map(|(i, c)| (format!("color{}", i), hex(*c))).collect()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use palette::Srgb;
+
+    use crate::wal::colors_json;
+
+    #[test]
+    fn it_picks_the_darkest_and_lightest_colours() {
+        let colours = [Srgb::new(128, 0, 0), Srgb::new(10, 10, 30), Srgb::new(240, 230, 200)];
+        let json = colors_json(Path::new("/wallpaper.png"), &colours, &colours);
+
+        assert!(json.contains("\"background\": \"#0a0a1e\","), "json = {}", json);
+        assert!(json.contains("\"foreground\": \"#f0e6c8\","), "json = {}", json);
+        assert!(json.contains("\"cursor\": \"#f0e6c8\"\n"), "json = {}", json);
+        assert!(json.contains("\"color2\": \"#f0e6c8\"\n"), "json = {}", json);
+    }
+}
@@ -0,0 +1,193 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+
+use crate::get_bytes;
+
+// Options which affect which files we look at in a directory.
+#[derive(Debug, Default)]
+pub struct WalkOptions {
+    // Whether to look in subdirectories as well.
+    pub recursive: bool,
+
+    // Whether to look at hidden files and directories (ones whose name
+    // starts with a dot).
+    pub hidden: bool,
+
+    // If set, we only look at files whose name matches this pattern,
+    // e.g. `*.jpg`.
+    pub pattern: Option<Pattern>,
+}
+
+// The files we found when walking a directory.
+#[derive(Debug, Default)]
+pub struct Walk {
+    pub images: Vec<PathBuf>,
+
+    // How many files we skipped because they aren't images.
+    pub skipped: usize,
+
+    // Files and directories we couldn't read.  We report these, but we
+    // don't want one bad file to stop us looking at everything else.
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+// Find all the images in a directory, and optionally its subdirectories.
+// The images are sorted by path, so the output is the same every time.
+pub fn find_images(dir: &Path, options: &WalkOptions) -> Walk {
+    let mut walk = Walk::default();
+    visit(dir, options, &mut walk);
+    walk.images.sort();
+    walk
+}
+
+// Read a list of images from a file (or stdin, if the path is `-`), with
+// one path per line, e.g. the output of `fd -e png`.
+//
+// Unlike a directory, we don't check these are images first -- you asked
+// for them by name, so if one is missing or broken, we want to tell you.
+pub fn read_file_list(path: &Path) -> io::Result<Walk> {
+    let mut text = String::new();
+
+    if path == Path::new(get_bytes::STDIN) {
+        io::stdin().read_to_string(&mut text)?;
+    } else {
+        fs::File::open(path)?.read_to_string(&mut text)?;
+    }
+
+    Ok(Walk {
+        images: parse_file_list(&text),
+        ..Walk::default()
+    })
+}
+
+// Each line is a path, as-is -- we don't trim spaces or handle quotes, so
+// paths with spaces in just work.  Blank lines and lines starting with `#`
+// are ignored.
+fn parse_file_list(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn visit(dir: &Path, options: &WalkOptions, walk: &mut Walk) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            walk.errors.push((dir.to_path_buf(), e));
+            return;
+        }
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                walk.errors.push((dir.to_path_buf(), e));
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        let name = entry.file_name();
+
+        if !options.hidden && name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        // Note: this follows symlinks to files, but we don't recurse into
+        // symlinked directories, so we can't get stuck in a loop.
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+
+        if is_dir {
+            if options.recursive {
+                visit(&path, options, walk);
+            }
+            continue;
+        }
+
+        if !path.is_file() {
+            continue;
+        }
+
+        // Files which don't match the pattern aren't skipped images, so
+        // we don't count them.
+        if let Some(pattern) = &options.pattern {
+            if !pattern.matches(&name.to_string_lossy()) {
+                continue;
+            }
+        }
+
+        match get_bytes::is_image(&path) {
+            Ok(true) => walk.images.push(path),
+            Ok(false) => walk.skipped += 1,
+            Err(e) => walk.errors.push((path, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use glob::Pattern;
+
+    use crate::walk::{find_images, parse_file_list, WalkOptions};
+
+    #[test]
+    fn it_finds_the_images_in_a_directory() {
+        let walk = find_images(Path::new("./src/tests"), &WalkOptions::default());
+
+        assert!(walk.images.contains(&Path::new("./src/tests/red.png").to_path_buf()));
+        assert!(walk.images.contains(&Path::new("./src/tests/garlic.gif").to_path_buf()));
+        assert!(!walk.images.contains(&Path::new("./src/tests/malformed.txt.png").to_path_buf()));
+
+        assert!(walk.skipped > 0);
+        assert!(walk.errors.is_empty(), "errors = {:?}", walk.errors);
+    }
+
+    #[test]
+    fn it_sorts_the_images() {
+        let walk = find_images(Path::new("./src/tests"), &WalkOptions::default());
+
+        let mut sorted = walk.images.clone();
+        sorted.sort();
+
+        assert_eq!(walk.images, sorted);
+    }
+
+    #[test]
+    fn it_only_recurses_if_asked() {
+        let walk = find_images(Path::new("./src"), &WalkOptions::default());
+        assert!(walk.images.is_empty());
+
+        let options = WalkOptions { recursive: true, ..WalkOptions::default() };
+        let walk = find_images(Path::new("./src"), &options);
+        assert!(walk.images.contains(&Path::new("./src/tests/red.png").to_path_buf()));
+    }
+
+    #[test]
+    fn it_only_finds_files_matching_the_pattern() {
+        let options = WalkOptions { pattern: Some(Pattern::new("*.png").unwrap()), ..WalkOptions::default() };
+        let walk = find_images(Path::new("./src/tests"), &options);
+
+        assert!(walk.images.contains(&Path::new("./src/tests/red.png").to_path_buf()));
+        assert!(walk.images.iter().all(|p| p.extension().unwrap() == "png"), "images = {:?}", walk.images);
+
+        // malformed.txt.png matches, but it isn't an image.
+        assert_eq!(walk.skipped, 1);
+    }
+
+    #[test]
+    fn it_parses_a_list_of_files() {
+        let text = "red.png\n\n# a comment\nmy photos/blue sky.jpg\r\n  \n";
+
+        assert_eq!(
+            parse_file_list(text),
+            vec![Path::new("red.png").to_path_buf(), Path::new("my photos/blue sky.jpg").to_path_buf()]
+        );
+    }
+}
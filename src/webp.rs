@@ -0,0 +1,79 @@
+use std::io::Cursor;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult, RgbImage, RgbaImage};
+use image_webp::WebPDecoder;
+
+use crate::frames::Frame;
+
+// WebP images can be lossy (VP8), lossless (VP8L), and either can have an
+// alpha channel.  The WebP decoder in our version of the `image` crate
+// gets the colours of lossy images noticeably wrong, so we use image-webp
+// instead, which is the decoder newer versions of `image` use.
+//
+// WebP images can also be animated, with the same `.webp` extension, so we
+// have to look inside the file to tell them apart.
+//
+// See https://developers.google.com/speed/webp/docs/riff_container
+
+pub fn decode(bytes: &[u8]) -> ImageResult<DynamicImage> {
+    let mut decoder = WebPDecoder::new(Cursor::new(bytes)).map_err(format_error)?;
+
+    let (width, height) = decoder.dimensions();
+    let mut buf = vec![0; buffer_size(&decoder)?];
+    decoder.read_image(&mut buf).map_err(format_error)?;
+
+    to_image(width, height, decoder.has_alpha(), buf)
+}
+
+pub fn is_animated(bytes: &[u8]) -> ImageResult<bool> {
+    let decoder = WebPDecoder::new(Cursor::new(bytes)).map_err(format_error)?;
+
+    Ok(decoder.is_animated())
+}
+
+// Get every frame of an animated WebP.  The decoder draws each frame onto
+// the canvas for us, so these are the frames as you'd see them, not the
+// (possibly smaller) rectangles stored in the file.
+pub fn get_frames(bytes: &[u8]) -> ImageResult<Vec<Frame>> {
+    let mut decoder = WebPDecoder::new(Cursor::new(bytes)).map_err(format_error)?;
+
+    let (width, height) = decoder.dimensions();
+    let size = buffer_size(&decoder)?;
+
+    let mut frames = Vec::new();
+
+    for _ in 0..decoder.num_frames() {
+        let mut buf = vec![0; size];
+        let delay = decoder.read_frame(&mut buf).map_err(format_error)?;
+        frames.push(Frame::new(to_image(width, height, decoder.has_alpha(), buf)?.into_rgba8(), delay));
+    }
+
+    if frames.is_empty() {
+        return Err(format_error("No frames in the animated WebP"));
+    }
+
+    Ok(frames)
+}
+
+fn buffer_size(decoder: &WebPDecoder<Cursor<&[u8]>>) -> ImageResult<usize> {
+    decoder
+        .output_buffer_size()
+        .ok_or_else(|| format_error("Image is too large to decode"))
+}
+
+// The decoder gives us RGBA if the image has an alpha channel, and RGB
+// if it doesn't.
+fn to_image(width: u32, height: u32, has_alpha: bool, buf: Vec<u8>) -> ImageResult<DynamicImage> {
+    let img = if has_alpha {
+        RgbaImage::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+    };
+
+    img.ok_or_else(|| format_error("Decoded image is the wrong size"))
+}
+
+fn format_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(ImageFormat::WebP), e))
+}
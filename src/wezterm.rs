@@ -0,0 +1,102 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::hex;
+use crate::json;
+use crate::terminal_colours::{background_and_foreground, CursorAndSelection, ANSI_COLOUR_TABLE};
+
+// With --format=wezterm, we print the terminal colours as a WezTerm colour
+// scheme, which you can save in one of WezTerm's `color_scheme_dirs`:
+//
+//      [colors]
+//      foreground = "#ffffff"
+//      background = "#000000"
+//      cursor_bg = "#ffffff"
+//      selection_bg = "#000055"
+//      ansi = ["#000000", ..., "#aaaaaa"]
+//      brights = ["#555555", ..., "#ffffff"]
+//
+//      [metadata]
+//      name = "wallpaper"
+//
+// WezTerm wants exactly 8 colours in each of `ansi` and `brights`, so if
+// we're missing a slot, we fill it from the ANSI colour table.  With
+// --terminal-colours=256, the rest of the colours go in `[colors.indexed]`.
+// TOML strings are escaped the same way as JSON strings.
+//
+// See https://wezfurlong.org/wezterm/config/appearance.html#defining-a-color-scheme-in-a-separate-file
+
+pub fn scheme(terminal_colours: &[Srgb<u8>], name: &str, light: bool, overrides: &CursorAndSelection) -> String {
+    let (background, foreground) = background_and_foreground(terminal_colours, light);
+    let (cursor, selection) = overrides.pick(terminal_colours, background, foreground);
+
+    let slot = |i: usize| {
+        let [r, g, b] = ANSI_COLOUR_TABLE[i];
+        let c = terminal_colours.get(i).copied().unwrap_or_else(|| Srgb::new(r, g, b));
+        format!("\"{}\"", hex(c))
+    };
+
+    let ansi: Vec<String> = (0..8).map(slot).collect();
+    let brights: Vec<String> = (8..16).map(slot).collect();
+
+    let mut toml = String::new();
+
+    writeln!(toml, "[colors]").unwrap();
+    writeln!(toml, "foreground = \"{}\"", hex(foreground)).unwrap();
+    writeln!(toml, "background = \"{}\"", hex(background)).unwrap();
+    writeln!(toml, "cursor_bg = \"{}\"", hex(cursor)).unwrap();
+    writeln!(toml, "selection_bg = \"{}\"", hex(selection)).unwrap();
+    writeln!(toml, "ansi = [{}]", ansi.join(", ")).unwrap();
+    writeln!(toml, "brights = [{}]", brights.join(", ")).unwrap();
+
+    if terminal_colours.len() > 16 {
+        writeln!(toml).unwrap();
+        writeln!(toml, "[colors.indexed]").unwrap();
+
+        for (i, c) in terminal_colours.iter().enumerate().skip(16) {
+            writeln!(toml, "{} = \"{}\"", i, hex(*c)).unwrap();
+        }
+    }
+
+    writeln!(toml).unwrap();
+    writeln!(toml, "[metadata]").unwrap();
+    writeln!(toml, "name = {}", json::string(name)).unwrap();
+
+    toml
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::terminal_colours::{red_ramp, CursorAndSelection};
+    use crate::wezterm::scheme;
+
+    #[test]
+    fn it_always_has_eight_ansi_and_bright_colours() {
+        let overrides = CursorAndSelection { cursor: None, selection: None };
+
+        for count in [16, 10, 0] {
+            let colours = red_ramp(count);
+            let toml: toml::Table = scheme(&colours, "a \"quoted\" name", false, &overrides).parse().unwrap();
+
+            assert_eq!(toml["colors"]["ansi"].as_array().unwrap().len(), 8);
+            assert_eq!(toml["colors"]["brights"].as_array().unwrap().len(), 8);
+            assert_eq!(toml["metadata"]["name"].as_str(), Some("a \"quoted\" name"));
+        }
+    }
+
+    #[test]
+    fn it_writes_the_indexed_colours() {
+        let overrides = CursorAndSelection { cursor: None, selection: None };
+
+        let colours: Vec<Srgb<u8>> = (0..=255).map(|i| Srgb::new(i, 0, 0)).collect();
+        let toml: toml::Table = scheme(&colours, "wallpaper", false, &overrides).parse().unwrap();
+
+        let indexed = toml["colors"]["indexed"].as_table().unwrap();
+        assert_eq!(indexed.len(), 240);
+        assert_eq!(indexed["16"].as_str(), Some("#100000"));
+        assert_eq!(indexed["255"].as_str(), Some("#ff0000"));
+    }
+}
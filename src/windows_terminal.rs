@@ -0,0 +1,97 @@
+use palette::Srgb;
+use serde::Serialize;
+
+use crate::hex;
+use crate::json;
+use crate::terminal_colours::{background_and_foreground, CursorAndSelection};
+
+// With --format=windows-terminal, we print the terminal colours as a
+// Windows Terminal colour scheme, which you can paste into the `schemes`
+// list in settings.json:
+//
+//      {
+//          "name": "wallpaper",
+//          "cursorColor": "#ffffff",
+//          ...
+//          "brightWhite": "#ffffff"
+//      }
+//
+// We build it from a struct rather than a map, so the keys are always in
+// the same order and the output diffs cleanly.  Windows Terminal calls
+// magenta "purple".
+//
+// See https://learn.microsoft.com/en-us/windows/terminal/customize-settings/color-schemes
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Scheme<'a> {
+    name: &'a str,
+    cursor_color: String,
+    selection_background: String,
+    background: String,
+    foreground: String,
+    black: String,
+    red: String,
+    green: String,
+    yellow: String,
+    blue: String,
+    purple: String,
+    cyan: String,
+    white: String,
+    bright_black: String,
+    bright_red: String,
+    bright_green: String,
+    bright_yellow: String,
+    bright_blue: String,
+    bright_purple: String,
+    bright_cyan: String,
+    bright_white: String,
+}
+
+pub fn scheme(terminal_colours: &[Srgb<u8>], name: &str, light: bool, overrides: &CursorAndSelection) -> String {
+    let (background, foreground) = background_and_foreground(terminal_colours, light);
+    let (cursor, selection) = overrides.pick(terminal_colours, background, foreground);
+
+    let slot = |i: usize| hex(terminal_colours[i]);
+
+    let scheme = Scheme {
+        name,
+        cursor_color: hex(cursor),
+        selection_background: hex(selection),
+        background: hex(background),
+        foreground: hex(foreground),
+        black: slot(0),
+        red: slot(1),
+        green: slot(2),
+        yellow: slot(3),
+        blue: slot(4),
+        purple: slot(5),
+        cyan: slot(6),
+        white: slot(7),
+        bright_black: slot(8),
+        bright_red: slot(9),
+        bright_green: slot(10),
+        bright_yellow: slot(11),
+        bright_blue: slot(12),
+        bright_purple: slot(13),
+        bright_cyan: slot(14),
+        bright_white: slot(15),
+    };
+
+    json::to_string(&scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terminal_colours::{red_ramp, CursorAndSelection};
+    use crate::windows_terminal::scheme;
+
+    #[test]
+    fn it_names_the_slots_in_order() {
+        let colours = red_ramp(16);
+        let json = scheme(&colours, "my \"wallpaper\"", false, &CursorAndSelection { cursor: None, selection: None });
+
+        assert!(json.starts_with("{\n    \"name\": \"my \\\"wallpaper\\\"\",\n    \"cursorColor\": \"#f00000\",\n"), "json = {}", json);
+        assert!(json.contains("\n    \"purple\": \"#500000\",\n"), "json = {}", json);
+        assert!(json.ends_with("\n    \"brightWhite\": \"#f00000\"\n}\n"), "json = {}", json);
+    }
+}
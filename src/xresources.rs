@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use palette::Srgb;
+
+use crate::hex;
+use crate::terminal_colours::background_and_foreground;
+
+// With --format=xresources, we print the terminal colours as X resources,
+// so you can load them with `xrdb -merge`, e.g.
+//
+//      *.foreground: #ffffff
+//      *.background: #000000
+//      *.cursorColor: #ffffff
+//      *.color0: #000000
+//      ...
+//      *.color15: #ffffff
+//
+// With --terminal-colours=256, we carry on up to color255, which xterm and
+// urxvt both understand.
+//
+// The background is colour 0 (black), and the foreground and cursor are
+// colour 15 (bright white) -- or the other way round with --light-theme.
+//
+// With --prefix, you can scope the resources to one program, e.g. `URxvt`
+// gives `URxvt.color0`.
+//
+// See https://wiki.archlinux.org/title/X_resources
+
+pub const DEFAULT_PREFIX: &str = "*";
+
+pub fn resources(terminal_colours: &[Srgb<u8>], prefix: &str, light: bool) -> String {
+    let (background, foreground) = background_and_foreground(terminal_colours, light);
+
+    let mut xresources = String::new();
+
+    writeln!(xresources, "{}.foreground: {}", prefix, hex(foreground)).unwrap();
+    writeln!(xresources, "{}.background: {}", prefix, hex(background)).unwrap();
+    writeln!(xresources, "{}.cursorColor: {}", prefix, hex(foreground)).unwrap();
+
+    for (i, c) in terminal_colours.iter().enumerate() {
+        writeln!(xresources, "{}.color{}: {}", prefix, i, hex(*c)).unwrap();
+    }
+
+    xresources
+}
+
+// Parse the program name (or class) for --prefix.  A resource name is made
+// of components separated by `.` or `*`, so we only allow those and the
+// characters which can go in a component.
+pub fn parse_prefix(s: &str) -> Result<String, String> {
+    let is_valid = !s.is_empty()
+        && !s.ends_with('.')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || ['_', '-', '.', '*'].contains(&c));
+
+    if !is_valid {
+        return Err(String::from(
+            "should be a program name or class, using letters, numbers, hyphens, underscores, `.` and `*`, e.g. URxvt",
+        ));
+    }
+
+    Ok(s.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terminal_colours::red_ramp;
+    use crate::xresources::{parse_prefix, resources};
+
+    #[test]
+    fn it_scopes_the_resources_with_the_prefix() {
+        let colours = red_ramp(16);
+        let xresources = resources(&colours, "URxvt", false);
+
+        assert!(
+            xresources.starts_with("URxvt.foreground: #f00000\nURxvt.background: #000000\nURxvt.cursorColor: #f00000\nURxvt.color0: #000000\n"),
+            "xresources = {}",
+            xresources
+        );
+        assert!(xresources.ends_with("URxvt.color15: #f00000\n"), "xresources = {}", xresources);
+
+        let xresources = resources(&colours, "*", true);
+        assert!(xresources.starts_with("*.foreground: #000000\n*.background: #f00000\n"), "xresources = {}", xresources);
+    }
+
+    #[test]
+    fn it_parses_a_prefix() {
+        assert_eq!(parse_prefix("URxvt"), Ok(String::from("URxvt")));
+        assert_eq!(parse_prefix("XTerm*vt100"), Ok(String::from("XTerm*vt100")));
+        assert_eq!(parse_prefix("*"), Ok(String::from("*")));
+
+        assert!(parse_prefix("").is_err());
+        assert!(parse_prefix("URxvt.").is_err());
+        assert!(parse_prefix("URxvt: red").is_err());
+        assert!(parse_prefix("a b").is_err());
+    }
+}